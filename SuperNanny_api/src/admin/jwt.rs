@@ -30,7 +30,7 @@ pub const VIEW_EVENTS: &str = "view_events";
 /// ---------------------------------------------------------------------
 /// JWT claims
 /// ---------------------------------------------------------------------
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AdminClaims {
     pub sub:   i32,
     pub perms: Vec<String>,
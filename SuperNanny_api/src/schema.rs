@@ -11,10 +11,43 @@ diesel::table! {
         tcp_connect -> Text,
         allowed_ips -> Text,
         allowed_domains -> Text,
+        fs_rules -> Text,
+        scoped -> Text,
+        seccomp -> Text,
+        resources -> Text,
+        isolation -> Text,
+        environment -> Text,
         updated_at -> Timestamp,
     }
 }
 
+diesel::table! {
+    app_policy_versions (version_id) {
+        version_id -> Int4,
+        app_name -> Text,
+        role_id -> Int4,
+        version -> Int4,
+        default_ro -> Text,
+        default_rw -> Text,
+        tcp_bind -> Text,
+        tcp_connect -> Text,
+        allowed_ips -> Text,
+        allowed_domains -> Text,
+        fs_rules -> Text,
+        scoped -> Text,
+        seccomp -> Text,
+        resources -> Text,
+        isolation -> Text,
+        environment -> Text,
+        deleted -> Bool,
+        author -> Text,
+        reason -> Text,
+        request_id -> Nullable<Int4>,
+        restored_version -> Nullable<Int4>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     default_policies (role_id) {
         role_id -> Int4,
@@ -24,6 +57,12 @@ diesel::table! {
         tcp_connect -> Text,
         allowed_ips -> Text,
         allowed_domains -> Text,
+        fs_rules -> Text,
+        scoped -> Text,
+        seccomp -> Text,
+        resources -> Text,
+        isolation -> Text,
+        environment -> Text,
     }
 }
 
@@ -60,6 +99,10 @@ diesel::table! {
         user_id -> Nullable<Int4>,
         remote_ip -> Nullable<Text>,
         domain -> Nullable<Text>,
+        cgroup_id -> Nullable<Int8>,
+        client_event_id -> Nullable<Text>,
+        script -> Nullable<Text>,
+        script_sha256 -> Nullable<Text>,
     }
 }
 
@@ -123,6 +166,7 @@ diesel::joinable!(user_roles -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     app_policy,
+    app_policy_versions,
     default_policies,
     permissions,
     role_permissions,
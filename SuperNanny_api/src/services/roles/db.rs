@@ -63,6 +63,12 @@ pub struct DefaultPolicy {
     pub tcp_connect:    String,
     pub allowed_ips:    String,
     pub allowed_domains:String,
+    pub fs_rules:       String,
+    pub scoped:         String,
+    pub seccomp:        String,
+    pub resources:      String,
+    pub isolation:      String,
+    pub environment:    String,
 }
 
 #[derive(Insertable, serde::Deserialize)]
@@ -75,6 +81,18 @@ pub struct NewDefaultPolicy {
     pub tcp_connect:    String,
     pub allowed_ips:    String,
     pub allowed_domains:String,
+    #[serde(default)]
+    pub fs_rules:       String,
+    #[serde(default)]
+    pub scoped:         String,
+    #[serde(default)]
+    pub seccomp:        String,
+    #[serde(default)]
+    pub resources:      String,
+    #[serde(default)]
+    pub isolation:      String,
+    #[serde(default)]
+    pub environment:    String,
 }
 
 #[derive(serde::Deserialize)]
@@ -85,6 +103,12 @@ pub struct DefaultPolicyPatch {
     pub tcp_connect:    Option<String>,
     pub allowed_ips:    Option<String>,
     pub allowed_domains:Option<String>,
+    pub fs_rules:       Option<String>,
+    pub scoped:         Option<String>,
+    pub seccomp:        Option<String>,
+    pub resources:      Option<String>,
+    pub isolation:      Option<String>,
+    pub environment:    Option<String>,
 }
 
 /* -------------------------------------------------------------------------- */
@@ -137,6 +161,12 @@ pub fn update_default_policy(pool: &DbPool, rid: i32, p: DefaultPolicyPatch) ->
             p.tcp_connect.map(|v| tcp_connect.eq(v)),
             p.allowed_ips.map(|v| allowed_ips.eq(v)),
             p.allowed_domains.map(|v| allowed_domains.eq(v)),
+            p.fs_rules.map(|v| fs_rules.eq(v)),
+            p.scoped.map(|v| scoped.eq(v)),
+            p.seccomp.map(|v| seccomp.eq(v)),
+            p.resources.map(|v| resources.eq(v)),
+            p.isolation.map(|v| isolation.eq(v)),
+            p.environment.map(|v| environment.eq(v)),
         ))
         .execute(&mut conn(pool)?)?;
     Ok(())
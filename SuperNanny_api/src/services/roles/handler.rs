@@ -3,7 +3,11 @@
 use actix_web::{delete, get, post, put, web, HttpResponse};
 use serde::Deserialize;
 use crate::{
-    admin::{jwt::MANAGE_ROLES, Needs}, admin::csrf::Csrf, services::{roles::db as roles_db, users::db as users_db}, state::AppState
+    admin::{jwt::MANAGE_ROLES, Needs}, admin::csrf::Csrf, services::{roles::db as roles_db, users::db as users_db}, state::AppState,
+};
+use supernanny_policy_syntax::{
+    validate_environment, validate_fs_rules, validate_isolation, validate_path_list,
+    validate_resources, validate_scopes, validate_seccomp,
 };
 
 /* -------------------------------------------------------------------------- */
@@ -49,6 +53,23 @@ async fn list_user_roles(state: web::Data<AppState>, uid: web::Path<i32>) -> Htt
 
 /* -------------------- default policies ------------------------------------ */
 
+/// Syntaxe des chemins (modèles, globs), des `fs_rules`, des scopes, du
+/// profil seccomp, des limites de ressources, des options d’isolation et de
+/// la section d’environnement.
+fn check_policy_fields(
+    ro: &str, rw: &str, fs_rules: &str, scoped: &str, seccomp: &str, resources: &str, isolation: &str,
+    environment: &str,
+) -> Result<(), String> {
+    validate_path_list(ro)?;
+    validate_path_list(rw)?;
+    validate_fs_rules(fs_rules)?;
+    validate_scopes(scoped)?;
+    validate_seccomp(seccomp)?;
+    validate_resources(resources)?;
+    validate_isolation(isolation)?;
+    validate_environment(environment)
+}
+
 #[get("/default_policies/{rid}")]
 async fn get_default(state: web::Data<AppState>, rid: web::Path<i32>) -> HttpResponse {
     match roles_db::get_default_policy(&state.db, rid.into_inner()) {
@@ -63,6 +84,9 @@ async fn create_default(
     state: web::Data<AppState>,
     body: web::Json<roles_db::NewDefaultPolicy>,
 ) -> HttpResponse {
+    if let Err(e) = check_policy_fields(&body.default_ro, &body.default_rw, &body.fs_rules, &body.scoped, &body.seccomp, &body.resources, &body.isolation, &body.environment) {
+        return HttpResponse::BadRequest().body(e);
+    }
     match roles_db::create_default_policy(&state.db, body.into_inner()) {
         Ok(_)  => HttpResponse::Ok().finish(),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
@@ -75,6 +99,10 @@ async fn update_default(
     rid: web::Path<i32>,
     body: web::Json<roles_db::DefaultPolicyPatch>,
 ) -> HttpResponse {
+    let field = |v: &Option<String>| v.clone().unwrap_or_default();
+    if let Err(e) = check_policy_fields(&field(&body.default_ro), &field(&body.default_rw), &field(&body.fs_rules), &field(&body.scoped), &field(&body.seccomp), &field(&body.resources), &field(&body.isolation), &field(&body.environment)) {
+        return HttpResponse::BadRequest().body(e);
+    }
     match roles_db::update_default_policy(&state.db, rid.into_inner(), body.into_inner()) {
         Ok(_)  => HttpResponse::Ok().finish(),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
@@ -92,6 +120,18 @@ struct RoleWithPolicy {
     tcp_connect:     String,
     allowed_ips:     String,
     allowed_domains: String,
+    #[serde(default)]
+    fs_rules:        String,
+    #[serde(default)]
+    scoped:          String,
+    #[serde(default)]
+    seccomp:         String,
+    #[serde(default)]
+    resources:       String,
+    #[serde(default)]
+    isolation:       String,
+    #[serde(default)]
+    environment:     String,
 }
 
 #[post("/create_with_default")]
@@ -100,6 +140,9 @@ async fn create_with_default(
     body: web::Json<RoleWithPolicy>,
 ) -> HttpResponse {
     let dto = body.into_inner();
+    if let Err(e) = check_policy_fields(&dto.default_ro, &dto.default_rw, &dto.fs_rules, &dto.scoped, &dto.seccomp, &dto.resources, &dto.isolation, &dto.environment) {
+        return HttpResponse::BadRequest().body(e);
+    }
 
     let rid = match roles_db::insert(&state.db, roles_db::NewRole { role_name: &dto.role_name }) {
         Ok(id) => id,
@@ -114,6 +157,12 @@ async fn create_with_default(
         tcp_connect:    dto.tcp_connect,
         allowed_ips:    dto.allowed_ips,
         allowed_domains:dto.allowed_domains,
+        fs_rules:       dto.fs_rules,
+        scoped:         dto.scoped,
+        seccomp:        dto.seccomp,
+        resources:      dto.resources,
+        isolation:      dto.isolation,
+        environment:    dto.environment,
     };
 
     if let Err(e) = roles_db::create_default_policy(&state.db, np) {
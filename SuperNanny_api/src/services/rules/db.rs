@@ -3,7 +3,7 @@ use chrono::NaiveDateTime;
 use diesel::prelude::*;
 
 use crate::{
-    schema::{default_policies, app_policy, roles},
+    schema::{default_policies, app_policy, app_policy_versions, roles},
     state::DbPool,
 };
 
@@ -35,6 +35,12 @@ pub struct DefaultPolicy {
     pub tcp_connect: String,
     pub allowed_ips: String,
     pub allowed_domains: String,
+    pub fs_rules: String,
+    pub scoped: String,
+    pub seccomp: String,
+    pub resources: String,
+    pub isolation: String,
+    pub environment: String,
 }
 
 #[derive(Insertable)]
@@ -47,6 +53,12 @@ pub struct NewDefaultPolicy<'a> {
     pub tcp_connect: &'a str,
     pub allowed_ips: &'a str,
     pub allowed_domains: &'a str,
+    pub fs_rules: &'a str,
+    pub scoped: &'a str,
+    pub seccomp: &'a str,
+    pub resources: &'a str,
+    pub isolation: &'a str,
+    pub environment: &'a str,
 }
 
 #[derive(AsChangeset)]
@@ -58,6 +70,12 @@ pub struct DefaultPolicyChangeset<'a> {
     pub tcp_connect:     Option<&'a str>,
     pub allowed_ips:     Option<&'a str>,
     pub allowed_domains: Option<&'a str>,
+    pub fs_rules:        Option<&'a str>,
+    pub scoped:          Option<&'a str>,
+    pub seccomp:         Option<&'a str>,
+    pub resources:       Option<&'a str>,
+    pub isolation:       Option<&'a str>,
+    pub environment:     Option<&'a str>,
 }
 
 // ---------------- app_policy ----------------------------------------------
@@ -73,6 +91,12 @@ pub struct AppPolicy {
     pub tcp_connect: String,
     pub allowed_ips: String,
     pub allowed_domains: String,
+    pub fs_rules: String,
+    pub scoped: String,
+    pub seccomp: String,
+    pub resources: String,
+    pub isolation: String,
+    pub environment: String,
     pub updated_at: NaiveDateTime,
 }
 
@@ -87,6 +111,30 @@ pub struct NewAppPolicy<'a> {
     pub tcp_connect: &'a str,
     pub allowed_ips: &'a str,
     pub allowed_domains: &'a str,
+    pub fs_rules: &'a str,
+    pub scoped: &'a str,
+    pub seccomp: &'a str,
+    pub resources: &'a str,
+    pub isolation: &'a str,
+    pub environment: &'a str,
+}
+
+/// Champs absents (`None`) : valeur en base conservée.
+#[derive(AsChangeset)]
+#[diesel(table_name = app_policy)]
+pub struct AppPolicyChangeset<'a> {
+    pub default_ro:      Option<&'a str>,
+    pub default_rw:      Option<&'a str>,
+    pub tcp_bind:        Option<&'a str>,
+    pub tcp_connect:     Option<&'a str>,
+    pub allowed_ips:     Option<&'a str>,
+    pub allowed_domains: Option<&'a str>,
+    pub fs_rules:        Option<&'a str>,
+    pub scoped:          Option<&'a str>,
+    pub seccomp:         Option<&'a str>,
+    pub resources:       Option<&'a str>,
+    pub isolation:       Option<&'a str>,
+    pub environment:     Option<&'a str>,
 }
 
 pub fn list_envs(pool: &DbPool) -> Result<Vec<AppPolicy>> {
@@ -104,39 +152,209 @@ pub fn by_id(pool: &DbPool, pid: i32) -> Result<Option<AppPolicy>> {
     Ok(app_policy::table.filter(app_policy::policy_id.eq(pid)).first::<AppPolicy>(&mut conn).optional()?)
 }
 
-pub fn insert_env(pool: &DbPool, p: NewAppPolicy) -> Result<()> {
+pub fn insert_env(pool: &DbPool, p: NewAppPolicy, author: &str, reason: &str) -> Result<()> {
     let mut conn = pool.get()?;
-    diesel::insert_into(app_policy::table).values(&p).execute(&mut conn)?;
+    conn.transaction(|conn| {
+        let policy = diesel::insert_into(app_policy::table)
+            .values(&p)
+            .get_result::<AppPolicy>(conn)?;
+        record_version(conn, &policy, false, author, reason, None)
+    })?;
     Ok(())
 }
 
 pub fn update_env(
     pool: &DbPool,
     pid: i32,
-    ro: &str,
-    rw: &str,
-    bind: &str,
-    conn_tcp: &str,
-    ips: &str,
-    domains: &str,
+    changes: AppPolicyChangeset,
+    author: &str,
+    reason: &str,
 ) -> Result<()> {
     let mut conn = pool.get()?;
-    diesel::update(app_policy::table.filter(app_policy::policy_id.eq(pid)))
-        .set((
-            app_policy::default_ro.eq(ro),
-            app_policy::default_rw.eq(rw),
-            app_policy::tcp_bind.eq(bind),
-            app_policy::tcp_connect.eq(conn_tcp),
-            app_policy::allowed_ips.eq(ips),
-            app_policy::allowed_domains.eq(domains),
-            app_policy::updated_at.eq(chrono::Utc::now().naive_utc()),
-        ))
-        .execute(&mut conn)?;
+    conn.transaction(|conn| {
+        let updated = diesel::update(app_policy::table.filter(app_policy::policy_id.eq(pid)))
+            .set((&changes, app_policy::updated_at.eq(chrono::Utc::now().naive_utc())))
+            .get_result::<AppPolicy>(conn)
+            .optional()?;
+        match updated {
+            Some(policy) => record_version(conn, &policy, false, author, reason, None).map(|_| ()),
+            None => Ok(()),
+        }
+    })?;
     Ok(())
 }
 
-pub fn delete_env(pool: &DbPool, pid: i32) -> Result<bool> {
+/// Supprime la config ; sa dernière version est conservée, marquée supprimée.
+pub fn delete_env(pool: &DbPool, pid: i32, author: &str, reason: &str) -> Result<bool> {
+    let mut conn = pool.get()?;
+    Ok(conn.transaction(|conn| {
+        let Some(policy) = app_policy::table
+            .filter(app_policy::policy_id.eq(pid))
+            .first::<AppPolicy>(conn)
+            .optional()?
+        else {
+            return Ok::<_, diesel::result::Error>(false);
+        };
+        record_version(conn, &policy, true, author, reason, None)?;
+        diesel::delete(app_policy::table.filter(app_policy::policy_id.eq(pid))).execute(conn)?;
+        Ok(true)
+    })?)
+}
+
+// ---------------- app_policy_versions -------------------------------------
+
+/// Un état d’une config, immuable une fois écrit.
+#[derive(Queryable, serde::Serialize)]
+pub struct PolicyVersion {
+    pub version_id: i32,
+    pub app_name: String,
+    pub role_id: i32,
+    pub version: i32,
+    pub default_ro: String,
+    pub default_rw: String,
+    pub tcp_bind: String,
+    pub tcp_connect: String,
+    pub allowed_ips: String,
+    pub allowed_domains: String,
+    pub fs_rules: String,
+    pub scoped: String,
+    pub seccomp: String,
+    pub resources: String,
+    pub isolation: String,
+    pub environment: String,
+    pub deleted: bool,
+    pub author: String,
+    pub reason: String,
+    /// Demande de changement dont l’approbation a produit cette version
+    pub request_id: Option<i32>,
+    /// Version restaurée par un rollback
+    pub restored_version: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = app_policy_versions)]
+struct NewPolicyVersion<'a> {
+    app_name: &'a str,
+    role_id: i32,
+    version: i32,
+    default_ro: &'a str,
+    default_rw: &'a str,
+    tcp_bind: &'a str,
+    tcp_connect: &'a str,
+    allowed_ips: &'a str,
+    allowed_domains: &'a str,
+    fs_rules: &'a str,
+    scoped: &'a str,
+    seccomp: &'a str,
+    resources: &'a str,
+    isolation: &'a str,
+    environment: &'a str,
+    deleted: bool,
+    author: &'a str,
+    reason: &'a str,
+    restored_version: Option<i32>,
+}
+
+/// Enregistre l’état `p` comme nouvelle version de sa config, dans la
+/// transaction de la modification.
+fn record_version(
+    conn: &mut PgConnection,
+    p: &AppPolicy,
+    deleted: bool,
+    author: &str,
+    reason: &str,
+    restored_version: Option<i32>,
+) -> QueryResult<PolicyVersion> {
+    let last = app_policy_versions::table
+        .filter(app_policy_versions::app_name.eq(&p.app_name))
+        .filter(app_policy_versions::role_id.eq(p.role_id))
+        .select(diesel::dsl::max(app_policy_versions::version))
+        .first::<Option<i32>>(conn)?;
+    diesel::insert_into(app_policy_versions::table)
+        .values(&NewPolicyVersion {
+            app_name: &p.app_name,
+            role_id: p.role_id,
+            version: last.unwrap_or(0) + 1,
+            default_ro: &p.default_ro,
+            default_rw: &p.default_rw,
+            tcp_bind: &p.tcp_bind,
+            tcp_connect: &p.tcp_connect,
+            allowed_ips: &p.allowed_ips,
+            allowed_domains: &p.allowed_domains,
+            fs_rules: &p.fs_rules,
+            scoped: &p.scoped,
+            seccomp: &p.seccomp,
+            resources: &p.resources,
+            isolation: &p.isolation,
+            environment: &p.environment,
+            deleted,
+            author,
+            reason,
+            restored_version,
+        })
+        .get_result::<PolicyVersion>(conn)
+}
+
+/// Historique d’une config, de la plus récente à la plus ancienne version.
+pub fn list_versions(pool: &DbPool, name: &str, role: i32) -> Result<Vec<PolicyVersion>> {
+    let mut conn = pool.get()?;
+    Ok(app_policy_versions::table
+        .filter(app_policy_versions::app_name.eq(name))
+        .filter(app_policy_versions::role_id.eq(role))
+        .order(app_policy_versions::version.desc())
+        .load::<PolicyVersion>(&mut conn)?)
+}
+
+pub fn version_by_id(pool: &DbPool, vid: i32) -> Result<Option<PolicyVersion>> {
+    let mut conn = pool.get()?;
+    Ok(app_policy_versions::table
+        .filter(app_policy_versions::version_id.eq(vid))
+        .first::<PolicyVersion>(&mut conn)
+        .optional()?)
+}
+
+/// Remet la config dans l’état de `v`, recréée si elle a été supprimée depuis,
+/// et enregistre cet état comme une nouvelle version.
+pub fn rollback(pool: &DbPool, v: &PolicyVersion, author: &str, reason: &str) -> Result<PolicyVersion> {
     let mut conn = pool.get()?;
-    Ok(diesel::delete(app_policy::table.filter(app_policy::policy_id.eq(pid)))
-        .execute(&mut conn)? > 0)
+    let p = NewAppPolicy {
+        app_name: &v.app_name,
+        role_id: v.role_id,
+        default_ro: &v.default_ro,
+        default_rw: &v.default_rw,
+        tcp_bind: &v.tcp_bind,
+        tcp_connect: &v.tcp_connect,
+        allowed_ips: &v.allowed_ips,
+        allowed_domains: &v.allowed_domains,
+        fs_rules: &v.fs_rules,
+        scoped: &v.scoped,
+        seccomp: &v.seccomp,
+        resources: &v.resources,
+        isolation: &v.isolation,
+        environment: &v.environment,
+    };
+    Ok(conn.transaction(|conn| {
+        let policy = diesel::insert_into(app_policy::table)
+            .values(&p)
+            .on_conflict((app_policy::app_name, app_policy::role_id))
+            .do_update()
+            .set((
+                app_policy::default_ro.eq(p.default_ro),
+                app_policy::default_rw.eq(p.default_rw),
+                app_policy::tcp_bind.eq(p.tcp_bind),
+                app_policy::tcp_connect.eq(p.tcp_connect),
+                app_policy::allowed_ips.eq(p.allowed_ips),
+                app_policy::allowed_domains.eq(p.allowed_domains),
+                app_policy::fs_rules.eq(p.fs_rules),
+                app_policy::scoped.eq(p.scoped),
+                app_policy::seccomp.eq(p.seccomp),
+                app_policy::resources.eq(p.resources),
+                app_policy::isolation.eq(p.isolation),
+                app_policy::environment.eq(p.environment),
+                app_policy::updated_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .get_result::<AppPolicy>(conn)?;
+        record_version(conn, &policy, false, author, reason, Some(v.version))
+    })?)
 }
@@ -0,0 +1,84 @@
+//! Différences entre deux versions d’une config.
+//!
+//! Tous les champs sont des listes séparées par des ':' ; `\:` (valeurs de
+//! `set=` dans `environment`) ne sépare pas.
+
+use serde::Serialize;
+
+use super::db::PolicyVersion;
+
+/// Un champ qui diffère entre deux versions.
+#[derive(Serialize)]
+pub struct FieldDiff {
+    pub field:   &'static str,
+    pub before:  String,
+    pub after:   String,
+    /// Entrées présentes seulement dans `after`
+    pub added:   Vec<String>,
+    /// Entrées présentes seulement dans `before`
+    pub removed: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct VersionDiff {
+    pub from:    i32,
+    pub to:      i32,
+    pub changes: Vec<FieldDiff>,
+}
+
+fn fields(v: &PolicyVersion) -> [(&'static str, &str); 12] {
+    [
+        ("default_ro",      &v.default_ro),
+        ("default_rw",      &v.default_rw),
+        ("tcp_bind",        &v.tcp_bind),
+        ("tcp_connect",     &v.tcp_connect),
+        ("allowed_ips",     &v.allowed_ips),
+        ("allowed_domains", &v.allowed_domains),
+        ("fs_rules",        &v.fs_rules),
+        ("scoped",          &v.scoped),
+        ("seccomp",         &v.seccomp),
+        ("resources",       &v.resources),
+        ("isolation",       &v.isolation),
+        ("environment",     &v.environment),
+    ]
+}
+
+/// Entrées non vides de `list`.
+fn entries(list: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let (mut start, mut escaped) = (0, false);
+    for (i, c) in list.char_indices() {
+        match c {
+            '\\' if !escaped => { escaped = true; continue; }
+            ':' if !escaped => {
+                out.push(&list[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        escaped = false;
+    }
+    out.push(&list[start..]);
+    out.retain(|e| !e.is_empty());
+    out
+}
+
+/// Champs modifiés de `from` à `to`, dans l’ordre du schéma.
+pub fn diff(from: &PolicyVersion, to: &PolicyVersion) -> VersionDiff {
+    let changes = fields(from)
+        .into_iter()
+        .zip(fields(to))
+        .filter(|((_, before), (_, after))| before != after)
+        .map(|((field, before), (_, after))| {
+            let (old, new) = (entries(before), entries(after));
+            FieldDiff {
+                field,
+                before:  before.to_string(),
+                after:   after.to_string(),
+                added:   new.iter().filter(|e| !old.contains(e)).map(|e| e.to_string()).collect(),
+                removed: old.iter().filter(|e| !new.contains(e)).map(|e| e.to_string()).collect(),
+            }
+        })
+        .collect();
+    VersionDiff { from: from.version_id, to: to.version_id, changes }
+}
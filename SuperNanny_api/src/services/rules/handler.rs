@@ -1,11 +1,15 @@
 use actix_web::{delete, get, post, put, web, HttpResponse};
 
 use crate::state::AppState;
-use super::db;
+use super::{db, diff};
 use serde::Deserialize;
-use crate::admin::Needs;
-use crate::admin::jwt::MANAGE_RULES;
+use crate::admin::{self, Needs};
+use crate::admin::jwt::{AdminClaims, MANAGE_RULES};
 use crate::admin::csrf::Csrf;
+use supernanny_policy_syntax::{
+    validate_environment, validate_fs_rules, validate_isolation, validate_path_list,
+    validate_resources, validate_scopes, validate_seccomp,
+};
 
 // ---------------- roles -----------------------------------
 
@@ -19,6 +23,14 @@ async fn roles(state: web::Data<AppState>) -> HttpResponse {
 
 // ---------------- app_policy -----------------------------------
 
+/// Auteur d’une modification, tel qu’il apparaît dans l’historique.
+fn author(state: &AppState, claims: &AdminClaims) -> String {
+    admin::db::get_admin_username(&state.db, claims.sub)
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| format!("admin#{}", claims.sub))
+}
+
 #[get("/envs")]
 async fn envs(state: web::Data<AppState>) -> HttpResponse {
     match db::list_envs(&state.db) {
@@ -51,10 +63,52 @@ struct EnvBody {
     default_ro: String, default_rw: String,
     tcp_bind: String, tcp_connect: String,
     allowed_ips: String, allowed_domains: String,
+    #[serde(default)]
+    fs_rules: String,
+    #[serde(default)]
+    scoped: String,
+    #[serde(default)]
+    seccomp: String,
+    #[serde(default)]
+    resources: String,
+    #[serde(default)]
+    isolation: String,
+    #[serde(default)]
+    environment: String,
+    /// Motif enregistré dans l’historique
+    #[serde(default)]
+    reason: String,
 }
 
 #[post("/env")]
-async fn create_env(state: web::Data<AppState>, body: web::Json<EnvBody>) -> HttpResponse {
+async fn create_env(
+    state: web::Data<AppState>,
+    claims: web::ReqData<AdminClaims>,
+    body: web::Json<EnvBody>,
+) -> HttpResponse {
+    for paths in [&body.default_ro, &body.default_rw] {
+        if let Err(e) = validate_path_list(paths) {
+            return HttpResponse::BadRequest().body(e.to_string());
+        }
+    }
+    if let Err(e) = validate_fs_rules(&body.fs_rules) {
+        return HttpResponse::BadRequest().body(e.to_string());
+    }
+    if let Err(e) = validate_scopes(&body.scoped) {
+        return HttpResponse::BadRequest().body(e.to_string());
+    }
+    if let Err(e) = validate_seccomp(&body.seccomp) {
+        return HttpResponse::BadRequest().body(e.to_string());
+    }
+    if let Err(e) = validate_resources(&body.resources) {
+        return HttpResponse::BadRequest().body(e.to_string());
+    }
+    if let Err(e) = validate_isolation(&body.isolation) {
+        return HttpResponse::BadRequest().body(e.to_string());
+    }
+    if let Err(e) = validate_environment(&body.environment) {
+        return HttpResponse::BadRequest().body(e.to_string());
+    }
     let p = db::NewAppPolicy {
         app_name: &body.app_name,
         role_id: body.role_id,
@@ -64,8 +118,14 @@ async fn create_env(state: web::Data<AppState>, body: web::Json<EnvBody>) -> Htt
         tcp_connect: &body.tcp_connect,
         allowed_ips: &body.allowed_ips,
         allowed_domains: &body.allowed_domains,
+        fs_rules: &body.fs_rules,
+        scoped: &body.scoped,
+        seccomp: &body.seccomp,
+        resources: &body.resources,
+        isolation: &body.isolation,
+        environment: &body.environment,
     };
-    match db::insert_env(&state.db, p) {
+    match db::insert_env(&state.db, p, &author(&state, &claims), &body.reason) {
         Ok(_)  => HttpResponse::Ok().finish(),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
@@ -74,9 +134,9 @@ async fn create_env(state: web::Data<AppState>, body: web::Json<EnvBody>) -> Htt
 #[derive(Deserialize)]
 pub struct EnvPatch {
     #[serde(default)]
-    pub ll_fs_ro:      Vec<String>,
+    pub ll_fs_ro:      Option<Vec<String>>,
     #[serde(default)]
-    pub ll_fs_rw:      Vec<String>,
+    pub ll_fs_rw:      Option<Vec<String>>,
     #[serde(default)]
     pub ll_tcp_bind:   Option<String>,
     #[serde(default)]
@@ -85,33 +145,156 @@ pub struct EnvPatch {
     pub allowed_ips:   Option<String>,
     #[serde(default)]
     pub allowed_domains:Option<String>,
+    #[serde(default)]
+    pub fs_rules:      Option<String>,
+    #[serde(default)]
+    pub scoped:        Option<String>,
+    #[serde(default)]
+    pub seccomp:       Option<String>,
+    #[serde(default)]
+    pub resources:     Option<String>,
+    #[serde(default)]
+    pub isolation:     Option<String>,
+    #[serde(default)]
+    pub environment:   Option<String>,
+    /// Motif enregistré dans l’historique
+    #[serde(default)]
+    pub reason:        String,
 }
 
 
 #[put("/env_id/{pid}")]
-async fn update_env(state: web::Data<AppState>, pid: web::Path<i32>, body: web::Json<EnvPatch>) -> HttpResponse {
-    let ro  = body.ll_fs_ro.join(":");
-    let rw  = body.ll_fs_rw.join(":");
-    let tcp_b = body.ll_tcp_bind.clone().unwrap_or_else(|| "9418".into());
-    let tcp_c = body.ll_tcp_connect.clone().unwrap_or_else(|| "80:443".into());
-    let ips = body.allowed_ips.clone().unwrap_or_default();
-    let dom = body.allowed_domains.clone().unwrap_or_default();
-
-    match db::update_env(&state.db, pid.into_inner(), &ro, &rw, &tcp_b, &tcp_c, &ips, &dom) {
+async fn update_env(
+    state: web::Data<AppState>,
+    claims: web::ReqData<AdminClaims>,
+    pid: web::Path<i32>,
+    body: web::Json<EnvPatch>,
+) -> HttpResponse {
+    // Champs absents du corps : valeur en base conservée
+    let ro = body.ll_fs_ro.as_ref().map(|v| v.join(":"));
+    let rw = body.ll_fs_rw.as_ref().map(|v| v.join(":"));
+    for paths in [&ro, &rw].into_iter().flatten() {
+        if let Err(e) = validate_path_list(paths) {
+            return HttpResponse::BadRequest().body(e.to_string());
+        }
+    }
+    let checks: [(&Option<String>, fn(&str) -> Result<(), String>); 6] = [
+        (&body.fs_rules,    validate_fs_rules),
+        (&body.scoped,      validate_scopes),
+        (&body.seccomp,     validate_seccomp),
+        (&body.resources,   validate_resources),
+        (&body.isolation,   validate_isolation),
+        (&body.environment, validate_environment),
+    ];
+    for (value, check) in checks {
+        if let Some(Err(e)) = value.as_deref().map(check) {
+            return HttpResponse::BadRequest().body(e.to_string());
+        }
+    }
+    let changes = db::AppPolicyChangeset {
+        default_ro:      ro.as_deref(),
+        default_rw:      rw.as_deref(),
+        tcp_bind:        body.ll_tcp_bind.as_deref(),
+        tcp_connect:     body.ll_tcp_connect.as_deref(),
+        allowed_ips:     body.allowed_ips.as_deref(),
+        allowed_domains: body.allowed_domains.as_deref(),
+        fs_rules:        body.fs_rules.as_deref(),
+        scoped:          body.scoped.as_deref(),
+        seccomp:         body.seccomp.as_deref(),
+        resources:       body.resources.as_deref(),
+        isolation:       body.isolation.as_deref(),
+        environment:     body.environment.as_deref(),
+    };
+
+    match db::update_env(&state.db, pid.into_inner(), changes, &author(&state, &claims), &body.reason) {
         Ok(_)  => HttpResponse::Ok().finish(),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
 }
 
+#[derive(Deserialize)]
+struct Reason {
+    #[serde(default)]
+    reason: String,
+}
+
 #[delete("/env_id/{pid}")]
-async fn delete_env(state: web::Data<AppState>, pid: web::Path<i32>) -> HttpResponse {
-    match db::delete_env(&state.db, pid.into_inner()) {
+async fn delete_env(
+    state: web::Data<AppState>,
+    claims: web::ReqData<AdminClaims>,
+    pid: web::Path<i32>,
+    q: web::Query<Reason>,
+) -> HttpResponse {
+    match db::delete_env(&state.db, pid.into_inner(), &author(&state, &claims), &q.reason) {
         Ok(true)  => HttpResponse::Ok().body("Supprimé"),
         Ok(false) => HttpResponse::NotFound().finish(),
         Err(e)    => HttpResponse::InternalServerError().body(e.to_string()),
     }
 }
 
+// ---------------- app_policy_versions ---------------------------
+
+#[derive(Deserialize)]
+struct VersionsQuery {
+    app_name: String,
+    role_id: i32,
+}
+
+/// Historique d’une config, supprimée ou non
+#[get("/versions")]
+async fn versions(state: web::Data<AppState>, q: web::Query<VersionsQuery>) -> HttpResponse {
+    match db::list_versions(&state.db, &q.app_name, q.role_id) {
+        Ok(v)  => HttpResponse::Ok().json(v),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[derive(Deserialize)]
+struct DiffQuery {
+    from: i32,
+    to: i32,
+}
+
+/// Champs modifiés entre deux versions d’une même config
+#[get("/versions/diff")]
+async fn versions_diff(state: web::Data<AppState>, q: web::Query<DiffQuery>) -> HttpResponse {
+    let (from, to) = match (db::version_by_id(&state.db, q.from), db::version_by_id(&state.db, q.to)) {
+        (Ok(Some(f)), Ok(Some(t))) => (f, t),
+        (Err(e), _) | (_, Err(e))  => return HttpResponse::InternalServerError().body(e.to_string()),
+        _                          => return HttpResponse::NotFound().finish(),
+    };
+    if (&from.app_name, from.role_id) != (&to.app_name, to.role_id) {
+        return HttpResponse::BadRequest().body("Les versions appartiennent à des configs différentes");
+    }
+    HttpResponse::Ok().json(diff::diff(&from, &to))
+}
+
+/// Restaure une version ; le résultat est enregistré comme nouvelle version
+#[post("/versions/{vid}/rollback")]
+async fn rollback(
+    state: web::Data<AppState>,
+    claims: web::ReqData<AdminClaims>,
+    vid: web::Path<i32>,
+    body: web::Json<Reason>,
+) -> HttpResponse {
+    let v = match db::version_by_id(&state.db, vid.into_inner()) {
+        Ok(Some(v)) => v,
+        Ok(None)    => return HttpResponse::NotFound().finish(),
+        Err(e)      => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    if v.deleted {
+        return HttpResponse::BadRequest().body("Impossible de restaurer une suppression");
+    }
+    let reason = match body.reason.trim() {
+        "" => format!("Rollback vers la version {}", v.version),
+        r  => r.to_string(),
+    };
+    match db::rollback(&state.db, &v, &author(&state, &claims), &reason) {
+        Ok(new) => HttpResponse::Ok().json(new),
+        Err(e)  => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
 // ---------------------------------------------------------------------------
 
 pub fn config(cfg: &mut web::ServiceConfig) {
@@ -126,5 +309,8 @@ pub fn config(cfg: &mut web::ServiceConfig) {
         .service(create_env)
         .service(update_env)
         .service(delete_env)
+        .service(versions)
+        .service(versions_diff)
+        .service(rollback)
     );
 }
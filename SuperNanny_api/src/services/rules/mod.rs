@@ -1,4 +1,5 @@
 pub mod db;
+pub mod diff;
 pub mod handler;
 
 pub use handler::config as init;
@@ -7,6 +7,10 @@ use yew::platform::spawn_local;
 use yew::prelude::*;
 
 use crate::api::{fetch_json, fetch_empty};
+use supernanny_policy_syntax::{
+    validate_environment, validate_fs_rules, validate_isolation, validate_path_list,
+    validate_resources, validate_scopes, validate_seccomp,
+};
 
 
 /* -------------------------------------------------------------------------- */
@@ -30,9 +34,50 @@ pub struct AppPolicy {
     tcp_connect: String,
     allowed_ips: String,
     allowed_domains: String,
+    #[serde(default)]
+    fs_rules: String,
+    #[serde(default)]
+    scoped: String,
+    #[serde(default)]
+    seccomp: String,
+    #[serde(default)]
+    resources: String,
+    #[serde(default)]
+    isolation: String,
+    #[serde(default)]
+    environment: String,
     updated_at: String,
 }
 
+/// Une version de l’historique d’une config
+#[derive(Clone, PartialEq, Deserialize, Debug)]
+struct PolicyVersion {
+    version_id: i32,
+    version: i32,
+    deleted: bool,
+    author: String,
+    reason: String,
+    request_id: Option<i32>,
+    restored_version: Option<i32>,
+    created_at: String,
+}
+
+#[derive(Clone, PartialEq, Deserialize, Debug)]
+struct FieldDiff {
+    field: String,
+    before: String,
+    after: String,
+    added: Vec<String>,
+    removed: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, Deserialize, Debug)]
+struct VersionDiff {
+    from: i32,
+    to: i32,
+    changes: Vec<FieldDiff>,
+}
+
 /* -------------------------------------------------------------------------- */
 /*                          helpers front / UI                                */
 /* -------------------------------------------------------------------------- */
@@ -58,6 +103,18 @@ struct UpdateEnvPayload {
     ll_tcp_connect: String,
     allowed_ips: String,
     allowed_domains: String,
+    fs_rules: String,
+    scoped: String,
+    seccomp: String,
+    resources: String,
+    isolation: String,
+    environment: String,
+    reason: String,
+}
+
+#[derive(Serialize, Debug)]
+struct RollbackPayload {
+    reason: String,
 }
 
 /* -------------------------------------------------------------------------- */
@@ -72,6 +129,12 @@ pub fn configurations() -> Html {
     let selected_role = use_state(|| -1);
     let selected_env = use_state(|| None::<AppPolicy>);
 
+    /* historique de la config sélectionnée */
+    let versions = use_state(Vec::<PolicyVersion>::new);
+    let version_diff = use_state(|| None::<VersionDiff>);
+    let history_rev = use_state(|| 0u32);
+    let f_reason = use_state(String::new);
+
     /* form - création */
     let f_app = use_state(String::new);
     let f_ro = use_state(String::new);
@@ -80,6 +143,12 @@ pub fn configurations() -> Html {
     let f_conn = use_state(String::new);
     let f_ips = use_state(String::new);
     let f_dom = use_state(String::new);
+    let f_fs = use_state(String::new);
+    let f_scope = use_state(String::new);
+    let f_seccomp = use_state(String::new);
+    let f_res = use_state(String::new);
+    let f_iso = use_state(String::new);
+    let f_env = use_state(String::new);
 
     /* ------------------------------------------------------------------ */
     /* 1) charge les rôles et toutes les configs au montage               */
@@ -111,6 +180,33 @@ pub fn configurations() -> Html {
         });
     }
 
+    /* ------------------------------------------------------------------ */
+    /* 2) charge l’historique de la config sélectionnée                   */
+    /* ------------------------------------------------------------------ */
+    {
+        let v = versions.clone();
+        let d = version_diff.clone();
+        let key = (
+            selected_env.as_ref().map(|e| (e.app_name.clone(), e.role_id)),
+            *history_rev,
+        );
+        use_effect_with(key, move |(env, _)| {
+            d.set(None);
+            match env.clone() {
+                None => v.set(Vec::new()),
+                Some((app, rid)) => spawn_local(async move {
+                    let app = String::from(js_sys::encode_uri_component(&app));
+                    let path = format!("/rules/versions?app_name={app}&role_id={rid}");
+                    match fetch_json::<(), Vec<PolicyVersion>>(Method::GET, &path, None::<&()>).await {
+                        Ok(list) => v.set(list),
+                        Err(e) => error!("Error fetching history: {:?}", e),
+                    }
+                }),
+            }
+            || ()
+        });
+    }
+
     /* ------------------------------------------------------------------ */
     /* 3) filtrage des envs par rôle                                       */
     /* ------------------------------------------------------------------ */
@@ -155,8 +251,24 @@ pub fn configurations() -> Html {
     let on_update_env = {
         let sel_env = selected_env.clone();
         let envs_st = envs.clone();
+        let f_reason = f_reason.clone();
+        let rev = history_rev.clone();
         Callback::from(move |_| {
             if let Some(env) = (*sel_env).clone() {
+                // Même vérification de syntaxe que le serveur
+                if let Err(e) = validate_path_list(&env.default_ro)
+                    .and(validate_path_list(&env.default_rw))
+                    .and(validate_fs_rules(&env.fs_rules))
+                    .and(validate_scopes(&env.scoped))
+                    .and(validate_seccomp(&env.seccomp))
+                    .and(validate_resources(&env.resources))
+                    .and(validate_isolation(&env.isolation))
+                    .and(validate_environment(&env.environment))
+                {
+                    error!("{e}");
+                    return;
+                }
+
                 // Updated payload structure with correct field names
                 let body = UpdateEnvPayload {
                     ll_fs_ro: env.default_ro.clone(),
@@ -165,12 +277,21 @@ pub fn configurations() -> Html {
                     ll_tcp_connect: env.tcp_connect.clone(),
                     allowed_ips: env.allowed_ips.clone(),
                     allowed_domains: env.allowed_domains.clone(),
+                    fs_rules: env.fs_rules.clone(),
+                    scoped: env.scoped.clone(),
+                    seccomp: env.seccomp.clone(),
+                    resources: env.resources.clone(),
+                    isolation: env.isolation.clone(),
+                    environment: env.environment.clone(),
+                    reason: (*f_reason).clone(),
                 };
                 
                 info!("Updating environment with payload: {:?}", &body);
                 
                 let sel_env2 = sel_env.clone();
                 let envs_st2 = envs_st.clone();
+                let f_reason2 = f_reason.clone();
+                let rev2 = rev.clone();
                 let path = format!("/rules/env_id/{}", env.policy_id);
                 spawn_local(async move {
                     match fetch_json::<_, ()>(Method::PUT, &path, Some(&body)).await {
@@ -184,6 +305,12 @@ pub fn configurations() -> Html {
                                 tcp_connect: body.ll_tcp_connect.clone(),
                                 allowed_ips: body.allowed_ips.clone(),
                                 allowed_domains: body.allowed_domains.clone(),
+                                fs_rules: body.fs_rules.clone(),
+                                scoped: body.scoped.clone(),
+                                seccomp: body.seccomp.clone(),
+                                resources: body.resources.clone(),
+                                isolation: body.isolation.clone(),
+                                environment: body.environment.clone(),
                                 ..env.clone()
                             };
                             
@@ -199,6 +326,10 @@ pub fn configurations() -> Html {
                             
                             // Update selected env
                             sel_env2.set(Some(updated_env));
+
+                            // La modification est une nouvelle version
+                            f_reason2.set(String::new());
+                            rev2.set(*rev2 + 1);
                         },
                         Err(e) => error!("Update failed: {:?}", e),
                     }
@@ -214,6 +345,7 @@ pub fn configurations() -> Html {
     let on_delete_env = {
         let envs_state = envs.clone();
         let selected_env_state = selected_env.clone();
+        let f_reason = f_reason.clone();
 
         Callback::from(move |_| {
             if let Some(env) = (*selected_env_state).clone() {
@@ -224,10 +356,12 @@ pub fn configurations() -> Html {
                 let pid                 = env.policy_id;
                 let envs_after_delete   = envs_state.clone();
                 let selected_env_clear  = selected_env_state.clone();
+                let reason              = String::from(js_sys::encode_uri_component(&f_reason));
+                let f_reason_clear      = f_reason.clone();
 
                 spawn_local(async move {
-                    // DELETE /rules/env_id/{pid}
-                    let path = format!("/rules/env_id/{pid}");
+                    // DELETE /rules/env_id/{pid}?reason=…
+                    let path = format!("/rules/env_id/{pid}?reason={reason}");
                     match fetch_empty(Method::DELETE, &path, None::<&()>).await {
                         Ok(_) => {
                             // 1) retrait immédiat de la liste
@@ -240,6 +374,7 @@ pub fn configurations() -> Html {
                             );
                             // 2) panneau central vidé
                             selected_env_clear.set(None);
+                            f_reason_clear.set(String::new());
                             info!("Suppression réussie");
                         }
                         Err(e) => error!("Suppression KO : {:?}", e),
@@ -266,6 +401,12 @@ pub fn configurations() -> Html {
         let f_conn = f_conn.clone();
         let f_ips  = f_ips.clone();
         let f_dom  = f_dom.clone();
+        let f_fs   = f_fs.clone();
+        let f_scope = f_scope.clone();
+        let f_seccomp = f_seccomp.clone();
+        let f_res = f_res.clone();
+        let f_iso = f_iso.clone();
+        let f_env = f_env.clone();
 
         // remise à zéro du formulaire
         let reset_form = {
@@ -276,6 +417,12 @@ pub fn configurations() -> Html {
             let f_conn = f_conn.clone();
             let f_ips  = f_ips.clone();
             let f_dom  = f_dom.clone();
+            let f_fs   = f_fs.clone();
+            let f_scope = f_scope.clone();
+            let f_seccomp = f_seccomp.clone();
+            let f_res = f_res.clone();
+            let f_iso = f_iso.clone();
+            let f_env = f_env.clone();
             move || {
                 f_app.set(String::new());
                 f_ro.set(String::new());
@@ -284,6 +431,12 @@ pub fn configurations() -> Html {
                 f_conn.set(String::new());
                 f_ips.set(String::new());
                 f_dom.set(String::new());
+                f_fs.set(String::new());
+                f_scope.set(String::new());
+                f_seccomp.set(String::new());
+                f_res.set(String::new());
+                f_iso.set(String::new());
+                f_env.set(String::new());
             }
         };
 
@@ -300,6 +453,18 @@ pub fn configurations() -> Html {
                 error!("Le nom de l’application est requis");
                 return;
             }
+            if let Err(e) = validate_path_list(&f_ro)
+                .and(validate_path_list(&f_rw))
+                .and(validate_fs_rules(&f_fs))
+                .and(validate_scopes(&f_scope))
+                .and(validate_seccomp(&f_seccomp))
+                .and(validate_resources(&f_res))
+                .and(validate_isolation(&f_iso))
+                .and(validate_environment(&f_env))
+            {
+                error!("{e}");
+                return;
+            }
 
             let payload = serde_json::json!({
                 "app_name":        app,
@@ -310,6 +475,12 @@ pub fn configurations() -> Html {
                 "tcp_connect":     (*f_conn).clone(),
                 "allowed_ips":     (*f_ips).clone(),
                 "allowed_domains": (*f_dom).clone(),
+                "fs_rules":        (*f_fs).clone(),
+                "scoped":          (*f_scope).clone(),
+                "seccomp":         (*f_seccomp).clone(),
+                "resources":       (*f_res).clone(),
+                "isolation":       (*f_iso).clone(),
+                "environment":     (*f_env).clone(),
             });
 
             let envs_after = envs_state.clone();
@@ -345,6 +516,69 @@ pub fn configurations() -> Html {
         })
     };
 
+    // ------------------------------------------------------------------
+    // 11) Historique : comparer deux versions, restaurer une version
+    // ------------------------------------------------------------------
+
+    let on_diff = {
+        let d = version_diff.clone();
+        Callback::from(move |(from, to): (i32, i32)| {
+            let d = d.clone();
+            spawn_local(async move {
+                let path = format!("/rules/versions/diff?from={from}&to={to}");
+                match fetch_json::<(), VersionDiff>(Method::GET, &path, None::<&()>).await {
+                    Ok(diff) => d.set(Some(diff)),
+                    Err(e) => error!("Error fetching diff: {:?}", e),
+                }
+            });
+        })
+    };
+
+    let on_rollback = {
+        let sel_env = selected_env.clone();
+        let envs_st = envs.clone();
+        let f_reason = f_reason.clone();
+        let rev = history_rev.clone();
+        Callback::from(move |v: PolicyVersion| {
+            let Some(env) = (*sel_env).clone() else { return };
+            if !confirm(&format!("Restaurer la version {} de cette configuration ?", v.version)) {
+                return;
+            }
+
+            let body = RollbackPayload { reason: (*f_reason).clone() };
+            let sel_env2 = sel_env.clone();
+            let envs_st2 = envs_st.clone();
+            let f_reason2 = f_reason.clone();
+            let rev2 = rev.clone();
+            spawn_local(async move {
+                let path = format!("/rules/versions/{}/rollback", v.version_id);
+                if let Err(e) = fetch_json::<_, PolicyVersion>(Method::POST, &path, Some(&body)).await {
+                    error!("Rollback KO : {e:?}");
+                    return;
+                }
+
+                // recharge la config restaurée
+                let path = format!("/rules/env_id/{}", env.policy_id);
+                match fetch_json::<(), AppPolicy>(Method::GET, &path, None::<&()>).await {
+                    Ok(restored) => {
+                        let mut list = (*envs_st2).clone();
+                        for e in &mut list {
+                            if e.policy_id == restored.policy_id {
+                                *e = restored.clone();
+                            }
+                        }
+                        envs_st2.set(list);
+                        sel_env2.set(Some(restored));
+                        f_reason2.set(String::new());
+                        rev2.set(*rev2 + 1);
+                        info!("Rollback OK");
+                    }
+                    Err(e) => error!("Impossible de recharger la config restaurée : {e:?}"),
+                }
+            });
+        })
+    };
+
     /* ----------- helpers input binding -------------------------------- */
     let bind_input = |st: UseStateHandle<String>| {
         Callback::from(move |e: InputEvent| {
@@ -365,6 +599,12 @@ pub fn configurations() -> Html {
                     "tcp_connect" => env.tcp_connect = value,
                     "allowed_ips" => env.allowed_ips = value,
                     "allowed_domains" => env.allowed_domains = value,
+                    "fs_rules" => env.fs_rules = value,
+                    "scoped" => env.scoped = value,
+                    "seccomp" => env.seccomp = value,
+                    "resources" => env.resources = value,
+                    "isolation" => env.isolation = value,
+                    "environment" => env.environment = value,
                     _ => {}
                 }
                 se.set(Some(env));
@@ -499,7 +739,7 @@ pub fn configurations() -> Html {
                                                     transition: border-color 0.3s, box-shadow 0.3s;
                                                 "
                                             />
-                                            <p style="margin-top: 0.3rem; color: #888; font-size: 0.8rem;">{"Chemins accessibles en lecture seule"}</p>
+                                            <p style="margin-top: 0.3rem; color: #888; font-size: 0.8rem;">{"Chemins accessibles en lecture seule (modèles ${HOME}, ${USER}, ${UID}, ${XDG_RUNTIME_DIR}, ${APP_DIR} et globs acceptés)"}</p>
                                         </div>
 
                                         <div style="margin-bottom: 1rem;">
@@ -519,7 +759,7 @@ pub fn configurations() -> Html {
                                                     transition: border-color 0.3s, box-shadow 0.3s;
                                                 "
                                             />
-                                            <p style="margin-top: 0.3rem; color: #888; font-size: 0.8rem;">{"Chemins accessibles en lecture/écriture"}</p>
+                                            <p style="margin-top: 0.3rem; color: #888; font-size: 0.8rem;">{"Chemins accessibles en lecture/écriture (modèles ${HOME}, ${USER}, ${UID}, ${XDG_RUNTIME_DIR}, ${APP_DIR} et globs acceptés)"}</p>
                                         </div>
 
                                         <div style="margin-bottom: 1rem;">
@@ -600,6 +840,147 @@ pub fn configurations() -> Html {
                                             />
                                         </div>
 
+                                        <div style="margin-bottom: 1rem;">
+                                            <label style="display: block; margin-bottom: 0.3rem; font-weight: 500;">{"FS_RULES"}</label>
+                                            <input 
+                                                type="text" 
+                                                value={env.fs_rules.clone()}
+                                                oninput={bind_env_input("fs_rules", selected_env.clone())}
+                                                style="
+                                                    width: 100%;
+                                                    padding: 0.75rem;
+                                                    background-color: #fff;
+                                                    border: 1px solid #ccc;
+                                                    border-radius: 4px;
+                                                    color: #333;
+                                                    box-sizing: border-box;
+                                                    transition: border-color 0.3s, box-shadow 0.3s;
+                                                "
+                                            />
+                                            <p style="margin-top: 0.3rem; color: #888; font-size: 0.8rem;">{"Droits explicites par chemin : /opt/toolchain=execute,read_dir:/dev/dri=read_file,ioctl_dev"}</p>
+                                        </div>
+
+                                        <div style="margin-bottom: 1rem;">
+                                            <label style="display: block; margin-bottom: 0.3rem; font-weight: 500;">{"SCOPED"}</label>
+                                            <input 
+                                                type="text" 
+                                                value={env.scoped.clone()}
+                                                oninput={bind_env_input("scoped", selected_env.clone())}
+                                                style="
+                                                    width: 100%;
+                                                    padding: 0.75rem;
+                                                    background-color: #fff;
+                                                    border: 1px solid #ccc;
+                                                    border-radius: 4px;
+                                                    color: #333;
+                                                    box-sizing: border-box;
+                                                    transition: border-color 0.3s, box-shadow 0.3s;
+                                                "
+                                            />
+                                            <p style="margin-top: 0.3rem; color: #888; font-size: 0.8rem;">{"IPC bloquées hors de la sandbox : abstract_unix_socket:signal"}</p>
+                                        </div>
+
+                                        <div style="margin-bottom: 1rem;">
+                                            <label style="display: block; margin-bottom: 0.3rem; font-weight: 500;">{"SECCOMP"}</label>
+                                            <input 
+                                                type="text" 
+                                                value={env.seccomp.clone()}
+                                                oninput={bind_env_input("seccomp", selected_env.clone())}
+                                                style="
+                                                    width: 100%;
+                                                    padding: 0.75rem;
+                                                    background-color: #fff;
+                                                    border: 1px solid #ccc;
+                                                    border-radius: 4px;
+                                                    color: #333;
+                                                    box-sizing: border-box;
+                                                    transition: border-color 0.3s, box-shadow 0.3s;
+                                                "
+                                            />
+                                            <p style="margin-top: 0.3rem; color: #888; font-size: 0.8rem;">{"Appels système refusés : no-ptrace:no-raw-sockets:deny=mount,umount2"}</p>
+                                        </div>
+
+                                        <div style="margin-bottom: 1rem;">
+                                            <label style="display: block; margin-bottom: 0.3rem; font-weight: 500;">{"RESOURCES"}</label>
+                                            <input 
+                                                type="text" 
+                                                value={env.resources.clone()}
+                                                oninput={bind_env_input("resources", selected_env.clone())}
+                                                style="
+                                                    width: 100%;
+                                                    padding: 0.75rem;
+                                                    background-color: #fff;
+                                                    border: 1px solid #ccc;
+                                                    border-radius: 4px;
+                                                    color: #333;
+                                                    box-sizing: border-box;
+                                                    transition: border-color 0.3s, box-shadow 0.3s;
+                                                "
+                                            />
+                                            <p style="margin-top: 0.3rem; color: #888; font-size: 0.8rem;">{"Limites cgroup v2 : memory=512M:pids=256:cpu=50%:io=200"}</p>
+                                        </div>
+
+                                        <div style="margin-bottom: 1rem;">
+                                            <label style="display: block; margin-bottom: 0.3rem; font-weight: 500;">{"ISOLATION"}</label>
+                                            <input 
+                                                type="text" 
+                                                value={env.isolation.clone()}
+                                                oninput={bind_env_input("isolation", selected_env.clone())}
+                                                style="
+                                                    width: 100%;
+                                                    padding: 0.75rem;
+                                                    background-color: #fff;
+                                                    border: 1px solid #ccc;
+                                                    border-radius: 4px;
+                                                    color: #333;
+                                                    box-sizing: border-box;
+                                                    transition: border-color 0.3s, box-shadow 0.3s;
+                                                "
+                                            />
+                                            <p style="margin-top: 0.3rem; color: #888; font-size: 0.8rem;">{"Namespaces : private-tmp:scratch-home:read-only-root:no-network:hostname=sandbox"}</p>
+                                        </div>
+
+                                        <div style="margin-bottom: 1rem;">
+                                            <label style="display: block; margin-bottom: 0.3rem; font-weight: 500;">{"ENVIRONMENT"}</label>
+                                            <input 
+                                                type="text" 
+                                                value={env.environment.clone()}
+                                                oninput={bind_env_input("environment", selected_env.clone())}
+                                                style="
+                                                    width: 100%;
+                                                    padding: 0.75rem;
+                                                    background-color: #fff;
+                                                    border: 1px solid #ccc;
+                                                    border-radius: 4px;
+                                                    color: #333;
+                                                    box-sizing: border-box;
+                                                    transition: border-color 0.3s, box-shadow 0.3s;
+                                                "
+                                            />
+                                            <p style="margin-top: 0.3rem; color: #888; font-size: 0.8rem;">{"Variables transmises : allow=PATH,HOME,LANG,LC_*:deny=AWS_*:redact=ghp_*:set=EDITOR=vim"}</p>
+                                        </div>
+
+                                        <div style="margin-bottom: 1rem;">
+                                            <label style="display: block; margin-bottom: 0.3rem; font-weight: 500;">{"Motif"}</label>
+                                            <input 
+                                                type="text" 
+                                                placeholder="Raison de la modification"
+                                                value={(*f_reason).clone()}
+                                                oninput={bind_input(f_reason.clone())}
+                                                style="
+                                                    width: 100%;
+                                                    padding: 0.75rem;
+                                                    background-color: #fff;
+                                                    border: 1px solid #ccc;
+                                                    border-radius: 4px;
+                                                    color: #333;
+                                                    box-sizing: border-box;
+                                                    transition: border-color 0.3s, box-shadow 0.3s;
+                                                "
+                                            />
+                                            <p style="margin-top: 0.3rem; color: #888; font-size: 0.8rem;">{"Enregistré dans l’historique avec la modification, la suppression ou la restauration"}</p>
+                                        </div>
+
                                         <button 
                                             onclick={on_update_env.clone()}
                                             style="
@@ -618,6 +999,107 @@ pub fn configurations() -> Html {
                                             { "Enregistrer" }
                                         </button>
                                     </div>
+
+                                    <h4 style="font-size: 1rem; font-weight: 500; margin-top: 2rem; margin-bottom: 1rem; border-bottom: 1px solid #eee; padding-bottom: 0.5rem;">{"Historique"}</h4>
+                                    {
+                                        if versions.is_empty() {
+                                            html! {
+                                                <p style="color: #888; text-align: center; padding: 1rem 0;">{"Aucune version enregistrée."}</p>
+                                            }
+                                        } else {
+                                            html! {
+                                                <ul style="list-style: none; padding: 0; margin: 0;">
+                                                    { for versions.iter().enumerate().map(|(i, v)| {
+                                                        // la liste va de la plus récente à la plus ancienne
+                                                        let previous = versions.get(i + 1).map(|p| p.version_id);
+                                                        let mut origin = Vec::new();
+                                                        if let Some(rid) = v.request_id {
+                                                            origin.push(format!("demande #{rid}"));
+                                                        }
+                                                        if let Some(rv) = v.restored_version {
+                                                            origin.push(format!("restaure la v{rv}"));
+                                                        }
+                                                        if v.deleted {
+                                                            origin.push("suppression".to_string());
+                                                        }
+                                                        html!{
+                                                            <li style="padding: 0.75rem 1rem; border-radius: 4px; margin-bottom: 0.5rem; background-color: #f5f5f5; border: 1px solid #ddd; font-size: 0.875rem;">
+                                                                <div style="display: flex; justify-content: space-between; align-items: center;">
+                                                                    <strong>{ format!("v{}", v.version) }</strong>
+                                                                    <span style="color: #888; font-size: 0.8rem;">{ format!("{} — {}", v.created_at, v.author) }</span>
+                                                                </div>
+                                                                <p style="margin: 0.3rem 0; color: #555;">
+                                                                    { if v.reason.is_empty() { "Sans motif".to_string() } else { v.reason.clone() } }
+                                                                </p>
+                                                                if !origin.is_empty() {
+                                                                    <p style="margin: 0.3rem 0; color: #888; font-size: 0.8rem;">{ origin.join(", ") }</p>
+                                                                }
+                                                                <div style="display: flex; gap: 0.5rem; margin-top: 0.5rem;">
+                                                                    if let Some(prev) = previous {
+                                                                        <button
+                                                                            onclick={{
+                                                                                let cb = on_diff.clone();
+                                                                                let to = v.version_id;
+                                                                                Callback::from(move |_| cb.emit((prev, to)))
+                                                                            }}
+                                                                            style="border: 1px solid #3f51b5; background: transparent; color: #3f51b5; padding: 0.25rem 0.5rem; font-size: 0.8rem; border-radius: 4px; cursor: pointer;"
+                                                                        >
+                                                                            { "Comparer à la précédente" }
+                                                                        </button>
+                                                                    }
+                                                                    if i > 0 && !v.deleted {
+                                                                        <button
+                                                                            onclick={{
+                                                                                let cb = on_rollback.clone();
+                                                                                let v = v.clone();
+                                                                                Callback::from(move |_| cb.emit(v.clone()))
+                                                                            }}
+                                                                            style="border: 1px solid #e67e22; background: transparent; color: #e67e22; padding: 0.25rem 0.5rem; font-size: 0.8rem; border-radius: 4px; cursor: pointer;"
+                                                                        >
+                                                                            { "Restaurer" }
+                                                                        </button>
+                                                                    }
+                                                                </div>
+                                                            </li>
+                                                        }
+                                                    })}
+                                                </ul>
+                                            }
+                                        }
+                                    }
+                                    {
+                                        if let Some(diff) = &*version_diff {
+                                            let label = |id: i32| versions
+                                                .iter()
+                                                .find(|v| v.version_id == id)
+                                                .map_or_else(|| format!("#{id}"), |v| format!("v{}", v.version));
+                                            html! {
+                                                <div style="margin-top: 1rem; padding: 1rem; border: 1px solid #ddd; border-radius: 4px; font-size: 0.8rem;">
+                                                    <strong>{ format!("Différences {} → {}", label(diff.from), label(diff.to)) }</strong>
+                                                    if diff.changes.is_empty() {
+                                                        <p style="color: #888;">{"Aucune différence."}</p>
+                                                    }
+                                                    { for diff.changes.iter().map(|c| html! {
+                                                        <div style="margin-top: 0.75rem;">
+                                                            <div style="font-weight: 500;">{ c.field.to_uppercase() }</div>
+                                                            { for c.removed.iter().map(|r| html! {
+                                                                <div style="color: #e74c3c; font-family: monospace;">{ format!("- {r}") }</div>
+                                                            })}
+                                                            { for c.added.iter().map(|a| html! {
+                                                                <div style="color: #27ae60; font-family: monospace;">{ format!("+ {a}") }</div>
+                                                            })}
+                                                            if c.added.is_empty() && c.removed.is_empty() {
+                                                                // mêmes entrées, ordre différent
+                                                                <div style="color: #888; font-family: monospace;">{ format!("{} → {}", c.before, c.after) }</div>
+                                                            }
+                                                        </div>
+                                                    })}
+                                                </div>
+                                            }
+                                        } else {
+                                            html! {}
+                                        }
+                                    }
                                 </div>
                             }
                         } else {
@@ -669,7 +1151,7 @@ pub fn configurations() -> Html {
                             <label style="display: block; margin-bottom: 0.3rem; font-weight: 500;">{"LL_FS_RO"}</label>
                             <input 
                                 type="text" 
-                                placeholder="/usr:/lib"
+                                placeholder="/usr:/lib:${HOME}/.config/app"
                                 value={(*f_ro).clone()} 
                                 oninput={bind_input(f_ro.clone())} 
                                 disabled={*selected_role == -1}
@@ -685,6 +1167,7 @@ pub fn configurations() -> Html {
                                 "
                             />
                             <p style="margin-top: 0.3rem; color: #888; font-size: 0.8rem;">{"Chemins accessibles en lecture seule (séparés par des ':')"}</p>
+                            <p style="margin-top: 0.3rem; color: #888; font-size: 0.8rem;">{"Variables : ${HOME}, ${USER}, ${UID}, ${XDG_RUNTIME_DIR}, ${APP_DIR} ; globs * ? [a-z] (pas de **)"}</p>
                         </div>
 
                         <div style="margin-bottom: 1rem;">
@@ -707,6 +1190,7 @@ pub fn configurations() -> Html {
                                 "
                             />
                             <p style="margin-top: 0.3rem; color: #888; font-size: 0.8rem;">{"Chemins accessibles en lecture/écriture (séparés par des ':')"}</p>
+                            <p style="margin-top: 0.3rem; color: #888; font-size: 0.8rem;">{"Variables : ${HOME}, ${USER}, ${UID}, ${XDG_RUNTIME_DIR}, ${APP_DIR} ; globs * ? [a-z] (pas de **)"}</p>
                         </div>
 
                         <div style="margin-bottom: 1rem;">
@@ -793,6 +1277,138 @@ pub fn configurations() -> Html {
                             />
                         </div>
 
+                        <div style="margin-bottom: 1rem;">
+                            <label style="display: block; margin-bottom: 0.3rem; font-weight: 500;">{"FS_RULES"}</label>
+                            <input 
+                                type="text" 
+                                placeholder="/opt/toolchain=execute,read_dir"
+                                value={(*f_fs).clone()} 
+                                oninput={bind_input(f_fs.clone())} 
+                                disabled={*selected_role == -1}
+                                style="
+                                    width: 100%;
+                                    padding: 0.75rem;
+                                    background-color: #fff;
+                                    border: 1px solid #ccc;
+                                    border-radius: 4px;
+                                    color: #333;
+                                    box-sizing: border-box;
+                                    transition: border-color 0.3s, box-shadow 0.3s;
+                                "
+                            />
+                            <p style="margin-top: 0.3rem; color: #888; font-size: 0.8rem;">{"Droits par chemin (<chemin>=<droit>,<droit>, séparés par des ':') : execute, read_file, read_dir, write_file, truncate, make_dir, ioctl_dev, ro, rw…"}</p>
+                        </div>
+
+                        <div style="margin-bottom: 1rem;">
+                            <label style="display: block; margin-bottom: 0.3rem; font-weight: 500;">{"SCOPED"}</label>
+                            <input 
+                                type="text" 
+                                placeholder="abstract_unix_socket:signal"
+                                value={(*f_scope).clone()} 
+                                oninput={bind_input(f_scope.clone())} 
+                                disabled={*selected_role == -1}
+                                style="
+                                    width: 100%;
+                                    padding: 0.75rem;
+                                    background-color: #fff;
+                                    border: 1px solid #ccc;
+                                    border-radius: 4px;
+                                    color: #333;
+                                    box-sizing: border-box;
+                                    transition: border-color 0.3s, box-shadow 0.3s;
+                                "
+                            />
+                            <p style="margin-top: 0.3rem; color: #888; font-size: 0.8rem;">{"Bloque les IPC vers l’extérieur de la sandbox (Linux ≥ 6.12, ignoré sinon) : abstract_unix_socket, signal"}</p>
+                        </div>
+
+                        <div style="margin-bottom: 1rem;">
+                            <label style="display: block; margin-bottom: 0.3rem; font-weight: 500;">{"SECCOMP"}</label>
+                            <input 
+                                type="text" 
+                                placeholder="no-ptrace:no-kernel-modules:deny=mount"
+                                value={(*f_seccomp).clone()} 
+                                oninput={bind_input(f_seccomp.clone())} 
+                                disabled={*selected_role == -1}
+                                style="
+                                    width: 100%;
+                                    padding: 0.75rem;
+                                    background-color: #fff;
+                                    border: 1px solid #ccc;
+                                    border-radius: 4px;
+                                    color: #333;
+                                    box-sizing: border-box;
+                                    transition: border-color 0.3s, box-shadow 0.3s;
+                                "
+                            />
+                            <p style="margin-top: 0.3rem; color: #888; font-size: 0.8rem;">{"Presets no-ptrace, no-kernel-modules, no-raw-sockets ; deny=<appel>,… refuse des appels ; allow=<appel>,… n’autorise que ceux-là (EPERM sinon)"}</p>
+                        </div>
+
+                        <div style="margin-bottom: 1rem;">
+                            <label style="display: block; margin-bottom: 0.3rem; font-weight: 500;">{"RESOURCES"}</label>
+                            <input 
+                                type="text" 
+                                placeholder="memory=512M:pids=256:cpu=50%"
+                                value={(*f_res).clone()} 
+                                oninput={bind_input(f_res.clone())} 
+                                disabled={*selected_role == -1}
+                                style="
+                                    width: 100%;
+                                    padding: 0.75rem;
+                                    background-color: #fff;
+                                    border: 1px solid #ccc;
+                                    border-radius: 4px;
+                                    color: #333;
+                                    box-sizing: border-box;
+                                    transition: border-color 0.3s, box-shadow 0.3s;
+                                "
+                            />
+                            <p style="margin-top: 0.3rem; color: #888; font-size: 0.8rem;">{"Limites par exécution : memory (octets, suffixes K/M/G/T), pids, cpu (% d’un CPU), io (poids 1 à 10000)"}</p>
+                        </div>
+
+                        <div style="margin-bottom: 1rem;">
+                            <label style="display: block; margin-bottom: 0.3rem; font-weight: 500;">{"ISOLATION"}</label>
+                            <input 
+                                type="text" 
+                                placeholder="private-tmp:no-network:hostname=sandbox"
+                                value={(*f_iso).clone()} 
+                                oninput={bind_input(f_iso.clone())} 
+                                disabled={*selected_role == -1}
+                                style="
+                                    width: 100%;
+                                    padding: 0.75rem;
+                                    background-color: #fff;
+                                    border: 1px solid #ccc;
+                                    border-radius: 4px;
+                                    color: #333;
+                                    box-sizing: border-box;
+                                    transition: border-color 0.3s, box-shadow 0.3s;
+                                "
+                            />
+                            <p style="margin-top: 0.3rem; color: #888; font-size: 0.8rem;">{"/tmp et /dev/shm privés (private-tmp), $HOME de travail par application (scratch-home), racine en lecture seule sauf chemins RW (read-only-root), réseau vide (no-network), hostname=<nom>"}</p>
+                        </div>
+
+                        <div style="margin-bottom: 1rem;">
+                            <label style="display: block; margin-bottom: 0.3rem; font-weight: 500;">{"ENVIRONMENT"}</label>
+                            <input 
+                                type="text" 
+                                placeholder="allow=PATH,HOME,LANG,LC_*:deny=AWS_*,*_TOKEN"
+                                value={(*f_env).clone()} 
+                                oninput={bind_input(f_env.clone())} 
+                                disabled={*selected_role == -1}
+                                style="
+                                    width: 100%;
+                                    padding: 0.75rem;
+                                    background-color: #fff;
+                                    border: 1px solid #ccc;
+                                    border-radius: 4px;
+                                    color: #333;
+                                    box-sizing: border-box;
+                                    transition: border-color 0.3s, box-shadow 0.3s;
+                                "
+                            />
+                            <p style="margin-top: 0.3rem; color: #888; font-size: 0.8rem;">{"allow=<motifs> ne transmet que ces variables ; deny=<motifs> les retire ; redact=<motifs> retire les variables dont la valeur correspond ; set=NOM=valeur force une valeur"}</p>
+                        </div>
+
                         <button 
                             onclick={on_create_env}
                             disabled={*selected_role == -1}
@@ -5,6 +5,7 @@ use std::fs::{self, File, OpenOptions};
 use std::io::{Read, Write};
 use std::os::raw::{c_char, c_int, c_void};
 use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
 use std::path::PathBuf;
 use std::time::{SystemTime, UNIX_EPOCH};
 use zeroize::Zeroize;
@@ -21,7 +22,9 @@ const PAM_PROMPT_ECHO_OFF: c_int = 1;
 // Token cache settings
 const TOKEN_CACHE_DIR: &str = "supernanny";
 const TOKEN_CACHE_FILE: &str = "session.cache";
-const TOKEN_VALIDITY_HOURS: u64 = 8;
+const TOKEN_LOCK_FILE: &str = "session.lock";
+// Access token lifetime when the server does not tell (`expires_in`)
+const DEFAULT_TOKEN_VALIDITY_SECS: u64 = 3600;
 
 // Add logging macro
 macro_rules! pam_log {
@@ -69,16 +72,23 @@ struct LoginResponse {
     token: String,
     #[serde(default)]
     refresh_token: Option<String>,
+    #[serde(default)]
+    expires_in: Option<u64>,
 }
 
 impl CachedToken {
-    fn new(token: String, username: String, refresh_token: Option<String>) -> Self {
+    fn new(response: LoginResponse, username: String) -> Self {
         let expires_at = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs()
-            + TOKEN_VALIDITY_HOURS * 3600;
-        Self { token, username, expires_at, refresh_token }
+            + response.expires_in.unwrap_or(DEFAULT_TOKEN_VALIDITY_SECS);
+        Self {
+            token: response.token,
+            username,
+            expires_at,
+            refresh_token: response.refresh_token,
+        }
     }
 
     fn is_expired(&self) -> bool {
@@ -87,7 +97,7 @@ impl CachedToken {
 
     fn is_near_expiry(&self) -> bool {
         let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-        (self.expires_at - now) < 1800 // 30 minutes
+        self.expires_at.saturating_sub(now) < 1800 // 30 minutes
     }
 }
 
@@ -187,19 +197,25 @@ fn handle_authenticate(pamh: *mut PamHandle, args: &[String]) -> Result<()> {
     // Check cached token first
     if let Ok(cached) = load_cached_token(uid) {
         pam_log!("DEBUG", "Found cached token for user: {}", cached.username);
-        if !cached.is_expired() && cached.username == username {
-            pam_log!("INFO", "Using valid cached token");
-            return Ok(());
-        }
-        if cached.is_near_expiry() {
-            pam_log!("DEBUG", "Token near expiry, attempting refresh");
-            if let Some(rt) = &cached.refresh_token {
-                if let Ok(new) = refresh_auth_token(rt, &username) {
-                    save_cached_token(uid, &new)?;
-                    pam_log!("INFO", "Token refreshed successfully");
-                    return Ok(());
+        if cached.username == username {
+            if !cached.is_near_expiry() {
+                pam_log!("INFO", "Using valid cached token");
+                return Ok(());
+            }
+            if cached.refresh_token.is_some() {
+                pam_log!("DEBUG", "Token near expiry, attempting refresh");
+                match refresh_session(uid, &username) {
+                    Ok(_) => {
+                        pam_log!("INFO", "Token refreshed successfully");
+                        return Ok(());
+                    }
+                    Err(e) => pam_log!("DEBUG", "Token refresh failed: {}", e),
                 }
             }
+            if !cached.is_expired() {
+                pam_log!("INFO", "Using cached token until it expires");
+                return Ok(());
+            }
         }
         pam_log!("DEBUG", "Cached token expired or invalid");
     } else {
@@ -260,12 +276,17 @@ pub unsafe extern "C" fn pam_sm_open_session(
 
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn pam_sm_close_session(
-    _pamh: *mut PamHandle,
+    pamh: *mut PamHandle,
     _flags: i32,
     _argc: i32,
     _argv: *const *const c_char,
 ) -> i32 {
     pam_log!("DEBUG", "pam_sm_close_session called");
+    // Closing must not fail: a server left unreachable only keeps the
+    // refresh token alive until it expires
+    if let Err(e) = handle_session_close(pamh) {
+        pam_log!("ERROR", "Session revocation failed: {}", e);
+    }
     0
 }
 
@@ -279,6 +300,26 @@ fn handle_session_open(pamh: *mut PamHandle) -> Result<()> {
     Ok(())
 }
 
+/// Revokes the session's refresh tokens on the server and forgets them.
+fn handle_session_close(pamh: *mut PamHandle) -> Result<()> {
+    let username = get_pam_user(pamh)?;
+    let uid = get_user_uid(&username)?;
+    let _lock = lock_session(uid);
+    let cached = match load_cached_token(uid) {
+        Ok(cached) => cached,
+        Err(_) => return Ok(()), // Not a SuperNanny session
+    };
+    clean_cached_token(uid)?;
+    if cached.username != username {
+        return Ok(());
+    }
+    if let Some(rt) = &cached.refresh_token {
+        revoke_refresh_token(rt)?;
+        pam_log!("INFO", "Session of {} revoked", username);
+    }
+    Ok(())
+}
+
 fn get_pam_user(pamh: *mut PamHandle) -> Result<String> {
     unsafe {
         let mut ptr: *const c_char = std::ptr::null();
@@ -439,7 +480,7 @@ fn authenticate_with_server(username: &str, password: &str) -> Result<CachedToke
     
     let lr: LoginResponse = resp.json()?;
     pam_log!("INFO", "Server authentication successful");
-    Ok(CachedToken::new(lr.token, username.to_string(), lr.refresh_token))
+    Ok(CachedToken::new(lr, username.to_string()))
 }
 
 fn refresh_auth_token(rt: &str, username: &str) -> Result<CachedToken> {
@@ -462,7 +503,61 @@ fn refresh_auth_token(rt: &str, username: &str) -> Result<CachedToken> {
     
     let lr: LoginResponse = resp.json()?;
     pam_log!("INFO", "Token refreshed successfully");
-    Ok(CachedToken::new(lr.token, username.to_string(), lr.refresh_token))
+    Ok(CachedToken::new(lr, username.to_string()))
+}
+
+/// Refreshes the cached session under the session lock. Refresh tokens are
+/// single use, and presenting one twice revokes the session: when another
+/// process of the user (e.g. the sandboxer) refreshed it meanwhile, its
+/// result is used instead.
+fn refresh_session(uid: u32, username: &str) -> Result<CachedToken> {
+    let _lock = lock_session(uid);
+    let cached = load_cached_token(uid)?;
+    if !cached.is_near_expiry() {
+        return Ok(cached);
+    }
+    let rt = cached
+        .refresh_token
+        .as_ref()
+        .ok_or_else(|| anyhow!("No refresh token"))?;
+    let new = refresh_auth_token(rt, username)?;
+    save_cached_token(uid, &new)?;
+    Ok(new)
+}
+
+fn revoke_refresh_token(rt: &str) -> Result<()> {
+    let url = std::env::var("SUPERNANNY_SERVER_URL").unwrap_or_else(|_| "https://127.0.0.1:8443".into());
+
+    let client = Client::builder()
+        .danger_accept_invalid_certs(true)
+        .timeout(std::time::Duration::from_secs(5))
+        .build()?;
+
+    let resp = client.post(&format!("{}/auth/logout", url))
+        .json(&serde_json::json!({ "refresh_token": rt }))
+        .send()?;
+
+    if !resp.status().is_success() {
+        return Err(anyhow!("Logout failed: {}", resp.status()));
+    }
+    Ok(())
+}
+
+/// Serializes the refreshes of the user's session, best effort: without
+/// the lock, refreshing is still attempted. Released when the file closes.
+fn lock_session(uid: u32) -> Option<File> {
+    let path = get_cache_dir_path(uid).join(TOKEN_LOCK_FILE);
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .mode(0o600)
+        .open(&path)
+        .ok()?;
+    match unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } {
+        0 => Some(file),
+        _ => None,
+    }
 }
 
 fn get_cache_dir_path(uid: u32) -> PathBuf {
@@ -6,8 +6,11 @@ BEGIN;
 -- =========================================================
 
 -- ---------- CLEAN SLATE ----------------------------------
+DROP TABLE IF EXISTS app_policy_versions     CASCADE;
 DROP TABLE IF EXISTS policy_change_requests  CASCADE;
+DROP TABLE IF EXISTS refresh_tokens          CASCADE;
 DROP TABLE IF EXISTS sandbox_events          CASCADE;
+DROP TABLE IF EXISTS enforcement_reports     CASCADE;
 DROP TABLE IF EXISTS app_policy              CASCADE;
 DROP TABLE IF EXISTS role_permissions        CASCADE;
 DROP TABLE IF EXISTS user_roles              CASCADE;
@@ -15,12 +18,16 @@ DROP TABLE IF EXISTS permissions             CASCADE;
 DROP TABLE IF EXISTS roles                   CASCADE;
 DROP TABLE IF EXISTS users                   CASCADE;
 DROP TABLE IF EXISTS default_policies        CASCADE;
+DROP TABLE IF EXISTS baseline_policies       CASCADE;
+DROP TABLE IF EXISTS app_identities          CASCADE;
 DROP TABLE IF EXISTS security_logs           CASCADE;
 DROP TABLE IF EXISTS user_admin              CASCADE;
 DROP TABLE IF EXISTS permission_admin        CASCADE;
 DROP TABLE IF EXISTS role_permissions_admin  CASCADE;
 DROP TYPE  IF EXISTS policy_status           CASCADE;
 DROP INDEX IF EXISTS unique_app_role_pending;
+DROP FUNCTION IF EXISTS reject_policy_version_change() CASCADE;
+DROP FUNCTION IF EXISTS check_policy_request_transition() CASCADE;
 
 -- ---------- SCHEMA ---------------------------------------
 CREATE TABLE users (
@@ -65,6 +72,12 @@ CREATE TABLE app_policy (
     tcp_connect TEXT NOT NULL,
     allowed_ips TEXT NOT NULL,
     allowed_domains TEXT NOT NULL,
+    fs_rules TEXT NOT NULL DEFAULT '',
+    scoped TEXT NOT NULL DEFAULT '',
+    seccomp TEXT NOT NULL DEFAULT '',
+    resources TEXT NOT NULL DEFAULT '',
+    isolation TEXT NOT NULL DEFAULT '',
+    environment TEXT NOT NULL DEFAULT '',
     updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
     UNIQUE (app_name, role_id),
     FOREIGN KEY (role_id) REFERENCES roles(role_id) ON DELETE CASCADE
@@ -90,8 +103,8 @@ CREATE TABLE role_permissions_admin (
   FOREIGN KEY (permission_admin_id) REFERENCES permission_admin(permission_admin_id) ON DELETE CASCADE
 );
 
--- ENUM for request status
-CREATE TYPE policy_status AS ENUM ('pending', 'approved', 'rejected');
+-- ENUM for request status: only a pending request changes, to any other
+CREATE TYPE policy_status AS ENUM ('pending', 'approved', 'rejected', 'withdrawn', 'superseded', 'expired');
 
 CREATE TABLE policy_change_requests (
     request_id SERIAL PRIMARY KEY,
@@ -106,21 +119,87 @@ CREATE TABLE policy_change_requests (
     tcp_connect TEXT NOT NULL,
     allowed_ips TEXT NOT NULL,
     allowed_domains TEXT NOT NULL,
+    fs_rules TEXT NOT NULL DEFAULT '',
+    scoped TEXT NOT NULL DEFAULT '',
+    seccomp TEXT NOT NULL DEFAULT '',
+    resources TEXT NOT NULL DEFAULT '',
+    isolation TEXT NOT NULL DEFAULT '',
+    environment TEXT NOT NULL DEFAULT '',
 
     allowed_ro_paths TEXT[] NOT NULL DEFAULT '{}',
     allowed_rw_paths TEXT[] NOT NULL DEFAULT '{}',
 
     change_justification TEXT NOT NULL,
     requested_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    amended_at TIMESTAMP,
 
     reviewed_by INTEGER REFERENCES users(user_id),
-    reviewed_at TIMESTAMP
+    reviewed_at TIMESTAMP,
+    -- The request of the same user for the same app and role replacing it
+    superseded_by INTEGER
 );
 
+CREATE FUNCTION check_policy_request_transition() RETURNS trigger AS $$
+BEGIN
+    IF OLD.status <> 'pending' THEN
+        RAISE EXCEPTION 'policy change request % is already %', OLD.request_id, OLD.status;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER policy_change_requests_transition
+BEFORE UPDATE ON policy_change_requests
+FOR EACH ROW EXECUTE FUNCTION check_policy_request_transition();
+
 CREATE UNIQUE INDEX idx_unique_pending_requests 
 ON policy_change_requests (app_name, role_id, requested_by) 
 WHERE status = 'pending';
 
+-- Every state an application policy went through. A row is never changed:
+-- no foreign key to app_policy, so the history outlives the policy
+CREATE TABLE app_policy_versions (
+    version_id SERIAL PRIMARY KEY,
+    app_name TEXT NOT NULL,
+    role_id INTEGER NOT NULL,
+    version INTEGER NOT NULL,
+
+    default_ro TEXT NOT NULL,
+    default_rw TEXT NOT NULL,
+    tcp_bind TEXT NOT NULL,
+    tcp_connect TEXT NOT NULL,
+    allowed_ips TEXT NOT NULL,
+    allowed_domains TEXT NOT NULL,
+    fs_rules TEXT NOT NULL DEFAULT '',
+    scoped TEXT NOT NULL DEFAULT '',
+    seccomp TEXT NOT NULL DEFAULT '',
+    resources TEXT NOT NULL DEFAULT '',
+    isolation TEXT NOT NULL DEFAULT '',
+    environment TEXT NOT NULL DEFAULT '',
+    -- The policy was deleted, the fields are its last state
+    deleted BOOLEAN NOT NULL DEFAULT FALSE,
+
+    author TEXT NOT NULL,
+    reason TEXT NOT NULL DEFAULT '',
+    -- Approved request the version comes from. No foreign key: deleting the
+    -- request would have to update the row
+    request_id INTEGER,
+    restored_version INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+
+    UNIQUE (app_name, role_id, version)
+);
+
+CREATE FUNCTION reject_policy_version_change() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'app_policy_versions rows are immutable';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER app_policy_versions_immutable
+BEFORE UPDATE OR DELETE ON app_policy_versions
+FOR EACH ROW EXECUTE FUNCTION reject_policy_version_change();
+
 CREATE TABLE default_policies (
     role_id INTEGER PRIMARY KEY,
     default_ro TEXT NOT NULL,
@@ -129,9 +208,48 @@ CREATE TABLE default_policies (
     tcp_connect TEXT NOT NULL,
     allowed_ips TEXT NOT NULL,
     allowed_domains TEXT NOT NULL,
+    fs_rules TEXT NOT NULL DEFAULT '',
+    scoped TEXT NOT NULL DEFAULT '',
+    seccomp TEXT NOT NULL DEFAULT '',
+    resources TEXT NOT NULL DEFAULT '',
+    isolation TEXT NOT NULL DEFAULT '',
+    environment TEXT NOT NULL DEFAULT '',
     FOREIGN KEY (role_id) REFERENCES roles(role_id) ON DELETE CASCADE
 );
 
+-- Imposed on every user, on every host ('*') or on one
+CREATE TABLE baseline_policies (
+    baseline_id SERIAL PRIMARY KEY,
+    hostname TEXT NOT NULL UNIQUE DEFAULT '*',
+    default_ro TEXT NOT NULL DEFAULT '',
+    default_rw TEXT NOT NULL DEFAULT '',
+    tcp_bind TEXT NOT NULL DEFAULT '',
+    tcp_connect TEXT NOT NULL DEFAULT '',
+    allowed_ips TEXT NOT NULL DEFAULT '',
+    allowed_domains TEXT NOT NULL DEFAULT '',
+    fs_rules TEXT NOT NULL DEFAULT '',
+    scoped TEXT NOT NULL DEFAULT '',
+    seccomp TEXT NOT NULL DEFAULT '',
+    resources TEXT NOT NULL DEFAULT '',
+    isolation TEXT NOT NULL DEFAULT '',
+    environment TEXT NOT NULL DEFAULT ''
+);
+
+-- Binds the policy named app_name to the binaries at exe_path, optionally
+-- only to those with this SHA-256 digest or from this package
+CREATE TABLE app_identities (
+    identity_id SERIAL PRIMARY KEY,
+    app_name TEXT NOT NULL,
+    exe_path TEXT NOT NULL,
+    sha256 TEXT,
+    package TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- A binary is bound to one policy
+CREATE UNIQUE INDEX idx_unique_app_identities
+ON app_identities (exe_path, COALESCE(sha256, ''), COALESCE(package, ''));
+
 CREATE TABLE sandbox_events (
     event_id SERIAL PRIMARY KEY,
     timestamp TIMESTAMP NOT NULL DEFAULT NOW(),
@@ -143,9 +261,47 @@ CREATE TABLE sandbox_events (
     user_id INTEGER,
     remote_ip TEXT,
     domain TEXT,
+    cgroup_id BIGINT,
+    -- Generated by the workstation: unique per user only, so that a client
+    -- cannot make the events of another user look like duplicates
+    client_event_id TEXT,
+    -- Script the application interpreted, e.g. tool.py for python3
+    script TEXT,
+    script_sha256 TEXT,
+    FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE SET NULL,
+    UNIQUE (user_id, client_event_id)
+);
+
+CREATE TABLE enforcement_reports (
+    report_id SERIAL PRIMARY KEY,
+    timestamp TIMESTAMP NOT NULL DEFAULT NOW(),
+    hostname TEXT NOT NULL,
+    app_name TEXT NOT NULL,
+    user_id INTEGER,
+    abi INTEGER NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('fully_enforced', 'partially_enforced', 'not_enforced')),
+    skipped_rules INTEGER NOT NULL DEFAULT 0,
+    report TEXT NOT NULL,
     FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE SET NULL
 );
 
+-- Opaque refresh tokens, stored as SHA-256 hashes. Each login starts a
+-- family; every refresh rotates the token within it. Presenting a rotated
+-- token again revokes the whole family.
+CREATE TABLE refresh_tokens (
+    token_id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    family_id TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    issued_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL,
+    rotated_at TIMESTAMP,
+    revoked_at TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_refresh_tokens_family ON refresh_tokens (family_id);
+
 CREATE TABLE security_logs (
     log_id SERIAL PRIMARY KEY,
     timestamp TIMESTAMP NOT NULL DEFAULT NOW(),
@@ -247,6 +403,23 @@ INSERT INTO app_policy (
    'localhost',
    NOW());
 
+INSERT INTO app_policy_versions (
+  app_name, role_id, version, default_ro, default_rw, tcp_bind, tcp_connect,
+  allowed_ips, allowed_domains, fs_rules, scoped, seccomp, resources, isolation, environment,
+  author, reason
+)
+SELECT app_name, role_id, 1, default_ro, default_rw, tcp_bind, tcp_connect,
+       allowed_ips, allowed_domains, fs_rules, scoped, seccomp, resources, isolation, environment,
+       'seed', 'Initial policy'
+FROM app_policy;
+
+-- The sandboxer identifies binaries by canonical path: /bin is a symlink to
+-- /usr/bin on merged-/usr systems
+INSERT INTO app_identities (app_name, exe_path) VALUES
+  ('/bin/firefox', '/usr/lib/firefox/firefox'),
+  ('/bin/ping',    '/usr/bin/ping'),
+  ('/bin/ls',      '/usr/bin/ls');
+
 
 -- ---------- SEQUENCE ALIGNMENT ---------------------------
 SELECT setval('roles_role_id_seq',             (SELECT MAX(role_id) FROM roles));
@@ -0,0 +1,73 @@
+-- Checks of the constraints and triggers of full_script.sql, on a scratch
+-- database where it was just run:
+--
+--   psql -v ON_ERROR_STOP=1 -f full_script.sql -f schema_tests.sql
+--
+-- Every check runs in a transaction rolled back at the end; a failed one
+-- raises and stops the script.
+
+BEGIN;
+
+-- A user whose approved request was recorded in the policy history can still
+-- be deleted, and so can its role: the history keeps the request id
+DO $$
+DECLARE
+    requester INTEGER;
+    reviewer INTEGER;
+    role INTEGER;
+    request INTEGER;
+BEGIN
+    INSERT INTO users (username, password_hash) VALUES ('schema_requester', 'x')
+    RETURNING user_id INTO requester;
+    INSERT INTO users (username, password_hash) VALUES ('schema_reviewer', 'x')
+    RETURNING user_id INTO reviewer;
+    INSERT INTO roles (role_name) VALUES ('schema_role') RETURNING role_id INTO role;
+    INSERT INTO user_roles (user_id, role_id) VALUES (requester, role);
+
+    INSERT INTO policy_change_requests (
+        app_name, role_id, requested_by, default_ro, default_rw, tcp_bind, tcp_connect,
+        allowed_ips, allowed_domains, change_justification
+    )
+    VALUES ('/bin/schema', role, requester, '/usr', '', '', '443', '', '', 'schema test')
+    RETURNING request_id INTO request;
+    UPDATE policy_change_requests
+    SET status = 'approved', reviewed_by = reviewer, reviewed_at = NOW()
+    WHERE request_id = request;
+
+    INSERT INTO app_policy_versions (
+        app_name, role_id, version, default_ro, default_rw, tcp_bind, tcp_connect,
+        allowed_ips, allowed_domains, author, reason, request_id
+    )
+    VALUES ('/bin/schema', role, 1, '/usr', '', '', '443', '', '', 'schema_reviewer', '', request);
+
+    DELETE FROM users WHERE user_id = requester;
+    ASSERT NOT EXISTS (SELECT 1 FROM policy_change_requests WHERE request_id = request),
+        'the request of a deleted user is deleted';
+    ASSERT (SELECT request_id FROM app_policy_versions WHERE app_name = '/bin/schema') = request,
+        'the history keeps the request id of a deleted request';
+
+    DELETE FROM roles WHERE role_id = role;
+    ASSERT EXISTS (SELECT 1 FROM app_policy_versions WHERE role_id = role),
+        'the history outlives the role';
+END;
+$$;
+
+-- History rows are never changed
+DO $$
+BEGIN
+    BEGIN
+        UPDATE app_policy_versions SET reason = 'changed';
+        RAISE EXCEPTION 'app_policy_versions rows were updated';
+    EXCEPTION WHEN raise_exception THEN
+        ASSERT SQLERRM = 'app_policy_versions rows are immutable', SQLERRM;
+    END;
+    BEGIN
+        DELETE FROM app_policy_versions;
+        RAISE EXCEPTION 'app_policy_versions rows were deleted';
+    EXCEPTION WHEN raise_exception THEN
+        ASSERT SQLERRM = 'app_policy_versions rows are immutable', SQLERRM;
+    END;
+END;
+$$;
+
+ROLLBACK;
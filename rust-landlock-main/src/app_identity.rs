@@ -0,0 +1,433 @@
+//! Identity of the sandboxed application.
+//!
+//! Policies used to be looked up by whatever the sandboxer was given as the
+//! application, so a copy of `/bin/bash` named `~/firefox` got Firefox's
+//! policy. The sandboxer now identifies the binary it is about to run by:
+//!
+//! - its canonical path, symlinks resolved, after a `PATH` lookup for a bare
+//!   name
+//! - the SHA-256 digest of its content
+//! - the package owning it, from `dpkg` or `rpm`, when there is one
+//!
+//! The server binds policies to identities ([`IdentityBinding`]), leaving the
+//! digest or the package open when they are not given. The policy of a binary
+//! is that of the most specific binding it matches. A binary matching none
+//! gets the policy named after its canonical path when no binding claims that
+//! name, and the server's "unknown" policy otherwise ([`policy_name`]).
+//!
+//! Interpreters run whatever they are given, so `python3 tool.py` is
+//! identified by the script as well: a script or jar run by one of the
+//! [`Interpreter`]s gets its own policy, and the interpreter's when it has
+//! none.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// What the sandboxer runs, as identified before fetching its policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppIdentity {
+    /// The application as given, looked up in `PATH`: what gets executed
+    pub exec_path: PathBuf,
+    /// Canonical path of the binary
+    pub path: String,
+    /// SHA-256 digest of the binary, in hex
+    pub sha256: String,
+    /// Package owning the binary
+    pub package: Option<String>,
+    /// The script or jar the binary interprets, if it is an interpreter
+    pub script: Option<Box<AppIdentity>>,
+}
+
+impl AppIdentity {
+    /// Identifies `app`, a path or a name to look up in `PATH`.
+    pub fn of(app: &str) -> Result<Self> {
+        let exec_path = match app.contains('/') {
+            true => PathBuf::from(app),
+            false => find_in_path(app).ok_or_else(|| anyhow!("{} not found in PATH", app))?,
+        };
+        Self::of_file(exec_path)
+    }
+
+    /// Identifies `app` run with `args`, along with the script it interprets.
+    /// A script that cannot be read, e.g. a module name, is left to the
+    /// interpreter to complain about.
+    pub fn of_command(app: &str, args: &[String]) -> Result<Self> {
+        let mut identity = Self::of(app)?;
+        identity.script = Interpreter::of(&identity.path)
+            .and_then(|interpreter| interpreter.script(args))
+            .and_then(|script| Self::of_file(PathBuf::from(script)).ok())
+            .map(Box::new);
+        Ok(identity)
+    }
+
+    fn of_file(exec_path: PathBuf) -> Result<Self> {
+        let canonical = fs::canonicalize(&exec_path)
+            .with_context(|| format!("Failed to canonicalize path {}", exec_path.display()))?;
+        let sha256 = file_digest(&canonical)
+            .with_context(|| format!("Failed to hash {}", canonical.display()))?;
+        let package = package_of(&canonical).or_else(|| package_of(&exec_path));
+        Ok(Self {
+            exec_path,
+            path: canonical.to_string_lossy().into_owned(),
+            sha256,
+            package,
+            script: None,
+        })
+    }
+
+    /// Canonical path of the script if there is one, of the binary otherwise:
+    /// what the policy is selected for first.
+    pub fn name(&self) -> &str {
+        self.script.as_ref().map_or(&self.path, |script| &script.path)
+    }
+
+    /// Query parameters identifying the application to the server: the script
+    /// and its interpreter, or the binary.
+    pub fn query(&self) -> Vec<(&'static str, String)> {
+        let mut query = Vec::new();
+        let mut push = |identity: &AppIdentity, keys: [&'static str; 3]| {
+            query.push((keys[0], identity.path.clone()));
+            query.push((keys[1], identity.sha256.clone()));
+            if let Some(package) = &identity.package {
+                query.push((keys[2], package.clone()));
+            }
+        };
+        match &self.script {
+            Some(script) => {
+                push(script, ["app_name", "sha256", "package"]);
+                push(self, ["interpreter", "interpreter_sha256", "interpreter_package"]);
+            }
+            None => push(self, ["app_name", "sha256", "package"]),
+        }
+        query
+    }
+}
+
+/// The interpreters whose scripts are identified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpreter {
+    Python,
+    Node,
+    Ruby,
+    Perl,
+    Shell,
+    /// Only with `-jar`: classes are not files
+    Java,
+}
+
+impl Interpreter {
+    /// The interpreter at `path`, whatever its version suffix, e.g.
+    /// `/usr/bin/python3.12`.
+    pub fn of(path: &str) -> Option<Self> {
+        let name = Path::new(path).file_name()?.to_str()?;
+        match name.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.') {
+            "python" | "pypy" => Some(Self::Python),
+            "node" | "nodejs" => Some(Self::Node),
+            "ruby" => Some(Self::Ruby),
+            "perl" => Some(Self::Perl),
+            "bash" | "sh" | "dash" => Some(Self::Shell),
+            "java" => Some(Self::Java),
+            _ => None,
+        }
+    }
+
+    /// Options running code given on the command line, or a module: no
+    /// script file then.
+    fn inline_options(self) -> &'static [&'static str] {
+        match self {
+            Self::Python => &["-c", "-m"],
+            Self::Node => &["-e", "--eval", "-p", "--print"],
+            Self::Ruby => &["-e"],
+            Self::Perl => &["-e", "-E"],
+            Self::Shell => &["-c"],
+            Self::Java => &[],
+        }
+    }
+
+    /// Options taking the next argument as their value.
+    fn value_options(self) -> &'static [&'static str] {
+        match self {
+            Self::Python => &["-W", "-X", "--check-hash-based-pycs"],
+            Self::Node => &["-r", "--require", "--import", "--loader", "-C", "--conditions"],
+            Self::Ruby => &["-I", "-r", "-C", "-E", "--encoding"],
+            Self::Perl => &["-I", "-M", "-m"],
+            Self::Shell => &["-o", "-O", "--rcfile", "--init-file"],
+            Self::Java => &["-cp", "-classpath", "--class-path", "-p", "--module-path"],
+        }
+    }
+
+    /// The script, or jar, the interpreter runs with `args`.
+    pub fn script(self, args: &[String]) -> Option<&str> {
+        let mut args = args.iter().map(String::as_str);
+        while let Some(arg) = args.next() {
+            if self == Self::Java && arg == "-jar" {
+                return args.next();
+            }
+            if arg == "--" {
+                return args.next().filter(|_| self != Self::Java);
+            }
+            if self.inline_options().iter().any(|o| arg == *o || (o.len() == 2 && arg.starts_with(o))) {
+                return None;
+            }
+            if self.value_options().contains(&arg) {
+                args.next();
+                continue;
+            }
+            if arg.starts_with('-') && arg != "-" {
+                continue;
+            }
+            // A Java main class, or stdin
+            return Some(arg).filter(|a| self != Self::Java && *a != "-");
+        }
+        None
+    }
+}
+
+/// A policy bound to an identity by the administrator. `None` fields match
+/// any binary.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdentityBinding {
+    /// Name of the bound policy
+    pub app_name: String,
+    pub exe_path: String,
+    #[serde(default)]
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub package: Option<String>,
+}
+
+impl IdentityBinding {
+    pub fn matches(&self, identity: &AppIdentity) -> bool {
+        self.exe_path == identity.path
+            && self
+                .sha256
+                .as_ref()
+                .map_or(true, |d| d.eq_ignore_ascii_case(&identity.sha256))
+            && self
+                .package
+                .as_ref()
+                .map_or(true, |p| identity.package.as_ref() == Some(p))
+    }
+
+    /// Bindings pinning the digest or the package take precedence.
+    fn specificity(&self) -> usize {
+        self.sha256.is_some() as usize * 2 + self.package.is_some() as usize
+    }
+}
+
+/// Name of the policy of `identity`, among the `bindings` and the names of
+/// the existing `policies`: that of its script, else that of its binary.
+/// `None` when both are unknown.
+pub fn policy_name(
+    bindings: &[IdentityBinding],
+    policies: &[&str],
+    identity: &AppIdentity,
+) -> Option<String> {
+    if let Some(script) = &identity.script {
+        if let Some(name) = policy_name(bindings, policies, script) {
+            return Some(name);
+        }
+    }
+    if let Some(binding) = bindings
+        .iter()
+        .filter(|b| b.matches(identity))
+        .max_by_key(|b| b.specificity())
+    {
+        return Some(binding.app_name.clone());
+    }
+    let claimed = bindings.iter().any(|b| b.app_name == identity.path);
+    if !claimed && policies.contains(&identity.path.as_str()) {
+        return Some(identity.path.clone());
+    }
+    None
+}
+
+/// Looks `name` up in `PATH` as `execvp()` does.
+pub fn find_in_path(name: &str) -> Option<PathBuf> {
+    let path = env::var_os("PATH")?;
+    env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| {
+            fs::metadata(candidate)
+                .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+                .unwrap_or(false)
+        })
+}
+
+fn file_digest(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Package owning `path`, asking `dpkg` then `rpm`.
+fn package_of(path: &Path) -> Option<String> {
+    let dpkg = query_package("dpkg-query", &["-S"], path)
+        .and_then(|out| out.split(':').next().map(|p| p.trim().to_string()));
+    dpkg.or_else(|| query_package("rpm", &["-qf", "--queryformat", "%{NAME}"], path))
+        .filter(|p| !p.is_empty())
+}
+
+fn query_package(program: &str, args: &[&str], path: &Path) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .arg(path)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout.lines().next().map(|line| line.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(path: &str, sha256: &str, package: Option<&str>) -> AppIdentity {
+        AppIdentity {
+            exec_path: PathBuf::from(path),
+            path: path.into(),
+            sha256: sha256.into(),
+            package: package.map(String::from),
+            script: None,
+        }
+    }
+
+    fn binding(app_name: &str, exe_path: &str, sha256: Option<&str>) -> IdentityBinding {
+        IdentityBinding {
+            app_name: app_name.into(),
+            exe_path: exe_path.into(),
+            sha256: sha256.map(String::from),
+            package: None,
+        }
+    }
+
+    #[test]
+    fn app_identity_of_file() {
+        let dir = env::temp_dir().join(format!("supernanny-identity-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let app = dir.join("app");
+        fs::write(&app, "abc").unwrap();
+        let link = dir.join("link");
+        std::os::unix::fs::symlink(&app, &link).unwrap();
+
+        let id = AppIdentity::of(link.to_str().unwrap()).unwrap();
+        assert_eq!(id.exec_path, link);
+        assert_eq!(id.path, fs::canonicalize(&app).unwrap().to_string_lossy());
+        assert_eq!(
+            id.sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert!(AppIdentity::of(dir.join("missing").to_str().unwrap()).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn app_identity_path_lookup() {
+        assert!(find_in_path("sh").map_or(false, |p| p.is_absolute()));
+        assert!(find_in_path("supernanny-no-such-binary").is_none());
+    }
+
+    #[test]
+    fn app_identity_policy_name() {
+        let firefox = identity("/usr/lib/firefox/firefox", "aa", Some("firefox"));
+        let bindings = vec![
+            binding("firefox", "/usr/lib/firefox/firefox", None),
+            binding("firefox-pinned", "/usr/lib/firefox/firefox", Some("AA")),
+            binding("/usr/bin/vim", "/usr/bin/vim.basic", Some("cc")),
+        ];
+        // The digest beats the path alone
+        assert_eq!(policy_name(&bindings, &[], &firefox).as_deref(), Some("firefox-pinned"));
+        let rebuilt = identity("/usr/lib/firefox/firefox", "bb", None);
+        assert_eq!(policy_name(&bindings, &[], &rebuilt).as_deref(), Some("firefox"));
+
+        // A copy of bash named firefox
+        let copy = identity("/home/alice/firefox", "bb", None);
+        assert_eq!(policy_name(&bindings, &["firefox"], &copy), None);
+
+        // Policies named after a path apply when no binding claims them
+        let ls = identity("/usr/bin/ls", "dd", None);
+        assert_eq!(policy_name(&bindings, &["/usr/bin/ls"], &ls).as_deref(), Some("/usr/bin/ls"));
+        let vim = identity("/usr/bin/vim", "ee", None);
+        assert_eq!(policy_name(&bindings, &["/usr/bin/vim"], &vim), None);
+
+        // Scripts get their own policy, else the interpreter's
+        let mut python = identity("/usr/bin/python3.12", "ff", None);
+        python.script = Some(Box::new(identity("/opt/tool.py", "11", None)));
+        let policies = ["/usr/bin/python3.12", "/opt/tool.py"];
+        assert_eq!(policy_name(&bindings, &policies, &python).as_deref(), Some("/opt/tool.py"));
+        assert_eq!(
+            policy_name(&bindings, &policies[..1], &python).as_deref(),
+            Some("/usr/bin/python3.12")
+        );
+        assert_eq!(python.name(), "/opt/tool.py");
+    }
+
+    #[test]
+    fn app_identity_interpreter_script() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        let python = Interpreter::of("/usr/bin/python3.12").unwrap();
+        assert_eq!(python, Interpreter::Python);
+        assert_eq!(Interpreter::of("/usr/bin/perl5.36.0"), Some(Interpreter::Perl));
+        assert_eq!(Interpreter::of("/usr/bin/vim"), None);
+
+        assert_eq!(python.script(&args(&["tool.py", "-v"])), Some("tool.py"));
+        assert_eq!(python.script(&args(&["-u", "-W", "ignore", "tool.py"])), Some("tool.py"));
+        assert_eq!(python.script(&args(&["-m", "http.server"])), None);
+        assert_eq!(python.script(&args(&["-c", "print(1)"])), None);
+        assert_eq!(python.script(&args(&["-"])), None);
+        assert_eq!(python.script(&args(&[])), None);
+        let node = Interpreter::Node;
+        assert_eq!(node.script(&args(&["-r", "dotenv/config", "server.js"])), Some("server.js"));
+        assert_eq!(node.script(&args(&["--inspect=9229", "--", "server.js"])), Some("server.js"));
+        assert_eq!(Interpreter::Shell.script(&args(&["-c", "ls"])), None);
+        assert_eq!(Interpreter::Shell.script(&args(&["-x", "build.sh"])), Some("build.sh"));
+        let java = Interpreter::Java;
+        assert_eq!(java.script(&args(&["-Xmx1g", "-jar", "app.jar", "x"])), Some("app.jar"));
+        assert_eq!(java.script(&args(&["-cp", "lib.jar", "com.Main"])), None);
+
+        let dir = env::temp_dir().join(format!("supernanny-script-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let interpreter = dir.join("python3");
+        fs::write(&interpreter, "").unwrap();
+        let script = dir.join("tool.py");
+        fs::write(&script, "abc").unwrap();
+
+        let id = AppIdentity::of_command(
+            interpreter.to_str().unwrap(),
+            &args(&["-u", script.to_str().unwrap()]),
+        )
+        .unwrap();
+        let script_id = id.script.as_ref().unwrap();
+        assert_eq!(script_id.path, fs::canonicalize(&script).unwrap().to_string_lossy());
+        assert_eq!(
+            script_id.sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let query = id.query();
+        assert_eq!(query[0], ("app_name", script_id.path.clone()));
+        assert!(query.iter().any(|(k, v)| *k == "interpreter" && *v == id.path));
+
+        // Not a readable script: the interpreter alone
+        let id = AppIdentity::of_command(interpreter.to_str().unwrap(), &args(&["missing.py"]));
+        assert!(id.unwrap().script.is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
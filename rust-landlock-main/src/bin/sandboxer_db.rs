@@ -1,21 +1,46 @@
 use anyhow::{anyhow, Context, Result};
 use dialoguer::{Input, Select};
-use landlock::{
-    Access, AccessFs, AccessNet, NetPort, PathBeneath, PathFd, Ruleset, RulesetAttr,
-    RulesetCreatedAttr, ABI,
-};
-use regex::Regex;
 use reqwest::blocking::Client;
 use serde::Deserialize;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
 use std::fs::{self, File};
-use std::io::{BufRead, BufReader, Read};
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
+use std::net::SocketAddr;
+use std::os::unix::io::RawFd;
+use std::os::unix::process::CommandExt;
+use std::panic::{self, AssertUnwindSafe};
 use std::process::{Command, ExitStatus};
+use std::sync::{Arc, RwLock};
+use supernanny_sandboxer::app_identity::AppIdentity;
+use supernanny_sandboxer::cgroup::{self, CgroupEvents, ResourceLimits, SandboxCgroup};
+use supernanny_sandboxer::denial_collector::{to_denial_entries, DenialCollector, LandlockDenial};
+use supernanny_sandboxer::dns_stub::{self, DnsStub, DomainAllowList};
+use supernanny_sandboxer::egress_filter::{self, IpAllowList};
+use supernanny_sandboxer::env_policy::EnvPolicy;
+use supernanny_sandboxer::event_spool::{self, EventSpool, SpooledEvent};
+use supernanny_sandboxer::fs_access;
+use supernanny_sandboxer::isolation::{self, Isolation};
+use supernanny_sandboxer::job_control;
+use supernanny_sandboxer::scope_access;
+use supernanny_sandboxer::syscall_profile::SyscallProfile;
+use supernanny_sandboxer::spec::{
+    AccessNames, Enforcement, NetPortSpec, PathBeneathReport, PathBeneathSpec, RestrictionReport,
+    RulesetSpec,
+};
+use supernanny_sandboxer::{
+    Access, AccessFs as PolicyAccessFs, AccessNet, BitFlags, RulesetStatus, Scope as PolicyScope,
+    ABI,
+};
+use supernanny_sandboxer::path_template::{self, TemplateVars};
+use supernanny_sandboxer::policy_cache;
+use supernanny_sandboxer::sandbox_report::{self, Reporter};
 use supernanny_sandboxer::policy_client::RuleSet;
-use supernanny_sandboxer::policy_client::{log_denial_event, User};
-use tempfile::TempDir;
+use supernanny_sandboxer::policy_client::{
+    self, log_enforcement_report, send_event_batch, User,
+};
+use supernanny_sandboxer::policy_learning::{self, LearnedRules, PolicyProposal};
 use zeroize::Zeroize;
 
 // ----------------------------------------------------------------------------
@@ -32,6 +57,9 @@ const MAX_DOMAINS: usize = 50;
 // Token cache settings (matching PAM module)
 const TOKEN_CACHE_DIR: &str = "supernanny";
 const TOKEN_CACHE_FILE: &str = "session.cache";
+const TOKEN_LOCK_FILE: &str = "session.lock";
+// Access token lifetime when the server does not tell (`expires_in`)
+const DEFAULT_TOKEN_VALIDITY_SECS: u64 = 3600;
 
 // ----------------------------------------------------------------------------
 // PAM Token Cache Integration
@@ -55,7 +83,7 @@ impl CachedToken {
     fn is_near_expiry(&self) -> bool {
         use std::time::{SystemTime, UNIX_EPOCH};
         let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-        (self.expires_at - now) < 1800 // 30 minutes
+        self.expires_at.saturating_sub(now) < 1800 // 30 minutes
     }
 }
 
@@ -92,7 +120,7 @@ fn refresh_auth_token(refresh_token: &str, username: &str) -> Result<CachedToken
         .context("Failed to build HTTP client")?;
     
     let resp = client
-        .post(&format!("{}/auth/refresh", url))
+        .post(format!("{}/auth/refresh", url))
         .json(&serde_json::json!({ "refresh_token": refresh_token }))
         .send()
         .context("Failed to send refresh token request")?;
@@ -106,6 +134,8 @@ fn refresh_auth_token(refresh_token: &str, username: &str) -> Result<CachedToken
         token: String,
         #[serde(default)]
         refresh_token: Option<String>,
+        #[serde(default)]
+        expires_in: Option<u64>,
     }
     
     let refresh_resp: RefreshResponse = resp.json()
@@ -115,7 +145,7 @@ fn refresh_auth_token(refresh_token: &str, username: &str) -> Result<CachedToken
     let expires_at = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
-        .as_secs() + 8 * 3600; // 8 hours
+        .as_secs() + refresh_resp.expires_in.unwrap_or(DEFAULT_TOKEN_VALIDITY_SECS);
     
     Ok(CachedToken {
         token: refresh_resp.token,
@@ -151,6 +181,48 @@ fn save_cached_token(uid: u32, token: &CachedToken) -> Result<()> {
     Ok(())
 }
 
+/// Refreshes the cached session under the session lock shared with the PAM
+/// module. Refresh tokens are single use, and presenting one twice revokes
+/// the session: when another process refreshed it meanwhile, its result is
+/// used instead.
+fn refresh_session(uid: u32, username: &str) -> Result<CachedToken> {
+    let _lock = lock_session(uid);
+    let cached = load_cached_token(uid)?;
+    if !cached.is_near_expiry() {
+        return Ok(cached);
+    }
+    let refresh_token = cached
+        .refresh_token
+        .as_ref()
+        .ok_or_else(|| anyhow!("No refresh token"))?;
+    let new_token = refresh_auth_token(refresh_token, username)?;
+    if let Err(e) = save_cached_token(uid, &new_token) {
+        // The old refresh token is spent: keep going with the new one
+        eprintln!("Warning: Failed to save refreshed token: {}", e);
+    }
+    Ok(new_token)
+}
+
+/// Takes the session lock, best effort: without it, refreshing is still
+/// attempted. Released when the returned file is closed.
+fn lock_session(uid: u32) -> Option<File> {
+    use std::fs::OpenOptions;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .mode(0o600)
+        .open(get_cache_dir_path(uid).join(TOKEN_LOCK_FILE))
+        .ok()?;
+    match unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } {
+        0 => Some(file),
+        _ => None,
+    }
+}
+
 fn get_current_uid() -> u32 {
     unsafe { libc::getuid() }
 }
@@ -206,80 +278,67 @@ fn get_credentials() -> Result<Credentials> {
         .context("Failed to determine current username")?;
     
     // Try to load cached token first
-    match load_cached_token(uid) {
-        Ok(cached) => {
-            // Verify the cached token belongs to the current user
-            if cached.username != current_username {
-                return Err(anyhow!(
-                    "Cached token belongs to different user: {} (expected: {})",
-                    cached.username,
-                    current_username
-                ));
-            }
-            
-            // Check if token is expired
-            if cached.is_expired() {
-                return Err(anyhow!("Cached authentication token has expired. Please log in again."));
-            }
-            
-            // Check if token is near expiry and try to refresh
-            if cached.is_near_expiry() {
-                if let Some(ref refresh_token) = cached.refresh_token {
-                    println!("Authentication token expires soon, attempting refresh...");
-                    
-                    match refresh_auth_token(refresh_token, &cached.username) {
-                        Ok(new_token) => {
-                            // Save the refreshed token
-                            if let Err(e) = save_cached_token(uid, &new_token) {
-                                eprintln!("Warning: Failed to save refreshed token: {}", e);
-                                // Continue with the new token anyway
-                            } else {
-                                println!("Authentication token refreshed successfully");
-                            }
-                            
-                            return Ok(Credentials::new(
-                                new_token.username.clone(),
-                                new_token.token.clone(),
-                                None, // We don't have User object from refresh
-                            ));
-                        }
-                        Err(e) => {
-                            eprintln!("Warning: Failed to refresh token: {}", e);
-                            // Fall back to using the current token if it's still valid
-                            if !cached.is_expired() {
-                                println!("Using existing token (refresh failed but token still valid)");
-                                return Ok(Credentials::new(
-                                    cached.username.clone(),
-                                    cached.token.clone(),
-                                    None,
-                                ));
-                            }
-                        }
-                    }
-                }
-            } else {
-                // Token is valid and not near expiry
+    let cached = load_cached_token(uid).map_err(|e| {
+        anyhow!(
+            "No valid authentication session found. Please log in first.\nDetails: {}",
+            e
+        )
+    })?;
+
+    // Verify the cached token belongs to the current user
+    if cached.username != current_username {
+        return Err(anyhow!(
+            "Cached token belongs to different user: {} (expected: {})",
+            cached.username,
+            current_username
+        ));
+    }
+    
+    // Refresh the token when it is about to expire, or already has
+    if !cached.is_near_expiry() {
+        return Ok(Credentials::new(
+            cached.username.clone(),
+            cached.token.clone(),
+            None,
+        ));
+    }
+    if cached.refresh_token.is_some() {
+        println!("Authentication token expires soon, attempting refresh...");
+        match refresh_session(uid, &cached.username) {
+            Ok(new_token) => {
+                println!("Authentication token refreshed successfully");
                 return Ok(Credentials::new(
-                    cached.username.clone(),
-                    cached.token.clone(),
-                    None,
+                    new_token.username.clone(),
+                    new_token.token.clone(),
+                    None, // We don't have User object from refresh
                 ));
             }
-        }
-        Err(e) => {
-            return Err(anyhow!(
-                "No valid authentication session found. Please log in first.\nDetails: {}",
-                e
-            ));
+            Err(e) => eprintln!("Warning: Failed to refresh token: {}", e),
         }
     }
-    
-    // If we reach here, all token operations failed
-    Err(anyhow!(
-        "Authentication failed. Please log in again using a PAM-enabled service (e.g., login, sudo, etc.)"
+    if cached.is_expired() {
+        return Err(anyhow!(
+            "Cached authentication token has expired. Please log in again using a PAM-enabled service (e.g., login, sudo, etc.)"
+        ));
+    }
+
+    // Fall back to using the current token while it is still valid
+    println!("Using existing token (refresh failed but token still valid)");
+    Ok(Credentials::new(
+        cached.username.clone(),
+        cached.token.clone(),
+        None,
     ))
 }
 
+/// Fresh credentials of the same user from the session cache, refreshed if
+/// needed: access tokens only last an hour, sandboxed apps often longer.
+fn renewed_credentials(credentials: &Credentials) -> Option<Credentials> {
+    get_credentials()
+        .ok()
+        .filter(|renewed| renewed.username == credentials.username)
+}
+
 // Fallback function for interactive authentication (kept for emergency use)
 fn get_credentials_interactive() -> Result<Credentials> {
     println!("Interactive authentication fallback - this should not normally be needed.");
@@ -317,12 +376,30 @@ fn get_credentials_interactive() -> Result<Credentials> {
 
 #[derive(Debug, Clone)]
 pub struct AppPolicy {
+    // Paths enforced by Landlock, with templates expanded
     ro_paths: HashSet<PathBuf>,
     rw_paths: HashSet<PathBuf>,
+    // Entries as stored on the server, possibly templates
+    ro_rules: HashSet<String>,
+    rw_rules: HashSet<String>,
+    // Explicit rights per path, as stored on the server and expanded
+    fs_rules: BTreeMap<String, BitFlags<PolicyAccessFs>>,
+    fs_paths: HashMap<PathBuf, BitFlags<PolicyAccessFs>>,
+    vars: TemplateVars,
     tcp_bind: HashSet<u16>,
     tcp_connect: HashSet<u16>,
     allowed_ips: HashSet<String>,
     allowed_domains: HashSet<String>,
+    // IPCs the sandbox may not reach outside of it
+    scoped: BitFlags<PolicyScope>,
+    // Syscalls refused by seccomp
+    seccomp: SyscallProfile,
+    // cgroup v2 limits of each run, applied by the parent
+    resources: ResourceLimits,
+    // Namespaces entered by the sandbox before Landlock
+    isolation: Isolation,
+    // Environment variables passed to the application
+    environment: EnvPolicy,
 }
 
 #[derive(Debug, Deserialize)]
@@ -332,28 +409,87 @@ struct RoleCheckResponse {
 
 impl From<RuleSet> for AppPolicy {
     fn from(rs: RuleSet) -> Self {
+        Self::from_ruleset(rs, TemplateVars::current(None))
+    }
+}
+
+impl AppPolicy {
+    // Expand path templates (${HOME}, globs, ...) with the given variables
+    fn from_ruleset(rs: RuleSet, vars: TemplateVars) -> Self {
+        let to_rules = |paths: HashSet<PathBuf>| -> HashSet<String> {
+            paths
+                .into_iter()
+                .map(|p| p.to_string_lossy().into_owned())
+                .collect()
+        };
+        let ro_rules = to_rules(rs.ro_paths);
+        let rw_rules = to_rules(rs.rw_paths);
+
         Self {
-            ro_paths: rs.ro_paths,
-            rw_paths: rs.rw_paths,
+            ro_paths: Self::expand_rules(&ro_rules, &vars),
+            rw_paths: Self::expand_rules(&rw_rules, &vars),
+            ro_rules,
+            rw_rules,
+            fs_paths: Self::expand_fs_rules(&rs.fs_rules, &vars),
+            fs_rules: rs.fs_rules,
+            vars,
             tcp_bind: rs.tcp_bind,
             tcp_connect: rs.tcp_connect,
             allowed_ips: rs.allowed_ips,
             allowed_domains: rs.allowed_domains,
+            scoped: rs.scoped,
+            seccomp: rs.seccomp,
+            resources: rs.resources,
+            isolation: rs.isolation,
+            environment: rs.environment,
         }
     }
-}
 
-impl AppPolicy {
+    fn expand_rules(rules: &HashSet<String>, vars: &TemplateVars) -> HashSet<PathBuf> {
+        let mut paths = HashSet::new();
+        for rule in rules {
+            match path_template::expand(rule, vars) {
+                Ok(expanded) if expanded.is_empty() => {
+                    println!("Note: Policy path {} matches no existing path", rule);
+                }
+                Ok(expanded) => paths.extend(expanded),
+                Err(e) => eprintln!("Warning: Skipping invalid policy path: {}", e),
+            }
+        }
+        paths
+    }
+
+    fn expand_fs_rules(
+        rules: &BTreeMap<String, BitFlags<PolicyAccessFs>>,
+        vars: &TemplateVars,
+    ) -> HashMap<PathBuf, BitFlags<PolicyAccessFs>> {
+        let mut paths: HashMap<PathBuf, BitFlags<PolicyAccessFs>> = HashMap::new();
+        for (rule, access) in rules {
+            let single = std::iter::once(rule.clone()).collect();
+            for path in Self::expand_rules(&single, vars) {
+                *paths.entry(path).or_insert(BitFlags::EMPTY) |= *access;
+            }
+        }
+        paths
+    }
+
     fn contains_path(&self, path: &Path) -> bool {
         self.ro_paths.contains(path) || self.rw_paths.contains(path)
     }
 
-    fn join_paths(paths: &HashSet<PathBuf>) -> String {
-        paths
-            .iter()
-            .map(|p| p.to_string_lossy().into_owned())
-            .collect::<Vec<_>>()
-            .join(":")
+    // Grant a concrete path; the server copy is stored as a template when possible
+    fn allow_ro(&mut self, path: PathBuf) {
+        self.ro_rules.insert(path_template::templatize(&path, &self.vars));
+        self.ro_paths.insert(path);
+    }
+
+    fn allow_rw(&mut self, path: PathBuf) {
+        self.rw_rules.insert(path_template::templatize(&path, &self.vars));
+        self.rw_paths.insert(path);
+    }
+
+    fn join_rules(rules: &HashSet<String>) -> String {
+        rules.iter().cloned().collect::<Vec<_>>().join(":")
     }
 
     fn join_ports(ports: &HashSet<u16>) -> String {
@@ -395,63 +531,84 @@ impl AppPolicy {
 
         Ok(())
     }
+}
 
-    // Create a policy from environment variables (for sandbox mode)
-    fn from_env() -> Result<Self> {
-        Ok(Self {
-            ro_paths: Self::parse_paths_from_env("LL_FS_RO")?,
-            rw_paths: Self::parse_paths_from_env("LL_FS_RW")?,
-            tcp_bind: Self::parse_ports_from_env("LL_TCP_BIND")?,
-            tcp_connect: Self::parse_ports_from_env("LL_TCP_CONNECT")?,
-            allowed_ips: Self::parse_strings_from_env("LL_ALLOWED_IPS")?,
-            allowed_domains: Self::parse_strings_from_env("LL_ALLOWED_DOMAINS")?,
-        })
-    }
+// ----------------------------------------------------------------------------
+// Server communication 
+// ----------------------------------------------------------------------------
 
-    // Helper methods for parsing environment variables
-    fn parse_paths_from_env(var: &str) -> Result<HashSet<PathBuf>> {
-        let mut paths = HashSet::new();
-        if let Ok(value) = env::var(var) {
-            for path_str in value.split(':').filter(|s| !s.is_empty()) {
-                let path = PathBuf::from(path_str);
-                Self::validate_path(&path)?;
-                paths.insert(path);
-            }
-        }
-        Ok(paths)
-    }
+/// Fetches the user's permissions and the policy the server resolved for
+/// `app`. With the server's public key installed, all of the user's policies
+/// are also fetched signed and cached for offline runs.
+fn fetch_policy(
+    identity: &AppIdentity,
+    credentials: &Credentials,
+) -> Result<(HashSet<String>, RuleSet)> {
+    // Verify user permissions
+    let permissions =
+        verify_user_permissions(&credentials.token).context("Failed to verify user permissions")?;
 
-    fn parse_ports_from_env(var: &str) -> Result<HashSet<u16>> {
-        let mut ports = HashSet::new();
-        if let Ok(value) = env::var(var) {
-            for port_str in value.split(':').filter(|s| !s.is_empty()) {
-                match port_str.parse::<u16>() {
-                    Ok(port) => {
-                        ports.insert(port);
-                    }
-                    Err(e) => {
-                        return Err(anyhow!("Invalid port in {}: {} - {}", var, port_str, e));
-                    }
-                }
-            }
-        }
-        Ok(ports)
+    // Retrieve policy from server
+    let ruleset = RuleSet::fetch_for_app(identity, &credentials.token)
+        .context("Failed to fetch policy from server")?;
+    // The signed cache only serves offline runs: failing to refresh it must
+    // not prevent this one
+    if let Err(e) = refresh_policy_cache(credentials) {
+        eprintln!("Warning: Failed to refresh the policy cache: {:#}", e);
     }
+    Ok((permissions, ruleset))
+}
 
-    fn parse_strings_from_env(var: &str) -> Result<HashSet<String>> {
-        let mut strings = HashSet::new();
-        if let Ok(value) = env::var(var) {
-            for s in value.split(':').filter(|s| !s.is_empty()) {
-                strings.insert(s.to_string());
-            }
-        }
-        Ok(strings)
+/// Fetches the signed policies of the user and caches them for offline runs,
+/// unless a cached copy still verifies and has not expired. Offline runs
+/// without a session load the cache of the system user, so the policies of
+/// another account are not cached.
+fn refresh_policy_cache(credentials: &Credentials) -> Result<()> {
+    if !Path::new(policy_cache::POLICY_KEY_PATH).exists() {
+        return Ok(());
+    }
+    if credentials.username != get_current_username()? {
+        return Err(anyhow!(
+            "Session of {} is not the system user's",
+            credentials.username
+        ));
+    }
+    let key = policy_cache::load_key()?;
+    let dir = policy_cache_dir().ok_or_else(|| anyhow!("No home directory"))?;
+    let now = policy_cache::now();
+    if policy_cache::is_fresh(&dir, &key, &credentials.username, now) {
+        return Ok(());
     }
+    let signed =
+        RuleSet::fetch_signed(&credentials.token).context("Failed to fetch signed policies")?;
+    let bundle = signed
+        .verify(&key, &credentials.username, now)
+        .context("Refusing the policies sent by the server")?;
+    policy_cache::save(&dir, &signed, &bundle)
 }
 
-// ----------------------------------------------------------------------------
-// Server communication 
-// ----------------------------------------------------------------------------
+/// Policy of the application from the newest valid signed cache of
+/// `username`, the user the cache was saved for.
+fn load_cached_policy(identity: &AppIdentity, username: &str) -> Result<RuleSet> {
+    let key = policy_cache::load_key()?;
+    let dir = policy_cache_dir().ok_or_else(|| anyhow!("No home directory"))?;
+    let bundle = policy_cache::load_latest(&dir, &key, username, policy_cache::now())?;
+
+    let expires = chrono::DateTime::from_timestamp(bundle.expires_at as i64, 0)
+        .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default();
+    let role_ids: Vec<String> = bundle.role_ids.iter().map(|id| id.to_string()).collect();
+    println!(
+        "Running offline with the policies cached for roles {} (valid until {})",
+        role_ids.join(", "),
+        expires
+    );
+    RuleSet::for_app(&bundle.ruleset, identity)
+}
+
+fn policy_cache_dir() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| policy_cache::cache_dir(Path::new(&home)))
+}
 
 fn verify_user_permissions(token: &str) -> Result<HashSet<String>> {
     let base_url = env::var("SERVER_URL").unwrap_or_else(|_| "https://127.0.0.1:8443".into());
@@ -486,6 +643,7 @@ fn update_policy_on_server(
     policy: &AppPolicy,
     token: &str,
     permissions: &HashSet<String>,
+    justification: &str,
 ) -> Result<()> {
     if !permissions.contains("manage_policies") {
         return Err(anyhow!("User does not have policy management permissions"));
@@ -499,66 +657,50 @@ fn update_policy_on_server(
         .build()
         .context("Failed to build HTTPS client")?;
     
-    // Create vectors from paths
-    let ro_paths_vec: Vec<String> = policy
-        .ro_paths
-        .iter()
-        .map(|p| p.to_string_lossy().into_owned())
-        .collect();
-    
-    let rw_paths_vec: Vec<String> = policy
-        .rw_paths
-        .iter()
-        .map(|p| p.to_string_lossy().into_owned())
-        .collect();
+    // Create vectors from the policy entries (templates are kept as-is)
+    let ro_paths_vec: Vec<String> = policy.ro_rules.iter().cloned().collect();
+    let rw_paths_vec: Vec<String> = policy.rw_rules.iter().cloned().collect();
 
-    // First attempt to check if there's an existing pending request
+    // A pending request of ours for the app is amended rather than superseded
     let check_url = format!("{}/policy/pending-requests", base_url);
     
     let res = client
         .get(&check_url)
+        .query(&[("app_name", app)])
         .header("Authorization", format!("Bearer {}", token))
         .send()
         .context("Failed to check for pending policy requests")?;
     
-    let status = res.status();
-    
-    // If we can successfully retrieve pending requests
     let mut existing_request_id = None;
-    if status.is_success() {
+    if res.status().is_success() {
         let pending_requests: serde_json::Value = res.json()
             .context("Failed to parse pending requests response")?;
-        
-        // Check if there's already a pending request for this app and role
-        if let Some(requests) = pending_requests.as_array() {
-            for request in requests {
-                if let (Some(req_app), Some(req_role)) = (
-                    request.get("app_name").and_then(|v| v.as_str()),
-                    request.get("role_id").and_then(|v| v.as_i64()),
-                ) {
-                    if req_app == app && req_role == 1 {
-                        // Found an existing request
-                        existing_request_id = request.get("id").and_then(|v| v.as_i64());
-                        break;
-                    }
-                }
-            }
-        }
+        existing_request_id = pending_requests
+            .as_array()
+            .and_then(|requests| requests.first())
+            .and_then(|request| request.get("request_id"))
+            .and_then(|id| id.as_i64());
     }
 
-    // Prepare the policy update payload
+    // Prepare the policy update payload; the server files it under our role
+    // with a policy for the app
     let payload = serde_json::json!({
         "app_name": app.to_string(),
-        "role_id": 1,
-        "default_ro": AppPolicy::join_paths(&policy.ro_paths),
-        "default_rw": AppPolicy::join_paths(&policy.rw_paths),
+        "default_ro": AppPolicy::join_rules(&policy.ro_rules),
+        "default_rw": AppPolicy::join_rules(&policy.rw_rules),
         "tcp_bind": AppPolicy::join_ports(&policy.tcp_bind),
         "tcp_connect": AppPolicy::join_ports(&policy.tcp_connect),
         "allowed_ips": AppPolicy::join_ips(&policy.allowed_ips),
         "allowed_domains": AppPolicy::join_domains(&policy.allowed_domains),
+        "fs_rules": fs_access::join_fs_rules(&policy.fs_rules),
+        "scoped": scope_access::join_scopes(policy.scoped),
+        "seccomp": policy.seccomp.to_string(),
+        "resources": policy.resources.to_string(),
+        "isolation": policy.isolation.to_string(),
+        "environment": policy.environment.to_string(),
         "allowed_ro_paths": ro_paths_vec,
         "allowed_rw_paths": rw_paths_vec,
-        "change_justification": "Automatically updated from sandboxer after access denial"
+        "change_justification": justification
     });
 
     // If we found an existing request, try to update it
@@ -587,44 +729,6 @@ fn update_policy_on_server(
     
     if !status.is_success() {
         let error_text = res.text().unwrap_or_else(|_| "Unknown error".to_string());
-        
-        // Special handling for duplicate key constraint errors
-        if error_text.contains("idx_unique_pending_requests") &&
-           error_text.contains("existe déjà") {
-            // Fall back to trying to delete the old request and create a new one
-            println!("Detected duplicate request, attempting to delete existing request and create a new one...");
-            
-            // Try to find and delete any existing requests
-            let delete_url = format!("{}/policy/delete-pending/{}/{}", base_url, app, 1);
-            let delete_res = client
-                .delete(&delete_url)
-                .header("Authorization", format!("Bearer {}", token))
-                .send();
-                
-            if let Ok(delete_res) = delete_res {
-                if delete_res.status().is_success() {
-                    println!("Successfully deleted existing pending request.");
-                    
-                    // Now try to create the request again
-                    let create_url = format!("{}/policy/request", base_url);
-                    let retry_res = client
-                        .post(&create_url)
-                        .header("Authorization", format!("Bearer {}", token))
-                        .header("Content-Type", "application/json")
-                        .json(&payload)
-                        .send();
-                        
-                    if let Ok(retry_res) = retry_res {
-                        if retry_res.status().is_success() {
-                            println!("Policy update request successfully submitted!");
-                            println!("Note: Changes require admin approval before they take effect.");
-                            return Ok(());
-                        }
-                    }
-                }
-            }
-        }
-        
         return Err(anyhow!(
             "Policy update request failed: {} - {}",
             status,
@@ -637,309 +741,216 @@ fn update_policy_on_server(
     Ok(())
 }
 
-fn enforce_landlock(policy: &AppPolicy) -> Result<()> {
-    let abi = ABI::V5;
-
-    // Create the base ruleset with all necessary access types
-    let base = Ruleset::default()
-        .handle_access(AccessFs::from_all(abi))
-        .context("Failed to handle filesystem access")?
-        .handle_access(AccessNet::BindTcp)
-        .context("Failed to handle TCP bind access")?
-        .handle_access(AccessNet::ConnectTcp)
-        .context("Failed to handle TCP connect access")?;
-
-    let mut created = base.create().context("Failed to create Landlock ruleset")?;
-
-    // Process read-only paths with improved error handling
-    for path in &policy.ro_paths {
-        if let Err(e) = AppPolicy::validate_path(path) {
-            eprintln!("Warning: Skipping invalid read-only path: {}", e);
-            continue;
-        }
-
-        match fs::canonicalize(path) {
-            Ok(canonical_path) => {
-                if let Ok(fd) = PathFd::new(canonical_path.as_os_str()) {
-                    let rule = PathBeneath::new(fd, AccessFs::from_read(abi));
-                    
-                    // Important: Store the result in a temporary variable first
-                    let result = created.add_rule(rule);
-                    
-                    // Then handle the result without using created again until reassigned
-                    match result {
-                        Ok(new_created) => created = new_created,
-                        Err(e) => {
-                            eprintln!(
-                                "Warning: Failed to add read-only rule for {}: {}",
-                                path.display(),
-                                e
-                            );
-                            // We need to break here because created has been consumed
-                            return Err(anyhow!("Failed to add ruleset rule: {}", e));
-                        }
-                    }
-                } else {
-                    eprintln!(
-                        "Warning: Failed to open path {}",
-                        canonical_path.display()
-                    );
-                }
-            },
-            Err(e) => {
-                eprintln!(
-                    "Warning: Failed to canonicalize path {}: {}",
-                    path.display(),
-                    e
-                );
-            }
-        }
-    }
-
-    // Apply the same pattern for read-write paths
-    for path in &policy.rw_paths {
-        if let Err(e) = AppPolicy::validate_path(path) {
-            eprintln!("Warning: Skipping invalid read-write path: {}", e);
-            continue;
-        }
+/// Applies the policy with Landlock and returns what the kernel enforced.
+///
+/// Path rules that cannot be added are listed as skipped in the report
+/// instead of being dropped silently. TCP to `dns_stub` is allowed, for the
+/// port-53 connections the egress filter redirects to it.
+fn enforce_landlock(policy: &AppPolicy, dns_stub: Option<SocketAddr>) -> Result<RestrictionReport> {
+    let abi = ABI::V6;
+
+    let mut spec = RulesetSpec {
+        handled_fs: PolicyAccessFs::from_all(abi),
+        handled_net: AccessNet::BindTcp | AccessNet::ConnectTcp,
+        // Best-effort: ignored by kernels older than ABI V6
+        scoped: policy.scoped,
+        ..RulesetSpec::default()
+    };
 
-        if let Ok(canonical_path) = fs::canonicalize(path) {
-            if let Ok(fd) = PathFd::new(canonical_path.as_os_str()) {
-                let rule = PathBeneath::new(fd, AccessFs::from_all(abi));
-                
-                // Store result first
-                let result = created.add_rule(rule);
-                
-                // Handle result safely
-                match result {
-                    Ok(new_created) => created = new_created,
-                    Err(e) => {
-                        eprintln!(
-                            "Warning: Failed to add read-write rule for {}: {}",
-                            path.display(),
-                            e
-                        );
-                        return Err(anyhow!("Failed to add ruleset rule: {}", e));
-                    }
-                }
-            } else {
-                eprintln!(
-                    "Warning: Failed to open path {}",
-                    canonical_path.display()
-                );
-            }
-        } else {
-            eprintln!(
-                "Warning: Failed to canonicalize path {}",
-                path.display()
-            );
+    let path_rules = policy
+        .ro_paths
+        .iter()
+        .map(|path| (path, PolicyAccessFs::from_read(abi)))
+        .chain(
+            policy
+                .rw_paths
+                .iter()
+                .map(|path| (path, PolicyAccessFs::from_all(abi))),
+        )
+        .chain(policy.fs_paths.iter().map(|(path, access)| (path, *access)));
+
+    let mut skipped = Vec::new();
+    for (path, access) in path_rules {
+        let canonical_path = AppPolicy::validate_path(path).and_then(|_| {
+            fs::canonicalize(path)
+                .with_context(|| format!("Failed to canonicalize path {}", path.display()))
+        });
+        match canonical_path {
+            Ok(canonical_path) => spec.path_beneath.push(PathBeneathSpec {
+                path: canonical_path,
+                access,
+            }),
+            Err(e) => skipped.push(PathBeneathReport {
+                path: path.clone(),
+                access: Enforcement {
+                    requested: access,
+                    enforced: BitFlags::EMPTY,
+                },
+                skipped: Some(format!("{:#}", e)),
+            }),
         }
     }
 
-    // TCP bind ports
     for port in &policy.tcp_bind {
-        let rule = NetPort::new(*port, AccessNet::BindTcp);
-        
-        // Store result first
-        let result = created.add_rule(rule);
-        
-        // Handle result safely
-        match result {
-            Ok(new_created) => created = new_created,
-            Err(e) => {
-                eprintln!(
-                    "Warning: Failed to add TCP bind rule for port {}: {}",
-                    port, e
-                );
-                return Err(anyhow!("Failed to add TCP bind rule: {}", e));
-            }
-        }
+        spec.net_port.push(NetPortSpec {
+            port: *port,
+            access: AccessNet::BindTcp.into(),
+        });
     }
-
-    // TCP connect ports
-    for port in &policy.tcp_connect {
-        let rule = NetPort::new(*port, AccessNet::ConnectTcp);
-        
-        // Store result first
-        let result = created.add_rule(rule);
-        
-        // Handle result safely
-        match result {
-            Ok(new_created) => created = new_created,
-            Err(e) => {
-                eprintln!(
-                    "Warning: Failed to add TCP connect rule for port {}: {}",
-                    port, e
-                );
-                return Err(anyhow!("Failed to add TCP connect rule: {}", e));
-            }
-        }
+    let stub_port = dns_stub.map(|stub| stub.port());
+    for port in policy.tcp_connect.iter().copied().chain(stub_port) {
+        spec.net_port.push(NetPortSpec {
+            port,
+            access: AccessNet::ConnectTcp.into(),
+        });
     }
 
-    // Apply the ruleset
-    created
+    let mut report = spec
         .restrict_self()
         .context("Failed to restrict process with Landlock rules")?;
+    report.path_beneath.extend(skipped);
 
-    Ok(())
+    for rule in report.skipped() {
+        eprintln!(
+            "Warning: Skipped Landlock rule for {}: {}",
+            rule.path.display(),
+            rule.skipped.as_deref().unwrap_or_default()
+        );
+    }
+
+    Ok(report)
 }
 
 // ----------------------------------------------------------------------------
-// Strace
+// Sandboxed execution
 // ----------------------------------------------------------------------------
 
-fn run_strace(
-    app_path: &Path,
-    args: &[String],
-    policy: &AppPolicy,
-    prefix: &str,
-) -> Result<(ExitStatus, TempDir)> {
+/// Outcome of a sandboxed run.
+struct SandboxRun {
+    status: ExitStatus,
+    /// Denials reported by the interceptor's eBPF collector.
+    landlock: Vec<LandlockDenial>,
+    /// `ip:`/`domain:` entries reported from inside the sandbox.
+    reported: HashSet<String>,
+    /// What Landlock enforced, as reported by the sandbox.
+    enforcement: Option<RestrictionReport>,
+    /// cgroup of the run, when one could be created.
+    cgroup_id: Option<u64>,
+    /// Resource limits hit during the run.
+    resource_events: CgroupEvents,
+}
+
+impl SandboxRun {
+    /// All denials as `process_denials` entries.
+    fn entries(&self) -> HashSet<String> {
+        let mut entries = to_denial_entries(&self.landlock);
+        entries.extend(self.reported.iter().cloned());
+        entries
+    }
+}
+
+/// Runs the app in a sandbox process and collects the denials raised meanwhile.
+///
+/// The sandbox process is forked from this one and never exec'd: the policy
+/// stays in memory, so there is no entry point another program could hand a
+/// policy of its own to. It is forked while this process is single-threaded,
+/// before the collectors start, and waits for them before running the app.
+fn run_sandboxed(app_path: &Path, args: &[String], policy: &AppPolicy) -> Result<SandboxRun> {
     // Validate app path
     if let Err(e) = AppPolicy::validate_path(app_path) {
         return Err(anyhow!("Invalid application path: {}", e));
     }
 
-    let tempdir = TempDir::new().context("Failed to create temporary directory")?;
-    let log_prefix = tempdir.path().join(prefix);
-    let log_path = log_prefix.to_string_lossy().to_string();
-
-    let mut envs: Vec<(String, String)> = env::vars().collect();
-    envs.push(("LL_FS_RO".into(), AppPolicy::join_paths(&policy.ro_paths)));
-    envs.push(("LL_FS_RW".into(), AppPolicy::join_paths(&policy.rw_paths)));
-    envs.push((
-        "LL_TCP_BIND".into(),
-        AppPolicy::join_ports(&policy.tcp_bind),
-    ));
-    envs.push((
-        "LL_TCP_CONNECT".into(),
-        AppPolicy::join_ports(&policy.tcp_connect),
-    ));
-    envs.push((
-        "LL_ALLOWED_IPS".into(),
-        AppPolicy::join_ips(&policy.allowed_ips),
-    ));
-    envs.push((
-        "LL_ALLOWED_DOMAINS".into(),
-        AppPolicy::join_domains(&policy.allowed_domains),
-    ));
-
-    let current_exe = env::current_exe().context("Failed to get current executable path")?;
-    let strace_path = env::var("STRACE_PATH").unwrap_or_else(|_| "/usr/bin/strace".to_string());
-
-    let status = Command::new(&strace_path)
-        .args(&[
-            "-ff",
-            "-yy",
-            "-e",
-            "trace=file,process,openat,getdents,stat,connect,socket,bind",
-        ])
-        .arg("-o")
-        .arg(&log_path)
-        .arg(&current_exe)
-        .arg("--sandbox")
-        .arg(app_path)
-        .args(args)
-        .env_clear()
-        .envs(envs)
-        .status()
-        .with_context(|| format!("Failed to execute strace at {}", strace_path))?;
-
-    Ok((status, tempdir))
-}
+    // Pipe for the denials detected inside the sandbox itself (IP egress filter)
+    let (report_read, report_write) = sandbox_report::pipe().context("Failed to create report pipe")?;
 
-fn parse_denied_lines(dir: &Path, prefix: &str) -> Result<HashSet<String>> {
-    // Improved regex patterns for better clarity and parsing
-    let path_re =
-        Regex::new(r#"openat\(.*?,\s*"([^"]+)"|stat\(.*?"([^"]+)"|getdents\(.*?,\s*"([^"]+)""#)
-            .context("Failed to compile path regex")?;
-    let net_re = Regex::new(r#"(connect|bind)\(.*?sin_port=htons\((\d+)\)"#)
-        .context("Failed to compile network regex")?;
-    let ip_re = Regex::new(r#"(connect|bind)\(.*?sin_addr=inet_addr\("([^"]+)"\)"#)
-        .context("Failed to compile IP regex")?;
-    let domain_re =
-        Regex::new(r#"getaddrinfo\(.*?,\s*"([^"]+)"#).context("Failed to compile domain regex")?;
-
-    let mut denials = HashSet::new();
-
-    for entry in fs::read_dir(dir).context("Failed to read log directory")? {
-        let entry = entry.context("Failed to read directory entry")?;
-        if !entry.file_name().to_string_lossy().starts_with(prefix) {
-            continue;
-        }
+    // Own cgroup for the run, for its limits and to tell its events apart.
+    // Without limits to enforce, running outside of it is fine.
+    let cgroup = match SandboxCgroup::create(&policy.resources) {
+        Ok(cgroup) => Some(cgroup),
+        Err(_) if policy.resources.is_empty() => None,
+        Err(e) => return Err(e.context("Failed to apply resource limits")),
+    };
+    let cgroup_procs = cgroup.as_ref().map(SandboxCgroup::procs_fd);
 
-        let file = match File::open(entry.path()) {
-            Ok(f) => f,
-            Err(e) => {
-                eprintln!(
-                    "Warning: Could not open log file {}: {}",
-                    entry.path().display(),
-                    e
-                );
-                continue;
-            }
-        };
+    // Written to once the collectors listen
+    let (ready_read, ready_write) = sandbox_report::pipe().context("Failed to create pipe")?;
 
-        for line in BufReader::new(file).lines() {
-            let line = match line {
-                Ok(l) => l,
-                Err(e) => {
-                    eprintln!("Warning: Error reading log line: {}", e);
-                    continue;
-                }
-            };
+    // Signals meant for the app, sent to us alone, are forwarded to it
+    let forwarding = job_control::forward_signals().context("Failed to set up signal forwarding")?;
 
-            // Only process lines with access denied errors
-            if !(line.contains("EACCES") || line.contains("EPERM")) {
-                continue;
-            }
-
-            // Match filesystem path denials
-            if let Some(cap) = path_re.captures(&line) {
-                if let Some(p) = cap.get(1).or_else(|| cap.get(2)).or_else(|| cap.get(3)) {
-                    let raw_path = PathBuf::from(p.as_str());
+    // Whatever is buffered would be printed by both processes
+    let _ = io::stdout().flush();
+    let pid = unsafe { libc::fork() };
+    if pid < 0 {
+        return Err(io::Error::last_os_error()).context("Failed to fork the sandbox");
+    }
+    if pid == 0 {
+        drop((report_read, ready_write));
+        sandbox_process(app_path, args, policy, cgroup_procs, ready_read, report_write);
+    }
+    drop((report_write, ready_read));
 
-                    // Only canonicalize paths if possible, otherwise use the raw path
-                    match fs::canonicalize(&raw_path) {
-                        Ok(canonical_path) => {
-                            denials.insert(canonical_path.to_string_lossy().to_string());
-                        }
-                        Err(_) => {
-                            // If canonicalization fails, use the original path but mark it
-                            denials.insert(format!("NONCANONICAL:{}", raw_path.to_string_lossy()));
-                        }
-                    }
-                }
-            }
-            // Match network port denials
-            else if let Some(cap) = net_re.captures(&line) {
-                if let (Some(op), Some(port)) = (cap.get(1), cap.get(2)) {
-                    // Validate port is a valid number before adding
-                    if let Ok(port_num) = port.as_str().parse::<u16>() {
-                        denials.insert(format!("tcp:{}:{}", op.as_str(), port_num));
-                    } else {
-                        eprintln!(
-                            "Warning: Invalid port number in denial log: {}",
-                            port.as_str()
-                        );
-                    }
-                }
-            }
-            // Match IP address denials
-            else if let Some(cap) = ip_re.captures(&line) {
-                if let Some(ip) = cap.get(2) {
-                    denials.insert(format!("ip:{}", ip.as_str()));
-                }
-            }
-            // Match domain name denials
-            else if let Some(cap) = domain_re.captures(&line) {
-                if let Some(domain) = cap.get(1) {
-                    denials.insert(format!("domain:{}", domain.as_str()));
-                }
-            }
+    // Subscribe before the app starts so that no early denial is missed
+    let collector = match DenialCollector::connect() {
+        Ok(c) => Some(c),
+        Err(e) => {
+            eprintln!("Warning: Landlock denial reporting unavailable: {:#}", e);
+            None
         }
+    };
+    let reported = sandbox_report::collect(report_read);
+
+    // Without it, the sandbox gives up before running the app
+    let _ = (&ready_write).write_all(&[1]);
+    drop(ready_write);
+    let status = job_control::wait_for(pid).context("Failed to wait for the sandbox")?;
+    drop(forwarding);
+
+    let cgroup_id = cgroup.as_ref().map(SandboxCgroup::id);
+    let resource_events = cgroup
+        .as_ref()
+        .map(SandboxCgroup::events)
+        .unwrap_or_default();
+    // Kills what the application left running
+    drop(cgroup);
+
+    let landlock = match collector {
+        Some(c) => c.finish().unwrap_or_else(|e| {
+            eprintln!("Warning: Failed to collect Landlock denials: {:#}", e);
+            Vec::new()
+        }),
+        None => Vec::new(),
+    };
+    for d in &landlock {
+        println!(
+            "  Landlock denied {} ({}) for pid {}: {} [{}]",
+            d.syscall,
+            d.comm,
+            d.pid,
+            d.path
+                .clone()
+                .or_else(|| d.port.map(|p| format!("port {}", p)))
+                .unwrap_or_default(),
+            d.access.join(",")
+        );
     }
 
-    Ok(denials)
+    let mut reported = reported.join().unwrap_or_default();
+    let enforcement = sandbox_report::take_enforcement(&mut reported).and_then(|json| {
+        serde_json::from_str(&json)
+            .map_err(|e| eprintln!("Warning: Invalid enforcement report: {}", e))
+            .ok()
+    });
+
+    Ok(SandboxRun {
+        status,
+        landlock,
+        reported,
+        enforcement,
+        cgroup_id,
+        resource_events,
+    })
 }
 
 fn process_denials(
@@ -1044,6 +1055,12 @@ fn process_denials(
                 policy.allowed_domains.insert(domain.to_string());
                 updated = true;
             }
+        } else if let Some(syscall) = entry.strip_prefix("syscall:") {
+            // Syscall profiles are only changed by administrators
+            println!("Syscall {} refused by the seccomp profile", syscall);
+        } else if let Some(name) = entry.strip_prefix("env:") {
+            // So is the environment section
+            println!("Environment variable {} stripped by the policy", name);
         } else {
             // Handle paths - CORRECTED SECTION
             let is_noncanonical = entry.starts_with("NONCANONICAL:");
@@ -1118,27 +1135,27 @@ fn process_denials(
             match selection {
                 0 => {
                     // Check limits before adding
-                    if policy.ro_paths.len() >= MAX_RO_PATHS {
+                    if policy.ro_rules.len() >= MAX_RO_PATHS {
                         println!(
                             "Warning: Maximum number of read-only paths ({}) reached.",
                             MAX_RO_PATHS
                         );
                         continue;
                     }
-                    policy.ro_paths.insert(canonical_path);
+                    policy.allow_ro(canonical_path);
                     updated = true;
                     println!("Added to read-only paths");
                 }
                 1 => {
                     // Check limits before adding
-                    if policy.rw_paths.len() >= MAX_RW_PATHS {
+                    if policy.rw_rules.len() >= MAX_RW_PATHS {
                         println!(
                             "Warning: Maximum number of read-write paths ({}) reached.",
                             MAX_RW_PATHS
                         );
                         continue;
                     }
-                    policy.rw_paths.insert(canonical_path);
+                    policy.allow_rw(canonical_path);
                     updated = true;
                     println!("Added to read-write paths");
                 }
@@ -1152,96 +1169,571 @@ fn process_denials(
     Ok(updated)
 }
 
+fn print_identity(what: &str, identity: &AppIdentity) {
+    println!(
+        "{}: {} (sha256 {}{})",
+        what,
+        identity.path,
+        &identity.sha256[..12],
+        identity
+            .package
+            .as_ref()
+            .map(|p| format!(", package {}", p))
+            .unwrap_or_default()
+    );
+}
+
+/// An event of a run of the application identified by `identity`, with the
+/// script it interprets.
+fn app_event(
+    identity: &AppIdentity,
+    subject: &str,
+    operation: &str,
+    result: &str,
+    cgroup_id: Option<u64>,
+) -> Result<SpooledEvent> {
+    let mut event = SpooledEvent::new(&identity.path, subject, operation, result, cgroup_id)?;
+    if let Some(script) = &identity.script {
+        event.script = Some(script.path.clone());
+        event.script_sha256 = Some(script.sha256.clone());
+    }
+    Ok(event)
+}
+
+/// Spools every denial for the server's event log.
+fn log_denials(
+    identity: &AppIdentity,
+    denials: &HashSet<String>,
+    cgroup_id: Option<u64>,
+    spool: Option<&EventSpool>,
+    token: &str,
+) {
+    let mut events = Vec::new();
+    for denial in denials {
+        let resource_type = if denial.starts_with("tcp:")
+            || denial.starts_with("ip:")
+            || denial.starts_with("domain:")
+        {
+            "network"
+        } else if denial.starts_with("syscall:") {
+            "syscall"
+        } else if denial.starts_with("env:") {
+            "environment"
+        } else {
+            "filesystem"
+        };
+
+        match app_event(identity, denial, resource_type, "denied", cgroup_id) {
+            Ok(event) => events.push(event),
+            Err(e) => eprintln!("Warning: Failed to log denial event: {:#}", e),
+        }
+    }
+    spool_events(spool, &events, token);
+}
+
+/// Reports the OOM kills and refused forks of a run. Limits are only changed by
+/// administrators, so they are not offered for update.
+fn report_resources(
+    identity: &AppIdentity,
+    run: &SandboxRun,
+    spool: Option<&EventSpool>,
+    token: &str,
+) {
+    let mut spooled = Vec::new();
+    let events = run.resource_events;
+    let hits = [
+        ("memory.max", "oom_kill", events.oom_kills, "OOM kill(s)"),
+        ("pids.max", "fork_refused", events.pids_max, "refused fork(s)"),
+    ];
+    for (limit, result, count, what) in hits.iter().filter(|h| h.2 > 0) {
+        println!("Resource limit {} reached: {} {}", limit, count, what);
+        match app_event(identity, limit, "resource", result, run.cgroup_id) {
+            Ok(event) => spooled.push(event),
+            Err(e) => eprintln!("Warning: Failed to log resource event: {:#}", e),
+        }
+    }
+    spool_events(spool, &spooled, token);
+}
+
+/// The event spool of `username`, under the home directory.
+fn event_spool(username: &str) -> Option<EventSpool> {
+    let home = env::var_os("HOME")?;
+    Some(EventSpool::new(&event_spool::spool_dir(Path::new(&home)), username))
+}
+
+/// Spools events until [`flush_events`] delivers them. Without a home
+/// directory to spool to, they are sent right away.
+fn spool_events(spool: Option<&EventSpool>, events: &[SpooledEvent], token: &str) {
+    if events.is_empty() {
+        return;
+    }
+    let res = match spool {
+        Some(spool) => spool.append(events),
+        None => send_event_batch(events, token),
+    };
+    if let Err(e) = res {
+        eprintln!("Warning: Failed to log sandbox events: {:#}", e);
+    }
+}
+
+/// Delivers the spooled events to the server; undelivered ones stay spooled
+/// for the next launch.
+fn flush_events(spool: Option<&EventSpool>, token: &str) {
+    if let Some(spool) = spool {
+        if let Err(e) = spool.flush(|batch| send_event_batch(batch, token)) {
+            eprintln!("Warning: Failed to deliver sandbox events: {:#}", e);
+        }
+    }
+}
+
+/// Uploads the enforcement report of a run and prints it with `--verbose`.
+/// Without it, only a weaker than requested enforcement is mentioned.
+fn report_enforcement(app: &str, run: &SandboxRun, verbose: bool, token: &str) {
+    let report = match &run.enforcement {
+        Some(report) => report,
+        None => {
+            eprintln!("Warning: The sandbox sent no Landlock enforcement report");
+            return;
+        }
+    };
+
+    let skipped = report.skipped().count();
+    if verbose {
+        print_enforcement(report);
+    } else if report.ruleset != RulesetStatus::FullyEnforced || skipped > 0 {
+        eprintln!(
+            "Warning: Landlock ruleset {} with {} skipped rule(s), use --verbose for details",
+            status_name(report.ruleset),
+            skipped
+        );
+    }
+
+    if let Err(e) = log_enforcement_report(app, report, token) {
+        eprintln!("Warning: Failed to log enforcement report: {}", e);
+    }
+}
+
+fn print_enforcement(report: &RestrictionReport) {
+    println!("Landlock enforcement report:");
+    println!("  ABI: {:?}", report.abi);
+    println!("  Ruleset: {}", status_name(report.ruleset));
+    println!("  No new privileges: {}", report.no_new_privs);
+
+    let dropped = [
+        ("filesystem rights", access_names(report.handled_fs.dropped())),
+        ("network rights", access_names(report.handled_net.dropped())),
+        ("scopes", access_names(report.scoped.dropped())),
+    ];
+    for (kind, names) in &dropped {
+        if !names.is_empty() {
+            println!("  Dropped {}: {}", kind, names);
+        }
+    }
+
+    let added = report.path_beneath.len() - report.skipped().count();
+    println!(
+        "  Rules added: {} path(s), {} port(s)",
+        added,
+        report.net_port.len()
+    );
+    for rule in &report.path_beneath {
+        match &rule.skipped {
+            Some(reason) => println!("    skipped {}: {}", rule.path.display(), reason),
+            None => println!(
+                "    {} [{}]",
+                rule.path.display(),
+                access_names(rule.access.enforced)
+            ),
+        }
+    }
+    for rule in &report.net_port {
+        println!(
+            "    port {} [{}]",
+            rule.port,
+            access_names(rule.access.enforced)
+        );
+    }
+}
+
+fn status_name(status: RulesetStatus) -> &'static str {
+    match status {
+        RulesetStatus::FullyEnforced => "fully enforced",
+        RulesetStatus::PartiallyEnforced => "partially enforced",
+        RulesetStatus::NotEnforced => "not enforced",
+    }
+}
+
+fn access_names<T: AccessNames>(access: BitFlags<T>) -> String {
+    T::names()
+        .iter()
+        .filter(|(_, right)| access.contains(*right))
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
 // ----------------------------------------------------------------------------
-// Main entrypoint (updated to use PAM token integration)
+// Learning mode
 // ----------------------------------------------------------------------------
 
-fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
+/// Adds learned rules to the policy within the usual limits and returns the
+/// paths that were actually added (read-only, read-write).
+fn merge_learned(policy: &mut AppPolicy, learned: &LearnedRules) -> (Vec<String>, Vec<String>) {
+    fn add<T: Clone + Eq + std::hash::Hash>(
+        set: &mut HashSet<T>,
+        items: impl IntoIterator<Item = T>,
+        max: usize,
+        what: &str,
+    ) -> Vec<T> {
+        let mut added = Vec::new();
+        for item in items {
+            if set.contains(&item) {
+                continue;
+            }
+            if set.len() >= max {
+                println!("Warning: Maximum {} ({}) reached, skipping the rest", what, max);
+                break;
+            }
+            set.insert(item.clone());
+            added.push(item);
+        }
+        added
+    }
+
+    // Learned paths are concrete; the policy stores them as templates
+    let vars = policy.vars.clone();
+    let as_rules = |paths: Vec<PathBuf>| -> Vec<String> {
+        paths
+            .iter()
+            .map(|p| path_template::templatize(p, &vars))
+            .collect()
+    };
+    let new_ro: Vec<PathBuf> = learned
+        .ro_paths
+        .iter()
+        .filter(|p| !policy.contains_path(p))
+        .cloned()
+        .collect();
+    let new_rw: Vec<PathBuf> = learned
+        .rw_paths
+        .iter()
+        .filter(|p| !policy.rw_paths.contains(*p))
+        .cloned()
+        .collect();
 
-    // Special --sandbox execution for the strace'd command
-    if args.len() >= 2 && args[1] == "--sandbox" {
-        return run_sandbox();
+    let ro = add(&mut policy.ro_rules, as_rules(new_ro), MAX_RO_PATHS, "read-only paths");
+    let rw = add(&mut policy.rw_rules, as_rules(new_rw), MAX_RW_PATHS, "read-write paths");
+    // A path learned as read-write supersedes its read-only rule
+    for rule in &rw {
+        policy.ro_rules.remove(rule);
     }
+    policy.ro_paths = AppPolicy::expand_rules(&policy.ro_rules, &vars);
+    policy.rw_paths = AppPolicy::expand_rules(&policy.rw_rules, &vars);
+    add(&mut policy.tcp_bind, learned.tcp_bind.iter().copied(), MAX_TCP_BIND_PORTS, "TCP bind ports");
+    add(
+        &mut policy.tcp_connect,
+        learned.tcp_connect.iter().copied(),
+        MAX_TCP_CONNECT_PORTS,
+        "TCP connect ports",
+    );
+    add(&mut policy.allowed_ips, learned.allowed_ips.iter().cloned(), MAX_IPS, "allowed IPs");
+    add(
+        &mut policy.allowed_domains,
+        learned.allowed_domains.iter().cloned(),
+        MAX_DOMAINS,
+        "allowed domains",
+    );
+
+    (ro, rw)
+}
 
-    // Validate command line usage
-    if args.len() < 2 {
-        return Err(anyhow!("Usage: {} <APP> [ARGS...]", args[0]));
+/// `--learn`: runs the app once, writes a policy proposal and optionally submits it.
+fn run_learning(
+    opts: &Options,
+    identity: &AppIdentity,
+    policy: &AppPolicy,
+    credentials: &Credentials,
+    permissions: &HashSet<String>,
+    spool: Option<&EventSpool>,
+    online: bool,
+) -> Result<()> {
+    let app = identity.name();
+
+    println!("Learning mode: running application with current policy...");
+    let run = run_sandboxed(&identity.exec_path, &opts.app_args, policy)
+        .context("Failed to run application in sandbox")?;
+    println!(
+        "Application exited with code: {}",
+        run.status.code().unwrap_or(-1)
+    );
+    let renewed = if online { renewed_credentials(credentials) } else { None };
+    let credentials = renewed.as_ref().unwrap_or(credentials);
+    report_enforcement(app, &run, opts.verbose, &credentials.token);
+    report_resources(identity, &run, spool, &credentials.token);
+
+    let denials = run.entries();
+    log_denials(identity, &denials, run.cgroup_id, spool, &credentials.token);
+    if online {
+        flush_events(spool, &credentials.token);
     }
 
-    // Check if we have --interactive-auth flag for fallback
-    let use_interactive = args.contains(&"--interactive-auth".to_string());
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let learned = policy_learning::learn(&run.landlock, &run.reported, home.as_deref());
+    let mut proposed = policy.clone();
+    let (new_ro, new_rw) = merge_learned(&mut proposed, &learned);
+
+    if learned.is_empty() {
+        println!("No access denial events were detected, no proposal written.");
+        return Ok(());
+    }
+
+    let justification = opts.justification.clone().unwrap_or_else(|| {
+        format!(
+            "Learned by sandboxer --learn from {} denial(s) of {}",
+            denials.len(),
+            app
+        )
+    });
+    let proposal = PolicyProposal {
+        app_name: app.to_string(),
+        role_id: None,
+        default_ro: AppPolicy::join_rules(&proposed.ro_rules),
+        default_rw: AppPolicy::join_rules(&proposed.rw_rules),
+        tcp_bind: AppPolicy::join_ports(&proposed.tcp_bind),
+        tcp_connect: AppPolicy::join_ports(&proposed.tcp_connect),
+        allowed_ips: AppPolicy::join_ips(&proposed.allowed_ips),
+        allowed_domains: AppPolicy::join_domains(&proposed.allowed_domains),
+        fs_rules: fs_access::join_fs_rules(&proposed.fs_rules),
+        scoped: scope_access::join_scopes(proposed.scoped),
+        seccomp: proposed.seccomp.to_string(),
+        resources: proposed.resources.to_string(),
+        isolation: proposed.isolation.to_string(),
+        environment: proposed.environment.to_string(),
+        allowed_ro_paths: new_ro,
+        allowed_rw_paths: new_rw,
+        change_justification: justification.clone(),
+    };
+
+    let output = opts.output.clone().unwrap_or_else(|| {
+        let name = Path::new(app)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "app".into());
+        PathBuf::from(format!("{}.policy-proposal.json", name))
+    });
+    let json = serde_json::to_string_pretty(&proposal).context("Failed to serialize proposal")?;
+    fs::write(&output, json + "\n")
+        .with_context(|| format!("Failed to write proposal to {}", output.display()))?;
+    println!(
+        "Policy proposal written to {} ({} read-only, {} read-write path(s))",
+        output.display(),
+        proposal.allowed_ro_paths.len(),
+        proposal.allowed_rw_paths.len()
+    );
+
+    if opts.submit {
+        update_policy_on_server(app, &proposed, &credentials.token, permissions, &justification)
+            .context("Failed to submit policy proposal")?;
+    }
+
+    Ok(())
+}
+
+// ----------------------------------------------------------------------------
+// Main entrypoint (updated to use PAM token integration)
+// ----------------------------------------------------------------------------
+
+const USAGE: &str = "[--interactive-auth] [--verbose] [--learn [--output FILE] [--submit] [--justification TEXT]] <APP> [ARGS...]";
+
+/// Command-line options placed before the application path.
+struct Options {
+    interactive_auth: bool,
+    verbose: bool,
+    learn: bool,
+    output: Option<PathBuf>,
+    submit: bool,
+    justification: Option<String>,
+    app: String,
+    app_args: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self> {
+        let mut opts = Options {
+            interactive_auth: false,
+            verbose: false,
+            learn: false,
+            output: None,
+            submit: false,
+            justification: None,
+            app: String::new(),
+            app_args: Vec::new(),
+        };
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--interactive-auth" => opts.interactive_auth = true,
+                "--verbose" => opts.verbose = true,
+                "--learn" => opts.learn = true,
+                "--submit" => opts.submit = true,
+                "--output" | "--justification" => {
+                    let value = iter
+                        .next()
+                        .ok_or_else(|| anyhow!("Missing value for {}", arg))?;
+                    if arg == "--output" {
+                        opts.output = Some(PathBuf::from(value));
+                    } else {
+                        opts.justification = Some(value.clone());
+                    }
+                }
+                other if other.starts_with("--") => {
+                    return Err(anyhow!("Unknown option: {}", other));
+                }
+                app => {
+                    opts.app = app.to_string();
+                    break;
+                }
+            }
+        }
+        opts.app_args = iter.cloned().collect();
+
+        if opts.app.is_empty() {
+            return Err(anyhow!("Missing application path"));
+        }
+        if !opts.learn && (opts.output.is_some() || opts.submit || opts.justification.is_some()) {
+            return Err(anyhow!("--output, --submit and --justification require --learn"));
+        }
+        Ok(opts)
+    }
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+
+    let opts = match Options::parse(&args[1..]) {
+        Ok(opts) => opts,
+        Err(e) => {
+            eprintln!("{}", e);
+            return Err(anyhow!("Usage: {} {}", args[0], USAGE));
+        }
+    };
+    let use_interactive = opts.interactive_auth;
 
     // Get app path and args
-    let app = &args[1];
-    let app_args = &args[2..];
+    let app_args = &opts.app_args[..];
 
     // Validate app path
-    let app_path = Path::new(app);
-    if let Err(e) = AppPolicy::validate_path(app_path) {
+    if let Err(e) = AppPolicy::validate_path(Path::new(&opts.app)) {
         return Err(anyhow!("Invalid application path: {}", e));
     }
 
+    // Policies, events and updates follow the binary actually run, whatever
+    // name it was given, and the script it interprets
+    let identity = AppIdentity::of_command(&opts.app, app_args)
+        .context("Failed to identify application")?;
+    let app = identity.name();
+    let app_path = identity.exec_path.as_path();
+    print_identity("Application", &identity);
+    if let Some(script) = &identity.script {
+        print_identity("Script", script);
+    }
+
     // Get credentials from PAM cache or fallback to interactive
-    let credentials = if use_interactive {
-        get_credentials_interactive().context("Interactive authentication failed")?
+    let cached = if use_interactive {
+        Ok(get_credentials_interactive().context("Interactive authentication failed")?)
     } else {
-        match get_credentials() {
-            Ok(creds) => creds,
-            Err(e) => {
-                eprintln!("Failed to retrieve cached authentication: {}", e);
-                eprintln!("Hint: Use --interactive-auth flag for manual authentication");
+        get_credentials()
+    };
+
+    // Policy and permissions from the server, or from the signed policy cache
+    // when the server cannot be reached or no session is left
+    let fetched = cached.map(|credentials| {
+        println!("Authentication successful! User: {}", credentials.username);
+        let fetched = fetch_policy(&identity, &credentials);
+        (credentials, fetched)
+    });
+    let (credentials, permissions, ruleset, online) = match fetched {
+        Ok((credentials, Ok((permissions, ruleset)))) => (credentials, permissions, ruleset, true),
+        Ok((credentials, Err(e))) => {
+            if !policy_client::is_unreachable(&e) {
                 return Err(e);
             }
+            eprintln!("Warning: Server unreachable: {:#}", e);
+            let ruleset = load_cached_policy(&identity, &credentials.username)
+                .context("Failed to run offline")?;
+            (credentials, HashSet::new(), ruleset, false)
+        }
+        Err(e) => {
+            eprintln!("Failed to retrieve cached authentication: {}", e);
+            let username = get_current_username()?;
+            let ruleset = load_cached_policy(&identity, &username).map_err(|offline| {
+                eprintln!("Offline mode unavailable: {:#}", offline);
+                eprintln!("Hint: Use --interactive-auth flag for manual authentication");
+                e
+            })?;
+            let credentials = Credentials::new(username, String::new(), None);
+            (credentials, HashSet::new(), ruleset, false)
         }
     };
 
-    println!("Authentication successful! User: {}", credentials.username);
-
-    // Verify user permissions
-    let permissions =
-        verify_user_permissions(&credentials.token).context("Failed to verify user permissions")?;
-
-    // Retrieve policy from server
-    let ruleset = RuleSet::fetch_for_app(app, &credentials.token)
-        .context("Failed to fetch policy from server")?;
-    let mut policy = AppPolicy::from(ruleset);
+    // Events of earlier runs that could not be delivered
+    let spool = event_spool(&credentials.username);
+    if online {
+        flush_events(spool.as_ref(), &credentials.token);
+    }
+    let mut policy = AppPolicy::from_ruleset(ruleset, TemplateVars::current(Some(app_path)));
     let original_policy = policy.clone();
 
-    // First run with strace to capture denials
-    println!("Running application with current policy...");
-    let (status, tempdir) = run_strace(app_path, app_args, &policy, "sandbox_log")
-        .context("Failed to run application with strace")?;
+    if opts.learn {
+        return run_learning(
+            &opts,
+            &identity,
+            &policy,
+            &credentials,
+            &permissions,
+            spool.as_ref(),
+            online,
+        );
+    }
 
-    // Parse denial logs
-    let denials =
-        parse_denied_lines(tempdir.path(), "sandbox_log").context("Failed to parse denial logs")?;
+    // First run, collecting Landlock denials
+    println!("Running application with current policy...");
+    let run = run_sandboxed(app_path, app_args, &policy)
+        .context("Failed to run application in sandbox")?;
+    // The access token may have expired during a long run
+    let credentials = match online {
+        true => renewed_credentials(&credentials).unwrap_or(credentials),
+        false => credentials,
+    };
+    report_enforcement(app, &run, opts.verbose, &credentials.token);
+    report_resources(&identity, &run, spool.as_ref(), &credentials.token);
+    let status = run.status;
+    let denials = run.entries();
 
     // Log denials if any
     if !denials.is_empty() {
         println!("Detected {} access denials", denials.len());
+        log_denials(&identity, &denials, run.cgroup_id, spool.as_ref(), &credentials.token);
+    }
+    if online {
+        flush_events(spool.as_ref(), &credentials.token);
+    }
 
-        for denial in &denials {
-            let resource_type = if denial.starts_with("tcp:")
-                || denial.starts_with("ip:")
-                || denial.starts_with("domain:")
-            {
-                "network"
-            } else {
-                "filesystem"
-            };
-
-            if let Err(e) = log_denial_event(app, denial, resource_type, &credentials.token) {
-                eprintln!("Warning: Failed to log denial event: {}", e);
-            }
-        }
+    if !denials.is_empty() {
 
         // Process denials and update policy if user has permission
         let updated = process_denials(denials.clone(), &mut policy, &permissions)?;
 
         // Update policy on server if changes were made
         if updated {
-            match update_policy_on_server(app, &policy, &credentials.token, &permissions) {
+            match update_policy_on_server(
+                app,
+                &policy,
+                &credentials.token,
+                &permissions,
+                "Automatically updated from sandboxer after access denial",
+            ) {
                 Ok(_) => {
                     println!("Your policy update request has been submitted and is pending approval.");
                     println!("Until approved, the current policy remains in effect.");
@@ -1263,11 +1755,11 @@ fn main() -> Result<()> {
 
             if rerun {
                 println!("Rerunning application with approved policy...");
-                match run_strace(app_path, app_args, &original_policy, "rerun_log") {
-                    Ok((status, _)) => {
+                match run_sandboxed(app_path, app_args, &original_policy) {
+                    Ok(rerun) => {
                         println!(
                             "Application rerun completed with exit code: {}",
-                            status.code().unwrap_or(-1)
+                            rerun.status.code().unwrap_or(-1)
                         );
                     }
                     Err(e) => {
@@ -1286,36 +1778,187 @@ fn main() -> Result<()> {
         println!("Application ran successfully with the current policy.");
     }
 
-    Ok(())
+    // Exit like the application did, 128+N if killed by signal N
+    std::process::exit(job_control::exit_code(status));
 }
 
-// Add this function to your sandboxer_db.rs file
+/// The forked sandbox process. It never returns to the caller, whose state
+/// (cgroup, collectors) belongs to the parent, and exits like the app did.
+fn sandbox_process(
+    app_path: &Path,
+    app_args: &[String],
+    policy: &AppPolicy,
+    cgroup_procs: Option<RawFd>,
+    ready: File,
+    report: File,
+) -> ! {
+    let run = panic::catch_unwind(AssertUnwindSafe(|| {
+        run_sandbox(app_path, app_args, policy, cgroup_procs, ready, report)
+    }));
+    let code = match run {
+        Ok(Ok(status)) => job_control::exit_code(status),
+        Ok(Err(e)) => {
+            eprintln!("Error: {:?}", e);
+            1
+        }
+        Err(_) => 101,
+    };
+    let _ = io::stdout().flush();
+    unsafe { libc::_exit(code) }
+}
 
-fn run_sandbox() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
-    
-    // Skip the first two args (program name and "--sandbox")
-    if args.len() < 3 {
-        return Err(anyhow!("Usage: {} --sandbox <APP> [ARGS...]", args[0]));
+fn run_sandbox(
+    app_path: &Path,
+    app_args: &[String],
+    policy: &AppPolicy,
+    cgroup_procs: Option<RawFd>,
+    mut ready: File,
+    report: File,
+) -> Result<ExitStatus> {
+    if let Some(fd) = cgroup_procs {
+        cgroup::enter(fd).context("Failed to enter the sandbox cgroup")?;
     }
-    
-    let app_path = &args[2];
-    let app_args = &args[3..];
-    
-    // Load policy from environment variables (set by parent strace process)
-    let policy = AppPolicy::from_env()
-        .context("Failed to load policy from environment variables")?;
-    
+    // Nothing is sent if the sandboxer failed or died meanwhile
+    ready
+        .read_exact(&mut [0])
+        .context("Sandboxer gone before the application started")?;
+    drop(ready);
+
+    // Namespaces go first: unshare() needs a single thread, and Landlock then
+    // keeps the application from undoing the mounts
+    let isolated = enter_namespaces(policy, &app_path.to_string_lossy())?;
+
+    let reporter = Some(Reporter::new(report));
+    let allowed_ips = Arc::new(RwLock::new(IpAllowList::from_entries(&policy.allowed_ips)));
+
+    // Started before Landlock so that its upstream queries are not confined.
+    // Without network, there is no upstream to reach.
+    let dns_stub = if policy.isolation.no_network {
+        None
+    } else {
+        start_dns_stub(policy, allowed_ips.clone(), reporter.clone())?
+    };
+
     // Apply Landlock restrictions based on the policy
-    enforce_landlock(&policy)
+    let report = enforce_landlock(policy, dns_stub)
         .context("Failed to apply Landlock restrictions")?;
+    if let (Some(reporter), Ok(json)) = (&reporter, serde_json::to_string(&report)) {
+        reporter.report(&format!("{}{}", sandbox_report::ENFORCEMENT_PREFIX, json));
+    }
+
+    // Environment of the application, without our own control variables
+    let (app_env, stripped) = policy.environment.apply(env::vars_os());
+    if let Some(reporter) = &reporter {
+        for name in &stripped {
+            reporter.report(&format!("env:{}", name));
+        }
+    }
+
+    let mut command = Command::new(app_path);
+    command.args(app_args).env_clear().envs(app_env);
+
+    // Without network, destination IPs are moot; without a syscall profile
+    // either, there is nothing left to supervise and the app replaces us
+    if isolated && policy.isolation.no_network && policy.seccomp.is_empty() {
+        let err = command.exec();
+        return Err(err)
+            .with_context(|| format!("Failed to execute application: {}", app_path.display()));
+    }
+
+    // Before the filter, in case the syscall profile refuses sigaction()
+    let _forwarding =
+        job_control::forward_signals().context("Failed to set up signal forwarding")?;
+
+    // Landlock only filters ports: destination IPs go through the egress
+    // filter, which also enforces the syscall profile
+    egress_filter::install(allowed_ips, policy.seccomp.clone(), reporter, dns_stub)
+        .context("Failed to apply seccomp filter")?;
     
-    // Execute the target application
-    let status = Command::new(app_path)
-        .args(app_args)
-        .status()
-        .with_context(|| format!("Failed to execute application: {}", app_path))?;
-    
-    // Exit with the same code as the target application
-    std::process::exit(status.code().unwrap_or(1));
+    // Execute the target application, staying around for the egress supervisor
+    let child = command
+        .spawn()
+        .with_context(|| format!("Failed to execute application: {}", app_path.display()))?;
+    job_control::wait(child).context("Failed to wait for the application")
+}
+
+/// Enters the namespaces of the `isolation` policy field, returning whether
+/// it did. Without user namespaces, the application is only confined by
+/// Landlock and seccomp.
+fn enter_namespaces(policy: &AppPolicy, app_path: &str) -> Result<bool> {
+    let isolation = &policy.isolation;
+    if isolation.is_empty() {
+        return Ok(false);
+    }
+
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let scratch = match (&home, isolation.scratch_home) {
+        (Some(home), true) => {
+            let app = Path::new(app_path)
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "app".into());
+            let scratch = isolation::scratch_home(home, &app);
+            fs::create_dir_all(&scratch)
+                .with_context(|| format!("Failed to create {}", scratch.display()))?;
+            Some(scratch)
+        }
+        _ => None,
+    };
+
+    if let Err(e) = isolation.unshare() {
+        eprintln!("Warning: Namespace isolation ({}) unavailable: {}", isolation, e);
+        if let Some(sysctl) = isolation::userns_restriction() {
+            eprintln!("  Unprivileged user namespaces are disabled by the {} sysctl", sysctl);
+        }
+        eprintln!("  The application runs without it, with Landlock restrictions only");
+        return Ok(false);
+    }
+
+    // Paths Landlock lets the application write to
+    let write = PolicyAccessFs::from_write(ABI::V6);
+    let writable: Vec<PathBuf> = policy
+        .rw_paths
+        .iter()
+        .chain(
+            policy
+                .fs_paths
+                .iter()
+                .filter(|(_, access)| access.intersects(write))
+                .map(|(path, _)| path),
+        )
+        .cloned()
+        .collect();
+
+    let home = home.as_deref().zip(scratch.as_deref());
+    isolation
+        .setup(home, &writable)
+        .context("Failed to set up namespace isolation")?;
+    Ok(true)
+}
+
+/// Starts the sandbox-local DNS stub enforcing `allowed_domains`. Without it,
+/// DNS servers are only subject to the IP allowlist, so a policy with allowed
+/// domains refuses to run without it.
+fn start_dns_stub(
+    policy: &AppPolicy,
+    allowed_ips: Arc<RwLock<IpAllowList>>,
+    reporter: Option<Reporter>,
+) -> Result<Option<SocketAddr>> {
+    let upstream = fs::read_to_string("/etc/resolv.conf")
+        .ok()
+        .and_then(|conf| dns_stub::upstream_from_resolv_conf(&conf))
+        .ok_or_else(|| anyhow!("No nameserver in /etc/resolv.conf"));
+    let domains = DomainAllowList::from_entries(&policy.allowed_domains);
+    let stub = upstream.and_then(|upstream| {
+        DnsStub::start(upstream, domains, allowed_ips, reporter)
+            .context("Failed to start DNS stub")
+    });
+    match stub {
+        Ok(stub) => Ok(Some(stub.addr())),
+        Err(e) if policy.allowed_domains.is_empty() => {
+            eprintln!("Warning: {:#}", e);
+            Ok(None)
+        }
+        Err(e) => Err(e.context("Cannot enforce allowed_domains")),
+    }
 }
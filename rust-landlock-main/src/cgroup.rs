@@ -0,0 +1,467 @@
+//! cgroup v2 resource limits for sandboxed runs.
+//!
+//! A policy can carry a `resources` list, with the same `:` separator as the
+//! other policy fields:
+//!
+//! ```text
+//! memory=512M:pids=256:cpu=50%:io=200
+//! ```
+//!
+//! `memory` sets `memory.max` (bytes, or with a `K`, `M`, `G` or `T` suffix),
+//! `pids` sets `pids.max`, `cpu` sets `cpu.max` as a share of one CPU (`150%`
+//! is one and a half CPUs) and `io` sets the default `io.weight` (1 to 10000).
+//!
+//! Each run gets its own cgroup, `sandbox-<pid>-<n>`, under the cgroup the
+//! sandboxer runs in, or under [`CGROUP_ENV`]. Limits need the matching
+//! controllers to be delegated to that cgroup, e.g. by starting the sandboxer
+//! with `systemd-run --user --scope -p Delegate=yes`. As a cgroup holding
+//! processes cannot enable controllers for its children, the sandboxer first
+//! moves itself into a `supervisor` leaf. The run's cgroup is killed and
+//! removed when dropped, after the application exits.
+
+use anyhow::{anyhow, Context, Result};
+use std::env;
+use std::ffi::CString;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// Environment variable naming the delegated cgroup to create runs under,
+/// as an absolute path in the cgroup v2 mount.
+pub const CGROUP_ENV: &str = "SUPERNANNY_CGROUP";
+
+const CGROUP2_MOUNT: &str = "/sys/fs/cgroup";
+const CGROUP2_SUPER_MAGIC: i64 = 0x6367_7270;
+/// `cpu.max` period, in microseconds.
+const CPU_PERIOD: u64 = 100_000;
+const IO_WEIGHTS: std::ops::RangeInclusive<u16> = 1..=10_000;
+const BYTE_SUFFIXES: [(char, u32); 4] = [('T', 40), ('G', 30), ('M', 20), ('K', 10)];
+
+/// A parsed `resources` list. Unset limits are left to the parent cgroup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// `memory.max`, in bytes.
+    pub memory_max: Option<u64>,
+    /// `pids.max`.
+    pub pids_max: Option<u64>,
+    /// `cpu.max`, in percent of one CPU.
+    pub cpu_percent: Option<u32>,
+    /// Default `io.weight`.
+    pub io_weight: Option<u16>,
+}
+
+impl ResourceLimits {
+    pub fn parse(list: &str) -> Result<Self> {
+        let mut limits = Self::default();
+        for entry in list.split(':').map(str::trim).filter(|e| !e.is_empty()) {
+            let (key, value) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Invalid resource limit: {}", entry))?;
+            let invalid = || anyhow!("Invalid {} limit: {}", key, value);
+            match key {
+                "memory" => limits.memory_max = Some(parse_bytes(value).ok_or_else(invalid)?),
+                "pids" => {
+                    let pids = value.parse().ok().filter(|n| *n > 0);
+                    limits.pids_max = Some(pids.ok_or_else(invalid)?);
+                }
+                "cpu" => {
+                    let percent = value
+                        .strip_suffix('%')
+                        .and_then(|p| p.parse().ok())
+                        .filter(|p| *p > 0);
+                    limits.cpu_percent = Some(percent.ok_or_else(invalid)?);
+                }
+                "io" => {
+                    let weight = value.parse().ok().filter(|w| IO_WEIGHTS.contains(w));
+                    limits.io_weight = Some(weight.ok_or_else(invalid)?);
+                }
+                _ => return Err(anyhow!("Unknown resource limit: {}", key)),
+            }
+        }
+        Ok(limits)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Controllers the limits need, as named in `cgroup.controllers`.
+    pub fn controllers(&self) -> Vec<&'static str> {
+        let mut controllers = Vec::new();
+        if self.memory_max.is_some() {
+            controllers.push("memory");
+        }
+        if self.pids_max.is_some() {
+            controllers.push("pids");
+        }
+        if self.cpu_percent.is_some() {
+            controllers.push("cpu");
+        }
+        if self.io_weight.is_some() {
+            controllers.push("io");
+        }
+        controllers
+    }
+
+    /// Interface files to write, with their content.
+    fn settings(&self) -> Vec<(&'static str, String)> {
+        let mut settings = Vec::new();
+        if let Some(bytes) = self.memory_max {
+            settings.push(("memory.max", bytes.to_string()));
+        }
+        if let Some(pids) = self.pids_max {
+            settings.push(("pids.max", pids.to_string()));
+        }
+        if let Some(percent) = self.cpu_percent {
+            let quota = u64::from(percent) * CPU_PERIOD / 100;
+            settings.push(("cpu.max", format!("{} {}", quota, CPU_PERIOD)));
+        }
+        if let Some(weight) = self.io_weight {
+            settings.push(("io.weight", format!("default {}", weight)));
+        }
+        settings
+    }
+}
+
+/// Canonical `resources` list.
+impl fmt::Display for ResourceLimits {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut entries = Vec::new();
+        if let Some(bytes) = self.memory_max {
+            entries.push(format!("memory={}", format_bytes(bytes)));
+        }
+        if let Some(pids) = self.pids_max {
+            entries.push(format!("pids={}", pids));
+        }
+        if let Some(percent) = self.cpu_percent {
+            entries.push(format!("cpu={}%", percent));
+        }
+        if let Some(weight) = self.io_weight {
+            entries.push(format!("io={}", weight));
+        }
+        f.write_str(&entries.join(":"))
+    }
+}
+
+fn parse_bytes(value: &str) -> Option<u64> {
+    let (digits, shift) = match BYTE_SUFFIXES
+        .iter()
+        .find(|(suffix, _)| value.ends_with(*suffix))
+    {
+        Some((_, shift)) => (&value[..value.len() - 1], *shift),
+        None => (value, 0),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(1 << shift))
+        .filter(|n| *n > 0)
+}
+
+fn format_bytes(bytes: u64) -> String {
+    BYTE_SUFFIXES
+        .iter()
+        .find(|(_, shift)| bytes % (1 << shift) == 0)
+        .map(|(suffix, shift)| format!("{}{}", bytes >> shift, suffix))
+        .unwrap_or_else(|| bytes.to_string())
+}
+
+/// Limit hits counted by the kernel during a run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CgroupEvents {
+    /// Processes killed for exceeding `memory.max`.
+    pub oom_kills: u64,
+    /// Forks refused by `pids.max`.
+    pub pids_max: u64,
+}
+
+impl CgroupEvents {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// The cgroup of one sandboxed run, killed and removed when dropped.
+#[derive(Debug)]
+pub struct SandboxCgroup {
+    path: PathBuf,
+    id: u64,
+    procs: File,
+}
+
+static RUNS: AtomicU32 = AtomicU32::new(0);
+
+impl SandboxCgroup {
+    /// Creates the cgroup of a new run with `limits` applied.
+    pub fn create(limits: &ResourceLimits) -> Result<Self> {
+        let base = match env::var_os(CGROUP_ENV) {
+            Some(path) => PathBuf::from(path),
+            None => current_cgroup()?,
+        };
+        if !is_cgroup2(&base) {
+            return Err(anyhow!("{} is not a cgroup v2 directory", base.display()));
+        }
+
+        let controllers = limits.controllers();
+        if !controllers.is_empty() {
+            enable_controllers(&base, &controllers)?;
+        }
+
+        let run = RUNS.fetch_add(1, Ordering::Relaxed);
+        let path = base.join(format!("sandbox-{}-{}", std::process::id(), run));
+        fs::create_dir(&path)
+            .with_context(|| format!("Failed to create cgroup {}", path.display()))?;
+
+        let setup = || -> Result<Self> {
+            for (file, value) in limits.settings() {
+                write_file(&path.join(file), &value)?;
+            }
+            let procs = OpenOptions::new()
+                .write(true)
+                .custom_flags(libc::O_CLOEXEC)
+                .open(path.join("cgroup.procs"))
+                .context("Failed to open cgroup.procs")?;
+            Ok(Self {
+                id: fs::metadata(&path)?.ino(),
+                path: path.clone(),
+                procs,
+            })
+        };
+        setup().map_err(|e| {
+            let _ = fs::remove_dir(&path);
+            e
+        })
+    }
+
+    /// cgroup id, as in BPF and audit records.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// File descriptor of `cgroup.procs`, for [`enter`].
+    pub fn procs_fd(&self) -> RawFd {
+        self.procs.as_raw_fd()
+    }
+
+    /// Limit hits so far, counting the whole subtree.
+    pub fn events(&self) -> CgroupEvents {
+        let count = |file: &str, key: &str| {
+            fs::read_to_string(self.path.join(file))
+                .ok()
+                .and_then(|events| event_count(&events, key))
+                .unwrap_or(0)
+        };
+        CgroupEvents {
+            oom_kills: count("memory.events", "oom_kill"),
+            pids_max: count("pids.events", "max"),
+        }
+    }
+}
+
+impl Drop for SandboxCgroup {
+    fn drop(&mut self) {
+        // Background processes left by the application keep the cgroup busy
+        // (cgroup.kill needs Linux 5.14)
+        let _ = write_file(&self.path.join("cgroup.kill"), "1");
+        for _ in 0..50 {
+            match fs::remove_dir(&self.path) {
+                Err(e) if e.raw_os_error() == Some(libc::EBUSY) => {
+                    thread::sleep(Duration::from_millis(10))
+                }
+                Err(e) => {
+                    eprintln!(
+                        "Warning: Failed to remove cgroup {}: {}",
+                        self.path.display(),
+                        e
+                    );
+                    return;
+                }
+                Ok(()) => return,
+            }
+        }
+        eprintln!(
+            "Warning: cgroup {} still has processes, not removed",
+            self.path.display()
+        );
+    }
+}
+
+/// Moves the calling process into the cgroup whose `cgroup.procs` is open as
+/// `procs_fd`; meant to be called from a `pre_exec` hook.
+pub fn enter(procs_fd: RawFd) -> std::io::Result<()> {
+    let pid = b"0";
+    if unsafe { libc::write(procs_fd, pid.as_ptr().cast(), pid.len()) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn current_cgroup() -> Result<PathBuf> {
+    let cgroups =
+        fs::read_to_string("/proc/self/cgroup").context("Failed to read /proc/self/cgroup")?;
+    let path = unified_path(&cgroups).ok_or_else(|| anyhow!("No cgroup v2 hierarchy"))?;
+    Ok(Path::new(CGROUP2_MOUNT).join(path.trim_start_matches('/')))
+}
+
+/// Path in the unified hierarchy, from `/proc/<pid>/cgroup`.
+fn unified_path(cgroups: &str) -> Option<&str> {
+    cgroups.lines().find_map(|l| l.strip_prefix("0::"))
+}
+
+fn is_cgroup2(path: &Path) -> bool {
+    let path = match CString::new(path.as_os_str().as_bytes()) {
+        Ok(path) => path,
+        Err(_) => return false,
+    };
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    let found = unsafe { libc::statfs(path.as_ptr(), &mut stat) } == 0;
+    found && stat.f_type as i64 == CGROUP2_SUPER_MAGIC
+}
+
+/// Enables `controllers` for the children of `base`.
+fn enable_controllers(base: &Path, controllers: &[&str]) -> Result<()> {
+    let has = |file: &str, controller: &str| -> Result<bool> {
+        let list = fs::read_to_string(base.join(file))
+            .with_context(|| format!("Failed to read {} of {}", file, base.display()))?;
+        Ok(list.split_whitespace().any(|c| c == controller))
+    };
+
+    let mut missing = Vec::new();
+    for controller in controllers {
+        if !has("cgroup.controllers", controller)? {
+            return Err(anyhow!(
+                "The {} controller is not delegated to {}",
+                controller,
+                base.display()
+            ));
+        }
+        if !has("cgroup.subtree_control", controller)? {
+            missing.push(format!("+{}", controller));
+        }
+    }
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    // Only a cgroup without processes can hand controllers down
+    let procs = fs::read_to_string(base.join("cgroup.procs")).unwrap_or_default();
+    if !procs.trim().is_empty() {
+        let leaf = base.join("supervisor");
+        if let Err(e) = fs::create_dir(&leaf) {
+            if e.kind() != std::io::ErrorKind::AlreadyExists {
+                return Err(e).with_context(|| format!("Failed to create {}", leaf.display()));
+            }
+        }
+        write_file(&leaf.join("cgroup.procs"), &std::process::id().to_string())?;
+    }
+    write_file(&base.join("cgroup.subtree_control"), &missing.join(" ")).with_context(|| {
+        format!(
+            "Failed to enable {} (are other processes in {}?)",
+            missing.join(" "),
+            base.display()
+        )
+    })
+}
+
+fn write_file(path: &Path, value: &str) -> Result<()> {
+    fs::write(path, value).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Counter `key` of a flat-keyed file such as `memory.events`.
+fn event_count(events: &str, key: &str) -> Option<u64> {
+    events.lines().find_map(|l| {
+        let (k, v) = l.split_once(' ')?;
+        if k == key {
+            v.trim().parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_round_trip() {
+        let limits = ResourceLimits::parse("io=200: memory=512M:cpu=150%:pids=256").unwrap();
+        assert_eq!(
+            limits,
+            ResourceLimits {
+                memory_max: Some(512 << 20),
+                pids_max: Some(256),
+                cpu_percent: Some(150),
+                io_weight: Some(200),
+            }
+        );
+        assert_eq!(limits.to_string(), "memory=512M:pids=256:cpu=150%:io=200");
+        assert_eq!(ResourceLimits::parse(&limits.to_string()).unwrap(), limits);
+        assert_eq!(limits.controllers(), ["memory", "pids", "cpu", "io"]);
+
+        assert!(ResourceLimits::parse("").unwrap().is_empty());
+        let odd = ResourceLimits::parse("memory=1000").unwrap();
+        assert_eq!(odd.memory_max, Some(1000));
+        assert_eq!(odd.to_string(), "memory=1000");
+        assert_eq!(
+            ResourceLimits::parse("memory=3072K").unwrap().to_string(),
+            "memory=3M"
+        );
+    }
+
+    #[test]
+    fn limits_invalid() {
+        for list in [
+            "memory",
+            "memory=0",
+            "memory=12X",
+            "memory=99999999999T",
+            "pids=0",
+            "pids=-1",
+            "cpu=50",
+            "cpu=0%",
+            "io=0",
+            "io=10001",
+            "swap=1G",
+        ] {
+            assert!(ResourceLimits::parse(list).is_err(), "{}", list);
+        }
+    }
+
+    #[test]
+    fn limits_settings() {
+        let limits = ResourceLimits::parse("memory=1G:pids=64:cpu=50%:io=10").unwrap();
+        assert_eq!(
+            limits.settings(),
+            [
+                ("memory.max", "1073741824".to_string()),
+                ("pids.max", "64".to_string()),
+                ("cpu.max", "50000 100000".to_string()),
+                ("io.weight", "default 10".to_string()),
+            ]
+        );
+        assert!(ResourceLimits::default().settings().is_empty());
+    }
+
+    #[test]
+    fn cgroup_files() {
+        let cgroups = "12:pids:/user.slice\n0::/user.slice/app.scope\n";
+        assert_eq!(unified_path(cgroups), Some("/user.slice/app.scope"));
+        assert_eq!(unified_path("4:memory:/\n"), None);
+
+        let events = "low 0\nhigh 0\nmax 12\noom 2\noom_kill 1\noom_group_kill 0\n";
+        assert_eq!(event_count(events, "oom_kill"), Some(1));
+        assert_eq!(event_count(events, "max"), Some(12));
+        assert_eq!(event_count(events, "swap"), None);
+    }
+}
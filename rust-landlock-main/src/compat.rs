@@ -1,4 +1,5 @@
 use crate::{uapi, Access, CompatError};
+use serde::{Deserialize, Serialize};
 
 #[cfg(test)]
 use std::convert::TryInto;
@@ -39,11 +40,8 @@ use strum_macros::{EnumCount as EnumCountMacro, EnumIter};
 ///
 /// In a nutshell, test the access rights you request on a kernel that support them and
 /// on a kernel that doesn't support them.
-#[cfg_attr(
-    test,
-    derive(Debug, PartialEq, Eq, PartialOrd, EnumIter, EnumCountMacro)
-)]
-#[derive(Copy, Clone)]
+#[cfg_attr(test, derive(PartialOrd, EnumIter, EnumCountMacro))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[non_exhaustive]
 pub enum ABI {
     /// Kernel not supporting Landlock, either because it is not built with Landlock
@@ -64,6 +62,9 @@ pub enum ABI {
     /// Fifth Landlock ABI, introduced with
     /// [Linux 6.10](https://git.kernel.org/stable/c/2fc0e7892c10734c1b7c613ef04836d57d4676d5).
     V5 = 5,
+    /// Sixth Landlock ABI, introduced with
+    /// [Linux 6.12](https://git.kernel.org/stable/c/e1b061b444fb01c237838f0d8238653afe6a8094).
+    V6 = 6,
 }
 
 impl ABI {
@@ -91,8 +92,9 @@ impl ABI {
             2 => ABI::V2,
             3 => ABI::V3,
             4 => ABI::V4,
+            5 => ABI::V5,
             // Returns the greatest known ABI.
-            _ => ABI::V5,
+            _ => ABI::V6,
         }
     }
 
@@ -378,7 +380,7 @@ pub trait Compatible: Sized + private::OptionCompatLevelMut {
     /// (thanks to [`AccessFs::Refer`](crate::AccessFs::Refer)) or silently do nothing.
     ///
     /// ```
-    /// use landlock::*;
+    /// use supernanny_sandboxer::*;
     ///
     /// fn ruleset_handling_renames() -> Result<RulesetCreated, RulesetError> {
     ///     Ok(Ruleset::default()
@@ -403,7 +405,7 @@ pub trait Compatible: Sized + private::OptionCompatLevelMut {
     /// and opportunistically handle restrictions supported by newer kernels.
     ///
     /// ```
-    /// use landlock::*;
+    /// use supernanny_sandboxer::*;
     ///
     /// fn ruleset_fragile() -> Result<RulesetCreated, RulesetError> {
     ///     Ok(Ruleset::default()
@@ -465,7 +467,8 @@ fn deprecated_set_best_effort() {
 
 /// See the [`Compatible`] documentation.
 #[cfg_attr(test, derive(EnumIter))]
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum CompatLevel {
     /// Takes into account the build requests if they are supported by the running system,
     /// or silently ignores them otherwise.
@@ -0,0 +1,180 @@
+//! Client for the Landlock denial collector run by the SuperNanny interceptor.
+//!
+//! The interceptor attaches eBPF programs to the Landlock hooks and reports every
+//! access they refuse. A sandboxer connects to [`DENIAL_SOCKET`]; the collector
+//! identifies it through `SO_PEERCRED` and streams back, as one JSON object per
+//! line, the denials raised by any of its descendants.
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+pub const DENIAL_SOCKET: &str = "/run/supernanny/denials.sock";
+
+const READY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// One access refused by Landlock, as reported by the collector.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct LandlockDenial {
+    pub pid: u32,
+    #[serde(default)]
+    pub comm: String,
+    pub syscall: String,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub access: Vec<String>,
+}
+
+impl LandlockDenial {
+    /// Converts the denial to the entry format understood by `process_denials`:
+    /// a canonical path (or `NONCANONICAL:<path>`), or `tcp:<bind|connect>:<port>`.
+    ///
+    /// Links, renames and symlinks are checked on the directories holding the
+    /// entries, so they map to the parent directory of the reported path.
+    pub fn to_denial_entry(&self) -> Option<String> {
+        match (self.syscall.as_str(), self.port, &self.path) {
+            ("bind" | "connect", Some(port), _) => Some(format!("tcp:{}:{}", self.syscall, port)),
+            ("link" | "rename" | "symlink", _, Some(path)) => {
+                Path::new(path).parent().map(|dir| path_entry(&dir.to_string_lossy()))
+            }
+            (_, _, Some(path)) => Some(path_entry(path)),
+            _ => None,
+        }
+    }
+}
+
+fn path_entry(path: &str) -> String {
+    match fs::canonicalize(path) {
+        Ok(canonical) => canonical.to_string_lossy().to_string(),
+        Err(_) => format!("NONCANONICAL:{}", path),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Ready {
+    ready: bool,
+}
+
+/// Subscription to the denials of the current process and its children.
+pub struct DenialCollector {
+    stream: UnixStream,
+    reader: JoinHandle<Vec<LandlockDenial>>,
+}
+
+impl DenialCollector {
+    /// Connects to the collector and waits until the current process is watched.
+    pub fn connect() -> Result<Self> {
+        Self::connect_to(Path::new(DENIAL_SOCKET))
+    }
+
+    pub fn connect_to(socket: &Path) -> Result<Self> {
+        let stream = UnixStream::connect(socket)
+            .with_context(|| format!("Failed to connect to denial collector at {}", socket.display()))?;
+        let reader_stream = stream
+            .try_clone()
+            .context("Failed to clone collector socket")?;
+
+        let (ready_tx, ready_rx) = channel::<bool>();
+        let reader = thread::spawn(move || {
+            let mut lines = BufReader::new(reader_stream).lines();
+            let ready = lines
+                .next()
+                .and_then(|l| l.ok())
+                .and_then(|l| serde_json::from_str::<Ready>(&l).ok())
+                .map_or(false, |r| r.ready);
+            let _ = ready_tx.send(ready);
+            if !ready {
+                return Vec::new();
+            }
+            lines
+                .map_while(Result::ok)
+                .filter_map(|l| serde_json::from_str::<LandlockDenial>(&l).ok())
+                .collect()
+        });
+
+        wait_ready(&ready_rx)?;
+        Ok(Self { stream, reader })
+    }
+
+    /// Signals the end of the run and returns every denial reported meanwhile.
+    pub fn finish(self) -> Result<Vec<LandlockDenial>> {
+        self.stream
+            .shutdown(Shutdown::Write)
+            .context("Failed to close collector socket")?;
+        self.reader
+            .join()
+            .map_err(|_| anyhow!("Denial collector reader panicked"))
+    }
+}
+
+fn wait_ready(rx: &Receiver<bool>) -> Result<()> {
+    match rx.recv_timeout(READY_TIMEOUT) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(anyhow!("Denial collector refused the subscription")),
+        Err(_) => Err(anyhow!("Denial collector did not answer in time")),
+    }
+}
+
+/// Collapses the collector's denials into `process_denials` entries.
+pub fn to_denial_entries(denials: &[LandlockDenial]) -> HashSet<String> {
+    denials.iter().filter_map(|d| d.to_denial_entry()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> LandlockDenial {
+        serde_json::from_str(line).unwrap()
+    }
+
+    #[test]
+    fn network_denial_entry() {
+        let d = parse(r#"{"pid":42,"syscall":"connect","path":null,"port":443,"access":["connect_tcp"]}"#);
+        assert_eq!(d.to_denial_entry().as_deref(), Some("tcp:connect:443"));
+    }
+
+    #[test]
+    fn filesystem_denial_entry() {
+        let d = parse(r#"{"pid":42,"syscall":"open","path":"/","access":["read_dir"]}"#);
+        assert_eq!(d.to_denial_entry().as_deref(), Some("/"));
+
+        let d = parse(r#"{"pid":42,"syscall":"mkdir","path":"/nonexistent/supernanny","access":["make_dir"]}"#);
+        assert_eq!(
+            d.to_denial_entry().as_deref(),
+            Some("NONCANONICAL:/nonexistent/supernanny")
+        );
+    }
+
+    #[test]
+    fn directory_denial_entry() {
+        let d = parse(r#"{"pid":42,"syscall":"rename","path":"/tmp/supernanny-missing","access":["refer"]}"#);
+        let tmp = fs::canonicalize("/tmp").unwrap();
+        assert_eq!(d.to_denial_entry(), Some(tmp.to_string_lossy().to_string()));
+
+        let d = parse(r#"{"pid":42,"syscall":"symlink","path":"/","access":["make_sym"]}"#);
+        assert_eq!(d.to_denial_entry(), None);
+
+        let d = parse(r#"{"pid":42,"syscall":"ioctl","path":"/dev/null","access":["ioctl_dev"]}"#);
+        assert_eq!(d.to_denial_entry().as_deref(), Some("/dev/null"));
+    }
+
+    #[test]
+    fn entries_are_deduplicated() {
+        let d = parse(r#"{"pid":1,"syscall":"bind","port":8080}"#);
+        let entries = to_denial_entries(&[d.clone(), d, parse(r#"{"pid":1,"syscall":"open"}"#)]);
+        assert_eq!(entries.len(), 1);
+        assert!(entries.contains("tcp:bind:8080"));
+    }
+}
@@ -0,0 +1,485 @@
+//! Sandbox-local DNS stub enforcing `allowed_domains`.
+//!
+//! Each sandbox runs its own stub on the loopback interface; the egress filter
+//! redirects every port-53 destination to it. Queries for allowed names are
+//! forwarded to the system's upstream resolver and the A/AAAA records of the
+//! answer are added to the IP allowlist, so the application can reach exactly
+//! the addresses it resolved. Other queries get `NXDOMAIN` and are reported as
+//! `domain:<name>` denials.
+
+use anyhow::{anyhow, Context, Result};
+use std::io::{Read, Write};
+use std::net::{
+    IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, UdpSocket,
+};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use crate::egress_filter::IpAllowList;
+use crate::sandbox_report::Reporter;
+
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(3);
+const MAX_UDP_MESSAGE: usize = 4096;
+const HEADER_LEN: usize = 12;
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const RCODE_SERVFAIL: u8 = 2;
+const RCODE_NXDOMAIN: u8 = 3;
+
+/// Domain patterns from the policy: `example.com` matches that name only,
+/// `*.example.com` matches any name below it (but not `example.com` itself).
+#[derive(Debug, Clone, Default)]
+pub struct DomainAllowList {
+    exact: Vec<String>,
+    suffixes: Vec<String>,
+}
+
+fn normalize_name(name: &str) -> String {
+    name.trim().trim_end_matches('.').to_ascii_lowercase()
+}
+
+impl DomainAllowList {
+    pub fn from_entries<I, S>(entries: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut list = Self::default();
+        for entry in entries {
+            let entry = normalize_name(entry.as_ref());
+            if entry.is_empty() {
+                continue;
+            }
+            match entry.strip_prefix("*.") {
+                Some(suffix) if !suffix.is_empty() && !suffix.contains('*') => {
+                    list.suffixes.push(format!(".{}", suffix))
+                }
+                _ if entry.contains('*') => {
+                    eprintln!("Warning: Skipping unsupported domain pattern: {}", entry)
+                }
+                _ => list.exact.push(entry),
+            }
+        }
+        list
+    }
+
+    pub fn allows(&self, name: &str) -> bool {
+        let name = normalize_name(name);
+        self.exact.contains(&name) || self.suffixes.iter().any(|s| name.ends_with(s))
+    }
+}
+
+/// First `nameserver` of a resolv.conf, on port 53.
+pub fn upstream_from_resolv_conf(content: &str) -> Option<SocketAddr> {
+    content.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        if fields.next() != Some("nameserver") {
+            return None;
+        }
+        // Drop an IPv6 zone id (fe80::1%eth0)
+        let addr = fields.next()?.split('%').next()?;
+        addr.parse::<IpAddr>().ok().map(|ip| SocketAddr::new(ip, 53))
+    })
+}
+
+/// A parsed DNS question: lowercase name and record type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Question {
+    pub name: String,
+    pub qtype: u16,
+    /// Offset of the end of the question section in the message.
+    end: usize,
+}
+
+fn read_u16(msg: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*msg.get(at)?, *msg.get(at + 1)?]))
+}
+
+/// Skips a (possibly compressed) name and returns the offset that follows it.
+fn skip_name(msg: &[u8], mut at: usize) -> Option<usize> {
+    loop {
+        let len = *msg.get(at)? as usize;
+        if len == 0 {
+            return Some(at + 1);
+        }
+        if len & 0xC0 == 0xC0 {
+            return Some(at + 2);
+        }
+        at += 1 + len;
+    }
+}
+
+/// Parses the first question of a query.
+pub fn parse_question(msg: &[u8]) -> Option<Question> {
+    if msg.len() < HEADER_LEN || read_u16(msg, 4)? == 0 {
+        return None;
+    }
+    let mut labels = Vec::new();
+    let mut at = HEADER_LEN;
+    loop {
+        let len = *msg.get(at)? as usize;
+        at += 1;
+        if len == 0 {
+            break;
+        }
+        // Compression is not valid in a query's question
+        if len & 0xC0 != 0 {
+            return None;
+        }
+        let label = msg.get(at..at + len)?;
+        labels.push(String::from_utf8_lossy(label).to_ascii_lowercase());
+        at += len;
+    }
+    let qtype = read_u16(msg, at)?;
+    read_u16(msg, at + 2)?;
+    Some(Question {
+        name: labels.join("."),
+        qtype,
+        end: at + 4,
+    })
+}
+
+/// Addresses from the A/AAAA records of the answer section.
+pub fn answer_addresses(msg: &[u8]) -> Vec<IpAddr> {
+    let mut ips = Vec::new();
+    let (qdcount, ancount) = match (read_u16(msg, 4), read_u16(msg, 6)) {
+        (Some(qd), Some(an)) => (qd, an),
+        _ => return ips,
+    };
+    let mut at = HEADER_LEN;
+    for _ in 0..qdcount {
+        match skip_name(msg, at) {
+            Some(end) => at = end + 4,
+            None => return ips,
+        }
+    }
+    for _ in 0..ancount {
+        let end = match skip_name(msg, at) {
+            Some(end) => end,
+            None => break,
+        };
+        let (rtype, rdlen) = match (read_u16(msg, end), read_u16(msg, end + 8)) {
+            (Some(t), Some(l)) => (t, l),
+            _ => break,
+        };
+        let rdata = end + 10;
+        let data = match msg.get(rdata..rdata + rdlen as usize) {
+            Some(d) => d,
+            None => break,
+        };
+        match (rtype, data.len()) {
+            (TYPE_A, 4) => ips.push(IpAddr::V4(Ipv4Addr::new(data[0], data[1], data[2], data[3]))),
+            (TYPE_AAAA, 16) => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(data);
+                ips.push(IpAddr::V6(Ipv6Addr::from(octets)));
+            }
+            _ => {}
+        }
+        at = rdata + rdlen as usize;
+    }
+    ips
+}
+
+/// Builds an empty response carrying `rcode` for the given query.
+fn error_response(query: &[u8], question: &Question, rcode: u8) -> Vec<u8> {
+    let mut resp = Vec::with_capacity(question.end);
+    resp.extend_from_slice(&query[..2]);
+    // QR=1, keep opcode and RD, RA=1
+    resp.push(0x80 | (query[2] & 0x79));
+    resp.push(0x80 | rcode);
+    resp.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+    resp.extend_from_slice(&query[HEADER_LEN..question.end]);
+    resp
+}
+
+#[derive(Clone)]
+struct Resolver {
+    upstream: SocketAddr,
+    domains: Arc<DomainAllowList>,
+    ips: Arc<RwLock<IpAllowList>>,
+    reporter: Option<Reporter>,
+}
+
+impl Resolver {
+    /// Answers one query. `forward` sends it upstream over the same transport.
+    fn answer(&self, query: &[u8], forward: impl Fn(&[u8]) -> Result<Vec<u8>>) -> Option<Vec<u8>> {
+        let question = parse_question(query)?;
+        if !self.domains.allows(&question.name) {
+            if let Some(r) = &self.reporter {
+                r.report(&format!("domain:{}", question.name));
+            }
+            return Some(error_response(query, &question, RCODE_NXDOMAIN));
+        }
+        match forward(query) {
+            Ok(resp) => {
+                if let Ok(mut ips) = self.ips.write() {
+                    for ip in answer_addresses(&resp) {
+                        ips.allow_host(ip);
+                    }
+                }
+                Some(resp)
+            }
+            Err(e) => {
+                eprintln!("Warning: DNS upstream failed for {}: {:#}", question.name, e);
+                Some(error_response(query, &question, RCODE_SERVFAIL))
+            }
+        }
+    }
+
+    fn forward_udp(&self, query: &[u8]) -> Result<Vec<u8>> {
+        let bind: SocketAddr = match self.upstream {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(bind)?;
+        socket.set_read_timeout(Some(UPSTREAM_TIMEOUT))?;
+        socket.connect(self.upstream)?;
+        socket.send(query)?;
+        let mut buf = vec![0u8; MAX_UDP_MESSAGE];
+        loop {
+            let n = socket.recv(&mut buf)?;
+            // Ignore stray datagrams that do not answer this query
+            if n >= 2 && buf[..2] == query[..2] {
+                buf.truncate(n);
+                return Ok(buf);
+            }
+        }
+    }
+
+    fn forward_tcp(&self, query: &[u8]) -> Result<Vec<u8>> {
+        let mut stream = TcpStream::connect_timeout(&self.upstream, UPSTREAM_TIMEOUT)?;
+        stream.set_read_timeout(Some(UPSTREAM_TIMEOUT))?;
+        write_tcp_message(&mut stream, query)?;
+        read_tcp_message(&mut stream)?.ok_or_else(|| anyhow!("Upstream closed the connection"))
+    }
+
+    fn serve_udp(self, socket: UdpSocket) {
+        let mut buf = vec![0u8; MAX_UDP_MESSAGE];
+        loop {
+            let (n, peer) = match socket.recv_from(&mut buf) {
+                Ok(r) => r,
+                Err(_) => continue,
+            };
+            let query = buf[..n].to_vec();
+            let resolver = self.clone();
+            let socket = match socket.try_clone() {
+                Ok(s) => s,
+                Err(_) => continue,
+            };
+            thread::spawn(move || {
+                if let Some(resp) = resolver.answer(&query, |q| resolver.forward_udp(q)) {
+                    let _ = socket.send_to(&resp, peer);
+                }
+            });
+        }
+    }
+
+    fn serve_tcp(self, listener: TcpListener) {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(s) => s,
+                Err(_) => continue,
+            };
+            let resolver = self.clone();
+            thread::spawn(move || {
+                let _ = stream.set_read_timeout(Some(UPSTREAM_TIMEOUT));
+                while let Ok(Some(query)) = read_tcp_message(&mut stream) {
+                    match resolver.answer(&query, |q| resolver.forward_tcp(q)) {
+                        Some(resp) if write_tcp_message(&mut stream, &resp).is_ok() => {}
+                        _ => break,
+                    }
+                }
+            });
+        }
+    }
+}
+
+fn read_tcp_message(stream: &mut TcpStream) -> Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 2];
+    if stream.read_exact(&mut len).is_err() {
+        return Ok(None);
+    }
+    let mut msg = vec![0u8; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut msg)?;
+    Ok(Some(msg))
+}
+
+fn write_tcp_message(stream: &mut TcpStream, msg: &[u8]) -> Result<()> {
+    let len = u16::try_from(msg.len()).context("DNS message too large")?;
+    stream.write_all(&len.to_be_bytes())?;
+    stream.write_all(msg)?;
+    Ok(())
+}
+
+/// Running stub, listening on the same loopback port over UDP and TCP.
+pub struct DnsStub {
+    addr: SocketAddr,
+}
+
+impl DnsStub {
+    /// Starts the stub on `127.0.0.1`. Its threads are created here, so call
+    /// this before restricting the current thread if upstream access must
+    /// not be confined.
+    pub fn start(
+        upstream: SocketAddr,
+        domains: DomainAllowList,
+        ips: Arc<RwLock<IpAllowList>>,
+        reporter: Option<Reporter>,
+    ) -> Result<Self> {
+        let (udp, tcp) = bind_pair().context("Failed to bind DNS stub")?;
+        let addr = udp.local_addr()?;
+        let resolver = Resolver {
+            upstream,
+            domains: Arc::new(domains),
+            ips,
+            reporter,
+        };
+
+        let udp_resolver = resolver.clone();
+        thread::Builder::new()
+            .name("dns-stub-udp".into())
+            .spawn(move || udp_resolver.serve_udp(udp))?;
+        thread::Builder::new()
+            .name("dns-stub-tcp".into())
+            .spawn(move || resolver.serve_tcp(tcp))?;
+
+        Ok(Self { addr })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+/// Binds UDP and TCP sockets sharing one ephemeral loopback port.
+fn bind_pair() -> Result<(UdpSocket, TcpListener)> {
+    for _ in 0..16 {
+        let udp = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+        if let Ok(tcp) = TcpListener::bind(udp.local_addr()?) {
+            return Ok((udp, tcp));
+        }
+    }
+    Err(anyhow!("No free loopback port for both UDP and TCP"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(id: u16, name: &str, qtype: u16) -> Vec<u8> {
+        let mut msg = id.to_be_bytes().to_vec();
+        msg.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+        for label in name.split('.') {
+            msg.push(label.len() as u8);
+            msg.extend_from_slice(label.as_bytes());
+        }
+        msg.extend_from_slice(&[0]);
+        msg.extend_from_slice(&qtype.to_be_bytes());
+        msg.extend_from_slice(&[0, 1]);
+        msg
+    }
+
+    /// Fake upstream answering every query with one A record.
+    fn fake_upstream(answer: Ipv4Addr) -> SocketAddr {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = socket.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            while let Ok((n, peer)) = socket.recv_from(&mut buf) {
+                let q = parse_question(&buf[..n]).unwrap();
+                let mut resp = buf[..q.end].to_vec();
+                resp[2] = 0x81;
+                resp[3] = 0x80;
+                resp[7] = 1;
+                // Compressed pointer to the question name
+                resp.extend_from_slice(&[0xC0, 0x0C, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4]);
+                resp.extend_from_slice(&answer.octets());
+                socket.send_to(&resp, peer).unwrap();
+            }
+        });
+        addr
+    }
+
+    fn ask(stub: &DnsStub, msg: &[u8]) -> Vec<u8> {
+        let client = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        client.send_to(msg, stub.addr()).unwrap();
+        let mut buf = [0u8; 512];
+        let n = client.recv(&mut buf).unwrap();
+        buf[..n].to_vec()
+    }
+
+    #[test]
+    fn domain_patterns() {
+        let list = DomainAllowList::from_entries(["Example.com.", "*.github.com", "a*.bad"]);
+        assert!(list.allows("example.com"));
+        assert!(!list.allows("www.example.com"));
+        assert!(list.allows("api.GitHub.com"));
+        assert!(list.allows("a.b.github.com."));
+        assert!(!list.allows("github.com"));
+        assert!(!list.allows("evilgithub.com"));
+        assert!(!list.allows("abc.bad"));
+    }
+
+    #[test]
+    fn resolv_conf_upstream() {
+        let conf = "# generated\nsearch lan\nnameserver 127.0.0.53\nnameserver 1.1.1.1\n";
+        assert_eq!(upstream_from_resolv_conf(conf), Some("127.0.0.53:53".parse().unwrap()));
+        assert_eq!(
+            upstream_from_resolv_conf("nameserver fe80::1%eth0"),
+            Some("[fe80::1]:53".parse().unwrap())
+        );
+        assert_eq!(upstream_from_resolv_conf("options edns0"), None);
+    }
+
+    #[test]
+    fn question_parsing() {
+        let q = parse_question(&query(7, "WWW.Example.com", TYPE_AAAA)).unwrap();
+        assert_eq!(q.name, "www.example.com");
+        assert_eq!(q.qtype, TYPE_AAAA);
+        assert_eq!(parse_question(&[0u8; 5]), None);
+    }
+
+    #[test]
+    fn allowed_lookup_is_forwarded_and_recorded() {
+        let ip = Ipv4Addr::new(140, 82, 121, 4);
+        let ips = Arc::new(RwLock::new(IpAllowList::default()));
+        let stub = DnsStub::start(
+            fake_upstream(ip),
+            DomainAllowList::from_entries(["*.github.com"]),
+            ips.clone(),
+            None,
+        )
+        .unwrap();
+
+        assert!(!ips.read().unwrap().allows(IpAddr::V4(ip)));
+        let resp = ask(&stub, &query(0x1234, "api.github.com", TYPE_A));
+        assert_eq!(&resp[..2], &[0x12, 0x34]);
+        assert_eq!(resp[3] & 0x0F, 0);
+        assert_eq!(answer_addresses(&resp), vec![IpAddr::V4(ip)]);
+        assert!(ips.read().unwrap().allows(IpAddr::V4(ip)));
+    }
+
+    #[test]
+    fn blocked_lookup_gets_nxdomain() {
+        let ip = Ipv4Addr::new(203, 0, 113, 7);
+        let ips = Arc::new(RwLock::new(IpAllowList::default()));
+        let stub = DnsStub::start(
+            fake_upstream(ip),
+            DomainAllowList::from_entries(["example.com"]),
+            ips.clone(),
+            None,
+        )
+        .unwrap();
+
+        let resp = ask(&stub, &query(0x4242, "tracker.evil.net", TYPE_A));
+        assert_eq!(&resp[..2], &[0x42, 0x42]);
+        assert_eq!(resp[3] & 0x0F, RCODE_NXDOMAIN);
+        assert!(answer_addresses(&resp).is_empty());
+        assert!(parse_question(&resp).is_some());
+        assert!(!ips.read().unwrap().allows(IpAddr::V4(ip)));
+    }
+}
@@ -0,0 +1,905 @@
+//! IP egress filter for sandboxed processes.
+//!
+//! Landlock can only restrict TCP ports, so destination addresses are checked
+//! with a seccomp user-notification filter: `connect()`, `sendto()`, `sendmsg()`
+//! and `sendmmsg()` are suspended, and a supervisor thread copies the call's
+//! arguments from the caller's memory and checks the destinations. It then
+//! performs the call itself on a duplicate of the caller's socket
+//! (`pidfd_getfd()`), from those copies, or fails it with `EACCES`, reporting
+//! an `ip:<addr>` denial. Another thread of the caller rewriting the `sockaddr`
+//! after the check therefore changes nothing. Calls on other than IPv4/IPv6
+//! sockets continue in the caller. DNS traffic is redirected to the sandbox's
+//! [`crate::dns_stub`] instead.
+//!
+//! io_uring is refused, its submissions never reaching seccomp.
+//!
+//! The same filter enforces the application's
+//! [`SyscallProfile`](crate::syscall_profile::SyscallProfile).
+
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashSet;
+use std::mem::{size_of, zeroed};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::raw::{c_int, c_long, c_ulong, c_ushort};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::mpsc::channel;
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+use crate::sandbox_report::Reporter;
+use crate::syscall_profile::{syscall_name, SyscallProfile, SOCK_TYPE_MASK};
+
+/// Maximum number of messages sent by a single `sendmmsg()` call.
+const MAX_MMSG: usize = 64;
+
+/// Maximum number of bytes sent by a single call.
+const MAX_PAYLOAD: usize = 256 * 1024;
+
+/// Maximum size of the ancillary data of a message.
+const MAX_CONTROL: usize = 64 * 1024;
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: Option<u32> = Some(0xC000_003E);
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: Option<u32> = Some(0xC000_00B7);
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const AUDIT_ARCH: Option<u32> = None;
+
+/// Syscall numbers with this bit set belong to the x32 ABI.
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+/// An IPv4 or IPv6 network in CIDR notation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNet {
+    /// Parses `a.b.c.d`, `a.b.c.d/n`, an IPv6 address or `addr6/n`.
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (addr, prefix) = match s.split_once('/') {
+            Some((a, p)) => (a, Some(p)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| anyhow!("Invalid IP address: {}", s))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(|| anyhow!("Invalid prefix length: {}", s))?,
+            None => max,
+        };
+        Ok(Self { addr, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, normalize(ip)) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                prefix_match(u32::from(net) as u128, u32::from(ip) as u128, self.prefix, 32)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                prefix_match(u128::from(net), u128::from(ip), self.prefix, 128)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn prefix_match(net: u128, ip: u128, prefix: u8, bits: u8) -> bool {
+    if prefix == 0 {
+        return true;
+    }
+    let shift = bits - prefix;
+    (net >> shift) == (ip >> shift)
+}
+
+/// Maps IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`) back to IPv4.
+fn normalize(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => IpAddr::V4(v4),
+            None => IpAddr::V6(v6),
+        },
+        v4 => v4,
+    }
+}
+
+/// Destinations a sandboxed process may send to. Without any network entry,
+/// loopback addresses stay reachable.
+#[derive(Debug, Clone, Default)]
+pub struct IpAllowList {
+    nets: Vec<IpNet>,
+    hosts: HashSet<IpAddr>,
+}
+
+impl IpAllowList {
+    /// Builds the list from policy entries, skipping (and warning about) invalid ones.
+    pub fn from_entries<I, S>(entries: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut list = Self::default();
+        for entry in entries {
+            match IpNet::parse(entry.as_ref()) {
+                Ok(net) => list.nets.push(net),
+                Err(e) => eprintln!("Warning: Skipping allowed IP entry: {}", e),
+            }
+        }
+        list
+    }
+
+    /// Allows a single address, e.g. one resolved for an allowed domain.
+    pub fn allow_host(&mut self, ip: IpAddr) {
+        self.hosts.insert(normalize(ip));
+    }
+
+    pub fn allows(&self, ip: IpAddr) -> bool {
+        let ip = normalize(ip);
+        (self.nets.is_empty() && ip.is_loopback())
+            || self.hosts.contains(&ip)
+            || self.nets.iter().any(|n| n.contains(ip))
+    }
+}
+
+/// Parses a raw `sockaddr`. Returns `None` for families other than IPv4/IPv6.
+pub fn parse_sockaddr(raw: &[u8]) -> Option<SocketAddr> {
+    if raw.len() < size_of::<libc::sa_family_t>() {
+        return None;
+    }
+    let family = libc::sa_family_t::from_ne_bytes([raw[0], raw[1]]) as c_int;
+    match family {
+        libc::AF_INET if raw.len() >= size_of::<libc::sockaddr_in>() => {
+            let sin: libc::sockaddr_in =
+                unsafe { std::ptr::read_unaligned(raw.as_ptr() as *const _) };
+            let ip = Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr));
+            Some(SocketAddr::new(IpAddr::V4(ip), u16::from_be(sin.sin_port)))
+        }
+        libc::AF_INET6 if raw.len() >= size_of::<libc::sockaddr_in6>() => {
+            let sin6: libc::sockaddr_in6 =
+                unsafe { std::ptr::read_unaligned(raw.as_ptr() as *const _) };
+            let ip = Ipv6Addr::from(sin6.sin6_addr.s6_addr);
+            Some(SocketAddr::new(IpAddr::V6(ip), u16::from_be(sin6.sin6_port)))
+        }
+        _ => None,
+    }
+}
+
+fn stmt(code: u32, k: u32) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn jump(code: u32, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+fn build_filter(arch: u32, profile: &SyscallProfile) -> Vec<libc::sock_filter> {
+    use libc::{
+        BPF_ABS, BPF_ALU, BPF_AND, BPF_JEQ, BPF_JMP, BPF_JSET, BPF_K, BPF_LD, BPF_RET, BPF_W,
+    };
+    let nr_offset = 0;
+    let arch_offset = 4;
+    // Low 32 bits of the first two arguments (little-endian)
+    let arg_offset = |i: u32| 16 + 8 * i;
+    let notify = || stmt(BPF_RET | BPF_K, libc::SECCOMP_RET_USER_NOTIF);
+
+    let mut filter = vec![
+        stmt(BPF_LD | BPF_W | BPF_ABS, arch_offset),
+        jump(BPF_JMP | BPF_JEQ | BPF_K, arch, 1, 0),
+        stmt(BPF_RET | BPF_K, libc::SECCOMP_RET_KILL_PROCESS),
+        stmt(BPF_LD | BPF_W | BPF_ABS, nr_offset),
+        jump(BPF_JMP | BPF_JSET | BPF_K, X32_SYSCALL_BIT, 0, 1),
+        stmt(BPF_RET | BPF_K, libc::SECCOMP_RET_KILL_PROCESS),
+    ];
+
+    // Destinations are checked by the supervisor, as is the profile for these;
+    // io_uring is refused there, whatever the profile allows
+    for nr in [
+        libc::SYS_connect,
+        libc::SYS_sendto,
+        libc::SYS_sendmsg,
+        libc::SYS_sendmmsg,
+        libc::SYS_io_uring_setup,
+        libc::SYS_io_uring_enter,
+        libc::SYS_io_uring_register,
+    ] {
+        filter.push(jump(BPF_JMP | BPF_JEQ | BPF_K, nr as u32, 0, 1));
+        filter.push(notify());
+    }
+
+    if profile.denies_raw_sockets() {
+        // Same check as syscall_profile::is_raw_socket()
+        filter.extend([
+            jump(BPF_JMP | BPF_JEQ | BPF_K, libc::SYS_socket as u32, 0, 10),
+            stmt(BPF_LD | BPF_W | BPF_ABS, arg_offset(0)),
+            jump(BPF_JMP | BPF_JEQ | BPF_K, libc::AF_PACKET as u32, 0, 1),
+            notify(),
+            jump(BPF_JMP | BPF_JEQ | BPF_K, libc::AF_INET as u32, 1, 0),
+            jump(BPF_JMP | BPF_JEQ | BPF_K, libc::AF_INET6 as u32, 0, 4),
+            stmt(BPF_LD | BPF_W | BPF_ABS, arg_offset(1)),
+            stmt(BPF_ALU | BPF_AND | BPF_K, SOCK_TYPE_MASK as u32),
+            jump(BPF_JMP | BPF_JEQ | BPF_K, libc::SOCK_RAW as u32, 0, 1),
+            notify(),
+            stmt(BPF_LD | BPF_W | BPF_ABS, nr_offset),
+        ]);
+    }
+
+    match profile.allowed() {
+        Some(allowed) => {
+            for nr in allowed {
+                filter.push(jump(BPF_JMP | BPF_JEQ | BPF_K, nr as u32, 0, 1));
+                filter.push(stmt(BPF_RET | BPF_K, libc::SECCOMP_RET_ALLOW));
+            }
+            filter.push(notify());
+        }
+        None => {
+            for nr in profile.denied() {
+                filter.push(jump(BPF_JMP | BPF_JEQ | BPF_K, nr as u32, 0, 1));
+                filter.push(notify());
+            }
+            filter.push(stmt(BPF_RET | BPF_K, libc::SECCOMP_RET_ALLOW));
+        }
+    }
+    filter
+}
+
+/// Installs the egress filter on the calling thread and starts its supervisor.
+///
+/// Must be called from the thread that spawns the application, after Landlock
+/// is enforced. Threads created before the call (including the supervisor) are
+/// not filtered; every process forked afterwards is. The supervisor stays under
+/// Landlock, so the calls it performs for the application obey its port rules.
+///
+/// `profile` is compiled into the same filter: the syscalls it refuses fail
+/// with `EPERM` and are reported as `syscall:<name>`.
+///
+/// With `dns_stub`, every port-53 destination is sent to the stub instead. The
+/// caller's copy is rewritten too: resolvers keep that buffer as their
+/// nameserver address, so replies from the stub pass their source checks.
+pub fn install(
+    allow: Arc<RwLock<IpAllowList>>,
+    profile: SyscallProfile,
+    reporter: Option<Reporter>,
+    dns_stub: Option<SocketAddr>,
+) -> Result<()> {
+    let arch = AUDIT_ARCH.ok_or_else(|| anyhow!("IP egress filter unsupported on this architecture"))?;
+
+    let mut filter = build_filter(arch, &profile);
+
+    // The supervisor must exist before the filter, or it would be filtered too.
+    let supervisor = Supervisor {
+        allow,
+        profile,
+        reporter,
+        dns_stub,
+    };
+    let (fd_tx, fd_rx) = channel::<RawFd>();
+    thread::Builder::new()
+        .name("egress-supervisor".into())
+        .spawn(move || {
+            if let Ok(fd) = fd_rx.recv() {
+                supervisor.run(fd);
+            }
+        })
+        .context("Failed to spawn egress supervisor")?;
+
+    if unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+        return Err(std::io::Error::last_os_error()).context("Failed to set no_new_privs");
+    }
+
+    let prog = libc::sock_fprog {
+        len: filter.len() as c_ushort,
+        filter: filter.as_mut_ptr(),
+    };
+    let set_filter = |flags: c_ulong| unsafe {
+        libc::syscall(
+            libc::SYS_seccomp,
+            libc::SECCOMP_SET_MODE_FILTER,
+            libc::SECCOMP_FILTER_FLAG_NEW_LISTENER | flags,
+            &prog as *const libc::sock_fprog,
+        )
+    };
+    // Once a call is picked up, only a fatal signal interrupts it: a restarted
+    // call would be performed twice (Linux 5.19+)
+    let mut fd = set_filter(libc::SECCOMP_FILTER_FLAG_WAIT_KILLABLE_RECV);
+    if fd < 0 && std::io::Error::last_os_error().raw_os_error() == Some(libc::EINVAL) {
+        fd = set_filter(0);
+    }
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error()).context("Failed to install seccomp egress filter");
+    }
+
+    fd_tx
+        .send(fd as RawFd)
+        .map_err(|_| anyhow!("Egress supervisor exited early"))?;
+    Ok(())
+}
+
+/// A `sockaddr` copied from the caller, and where it lives in its memory.
+/// Empty for a null address.
+struct Name {
+    ptr: u64,
+    raw: Vec<u8>,
+}
+
+/// One message to send, copied from the caller's memory.
+struct Message {
+    name: Name,
+    data: Vec<u8>,
+    control: Vec<u8>,
+}
+
+/// A call the supervisor performs itself, on copies of the caller's arguments.
+enum Call {
+    Connect(Name),
+    /// `sendto()` and `sendmsg()` send one message; `sendmmsg()` carries the
+    /// address of its `mmsghdr` array, whose `msg_len` fields are filled in.
+    Send {
+        messages: Vec<Message>,
+        flags: c_int,
+        mmsg: Option<u64>,
+    },
+}
+
+impl Call {
+    fn names_mut(&mut self) -> Vec<&mut Name> {
+        match self {
+            Call::Connect(name) => vec![name],
+            Call::Send { messages, .. } => messages.iter_mut().map(|m| &mut m.name).collect(),
+        }
+    }
+
+    /// Runs the call on `sock`, a duplicate of the caller's socket. Returns
+    /// the syscall's result, or its errno.
+    fn perform(&self, sock: &OwnedFd, pid: u32) -> Result<i64, c_int> {
+        let ret = match self {
+            Call::Connect(name) => unsafe {
+                libc::connect(
+                    sock.as_raw_fd(),
+                    name_ptr(name),
+                    name.raw.len() as libc::socklen_t,
+                ) as i64
+            },
+            Call::Send {
+                messages,
+                flags,
+                mmsg,
+            } => {
+                let mut iovs: Vec<libc::iovec> = messages
+                    .iter()
+                    .map(|m| libc::iovec {
+                        iov_base: m.data.as_ptr() as *mut libc::c_void,
+                        iov_len: m.data.len(),
+                    })
+                    .collect();
+                let mut headers: Vec<libc::mmsghdr> = messages
+                    .iter()
+                    .zip(iovs.iter_mut())
+                    .map(|(m, iov)| {
+                        let mut header: libc::mmsghdr = unsafe { zeroed() };
+                        header.msg_hdr.msg_name = name_ptr(&m.name) as *mut libc::c_void;
+                        header.msg_hdr.msg_namelen = m.name.raw.len() as libc::socklen_t;
+                        header.msg_hdr.msg_iov = iov;
+                        header.msg_hdr.msg_iovlen = 1;
+                        if !m.control.is_empty() {
+                            header.msg_hdr.msg_control = m.control.as_ptr() as *mut libc::c_void;
+                            header.msg_hdr.msg_controllen = m.control.len() as _;
+                        }
+                        header
+                    })
+                    .collect();
+                match mmsg {
+                    None => unsafe {
+                        libc::sendmsg(sock.as_raw_fd(), &headers[0].msg_hdr, *flags) as i64
+                    },
+                    Some(ptr) => {
+                        let sent = unsafe {
+                            libc::sendmmsg(
+                                sock.as_raw_fd(),
+                                headers.as_mut_ptr(),
+                                headers.len() as _,
+                                *flags as _,
+                            )
+                        };
+                        for (i, header) in headers.iter().enumerate().take(sent.max(0) as usize) {
+                            let msg_len = ptr
+                                + (i * size_of::<libc::mmsghdr>() + size_of::<libc::msghdr>()) as u64;
+                            write_remote(pid, msg_len, &header.msg_len.to_ne_bytes());
+                        }
+                        sent as i64
+                    }
+                }
+            }
+        };
+        if ret < 0 {
+            Err(std::io::Error::last_os_error()
+                .raw_os_error()
+                .unwrap_or(libc::EIO))
+        } else {
+            Ok(ret)
+        }
+    }
+}
+
+fn name_ptr(name: &Name) -> *const libc::sockaddr {
+    if name.raw.is_empty() {
+        std::ptr::null()
+    } else {
+        name.raw.as_ptr() as *const libc::sockaddr
+    }
+}
+
+enum Verdict {
+    /// Not an IP socket: the call cannot reach a destination we check.
+    Continue,
+    /// Checked: the supervisor sends it on a duplicate of the socket.
+    Perform(Call, OwnedFd),
+    /// Refused; carries the address to report, if any.
+    Deny(Option<IpAddr>),
+    /// Refused by the syscall profile.
+    Refuse(c_long),
+    /// Fails as the kernel would have failed it, e.g. on a closed descriptor.
+    Fail(c_int),
+}
+
+struct Supervisor {
+    allow: Arc<RwLock<IpAllowList>>,
+    profile: SyscallProfile,
+    reporter: Option<Reporter>,
+    dns_stub: Option<SocketAddr>,
+}
+
+impl Supervisor {
+    fn run(&self, fd: RawFd) {
+        loop {
+            let mut req: libc::seccomp_notif = unsafe { zeroed() };
+            if unsafe { libc::ioctl(fd, libc::SECCOMP_IOCTL_NOTIF_RECV, &mut req) } != 0 {
+                match std::io::Error::last_os_error().raw_os_error() {
+                    // Interrupted, or the caller died before we picked the request up
+                    Some(libc::EINTR) | Some(libc::ENOENT) => continue,
+                    _ => break,
+                }
+            }
+
+            // The target may have died (and its pid been reused) while we read
+            // its memory or opened its pidfd
+            let mut id = req.id;
+            let nr = req.data.nr as c_long;
+            let verdict = if is_io_uring(nr) || self.profile.refuses(nr, &req.data.args) {
+                Verdict::Refuse(nr)
+            } else {
+                self.inspect(&req)
+            };
+            if unsafe { libc::ioctl(fd, libc::SECCOMP_IOCTL_NOTIF_ID_VALID, &mut id) } != 0 {
+                continue;
+            }
+
+            let result = match verdict {
+                Verdict::Continue => {
+                    respond(fd, req.id, None);
+                    continue;
+                }
+                Verdict::Perform(call, sock) => {
+                    // Sends may block: each one gets a thread, so at most one
+                    // per application thread
+                    let pid = req.pid;
+                    let id = req.id;
+                    let spawned = thread::Builder::new()
+                        .name("egress-call".into())
+                        .spawn(move || respond(fd, id, Some(call.perform(&sock, pid))));
+                    if spawned.is_err() {
+                        respond(fd, req.id, Some(Err(libc::EAGAIN)));
+                    }
+                    continue;
+                }
+                Verdict::Deny(ip) => {
+                    if let (Some(ip), Some(r)) = (ip, &self.reporter) {
+                        r.report(&format!("ip:{}", normalize(ip)));
+                    }
+                    Err(libc::EACCES)
+                }
+                Verdict::Refuse(nr) => {
+                    if let Some(r) = &self.reporter {
+                        let name = syscall_name(nr)
+                            .map(str::to_string)
+                            .unwrap_or_else(|| nr.to_string());
+                        r.report(&format!("syscall:{}", name));
+                    }
+                    Err(libc::EPERM)
+                }
+                Verdict::Fail(errno) => Err(errno),
+            };
+            respond(fd, req.id, Some(result));
+        }
+    }
+
+    /// Copies the socket and the arguments of a `connect()` or send, then
+    /// checks the copies: the caller can no longer change the destination.
+    fn inspect(&self, req: &libc::seccomp_notif) -> Verdict {
+        let sock = match open_pidfd(req.pid).and_then(|pidfd| take_fd(&pidfd, req.data.args[0] as c_int)) {
+            Ok(sock) => sock,
+            Err(e) if e.raw_os_error() == Some(libc::EBADF) => return Verdict::Fail(libc::EBADF),
+            Err(_) => return Verdict::Deny(None),
+        };
+        if !is_ip_socket(&sock) {
+            return Verdict::Continue;
+        }
+        let mut call = match read_call(req) {
+            Ok(call) => call,
+            Err(()) => return Verdict::Deny(None),
+        };
+        match self.check(req.pid, &mut call) {
+            Ok(()) => Verdict::Perform(call, sock),
+            Err(ip) => Verdict::Deny(ip),
+        }
+    }
+
+    fn check(&self, pid: u32, call: &mut Call) -> Result<(), Option<IpAddr>> {
+        for name in call.names_mut() {
+            let addr = match parse_sockaddr(&name.raw) {
+                Some(addr) => addr,
+                None => continue,
+            };
+            if let Some(stub) = self.dns_stub {
+                if same_endpoint(addr, stub) {
+                    continue;
+                }
+                if addr.port() == 53 {
+                    let raw = encode_sockaddr(stub, addr.is_ipv6());
+                    if raw.len() > name.raw.len() || !write_remote(pid, name.ptr, &raw) {
+                        return Err(None);
+                    }
+                    name.raw = raw;
+                    continue;
+                }
+            }
+            let allowed = self
+                .allow
+                .read()
+                .map(|l| l.allows(addr.ip()))
+                .unwrap_or(false);
+            if !allowed {
+                return Err(Some(addr.ip()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// io_uring submissions never go through seccomp, so rings are refused.
+fn is_io_uring(nr: c_long) -> bool {
+    nr == libc::SYS_io_uring_setup || nr == libc::SYS_io_uring_enter || nr == libc::SYS_io_uring_register
+}
+
+/// Answers a notification: `None` lets the call continue in the caller.
+fn respond(fd: RawFd, id: u64, result: Option<Result<i64, c_int>>) {
+    let mut resp: libc::seccomp_notif_resp = unsafe { zeroed() };
+    resp.id = id;
+    match result {
+        None => resp.flags = libc::SECCOMP_USER_NOTIF_FLAG_CONTINUE as u32,
+        Some(Ok(val)) => resp.val = val,
+        Some(Err(errno)) => resp.error = -errno,
+    }
+    unsafe { libc::ioctl(fd, libc::SECCOMP_IOCTL_NOTIF_SEND, &mut resp) };
+}
+
+fn same_endpoint(a: SocketAddr, b: SocketAddr) -> bool {
+    a.port() == b.port() && normalize(a.ip()) == normalize(b.ip())
+}
+
+/// Raw `sockaddr_in`, or `sockaddr_in6` (IPv4-mapped if needed) when `as_v6`.
+pub fn encode_sockaddr(addr: SocketAddr, as_v6: bool) -> Vec<u8> {
+    let ip = normalize(addr.ip());
+    let bytes = |p: *const u8, len: usize| unsafe { std::slice::from_raw_parts(p, len).to_vec() };
+    match (ip, as_v6) {
+        (IpAddr::V4(v4), false) => {
+            let mut sin: libc::sockaddr_in = unsafe { zeroed() };
+            sin.sin_family = libc::AF_INET as libc::sa_family_t;
+            sin.sin_port = addr.port().to_be();
+            sin.sin_addr.s_addr = u32::from(v4).to_be();
+            bytes(&sin as *const _ as *const u8, size_of::<libc::sockaddr_in>())
+        }
+        (ip, _) => {
+            let v6 = match ip {
+                IpAddr::V4(v4) => v4.to_ipv6_mapped(),
+                IpAddr::V6(v6) => v6,
+            };
+            let mut sin6: libc::sockaddr_in6 = unsafe { zeroed() };
+            sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sin6.sin6_port = addr.port().to_be();
+            sin6.sin6_addr.s6_addr = v6.octets();
+            bytes(&sin6 as *const _ as *const u8, size_of::<libc::sockaddr_in6>())
+        }
+    }
+}
+
+/// pidfd of the caller, given the id of its thread.
+fn open_pidfd(tid: u32) -> std::io::Result<OwnedFd> {
+    let open = |pid: libc::pid_t, flags: c_int| {
+        let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, flags) };
+        if fd < 0 {
+            Err(std::io::Error::last_os_error())
+        } else {
+            Ok(unsafe { OwnedFd::from_raw_fd(fd as RawFd) })
+        }
+    };
+    // Threads other than the leader need PIDFD_THREAD (Linux 6.9+), or their
+    // thread group from /proc, when Landlock lets us read it
+    open(tid as libc::pid_t, 0)
+        .or_else(|_| open(tid as libc::pid_t, PIDFD_THREAD))
+        .or_else(|e| thread_group(tid).map_or(Err(e), |tgid| open(tgid, 0)))
+}
+
+const PIDFD_THREAD: c_int = libc::O_EXCL;
+
+fn thread_group(tid: u32) -> Option<libc::pid_t> {
+    std::fs::read_to_string(format!("/proc/{}/status", tid))
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("Tgid:"))?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Duplicates descriptor `target` of the process behind `pidfd`.
+fn take_fd(pidfd: &OwnedFd, target: c_int) -> std::io::Result<OwnedFd> {
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_getfd, pidfd.as_raw_fd(), target, 0) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as RawFd) })
+}
+
+fn is_ip_socket(sock: &OwnedFd) -> bool {
+    let mut domain: c_int = 0;
+    let mut len = size_of::<c_int>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            sock.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_DOMAIN,
+            &mut domain as *mut c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    ret == 0 && (domain == libc::AF_INET || domain == libc::AF_INET6)
+}
+
+/// Reads `len` bytes at `addr` in the memory of `pid`.
+fn read_remote(pid: u32, addr: u64, len: usize) -> Option<Vec<u8>> {
+    let mut buf = vec![0u8; len];
+    if len == 0 {
+        return Some(buf);
+    }
+    let local = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: len,
+    };
+    let remote = libc::iovec {
+        iov_base: addr as *mut libc::c_void,
+        iov_len: len,
+    };
+    let read = unsafe { libc::process_vm_readv(pid as libc::pid_t, &local, 1, &remote, 1, 0) };
+    if read != len as isize {
+        return None;
+    }
+    Some(buf)
+}
+
+/// Overwrites `data.len()` bytes at `addr` in the memory of `pid`.
+fn write_remote(pid: u32, addr: u64, data: &[u8]) -> bool {
+    let local = libc::iovec {
+        iov_base: data.as_ptr() as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+    let remote = libc::iovec {
+        iov_base: addr as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+    let written = unsafe { libc::process_vm_writev(pid as libc::pid_t, &local, 1, &remote, 1, 0) };
+    written == data.len() as isize
+}
+
+fn read_name(pid: u32, ptr: u64, len: u64) -> Result<Name, ()> {
+    if ptr == 0 {
+        return Ok(Name { ptr, raw: Vec::new() });
+    }
+    let len = (len as usize).min(size_of::<libc::sockaddr_storage>());
+    let raw = read_remote(pid, ptr, len).ok_or(())?;
+    Ok(Name { ptr, raw })
+}
+
+/// Concatenates the buffers of `iovs`, up to [`MAX_PAYLOAD`] bytes: a stream
+/// socket then sees a short write, datagrams that large fail anyway.
+fn read_payload(pid: u32, iovs: &[libc::iovec]) -> Result<Vec<u8>, ()> {
+    let mut data = Vec::new();
+    for iov in iovs {
+        let len = iov.iov_len.min(MAX_PAYLOAD - data.len());
+        data.extend(read_remote(pid, iov.iov_base as u64, len).ok_or(())?);
+    }
+    Ok(data)
+}
+
+fn read_message(pid: u32, ptr: u64) -> Result<Message, ()> {
+    let raw = read_remote(pid, ptr, size_of::<libc::msghdr>()).ok_or(())?;
+    let msg: libc::msghdr = unsafe { std::ptr::read_unaligned(raw.as_ptr() as *const _) };
+    let name = read_name(pid, msg.msg_name as u64, msg.msg_namelen as u64)?;
+
+    let count = msg.msg_iovlen as usize;
+    if count > libc::UIO_MAXIOV as usize || msg.msg_controllen as usize > MAX_CONTROL {
+        return Err(());
+    }
+    let raw = read_remote(pid, msg.msg_iov as u64, count * size_of::<libc::iovec>()).ok_or(())?;
+    let iovs: Vec<libc::iovec> = raw
+        .chunks_exact(size_of::<libc::iovec>())
+        .map(|chunk| unsafe { std::ptr::read_unaligned(chunk.as_ptr() as *const _) })
+        .collect();
+    let data = read_payload(pid, &iovs)?;
+    let control = if msg.msg_control.is_null() {
+        Vec::new()
+    } else {
+        read_remote(pid, msg.msg_control as u64, msg.msg_controllen as usize).ok_or(())?
+    };
+    Ok(Message { name, data, control })
+}
+
+/// Copies the arguments of the notified syscall. Unreadable arguments yield
+/// `Err(())` so that the call is refused rather than sent unchecked.
+fn read_call(req: &libc::seccomp_notif) -> Result<Call, ()> {
+    let pid = req.pid;
+    let args = req.data.args;
+    let nr = req.data.nr as c_long;
+
+    if nr == libc::SYS_connect {
+        read_name(pid, args[1], args[2]).map(Call::Connect)
+    } else if nr == libc::SYS_sendto {
+        let len = (args[2] as usize).min(MAX_PAYLOAD);
+        let data = read_remote(pid, args[1], len).ok_or(())?;
+        let name = read_name(pid, args[4], args[5])?;
+        Ok(Call::Send {
+            messages: vec![Message {
+                name,
+                data,
+                control: Vec::new(),
+            }],
+            flags: args[3] as c_int,
+            mmsg: None,
+        })
+    } else if nr == libc::SYS_sendmsg {
+        Ok(Call::Send {
+            messages: vec![read_message(pid, args[1])?],
+            flags: args[2] as c_int,
+            mmsg: None,
+        })
+    } else if nr == libc::SYS_sendmmsg {
+        // Fewer messages than asked for are sent, which callers handle
+        let count = (args[2] as usize).min(MAX_MMSG);
+        let messages = (0..count)
+            .map(|i| read_message(pid, args[1] + (i * size_of::<libc::mmsghdr>()) as u64))
+            .collect::<Result<_, _>>()?;
+        Ok(Call::Send {
+            messages,
+            flags: args[3] as c_int,
+            mmsg: Some(args[1]),
+        })
+    } else {
+        Err(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cidr_matching() {
+        let list = IpAllowList::from_entries(["127.0.0.1/8", "192.168.1.0/24", "10.0.0.5"]);
+        assert!(list.allows("127.42.0.1".parse().unwrap()));
+        assert!(list.allows("192.168.1.200".parse().unwrap()));
+        assert!(!list.allows("192.168.2.1".parse().unwrap()));
+        assert!(list.allows("10.0.0.5".parse().unwrap()));
+        assert!(!list.allows("10.0.0.6".parse().unwrap()));
+        // IPv4-mapped IPv6 destinations are checked as IPv4
+        assert!(list.allows("::ffff:192.168.1.1".parse().unwrap()));
+        assert!(!list.allows("2001:db8::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn ipv6_and_hosts() {
+        let mut list = IpAllowList::from_entries(["2001:db8::/32", "not-an-ip", "1.2.3.4/33"]);
+        assert!(list.allows("2001:db8:1::1".parse().unwrap()));
+        assert!(!list.allows("8.8.8.8".parse().unwrap()));
+        list.allow_host("8.8.8.8".parse().unwrap());
+        assert!(list.allows("8.8.8.8".parse().unwrap()));
+    }
+
+    #[test]
+    fn empty_list_allows_loopback_only() {
+        let mut list = IpAllowList::from_entries(Vec::<String>::new());
+        assert!(list.allows("127.0.0.1".parse().unwrap()));
+        assert!(list.allows("::1".parse().unwrap()));
+        assert!(!list.allows("203.0.113.9".parse().unwrap()));
+        // Addresses of allowed domains do not change that
+        list.allow_host("203.0.113.9".parse().unwrap());
+        assert!(list.allows("127.0.0.1".parse().unwrap()));
+        let only = IpAllowList::from_entries(["10.0.0.0/8"]);
+        assert!(!only.allows("127.0.0.1".parse().unwrap()));
+        let all = IpAllowList::from_entries(["0.0.0.0/0"]);
+        assert!(all.allows("203.0.113.9".parse().unwrap()));
+    }
+
+    #[test]
+    fn sockaddr_parsing() {
+        let mut sin: libc::sockaddr_in = unsafe { zeroed() };
+        sin.sin_family = libc::AF_INET as libc::sa_family_t;
+        sin.sin_port = 443u16.to_be();
+        sin.sin_addr.s_addr = u32::from(Ipv4Addr::new(93, 184, 216, 34)).to_be();
+        let raw = unsafe {
+            std::slice::from_raw_parts(&sin as *const _ as *const u8, size_of::<libc::sockaddr_in>())
+        };
+        assert_eq!(parse_sockaddr(raw), Some("93.184.216.34:443".parse().unwrap()));
+        assert_eq!(parse_sockaddr(&raw[..4]), None);
+
+        let mut un: libc::sockaddr_un = unsafe { zeroed() };
+        un.sun_family = libc::AF_UNIX as libc::sa_family_t;
+        let raw = unsafe {
+            std::slice::from_raw_parts(&un as *const _ as *const u8, size_of::<libc::sockaddr_un>())
+        };
+        assert_eq!(parse_sockaddr(raw), None);
+    }
+
+    #[test]
+    fn sockaddr_encoding_round_trip() {
+        let stub: SocketAddr = "127.0.0.1:40053".parse().unwrap();
+        let v4 = encode_sockaddr(stub, false);
+        assert_eq!(v4.len(), size_of::<libc::sockaddr_in>());
+        assert_eq!(parse_sockaddr(&v4), Some(stub));
+
+        // IPv6 sockets get the IPv4-mapped form of the stub address
+        let v6 = encode_sockaddr(stub, true);
+        assert_eq!(v6.len(), size_of::<libc::sockaddr_in6>());
+        let parsed = parse_sockaddr(&v6).unwrap();
+        assert!(same_endpoint(parsed, stub));
+        assert!(parsed.is_ipv6());
+    }
+
+    #[test]
+    fn filter_jumps_stay_in_program() {
+        for list in [
+            "",
+            "no-raw-sockets:deny=mount",
+            "allow=read,write:no-raw-sockets",
+        ] {
+            let profile = SyscallProfile::parse(list).unwrap();
+            let filter = build_filter(0xC000_003E, &profile);
+            assert!(filter.len() <= libc::BPF_MAXINSNS as usize);
+            for (pc, insn) in filter.iter().enumerate() {
+                let class = insn.code as u32 & 0x07;
+                if class == libc::BPF_JMP {
+                    assert!(pc + 1 + (insn.jt.max(insn.jf) as usize) < filter.len());
+                } else if class != libc::BPF_RET {
+                    assert!(pc + 1 < filter.len());
+                }
+            }
+            let last = filter.last().unwrap();
+            assert_eq!(last.code as u32, libc::BPF_RET | libc::BPF_K);
+        }
+    }
+}
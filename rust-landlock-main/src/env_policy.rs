@@ -0,0 +1,240 @@
+//! Environment of the sandboxed application.
+//!
+//! Without a policy, the application gets the whole environment of the user,
+//! secrets included. A policy can carry an `environment` list, with the same
+//! `:` separator as the other policy fields:
+//!
+//! ```text
+//! allow=PATH,HOME,LANG,LC_*:deny=AWS_*,*_TOKEN:redact=ghp_*,AKIA*:set=EDITOR=vim
+//! ```
+//!
+//! - `allow=`: variable name globs; with at least one, only the matching
+//!   variables are passed
+//! - `deny=`: variable name globs, stripped even when allowed
+//! - `redact=`: value globs; variables holding a matching value are stripped,
+//!   whatever their name
+//! - `set=NAME=VALUE`: forced value, set after filtering. `\:` stands for a
+//!   colon in `VALUE`.
+//!
+//! The sandboxer's own control variables ([`is_control_variable`]) never
+//! reach the application. The names of the variables stripped by the policy
+//! are reported as `env:<NAME>`, never their values.
+
+use crate::path_template::glob_match;
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fmt;
+
+/// A parsed `environment` list.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnvPolicy {
+    allow: Option<Vec<String>>,
+    deny: Vec<String>,
+    redact: Vec<String>,
+    set: BTreeMap<String, String>,
+}
+
+impl EnvPolicy {
+    pub fn parse(list: &str) -> Result<Self> {
+        let mut policy = Self::default();
+        for entry in split_entries(list) {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+            let (key, value) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Invalid environment entry: {}", entry))?;
+            match key {
+                "allow" => policy
+                    .allow
+                    .get_or_insert_with(Vec::new)
+                    .extend(parse_globs(value, is_name_glob)?),
+                "deny" => policy.deny.extend(parse_globs(value, is_name_glob)?),
+                "redact" => policy.redact.extend(parse_globs(value, |g| !g.is_empty())?),
+                "set" => {
+                    let (name, forced) = value
+                        .split_once('=')
+                        .ok_or_else(|| anyhow!("Missing value in set={}", value))?;
+                    if !is_name(name) || is_control_variable(name) {
+                        return Err(anyhow!("Invalid variable name: {}", name));
+                    }
+                    policy.set.insert(name.into(), forced.into());
+                }
+                _ => return Err(anyhow!("Unknown environment entry: {}", key)),
+            }
+        }
+        Ok(policy)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Environment of the application built from `vars`, and the names of
+    /// the variables the policy stripped, sorted.
+    pub fn apply<I>(&self, vars: I) -> (Vec<(OsString, OsString)>, Vec<String>)
+    where
+        I: IntoIterator<Item = (OsString, OsString)>,
+    {
+        let matches = |globs: &[String], text: &str| {
+            let text: Vec<char> = text.chars().collect();
+            globs.iter().any(|g| {
+                let glob: Vec<char> = g.chars().collect();
+                glob_match(&glob, &text)
+            })
+        };
+
+        let mut kept = Vec::new();
+        let mut stripped = Vec::new();
+        for (name, value) in vars {
+            let name_str = name.to_string_lossy();
+            if is_control_variable(&name_str) || self.set.contains_key(name_str.as_ref()) {
+                continue;
+            }
+            let allowed = self.allow.as_ref().map_or(true, |a| matches(a, &name_str));
+            if !allowed
+                || matches(&self.deny, &name_str)
+                || matches(&self.redact, &value.to_string_lossy())
+            {
+                stripped.push(name_str.into_owned());
+            } else {
+                kept.push((name, value));
+            }
+        }
+        kept.extend(
+            self.set
+                .iter()
+                .map(|(name, value)| (name.into(), value.into())),
+        );
+        stripped.sort();
+        (kept, stripped)
+    }
+}
+
+/// Canonical `environment` list.
+impl fmt::Display for EnvPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut entries = Vec::new();
+        if let Some(allow) = &self.allow {
+            entries.push(format!("allow={}", allow.join(",")));
+        }
+        if !self.deny.is_empty() {
+            entries.push(format!("deny={}", self.deny.join(",")));
+        }
+        if !self.redact.is_empty() {
+            entries.push(format!("redact={}", self.redact.join(",")));
+        }
+        for (name, value) in &self.set {
+            entries.push(format!("set={}={}", name, value.replace(':', "\\:")));
+        }
+        f.write_str(&entries.join(":"))
+    }
+}
+
+/// The sandboxer's own variables, never passed to the application.
+pub fn is_control_variable(name: &str) -> bool {
+    name.starts_with("SUPERNANNY_")
+}
+
+/// Splits on `:`, except on `\:`.
+fn split_entries(list: &str) -> Vec<String> {
+    let mut entries = vec![String::new()];
+    let mut chars = list.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&':') => {
+                chars.next();
+                entries.last_mut().unwrap().push(':');
+            }
+            ':' => entries.push(String::new()),
+            _ => entries.last_mut().unwrap().push(c),
+        }
+    }
+    entries
+}
+
+fn parse_globs(list: &str, valid: fn(&str) -> bool) -> Result<Vec<String>> {
+    list.split(',')
+        .map(|g| match valid(g) {
+            true => Ok(g.to_string()),
+            false => Err(anyhow!("Invalid environment pattern: {:?}", g)),
+        })
+        .collect()
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn is_name_glob(glob: &str) -> bool {
+    !glob.is_empty()
+        && glob
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '*' || c == '?')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cgroup::CGROUP_ENV;
+
+    fn vars(list: &[(&str, &str)]) -> Vec<(OsString, OsString)> {
+        list.iter().map(|(n, v)| (n.into(), v.into())).collect()
+    }
+
+    #[test]
+    fn env_policy_round_trip() {
+        let list = "allow=PATH,LC_*:deny=AWS_*:redact=ghp_*:set=PATH=/usr/bin\\:/bin";
+        let policy = EnvPolicy::parse(list).unwrap();
+        assert_eq!(policy.to_string(), list);
+        assert_eq!(policy.set["PATH"], "/usr/bin:/bin");
+        assert!(EnvPolicy::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn env_policy_apply() {
+        let policy = EnvPolicy::parse(
+            "allow=HOME,LANG,LC_*,AWS_*,TOKEN:deny=AWS_SECRET*:redact=ghp_*:set=EDITOR=vim",
+        )
+        .unwrap();
+        let (kept, stripped) = policy.apply(vars(&[
+            ("HOME", "/home/u"),
+            ("LC_ALL", "C"),
+            ("AWS_REGION", "eu-west-3"),
+            ("AWS_SECRET_ACCESS_KEY", "abc"),
+            ("TOKEN", "ghp_123"),
+            ("SSH_AUTH_SOCK", "/run/agent"),
+            ("EDITOR", "nano"),
+            (CGROUP_ENV, "/sys/fs/cgroup/user.slice"),
+        ]));
+        assert_eq!(
+            kept,
+            vars(&[
+                ("HOME", "/home/u"),
+                ("LC_ALL", "C"),
+                ("AWS_REGION", "eu-west-3"),
+                ("EDITOR", "vim"),
+            ])
+        );
+        assert_eq!(stripped, ["AWS_SECRET_ACCESS_KEY", "SSH_AUTH_SOCK", "TOKEN"]);
+    }
+
+    #[test]
+    fn env_policy_default_strips_control_variables() {
+        let (kept, stripped) =
+            EnvPolicy::default().apply(vars(&[(CGROUP_ENV, "/sys/fs/cgroup"), ("TERM", "xterm")]));
+        assert_eq!(kept, vars(&[("TERM", "xterm")]));
+        assert!(stripped.is_empty());
+    }
+
+    #[test]
+    fn env_policy_invalid() {
+        for list in ["keep=PATH", "PATH", "allow=", "deny=A-B", "set=1A=b", "set=SUPERNANNY_A=b"] {
+            assert!(EnvPolicy::parse(list).is_err(), "{}", list);
+        }
+    }
+}
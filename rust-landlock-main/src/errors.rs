@@ -1,4 +1,4 @@
-use crate::{Access, AccessFs, AccessNet, BitFlags};
+use crate::{Access, AccessFs, AccessNet, BitFlags, HandledAccess, PrivateHandledAccess, Scope};
 use std::io;
 use std::path::PathBuf;
 use thiserror::Error;
@@ -10,6 +10,8 @@ pub enum RulesetError {
     #[error(transparent)]
     HandleAccesses(#[from] HandleAccessesError),
     #[error(transparent)]
+    Scope(#[from] ScopeError),
+    #[error(transparent)]
     CreateRuleset(#[from] CreateRulesetError),
     #[error(transparent)]
     AddRules(#[from] AddRulesError),
@@ -32,12 +34,20 @@ fn ruleset_error_breaking_change() {
 #[non_exhaustive]
 pub enum HandleAccessError<T>
 where
-    T: Access,
+    T: HandledAccess,
 {
     #[error(transparent)]
     Compat(#[from] CompatError<T>),
 }
 
+/// Identifies errors when updating the ruleset's scopes.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ScopeError {
+    #[error(transparent)]
+    Compat(#[from] CompatError<Scope>),
+}
+
 #[derive(Debug, Error)]
 #[non_exhaustive]
 pub enum HandleAccessesError {
@@ -51,7 +61,7 @@ pub enum HandleAccessesError {
 // HandleAccessesError (with #[from]).
 impl<A> From<HandleAccessError<A>> for HandleAccessesError
 where
-    A: Access,
+    A: PrivateHandledAccess,
 {
     fn from(error: HandleAccessError<A>) -> Self {
         A::into_handle_accesses_error(error)
@@ -66,7 +76,8 @@ pub enum CreateRulesetError {
     #[error("failed to create a ruleset: {source}")]
     #[non_exhaustive]
     CreateRulesetCall { source: io::Error },
-    /// Missing call to [`RulesetAttr::handle_access()`](crate::RulesetAttr::handle_access).
+    /// Missing call to [`RulesetAttr::handle_access()`](crate::RulesetAttr::handle_access)
+    /// or [`RulesetAttr::scope()`](crate::RulesetAttr::scope).
     #[error("missing handled access")]
     MissingHandledAccess,
 }
@@ -76,7 +87,7 @@ pub enum CreateRulesetError {
 #[non_exhaustive]
 pub enum AddRuleError<T>
 where
-    T: Access,
+    T: HandledAccess,
 {
     /// The `landlock_add_rule()` system call failed.
     #[error("failed to add a rule: {source}")]
@@ -96,7 +107,7 @@ where
 // AddRulesError (with #[from]).
 impl<A> From<AddRuleError<A>> for AddRulesError
 where
-    A: Access,
+    A: PrivateHandledAccess,
 {
     fn from(error: AddRuleError<A>) -> Self {
         A::into_add_rules_error(error)
@@ -0,0 +1,335 @@
+//! Durable spool of the sandbox events bound for the server.
+//!
+//! Events used to be posted one by one to `POST /events/log` and were lost
+//! whenever the server was down or slow. They are now appended to a journal
+//! per user, `~/.cache/supernanny/events/<user>.jsonl`, one [`SpooledEvent`]
+//! per line, and delivered in batches to `POST /events/batch`. Each event
+//! carries a random id the server deduplicates on, so a batch sent again after
+//! a lost response is recorded once.
+//!
+//! The journal is flushed after each run and on every launch that reaches the
+//! server; what could not be delivered stays for the next one. It is capped at
+//! [`MAX_SPOOL_SIZE`]: past it, new events are refused with an error rather
+//! than older ones overwritten.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use crate::policy_cache::now;
+use crate::policy_client::is_transient;
+
+/// Largest journal, in bytes.
+pub const MAX_SPOOL_SIZE: u64 = 16 << 20;
+
+/// Events sent per request.
+pub const BATCH_SIZE: usize = 200;
+
+/// Waits between the attempts to deliver a batch after a transient failure.
+const RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(2),
+];
+
+/// One line of the journal, in the format of `POST /events/batch`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpooledEvent {
+    /// Idempotency key, 128 random bits in hex
+    pub event_id: String,
+    /// Unix timestamp, in seconds
+    pub occurred_at: u64,
+    pub hostname: String,
+    pub app_name: String,
+    pub denied_path: Option<String>,
+    pub operation: String,
+    pub result: String,
+    pub remote_ip: Option<String>,
+    pub domain: Option<String>,
+    pub cgroup_id: Option<u64>,
+    /// Canonical path of the script the application interprets
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script_sha256: Option<String>,
+}
+
+impl SpooledEvent {
+    pub fn new(
+        app_name: &str,
+        denied_path: &str,
+        operation: &str,
+        result: &str,
+        cgroup_id: Option<u64>,
+    ) -> Result<Self> {
+        Ok(Self {
+            event_id: random_id()?,
+            occurred_at: now(),
+            hostname: hostname::get()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            app_name: app_name.into(),
+            denied_path: Some(denied_path.into()),
+            operation: operation.into(),
+            result: result.into(),
+            remote_ip: Some("127.0.0.1".into()),
+            domain: Some("localhost".into()),
+            cgroup_id,
+            script: None,
+            script_sha256: None,
+        })
+    }
+}
+
+fn random_id() -> Result<String> {
+    let mut bytes = [0; 16];
+    File::open("/dev/urandom")
+        .and_then(|mut f| f.read_exact(&mut bytes))
+        .context("Failed to generate an event id")?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Where the events of the user owning `home` are spooled.
+pub fn spool_dir(home: &Path) -> PathBuf {
+    home.join(".cache/supernanny/events")
+}
+
+/// The journal of one user. Sandboxers of the same user running concurrently
+/// take turns through a lock file next to it.
+#[derive(Debug, Clone)]
+pub struct EventSpool {
+    journal: PathBuf,
+    lock: PathBuf,
+    max_size: u64,
+}
+
+impl EventSpool {
+    pub fn new(dir: &Path, username: &str) -> Self {
+        Self {
+            journal: dir.join(format!("{}.jsonl", username)),
+            lock: dir.join(format!("{}.lock", username)),
+            max_size: MAX_SPOOL_SIZE,
+        }
+    }
+
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Appends `events` to the journal and syncs it. Events that would take
+    /// the journal past its cap are dropped, and reported in the error.
+    pub fn append(&self, events: &[SpooledEvent]) -> Result<()> {
+        let _lock = self.lock()?;
+        let mut journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .mode(0o600)
+            .open(&self.journal)
+            .with_context(|| format!("Failed to open {}", self.journal.display()))?;
+        let mut size = journal.metadata()?.len();
+
+        let mut lines = String::new();
+        let mut dropped = 0;
+        for event in events {
+            let line = serde_json::to_string(event)? + "\n";
+            if size + line.len() as u64 > self.max_size {
+                dropped += 1;
+                continue;
+            }
+            size += line.len() as u64;
+            lines.push_str(&line);
+        }
+        journal
+            .write_all(lines.as_bytes())
+            .and_then(|_| journal.sync_data())
+            .context("Failed to write event spool")?;
+
+        if dropped > 0 {
+            return Err(anyhow!(
+                "Event spool full ({} bytes), {} event(s) dropped",
+                self.max_size,
+                dropped
+            ));
+        }
+        Ok(())
+    }
+
+    /// Events waiting for delivery, oldest first. Unreadable lines, e.g. one
+    /// torn by a crash, are skipped.
+    pub fn pending(&self) -> Result<Vec<SpooledEvent>> {
+        let content = match fs::read_to_string(&self.journal) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).context("Failed to read event spool"),
+        };
+        Ok(content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    /// Delivers the pending events in batches of [`BATCH_SIZE`] with
+    /// `deliver`, retrying transient failures with backoff. Delivered events
+    /// leave the journal, the others stay for the next flush. Returns how
+    /// many were delivered.
+    pub fn flush<F>(&self, mut deliver: F) -> Result<usize>
+    where
+        F: FnMut(&[SpooledEvent]) -> Result<()>,
+    {
+        let _lock = self.lock()?;
+        let events = self.pending()?;
+
+        let mut delivered = 0;
+        for batch in events.chunks(BATCH_SIZE) {
+            if let Err(e) = deliver_with_retries(batch, &mut deliver) {
+                self.rewrite(&events[delivered..])?;
+                return Err(e.context(format!(
+                    "{} event(s) kept for later delivery",
+                    events.len() - delivered
+                )));
+            }
+            delivered += batch.len();
+        }
+
+        match fs::remove_file(&self.journal) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                Err(e).context("Failed to empty event spool")
+            }
+            _ => Ok(delivered),
+        }
+    }
+
+    /// Replaces the journal with `events`.
+    fn rewrite(&self, events: &[SpooledEvent]) -> Result<()> {
+        let tmp = self.journal.with_extension("tmp");
+        let mut f = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp)
+            .with_context(|| format!("Failed to open {}", tmp.display()))?;
+        for event in events {
+            f.write_all((serde_json::to_string(event)? + "\n").as_bytes())
+                .context("Failed to write event spool")?;
+        }
+        f.sync_data().context("Failed to write event spool")?;
+        fs::rename(&tmp, &self.journal)
+            .with_context(|| format!("Failed to write {}", self.journal.display()))
+    }
+
+    /// Takes the user's spool lock, released when the returned file is closed.
+    fn lock(&self) -> Result<File> {
+        if let Some(dir) = self.journal.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let lock = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .mode(0o600)
+            .open(&self.lock)
+            .with_context(|| format!("Failed to open {}", self.lock.display()))?;
+        if unsafe { libc::flock(lock.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(io::Error::last_os_error()).context("Failed to lock event spool");
+        }
+        Ok(lock)
+    }
+}
+
+fn deliver_with_retries<F>(batch: &[SpooledEvent], deliver: &mut F) -> Result<()>
+where
+    F: FnMut(&[SpooledEvent]) -> Result<()>,
+{
+    let mut delays = RETRY_DELAYS.iter();
+    loop {
+        match deliver(batch) {
+            Ok(()) => return Ok(()),
+            Err(e) => match delays.next() {
+                Some(delay) if is_transient(&e) => thread::sleep(*delay),
+                _ => return Err(e),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spool(name: &str) -> (PathBuf, EventSpool) {
+        let dir = std::env::temp_dir().join(format!(
+            "supernanny-spool-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let spool = EventSpool::new(&dir, "alice");
+        (dir, spool)
+    }
+
+    fn events(n: usize) -> Vec<SpooledEvent> {
+        (0..n)
+            .map(|i| SpooledEvent::new("/usr/bin/app", &format!("/tmp/{}", i), "filesystem", "denied", None).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn event_spool_flush() {
+        let (dir, spool) = spool("flush");
+        let sent = events(BATCH_SIZE + 1);
+        spool.append(&sent).unwrap();
+        // A line torn by a crash
+        OpenOptions::new()
+            .append(true)
+            .open(dir.join("alice.jsonl"))
+            .and_then(|mut f| f.write_all(b"{\"event_id\":"))
+            .unwrap();
+        assert_eq!(spool.pending().unwrap(), sent);
+
+        // The second batch fails and stays
+        let mut batches = 0;
+        let err = spool.flush(|_| {
+            batches += 1;
+            match batches {
+                1 => Ok(()),
+                _ => Err(anyhow!("refused")),
+            }
+        });
+        assert!(err.is_err());
+        assert_eq!(spool.pending().unwrap(), sent[BATCH_SIZE..]);
+
+        let mut delivered = Vec::new();
+        assert_eq!(spool.flush(|batch| {
+            delivered.extend_from_slice(batch);
+            Ok(())
+        }).unwrap(), 1);
+        assert_eq!(delivered, sent[BATCH_SIZE..]);
+        assert!(spool.pending().unwrap().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn event_spool_size_cap() {
+        let (dir, spool) = spool("cap");
+        let sent = events(3);
+        let line = serde_json::to_string(&sent[0]).unwrap().len() as u64 + 1;
+        let spool = spool.with_max_size(2 * line);
+
+        assert!(spool.append(&sent).is_err());
+        assert_eq!(spool.pending().unwrap(), sent[..2]);
+        assert!(spool.append(&sent[2..]).is_err());
+        assert_eq!(spool.pending().unwrap().len(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
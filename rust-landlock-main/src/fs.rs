@@ -1,9 +1,9 @@
 use crate::compat::private::OptionCompatLevelMut;
 use crate::{
     uapi, Access, AddRuleError, AddRulesError, CompatError, CompatLevel, CompatResult, CompatState,
-    Compatible, HandleAccessError, HandleAccessesError, PathBeneathError, PathFdError,
-    PrivateAccess, PrivateRule, Rule, Ruleset, RulesetCreated, RulesetError, TailoredCompatLevel,
-    TryCompat, ABI,
+    Compatible, HandleAccessError, HandleAccessesError, HandledAccess, PathBeneathError,
+    PathFdError, PrivateHandledAccess, PrivateRule, Rule, Ruleset, RulesetCreated, RulesetError,
+    TailoredCompatLevel, TryCompat, ABI,
 };
 use enumflags2::{bitflags, make_bitflags, BitFlags};
 use std::fs::OpenOptions;
@@ -27,7 +27,7 @@ use strum::IntoEnumIterator;
 /// # Example
 ///
 /// ```
-/// use landlock::{ABI, Access, AccessFs, BitFlags, make_bitflags};
+/// use supernanny_sandboxer::{ABI, Access, AccessFs, BitFlags, make_bitflags};
 ///
 /// let exec = AccessFs::Execute;
 ///
@@ -110,7 +110,7 @@ impl AccessFs {
     pub fn from_read(abi: ABI) -> BitFlags<Self> {
         match abi {
             ABI::Unsupported => BitFlags::EMPTY,
-            ABI::V1 | ABI::V2 | ABI::V3 | ABI::V4 | ABI::V5 => make_bitflags!(AccessFs::{
+            ABI::V1 | ABI::V2 | ABI::V3 | ABI::V4 | ABI::V5 | ABI::V6 => make_bitflags!(AccessFs::{
                 Execute
                 | ReadFile
                 | ReadDir
@@ -138,7 +138,7 @@ impl AccessFs {
             }),
             ABI::V2 => Self::from_write(ABI::V1) | AccessFs::Refer,
             ABI::V3 | ABI::V4 => Self::from_write(ABI::V2) | AccessFs::Truncate,
-            ABI::V5 => Self::from_write(ABI::V4) | AccessFs::IoctlDev,
+            ABI::V5 | ABI::V6 => Self::from_write(ABI::V4) | AccessFs::IoctlDev,
         }
     }
 
@@ -159,7 +159,9 @@ fn consistent_access_fs_rw() {
     }
 }
 
-impl PrivateAccess for AccessFs {
+impl HandledAccess for AccessFs {}
+
+impl PrivateHandledAccess for AccessFs {
     fn ruleset_handle_access(
         ruleset: &mut Ruleset,
         access: BitFlags<Self>,
@@ -214,7 +216,7 @@ where
 /// # Example
 ///
 /// ```
-/// use landlock::{AccessFs, PathBeneath, PathFd, PathFdError};
+/// use supernanny_sandboxer::{AccessFs, PathBeneath, PathFd, PathFdError};
 ///
 /// fn home_dir() -> Result<PathBeneath<PathFd>, PathFdError> {
 ///     Ok(PathBeneath::new(PathFd::new("/home")?, AccessFs::ReadDir))
@@ -489,7 +491,7 @@ fn path_beneath_check_consistency() {
 /// # Example
 ///
 /// ```
-/// use landlock::{AccessFs, PathBeneath, PathFd, PathFdError};
+/// use supernanny_sandboxer::{AccessFs, PathBeneath, PathFd, PathFdError};
 ///
 /// fn allowed_root_dir(access: AccessFs) -> Result<PathBeneath<PathFd>, PathFdError> {
 ///     let fd = PathFd::new("/")?;
@@ -553,7 +555,7 @@ fn path_fd() {
 /// # Example
 ///
 /// ```
-/// use landlock::{
+/// use supernanny_sandboxer::{
 ///     ABI, Access, AccessFs, Ruleset, RulesetAttr, RulesetCreatedAttr, RulesetStatus, RulesetError,
 ///     path_beneath_rules,
 /// };
@@ -0,0 +1,135 @@
+//! Per-path filesystem access rights.
+//!
+//! Besides `default_ro`/`default_rw`, a policy has an `fs_rules` list granting
+//! an explicit set of [`AccessFs`] rights on a path, e.g. execute without read
+//! on a toolchain directory or `ioctl_dev` on `/dev/dri` only. The list uses
+//! the same `:` separator as the other policy fields:
+//!
+//! ```text
+//! /opt/toolchain=execute,read_dir:/dev/dri=read_file,write_file,ioctl_dev:${HOME}/log=ro,make_reg
+//! ```
+//!
+//! Rights use the names reported by the denial collector; `ro` and `rw` stand
+//! for the rights of `default_ro` and `default_rw`.
+
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+
+use crate::{Access, AccessFs, BitFlags, ABI};
+
+/// ABI used for the `ro` and `rw` shorthands.
+const SHORTHAND_ABI: ABI = ABI::V5;
+
+/// Rights names, in UAPI bit order.
+pub(crate) const ACCESS_FS_NAMES: [(&str, AccessFs); 16] = [
+    ("execute", AccessFs::Execute),
+    ("write_file", AccessFs::WriteFile),
+    ("read_file", AccessFs::ReadFile),
+    ("read_dir", AccessFs::ReadDir),
+    ("remove_dir", AccessFs::RemoveDir),
+    ("remove_file", AccessFs::RemoveFile),
+    ("make_char", AccessFs::MakeChar),
+    ("make_dir", AccessFs::MakeDir),
+    ("make_reg", AccessFs::MakeReg),
+    ("make_sock", AccessFs::MakeSock),
+    ("make_fifo", AccessFs::MakeFifo),
+    ("make_block", AccessFs::MakeBlock),
+    ("make_sym", AccessFs::MakeSym),
+    ("refer", AccessFs::Refer),
+    ("truncate", AccessFs::Truncate),
+    ("ioctl_dev", AccessFs::IoctlDev),
+];
+
+/// Parses a comma-separated list of rights.
+pub fn parse_access(spec: &str) -> Result<BitFlags<AccessFs>> {
+    let mut access = BitFlags::EMPTY;
+    for name in spec.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        access |= match name {
+            "ro" => AccessFs::from_read(SHORTHAND_ABI),
+            "rw" => AccessFs::from_all(SHORTHAND_ABI),
+            _ => ACCESS_FS_NAMES
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, a)| BitFlags::from(*a))
+                .ok_or_else(|| anyhow!("Unknown filesystem right: {}", name))?,
+        };
+    }
+    if access.is_empty() {
+        return Err(anyhow!("Empty set of filesystem rights"));
+    }
+    Ok(access)
+}
+
+/// Formats rights as a comma-separated list, in UAPI order.
+pub fn format_access(access: BitFlags<AccessFs>) -> String {
+    ACCESS_FS_NAMES
+        .iter()
+        .filter(|(_, a)| access.contains(*a))
+        .map(|(n, _)| *n)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Parses an `fs_rules` list into path → rights. Rights granted twice on the
+/// same path are merged.
+pub fn parse_fs_rules(list: &str) -> Result<BTreeMap<String, BitFlags<AccessFs>>> {
+    let mut rules: BTreeMap<String, BitFlags<AccessFs>> = BTreeMap::new();
+    for entry in list.split(':').filter(|e| !e.is_empty()) {
+        let (path, spec) = entry
+            .rsplit_once('=')
+            .ok_or_else(|| anyhow!("Expected <path>=<rights> in fs rule: {}", entry))?;
+        if path.is_empty() {
+            return Err(anyhow!("Missing path in fs rule: {}", entry));
+        }
+        let access = parse_access(spec)?;
+        *rules.entry(path.to_string()).or_insert(BitFlags::EMPTY) |= access;
+    }
+    Ok(rules)
+}
+
+/// Inverse of [`parse_fs_rules`].
+pub fn join_fs_rules(rules: &BTreeMap<String, BitFlags<AccessFs>>) -> String {
+    rules
+        .iter()
+        .map(|(path, access)| format!("{}={}", path, format_access(*access)))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_format_rights() {
+        let access = parse_access("execute, read_dir").unwrap();
+        assert_eq!(access, AccessFs::Execute | AccessFs::ReadDir);
+        assert_eq!(format_access(access), "execute,read_dir");
+        assert_eq!(parse_access("ro").unwrap(), AccessFs::from_read(ABI::V5));
+        assert_eq!(parse_access("rw").unwrap(), AccessFs::from_all(ABI::V5));
+        assert!(parse_access("read").is_err());
+        assert!(parse_access("").is_err());
+    }
+
+    #[test]
+    fn names_follow_uapi_order() {
+        for (i, (_, access)) in ACCESS_FS_NAMES.iter().enumerate() {
+            assert_eq!(BitFlags::from(*access).bits(), 1 << i);
+        }
+    }
+
+    #[test]
+    fn fs_rules_round_trip() {
+        let list = "/opt/toolchain=execute,read_dir:/dev/dri=ioctl_dev:/dev/dri=read_file";
+        let rules = parse_fs_rules(list).unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules["/dev/dri"], AccessFs::IoctlDev | AccessFs::ReadFile);
+        assert_eq!(
+            join_fs_rules(&rules),
+            "/dev/dri=read_file,ioctl_dev:/opt/toolchain=execute,read_dir"
+        );
+        assert!(parse_fs_rules("/opt/toolchain").is_err());
+        assert!(parse_fs_rules("=execute").is_err());
+        assert!(parse_fs_rules("").unwrap().is_empty());
+    }
+}
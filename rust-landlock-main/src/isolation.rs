@@ -0,0 +1,341 @@
+//! Namespace isolation of the sandboxed application.
+//!
+//! Landlock refuses accesses but hides nothing: the application still sees the
+//! host's `/tmp`, home directory and network. A policy can carry an
+//! `isolation` list, with the same `:` separator as the other policy fields:
+//!
+//! ```text
+//! private-tmp:scratch-home:read-only-root:no-network:hostname=sandbox
+//! ```
+//!
+//! - `private-tmp`: fresh tmpfs on `/tmp` and `/dev/shm`
+//! - `scratch-home`: a per-application directory bind-mounted over `$HOME`
+//! - `read-only-root`: every mount read-only, except the writable paths of the
+//!   policy
+//! - `no-network`: empty network namespace, with only the loopback interface
+//! - `hostname=<name>`: own UTS namespace with this hostname
+//!
+//! The sandbox process enters the namespaces before starting any thread
+//! and before Landlock, which then keeps the application from undoing the
+//! mounts. Unprivileged users get a user namespace that only maps their own
+//! uid and gid, so the application has no capability left once executed.
+
+use anyhow::{anyhow, Context, Result};
+use std::ffi::CString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::os::raw::{c_char, c_int, c_short, c_ulong};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// A parsed `isolation` list.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Isolation {
+    pub private_tmp: bool,
+    pub scratch_home: bool,
+    pub read_only_root: bool,
+    pub no_network: bool,
+    pub hostname: Option<String>,
+}
+
+impl Isolation {
+    pub fn parse(list: &str) -> Result<Self> {
+        let mut isolation = Self::default();
+        for entry in list.split(':').map(str::trim).filter(|e| !e.is_empty()) {
+            match entry {
+                "private-tmp" => isolation.private_tmp = true,
+                "scratch-home" => isolation.scratch_home = true,
+                "read-only-root" => isolation.read_only_root = true,
+                "no-network" => isolation.no_network = true,
+                _ => match entry.strip_prefix("hostname=") {
+                    Some(name) if is_hostname(name) => isolation.hostname = Some(name.into()),
+                    Some(name) => return Err(anyhow!("Invalid hostname: {}", name)),
+                    None => return Err(anyhow!("Unknown isolation option: {}", entry)),
+                },
+            }
+        }
+        Ok(isolation)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn namespaces(&self) -> c_int {
+        let mut flags = 0;
+        if self.private_tmp || self.scratch_home || self.read_only_root {
+            flags |= libc::CLONE_NEWNS;
+        }
+        if self.no_network {
+            flags |= libc::CLONE_NEWNET;
+        }
+        if self.hostname.is_some() {
+            flags |= libc::CLONE_NEWUTS;
+        }
+        flags
+    }
+
+    /// Moves the calling process into new namespaces, inside a user namespace
+    /// when not running as root. Fails if the process has several threads.
+    pub fn unshare(&self) -> io::Result<()> {
+        let mut flags = self.namespaces();
+        if flags == 0 {
+            return Ok(());
+        }
+        let (uid, gid, euid) = unsafe { (libc::getuid(), libc::getgid(), libc::geteuid()) };
+        if euid != 0 {
+            flags |= libc::CLONE_NEWUSER;
+        }
+        if unsafe { libc::unshare(flags) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if flags & libc::CLONE_NEWUSER != 0 {
+            fs::write("/proc/self/setgroups", "deny")?;
+            fs::write("/proc/self/uid_map", format!("{} {} 1", uid, uid))?;
+            fs::write("/proc/self/gid_map", format!("{} {} 1", gid, gid))?;
+        }
+        Ok(())
+    }
+
+    /// Sets up the namespaces entered with [`unshare`](Self::unshare).
+    ///
+    /// `home` is the home directory with its scratch replacement, for
+    /// `scratch-home`. `writable` are the paths left writable by
+    /// `read-only-root`; missing ones are ignored.
+    pub fn setup(&self, home: Option<(&Path, &Path)>, writable: &[PathBuf]) -> Result<()> {
+        if self.namespaces() & libc::CLONE_NEWNS != 0 {
+            // Nothing mounted below may propagate to the host
+            mount("none", "/", "", libc::MS_REC | libc::MS_PRIVATE, "")
+                .context("Failed to make the mounts private")?;
+        }
+
+        if self.read_only_root {
+            set_read_only(Path::new("/"), true, true)
+                .context("Failed to make the root read-only (Linux 5.12 or later is needed)")?;
+        }
+        if self.scratch_home {
+            let (home, scratch) = home.ok_or_else(|| anyhow!("No home directory to replace"))?;
+            self.bind(scratch, home)
+                .with_context(|| format!("Failed to mount {}", scratch.display()))?;
+        }
+        if self.read_only_root {
+            for path in writable.iter().filter(|p| p.exists()) {
+                self.bind(path, path)
+                    .with_context(|| format!("Failed to keep {} writable", path.display()))?;
+            }
+        }
+
+        if self.private_tmp {
+            for dir in ["/tmp", "/dev/shm"] {
+                if !Path::new(dir).is_dir() {
+                    continue;
+                }
+                let flags = libc::MS_NOSUID | libc::MS_NODEV;
+                mount("tmpfs", dir, "tmpfs", flags, "mode=1777")
+                    .with_context(|| format!("Failed to mount a private {}", dir))?;
+            }
+        }
+
+        if let Some(name) = &self.hostname {
+            if unsafe { libc::sethostname(name.as_ptr().cast(), name.len()) } != 0 {
+                return Err(io::Error::last_os_error()).context("Failed to set the hostname");
+            }
+        }
+        if self.no_network {
+            loopback_up().context("Failed to bring up the loopback interface")?;
+        }
+        Ok(())
+    }
+
+    /// Writable bind mount of `source` on `target`, submounts included.
+    fn bind(&self, source: &Path, target: &Path) -> Result<()> {
+        mount(source, target, "", libc::MS_BIND | libc::MS_REC, "")?;
+        if self.read_only_root {
+            // Submounts inherited from the host may be locked read-only
+            set_read_only(target, false, true).or_else(|_| set_read_only(target, false, false))?;
+        }
+        Ok(())
+    }
+}
+
+/// Canonical `isolation` list.
+impl fmt::Display for Isolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let flags = [
+            (self.private_tmp, "private-tmp"),
+            (self.scratch_home, "scratch-home"),
+            (self.read_only_root, "read-only-root"),
+            (self.no_network, "no-network"),
+        ];
+        let mut entries: Vec<String> = flags
+            .iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, name)| name.to_string())
+            .collect();
+        if let Some(name) = &self.hostname {
+            entries.push(format!("hostname={}", name));
+        }
+        f.write_str(&entries.join(":"))
+    }
+}
+
+/// Per-application directory bind-mounted over `home` by `scratch-home`.
+pub fn scratch_home(home: &Path, app: &str) -> PathBuf {
+    home.join(".local/share/supernanny/scratch").join(app)
+}
+
+/// The sysctl keeping unprivileged users from creating user namespaces, if
+/// one does.
+pub fn userns_restriction() -> Option<&'static str> {
+    let sysctls = [
+        ("kernel/unprivileged_userns_clone", "0"),
+        ("user/max_user_namespaces", "0"),
+        ("kernel/apparmor_restrict_unprivileged_userns", "1"),
+    ];
+    sysctls.iter().find_map(|(name, value)| {
+        let current = fs::read_to_string(Path::new("/proc/sys").join(name)).ok()?;
+        (current.trim() == *value).then_some(*name)
+    })
+}
+
+fn is_hostname(name: &str) -> bool {
+    (1..=64).contains(&name.len())
+        && !name.starts_with(['-', '.'])
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+}
+
+fn cstring<P: AsRef<Path>>(path: P) -> io::Result<CString> {
+    CString::new(path.as_ref().as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+fn mount<S: AsRef<Path>, T: AsRef<Path>>(
+    source: S,
+    target: T,
+    fstype: &str,
+    flags: c_ulong,
+    data: &str,
+) -> io::Result<()> {
+    let (source, target) = (cstring(source)?, cstring(target)?);
+    let (fstype, data) = (cstring(fstype)?, cstring(data)?);
+    let res = unsafe {
+        libc::mount(
+            source.as_ptr(),
+            target.as_ptr(),
+            fstype.as_ptr(),
+            flags,
+            data.as_ptr().cast(),
+        )
+    };
+    if res != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn set_read_only(path: &Path, read_only: bool, recursive: bool) -> io::Result<()> {
+    let path = cstring(path)?;
+    let mut attr: libc::mount_attr = unsafe { std::mem::zeroed() };
+    if read_only {
+        attr.attr_set = libc::MOUNT_ATTR_RDONLY;
+    } else {
+        attr.attr_clr = libc::MOUNT_ATTR_RDONLY;
+    }
+    let flags = if recursive { libc::AT_RECURSIVE } else { 0 };
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_mount_setattr,
+            libc::AT_FDCWD,
+            path.as_ptr(),
+            flags,
+            &attr as *const libc::mount_attr,
+            std::mem::size_of::<libc::mount_attr>(),
+        )
+    };
+    if res != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn loopback_up() -> io::Result<()> {
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut req: libc::ifreq = unsafe { std::mem::zeroed() };
+    for (dst, src) in req.ifr_name.iter_mut().zip(b"lo") {
+        *dst = *src as c_char;
+    }
+    let res = unsafe {
+        if libc::ioctl(fd, libc::SIOCGIFFLAGS as _, &mut req) == 0 {
+            req.ifr_ifru.ifru_flags |= libc::IFF_UP as c_short;
+            libc::ioctl(fd, libc::SIOCSIFFLAGS as _, &req)
+        } else {
+            -1
+        }
+    };
+    let err = io::Error::last_os_error();
+    unsafe { libc::close(fd) };
+    if res != 0 {
+        return Err(err);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn isolation_round_trip() {
+        let isolation =
+            Isolation::parse("hostname=box:no-network: private-tmp:scratch-home:read-only-root")
+                .unwrap();
+        assert_eq!(
+            isolation,
+            Isolation {
+                private_tmp: true,
+                scratch_home: true,
+                read_only_root: true,
+                no_network: true,
+                hostname: Some("box".into()),
+            }
+        );
+        assert_eq!(
+            isolation.to_string(),
+            "private-tmp:scratch-home:read-only-root:no-network:hostname=box"
+        );
+        assert_eq!(Isolation::parse(&isolation.to_string()).unwrap(), isolation);
+
+        let empty = Isolation::parse("").unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(empty.namespaces(), 0);
+        assert!(empty.unshare().is_ok());
+    }
+
+    #[test]
+    fn isolation_namespaces() {
+        let tmp = Isolation::parse("private-tmp").unwrap();
+        assert_eq!(tmp.namespaces(), libc::CLONE_NEWNS);
+        let net = Isolation::parse("no-network:hostname=a").unwrap();
+        assert_eq!(net.namespaces(), libc::CLONE_NEWNET | libc::CLONE_NEWUTS);
+    }
+
+    #[test]
+    fn isolation_invalid() {
+        for list in [
+            "private-home",
+            "hostname=",
+            "hostname=-box",
+            "hostname=a b",
+            "hostname=a_b",
+            &format!("hostname={}", "a".repeat(65)),
+        ] {
+            assert!(Isolation::parse(list).is_err(), "{}", list);
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! Supervision of the sandboxed child by the sandboxer and sandbox
+//! processes.
+//!
+//! Neither changes process group: the application stays in the terminal's
+//! foreground group and gets the terminal's signals (Ctrl-C, Ctrl-Z, window
+//! size changes) directly, so editors, pagers and REPLs behave as usual.
+//! While a supervisor waits for its child:
+//!
+//! - it survives the signals the child handles itself, and forwards to the
+//!   child those sent to the supervisor alone (`kill`, systemd's `SIGTERM`);
+//! - it stops when the child stops, so that the shell sees the job stopped,
+//!   and continues the child when continued itself (`fg`, `bg`);
+//! - it reports a child killed by signal N as exit status `128+N`, like shells
+//!   do ([`exit_code`]).
+
+use std::io;
+use std::mem::zeroed;
+use std::os::raw::c_int;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Child, ExitStatus};
+use std::ptr;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+/// Signals forwarded to the child when it did not get them already.
+const FORWARDED: [c_int; 8] = [
+    libc::SIGHUP,
+    libc::SIGINT,
+    libc::SIGQUIT,
+    libc::SIGTERM,
+    libc::SIGUSR1,
+    libc::SIGUSR2,
+    libc::SIGWINCH,
+    libc::SIGTSTP,
+];
+
+/// Pid of the child being waited for by [`wait`], 0 when none.
+static CHILD: AtomicI32 = AtomicI32::new(0);
+
+/// Signal handlers installed by [`forward_signals`]; the previous ones are
+/// restored on drop.
+pub struct SignalForwarding {
+    previous: Vec<(c_int, libc::sigaction)>,
+}
+
+/// Installs the handlers forwarding signals to the child passed to [`wait`].
+///
+/// The handlers are reset by `execve()`, so the child starts with the default
+/// dispositions.
+pub fn forward_signals() -> io::Result<SignalForwarding> {
+    let mut forwarding = SignalForwarding {
+        previous: Vec::new(),
+    };
+    for sig in FORWARDED {
+        let mut action: libc::sigaction = unsafe { zeroed() };
+        action.sa_sigaction = forward as *const () as libc::sighandler_t;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART;
+        let mut previous: libc::sigaction = unsafe { zeroed() };
+        unsafe {
+            libc::sigemptyset(&mut action.sa_mask);
+            if libc::sigaction(sig, &action, &mut previous) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        forwarding.previous.push((sig, previous));
+    }
+    Ok(forwarding)
+}
+
+impl Drop for SignalForwarding {
+    fn drop(&mut self) {
+        for (sig, previous) in &self.previous {
+            unsafe { libc::sigaction(*sig, previous, ptr::null_mut()) };
+        }
+    }
+}
+
+extern "C" fn forward(sig: c_int, info: *mut libc::siginfo_t, _: *mut libc::c_void) {
+    let child = CHILD.load(Ordering::SeqCst);
+    if child <= 0 || info.is_null() {
+        return;
+    }
+    unsafe {
+        let errno = *libc::__errno_location();
+        if sent_to_supervisor_only(&*info) {
+            libc::kill(child, sig);
+        }
+        *libc::__errno_location() = errno;
+    }
+}
+
+/// Whether the child missed the signal: the terminal signals the whole
+/// foreground group (`SI_KERNEL`), and so does a process of our group
+/// calling `kill(0, ...)`, as vim does on Ctrl-Z.
+unsafe fn sent_to_supervisor_only(info: &libc::siginfo_t) -> bool {
+    // Positive codes are raised by the kernel, not by kill() and friends
+    if info.si_code > 0 {
+        return false;
+    }
+    libc::getpgid(info.si_pid()) != libc::getpgrp()
+}
+
+/// Waits for `child` to terminate, stopping along with it meanwhile.
+///
+/// Signals are forwarded to it while [`forward_signals`] is in effect.
+pub fn wait(child: Child) -> io::Result<ExitStatus> {
+    wait_for(child.id() as libc::pid_t)
+}
+
+/// [`wait`] for a child forked without [`std::process::Command`].
+pub fn wait_for(pid: libc::pid_t) -> io::Result<ExitStatus> {
+    CHILD.store(pid, Ordering::SeqCst);
+    let status = wait_pid(pid);
+    CHILD.store(0, Ordering::SeqCst);
+    status
+}
+
+fn wait_pid(pid: libc::pid_t) -> io::Result<ExitStatus> {
+    loop {
+        let mut status = 0;
+        if unsafe { libc::waitpid(pid, &mut status, libc::WUNTRACED) } < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        if libc::WIFSTOPPED(status) {
+            // Let our own parent, e.g. the shell, see the job stopped, and
+            // take the child along once continued
+            unsafe {
+                libc::kill(libc::getpid(), libc::SIGSTOP);
+                libc::kill(pid, libc::SIGCONT);
+            }
+            continue;
+        }
+        return Ok(ExitStatus::from_raw(status));
+    }
+}
+
+/// Exit status reporting `status` to our own parent: the child's exit code,
+/// or `128+N` when killed by signal N.
+pub fn exit_code(status: ExitStatus) -> i32 {
+    match (status.code(), status.signal()) {
+        (Some(code), _) => code,
+        (None, Some(sig)) => 128 + sig,
+        (None, None) => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
+
+    #[test]
+    fn job_control_exit_code() {
+        assert_eq!(exit_code(ExitStatus::from_raw(3 << 8)), 3);
+        assert_eq!(exit_code(ExitStatus::from_raw(libc::SIGKILL)), 137);
+
+        let status = Command::new("sh")
+            .args(["-c", "kill -TERM $$"])
+            .status()
+            .unwrap();
+        assert_eq!(exit_code(status), 128 + libc::SIGTERM);
+    }
+
+    #[test]
+    fn job_control_forwards_signals() {
+        let _forwarding = forward_signals().unwrap();
+        let child = Command::new("sleep").arg("10").spawn().unwrap();
+
+        // Sent from another process group, to us only
+        let mut killer = Command::new("sh");
+        killer.args(["-c", &format!("sleep 0.2; kill -USR1 {}", std::process::id())]);
+        unsafe {
+            killer.pre_exec(|| match libc::setpgid(0, 0) {
+                0 => Ok(()),
+                _ => Err(io::Error::last_os_error()),
+            });
+        }
+        let mut killer = killer.spawn().unwrap();
+
+        assert_eq!(exit_code(wait(child).unwrap()), 128 + libc::SIGUSR1);
+        killer.wait().unwrap();
+    }
+}
@@ -79,12 +79,13 @@
 #[macro_use]
 extern crate lazy_static;
 
-pub use access::Access;
+pub use access::{Access, HandledAccess};
 pub use compat::{CompatLevel, Compatible, ABI};
 pub use enumflags2::{make_bitflags, BitFlags};
 pub use errors::{
     AccessError, AddRuleError, AddRulesError, CompatError, CreateRulesetError, HandleAccessError,
     HandleAccessesError, PathBeneathError, PathFdError, RestrictSelfError, RulesetError,
+    ScopeError,
 };
 pub use fs::{path_beneath_rules, AccessFs, PathBeneath, PathFd};
 pub use net::{AccessNet, NetPort};
@@ -92,8 +93,9 @@ pub use ruleset::{
     RestrictionStatus, Rule, Ruleset, RulesetAttr, RulesetCreated, RulesetCreatedAttr,
     RulesetStatus,
 };
+pub use scope::Scope;
 
-use access::PrivateAccess;
+use access::PrivateHandledAccess;
 use compat::{CompatResult, CompatState, Compatibility, TailoredCompatLevel, TryCompat};
 use ruleset::PrivateRule;
 
@@ -110,8 +112,35 @@ mod errors;
 mod fs;
 mod net;
 mod ruleset;
+mod scope;
 mod uapi;
+
+// Makes sure private traits cannot be implemented outside of this crate.
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for crate::AccessFs {}
+    impl Sealed for crate::AccessNet {}
+    impl Sealed for crate::Scope {}
+}
+pub mod app_identity;
+pub mod cgroup;
+pub mod denial_collector;
+pub mod dns_stub;
+pub mod egress_filter;
+pub mod env_policy;
+pub mod event_spool;
+pub mod fs_access;
+pub mod isolation;
+pub mod job_control;
+pub mod path_template;
+pub mod policy_cache;
 pub mod policy_client;
+pub mod policy_learning;
+pub mod sandbox_report;
+pub mod scope_access;
+pub mod spec;
+pub mod syscall_profile;
 
 #[cfg(test)]
 mod tests {
@@ -403,4 +432,35 @@ mod tests {
             false,
         );
     }
+
+    #[test]
+    fn abi_v6_scope_mix() {
+        check_ruleset_support(
+            ABI::V5,
+            Some(ABI::V6),
+            move |ruleset: Ruleset| -> _ {
+                Ok(ruleset
+                    .handle_access(AccessFs::IoctlDev)?
+                    .scope(Scope::AbstractUnixSocket | Scope::Signal)?
+                    .create()?
+                    .restrict_self()?)
+            },
+            false,
+        );
+    }
+
+    #[test]
+    fn abi_v6_scope_only() {
+        check_ruleset_support(
+            ABI::V6,
+            Some(ABI::V6),
+            move |ruleset: Ruleset| -> _ {
+                Ok(ruleset
+                    .scope(Scope::AbstractUnixSocket | Scope::Signal)?
+                    .create()?
+                    .restrict_self()?)
+            },
+            false,
+        );
+    }
 }
@@ -1,8 +1,8 @@
 use crate::compat::private::OptionCompatLevelMut;
 use crate::{
     uapi, Access, AddRuleError, AddRulesError, CompatError, CompatLevel, CompatResult, CompatState,
-    Compatible, HandleAccessError, HandleAccessesError, PrivateAccess, PrivateRule, Rule, Ruleset,
-    RulesetCreated, TailoredCompatLevel, TryCompat, ABI,
+    Compatible, HandleAccessError, HandleAccessesError, HandledAccess, PrivateHandledAccess,
+    PrivateRule, Rule, Ruleset, RulesetCreated, TailoredCompatLevel, TryCompat, ABI,
 };
 use enumflags2::{bitflags, BitFlags};
 use std::mem::zeroed;
@@ -16,7 +16,7 @@ use std::mem::zeroed;
 /// # Example
 ///
 /// ```
-/// use landlock::{ABI, Access, AccessNet, BitFlags, make_bitflags};
+/// use supernanny_sandboxer::{ABI, Access, AccessNet, BitFlags, make_bitflags};
 ///
 /// let bind = AccessNet::BindTcp;
 ///
@@ -55,12 +55,14 @@ impl Access for AccessNet {
     fn from_all(abi: ABI) -> BitFlags<Self> {
         match abi {
             ABI::Unsupported | ABI::V1 | ABI::V2 | ABI::V3 => BitFlags::EMPTY,
-            ABI::V4 | ABI::V5 => AccessNet::BindTcp | AccessNet::ConnectTcp,
+            ABI::V4 | ABI::V5 | ABI::V6 => AccessNet::BindTcp | AccessNet::ConnectTcp,
         }
     }
 }
 
-impl PrivateAccess for AccessNet {
+impl HandledAccess for AccessNet {}
+
+impl PrivateHandledAccess for AccessNet {
     fn ruleset_handle_access(
         ruleset: &mut Ruleset,
         access: BitFlags<Self>,
@@ -95,7 +97,7 @@ impl PrivateAccess for AccessNet {
 /// # Example
 ///
 /// ```
-/// use landlock::{AccessNet, NetPort};
+/// use supernanny_sandboxer::{AccessNet, NetPort};
 ///
 /// fn bind_http() -> NetPort {
 ///     NetPort::new(80, AccessNet::BindTcp)
@@ -0,0 +1,442 @@
+//! Path templates in policies.
+//!
+//! Policy paths may use `${HOME}`, `${USER}`, `${UID}`, `${XDG_RUNTIME_DIR}`
+//! and `${APP_DIR}` (directory of the sandboxed executable), so that a single
+//! role policy can grant e.g. `${HOME}/.config/app` to every user. A path
+//! component may also be a glob (`*`, `?`, `[a-z]`, `[!0-9]`), matched against
+//! the existing entries of its directory. Globs are bounded: `**` is not
+//! supported, the first component must be literal and an expansion yields at
+//! most [`MAX_GLOB_MATCHES`] paths.
+
+use anyhow::{anyhow, Result};
+use std::ffi::CStr;
+use std::fs;
+use std::os::raw::c_char;
+use std::path::{Component, Path, PathBuf};
+
+/// Upper bound on the number of paths a single template expands to.
+pub const MAX_GLOB_MATCHES: usize = 64;
+
+/// Maximum length of a template or of an expanded path.
+const MAX_PATH_LEN: usize = 4096;
+
+/// Variables that may start a template, i.e. expand to an absolute path.
+const PATH_VARS: &[&str] = &["HOME", "XDG_RUNTIME_DIR", "APP_DIR"];
+
+/// Variables that may only appear after the first component.
+const NAME_VARS: &[&str] = &["USER", "UID"];
+
+/// Values substituted in templates.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TemplateVars {
+    pub home: Option<PathBuf>,
+    pub user: Option<String>,
+    pub uid: u32,
+    pub xdg_runtime_dir: Option<PathBuf>,
+    pub app_dir: Option<PathBuf>,
+}
+
+impl TemplateVars {
+    /// Variables of the calling user; `${APP_DIR}` is only set when `app` is given.
+    ///
+    /// They come from the password database and the uid, never from the
+    /// environment: with `HOME=/`, a `${HOME}` rule would cover the whole
+    /// filesystem.
+    pub fn current(app: Option<&Path>) -> Self {
+        let uid = unsafe { libc::getuid() };
+        let (user, home) = passwd_entry(uid);
+        let xdg_runtime_dir = PathBuf::from(format!("/run/user/{}", uid));
+        let app_dir = app.and_then(|a| {
+            let canonical = fs::canonicalize(a).unwrap_or_else(|_| a.to_path_buf());
+            canonical.parent().map(Path::to_path_buf)
+        });
+
+        Self {
+            home,
+            user,
+            uid,
+            xdg_runtime_dir: Some(xdg_runtime_dir),
+            app_dir,
+        }
+    }
+
+    fn get(&self, name: &str) -> Result<String> {
+        let value = match name {
+            "HOME" => self.home.as_ref().map(|p| p.to_string_lossy().into_owned()),
+            "USER" => self.user.clone(),
+            "UID" => Some(self.uid.to_string()),
+            "XDG_RUNTIME_DIR" => self
+                .xdg_runtime_dir
+                .as_ref()
+                .map(|p| p.to_string_lossy().into_owned()),
+            "APP_DIR" => self.app_dir.as_ref().map(|p| p.to_string_lossy().into_owned()),
+            _ => return Err(anyhow!("Unknown variable ${{{}}}", name)),
+        };
+        value.ok_or_else(|| anyhow!("Variable ${{{}}} is not set", name))
+    }
+}
+
+fn passwd_entry(uid: u32) -> (Option<String>, Option<PathBuf>) {
+    let pw = unsafe { libc::getpwuid(uid) };
+    if pw.is_null() {
+        return (None, None);
+    }
+    let (name, dir) = unsafe { ((*pw).pw_name, (*pw).pw_dir) };
+    let to_string = |s: *const c_char| {
+        if s.is_null() {
+            None
+        } else {
+            Some(unsafe { CStr::from_ptr(s) }.to_string_lossy().into_owned())
+        }
+    };
+    (to_string(name), to_string(dir).map(PathBuf::from))
+}
+
+/// Splits `s` into literal text and `${NAME}` references.
+fn variables(s: &str) -> Result<Vec<(usize, usize, &str)>> {
+    let mut vars = Vec::new();
+    let mut rest = 0;
+    while let Some(start) = s[rest..].find('$').map(|i| rest + i) {
+        if !s[start..].starts_with("${") {
+            return Err(anyhow!("Expected '{{' after '$' in {}", s));
+        }
+        let end = match s[start..].find('}') {
+            Some(i) => start + i,
+            None => return Err(anyhow!("Unterminated variable in {}", s)),
+        };
+        let name = &s[start + 2..end];
+        if !PATH_VARS.contains(&name) && !NAME_VARS.contains(&name) {
+            return Err(anyhow!("Unknown variable ${{{}}} in {}", name, s));
+        }
+        vars.push((start, end + 1, name));
+        rest = end + 1;
+    }
+    Ok(vars)
+}
+
+fn is_glob(component: &str) -> bool {
+    component.contains(&['*', '?', '['][..])
+}
+
+/// Checks the syntax of a policy path, without expanding it.
+pub fn validate_template(template: &str) -> Result<()> {
+    if template.len() > MAX_PATH_LEN {
+        return Err(anyhow!("Path too long: {}", template));
+    }
+    if template.contains('\0') {
+        return Err(anyhow!("Path contains null bytes: {}", template));
+    }
+    let vars = variables(template)?;
+    match vars.first() {
+        Some((0, _, name)) if !PATH_VARS.contains(name) => {
+            return Err(anyhow!(
+                "${{{}}} cannot start a path, use one of ${{HOME}}, ${{XDG_RUNTIME_DIR}}, ${{APP_DIR}}: {}",
+                name,
+                template
+            ));
+        }
+        Some((0, _, _)) => {}
+        _ if !template.starts_with('/') => {
+            return Err(anyhow!("Path is not absolute: {}", template));
+        }
+        _ => {}
+    }
+    if vars
+        .iter()
+        .any(|(start, _, name)| *start > 0 && PATH_VARS.contains(name))
+    {
+        return Err(anyhow!(
+            "Only ${{USER}} and ${{UID}} may appear inside a path: {}",
+            template
+        ));
+    }
+    if let Some((0, end, _)) = vars.first() {
+        if !template[*end..].is_empty() && !template[*end..].starts_with('/') {
+            return Err(anyhow!("Expected '/' after the first variable: {}", template));
+        }
+    }
+
+    let mut components = template.split('/').filter(|c| !c.is_empty());
+    match components.next() {
+        Some(first) if is_glob(first) => {
+            return Err(anyhow!("First path component cannot be a glob: {}", template));
+        }
+        _ => {}
+    }
+    for component in template.split('/') {
+        if component == ".." || component == "." {
+            return Err(anyhow!("Path contains potential traversal: {}", template));
+        }
+        if component.contains("**") {
+            return Err(anyhow!("Recursive globs are not supported: {}", template));
+        }
+        if component.contains('[') && !valid_brackets(component) {
+            return Err(anyhow!("Unterminated '[' in {}", template));
+        }
+    }
+    Ok(())
+}
+
+fn valid_brackets(component: &str) -> bool {
+    let mut chars = component.chars();
+    while let Some(c) = chars.next() {
+        if c == '[' && !chars.by_ref().any(|c| c == ']') {
+            return false;
+        }
+    }
+    true
+}
+
+/// Substitutes variables and expands globs.
+///
+/// A template without glob always yields one path, whether it exists or not;
+/// a glob yields the existing matches only, which may be none.
+pub fn expand(template: &str, vars: &TemplateVars) -> Result<Vec<PathBuf>> {
+    validate_template(template)?;
+
+    let mut substituted = String::with_capacity(template.len());
+    let mut last = 0;
+    for (start, end, name) in variables(template)? {
+        substituted.push_str(&template[last..start]);
+        let value = vars.get(name)?;
+        if NAME_VARS.contains(&name) && value.contains('/') {
+            return Err(anyhow!("${{{}}} contains a '/': {}", name, value));
+        }
+        substituted.push_str(&value);
+        last = end;
+    }
+    substituted.push_str(&template[last..]);
+
+    let path = PathBuf::from(&substituted);
+    if !path.is_absolute() || substituted.len() > MAX_PATH_LEN {
+        return Err(anyhow!("Invalid expanded path: {}", substituted));
+    }
+    if path.components().any(|c| c == Component::ParentDir) {
+        return Err(anyhow!("Path contains potential traversal: {}", substituted));
+    }
+    if !substituted.split('/').any(is_glob) {
+        return Ok(vec![path]);
+    }
+
+    let mut matches = vec![PathBuf::from("/")];
+    for component in substituted.split('/').filter(|c| !c.is_empty()) {
+        if !is_glob(component) {
+            for m in &mut matches {
+                m.push(component);
+            }
+            continue;
+        }
+        let pattern: Vec<char> = component.chars().collect();
+        let mut next = Vec::new();
+        for dir in &matches {
+            let entries = match fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            let mut names: Vec<String> = entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .filter(|n| !n.starts_with('.') || component.starts_with('.'))
+                .filter(|n| {
+                    let name: Vec<char> = n.chars().collect();
+                    glob_match(&pattern, &name)
+                })
+                .collect();
+            names.sort();
+            for name in names {
+                if next.len() >= MAX_GLOB_MATCHES {
+                    return Err(anyhow!(
+                        "{} matches more than {} paths",
+                        template,
+                        MAX_GLOB_MATCHES
+                    ));
+                }
+                next.push(dir.join(name));
+            }
+        }
+        matches = next;
+    }
+    Ok(matches)
+}
+
+/// Matches one path component against a glob.
+pub(crate) fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|i| glob_match(&pattern[1..], &name[i..])),
+        Some('?') => !name.is_empty() && glob_match(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let close = match pattern.iter().skip(2).position(|c| *c == ']') {
+                Some(i) => i + 2,
+                None => return false,
+            };
+            let c = match name.first() {
+                Some(c) => *c,
+                None => return false,
+            };
+            let (negate, set) = match pattern[1] {
+                '!' | '^' => (true, &pattern[2..close]),
+                _ => (false, &pattern[1..close]),
+            };
+            let mut found = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == '-' {
+                    found |= set[i] <= c && c <= set[i + 2];
+                    i += 3;
+                } else {
+                    found |= set[i] == c;
+                    i += 1;
+                }
+            }
+            found != negate && glob_match(&pattern[close + 1..], &name[1..])
+        }
+        Some(p) => name.first() == Some(p) && glob_match(&pattern[1..], &name[1..]),
+    }
+}
+
+/// Rewrites a concrete path under the user's home or runtime directory as a
+/// template, so that rules learned by one user apply to every user of the role.
+pub fn templatize(path: &Path, vars: &TemplateVars) -> String {
+    let prefixes = [
+        ("XDG_RUNTIME_DIR", vars.xdg_runtime_dir.as_deref()),
+        ("HOME", vars.home.as_deref()),
+    ];
+    for (name, prefix) in prefixes.iter() {
+        let prefix = match prefix {
+            Some(p) if *p != Path::new("/") => p,
+            _ => continue,
+        };
+        if let Ok(rest) = path.strip_prefix(prefix) {
+            return if rest.as_os_str().is_empty() {
+                format!("${{{}}}", name)
+            } else {
+                format!("${{{}}}/{}", name, rest.display())
+            };
+        }
+    }
+    path.to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn vars() -> TemplateVars {
+        TemplateVars {
+            home: Some("/home/alice".into()),
+            user: Some("alice".into()),
+            uid: 1000,
+            xdg_runtime_dir: Some("/run/user/1000".into()),
+            app_dir: Some("/opt/app/bin".into()),
+        }
+    }
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let name: Vec<char> = name.chars().collect();
+        glob_match(&pattern, &name)
+    }
+
+    #[test]
+    fn substitutes_variables() {
+        let v = vars();
+        assert_eq!(
+            expand("${HOME}/.config/app", &v).unwrap(),
+            vec![PathBuf::from("/home/alice/.config/app")]
+        );
+        assert_eq!(
+            expand("/var/tmp/${USER}-${UID}", &v).unwrap(),
+            vec![PathBuf::from("/var/tmp/alice-1000")]
+        );
+        assert_eq!(
+            expand("${XDG_RUNTIME_DIR}/bus", &v).unwrap(),
+            vec![PathBuf::from("/run/user/1000/bus")]
+        );
+        assert!(expand("${APP_DIR}/../lib", &v).is_err());
+        assert_eq!(expand("/usr/lib", &v).unwrap(), vec![PathBuf::from("/usr/lib")]);
+    }
+
+    #[test]
+    fn current_ignores_environment() {
+        let uid = unsafe { libc::getuid() };
+        let (user, home) = passwd_entry(uid);
+        env::set_var("HOME", "/");
+        env::set_var("USER", "nobody-else");
+        env::set_var("XDG_RUNTIME_DIR", "/");
+        let v = TemplateVars::current(None);
+        assert_eq!(v.home, home);
+        assert_eq!(v.user, user);
+        assert_eq!(
+            v.xdg_runtime_dir,
+            Some(PathBuf::from(format!("/run/user/{}", uid)))
+        );
+        if let Some(home) = home {
+            assert_eq!(
+                expand("${HOME}/.config", &v).unwrap(),
+                vec![home.join(".config")]
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        for template in [
+            "relative/path",
+            "${HOME",
+            "$HOME/.config",
+            "${SHELL}/x",
+            "${USER}/x",
+            "/home/${HOME}",
+            "${HOME}x/.config",
+            "/*/passwd",
+            "/usr/**/lib",
+            "/usr/lib[",
+            "/usr/./lib",
+        ] {
+            assert!(validate_template(template).is_err(), "{}", template);
+        }
+        let unset = TemplateVars::default();
+        assert!(expand("${APP_DIR}/data", &unset).is_err());
+    }
+
+    #[test]
+    fn glob_components() {
+        assert!(matches("*.so", "libc.so"));
+        assert!(!matches("*.so", "libc.so.6"));
+        assert!(matches("lib?.so", "libc.so"));
+        assert!(matches("python3.[0-9]*", "python3.11"));
+        assert!(!matches("python3.[!0-9]", "python3.1"));
+        assert!(matches("*", ""));
+    }
+
+    #[test]
+    fn expands_globs_on_disk() {
+        let dir = env::temp_dir().join(format!("path_template_{}", std::process::id()));
+        fs::create_dir_all(dir.join("a1")).unwrap();
+        fs::create_dir_all(dir.join("a2")).unwrap();
+        fs::create_dir_all(dir.join("b1")).unwrap();
+        fs::create_dir_all(dir.join(".a3")).unwrap();
+
+        let v = vars();
+        let found = expand(&format!("{}/a*", dir.display()), &v).unwrap();
+        assert_eq!(found, vec![dir.join("a1"), dir.join("a2")]);
+        let found = expand(&format!("{}/z*", dir.display()), &v).unwrap();
+        assert!(found.is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn templatize_home_and_runtime_dir() {
+        let v = vars();
+        assert_eq!(
+            templatize(Path::new("/home/alice/.cache/app"), &v),
+            "${HOME}/.cache/app"
+        );
+        assert_eq!(templatize(Path::new("/run/user/1000"), &v), "${XDG_RUNTIME_DIR}");
+        assert_eq!(templatize(Path::new("/home/alicea"), &v), "/home/alicea");
+        assert_eq!(templatize(Path::new("/etc/hosts"), &v), "/etc/hosts");
+    }
+}
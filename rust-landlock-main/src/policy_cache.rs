@@ -0,0 +1,259 @@
+//! Signed local cache of the user's policies, for running without the server.
+//!
+//! `GET /auth/ruleset/signed` returns the policies of the user's roles in a
+//! [`SignedPolicies`] envelope: a JSON [`PolicyBundle`] and its Ed25519
+//! signature, both base64-encoded. The bundle names the user and roles it was
+//! issued for and a validity window.
+//!
+//! The sandboxer verifies the envelope with the server's public key, installed
+//! by the administrator in [`POLICY_KEY_PATH`], and keeps the latest one per
+//! set of roles under `~/.cache/supernanny/policies/`, fetched again once none
+//! verifies or they have expired. When the server cannot be reached, it falls
+//! back to the newest cached bundle of the current user that still verifies
+//! and has not expired. Edited, foreign and expired caches are refused.
+
+use anyhow::{anyhow, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ed25519_dalek::{Signature, VerifyingKey, PUBLIC_KEY_LENGTH};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Server public key, base64-encoded. Only root may write it: anyone able to
+/// replace it could sign their own policies.
+pub const POLICY_KEY_PATH: &str = "/etc/supernanny/policy_signing.pub";
+
+/// Tolerated clock difference with the server, in seconds.
+const CLOCK_SKEW: u64 = 300;
+
+/// Policies as signed by the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedPolicies {
+    pub payload: String,
+    pub signature: String,
+}
+
+/// Signed content: the `/auth/ruleset` response for one user and their roles.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyBundle {
+    pub username: String,
+    pub role_ids: Vec<i32>,
+    /// Unix timestamps, in seconds
+    pub issued_at: u64,
+    pub expires_at: u64,
+    pub ruleset: serde_json::Value,
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Reads the server public key from [`POLICY_KEY_PATH`].
+pub fn load_key() -> Result<VerifyingKey> {
+    let encoded = fs::read_to_string(POLICY_KEY_PATH)
+        .with_context(|| format!("Failed to read {}", POLICY_KEY_PATH))?;
+    parse_key(&encoded)
+}
+
+pub fn parse_key(encoded: &str) -> Result<VerifyingKey> {
+    let bytes = STANDARD
+        .decode(encoded.trim())
+        .context("Invalid policy signing key")?;
+    let bytes: [u8; PUBLIC_KEY_LENGTH] = bytes
+        .try_into()
+        .map_err(|_| anyhow!("Invalid policy signing key length"))?;
+    VerifyingKey::from_bytes(&bytes).context("Invalid policy signing key")
+}
+
+impl SignedPolicies {
+    /// Checks the signature, then that the bundle was issued to `username` and
+    /// is valid at `now`.
+    pub fn verify(&self, key: &VerifyingKey, username: &str, now: u64) -> Result<PolicyBundle> {
+        let payload = STANDARD
+            .decode(&self.payload)
+            .context("Invalid policy payload encoding")?;
+        let signature = STANDARD
+            .decode(&self.signature)
+            .context("Invalid policy signature encoding")?;
+        let signature = Signature::from_slice(&signature).context("Invalid policy signature")?;
+        key.verify_strict(&payload, &signature)
+            .map_err(|_| anyhow!("Policy signature mismatch, the policies were tampered with"))?;
+
+        let bundle: PolicyBundle =
+            serde_json::from_slice(&payload).context("Invalid policy payload")?;
+        if bundle.username != username {
+            return Err(anyhow!(
+                "Policies issued to {}, not to {}",
+                bundle.username,
+                username
+            ));
+        }
+        if bundle.issued_at > now + CLOCK_SKEW {
+            return Err(anyhow!("Policies issued in the future, check the clock"));
+        }
+        if bundle.expires_at <= now {
+            return Err(anyhow!("Cached policies expired"));
+        }
+        Ok(bundle)
+    }
+}
+
+/// Where the signed policies of the user owning `home` are cached.
+pub fn cache_dir(home: &Path) -> PathBuf {
+    home.join(".cache/supernanny/policies")
+}
+
+fn cache_file(dir: &Path, bundle: &PolicyBundle) -> PathBuf {
+    let role_ids: Vec<String> = bundle.role_ids.iter().map(|id| id.to_string()).collect();
+    dir.join(format!("{}.{}.json", bundle.username, role_ids.join("-")))
+}
+
+/// Caches verified policies, replacing the previous ones of the same roles.
+pub fn save(dir: &Path, signed: &SignedPolicies, bundle: &PolicyBundle) -> Result<()> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let file = cache_file(dir, bundle);
+    let tmp = file.with_extension("tmp");
+    let mut f = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp)
+        .with_context(|| format!("Failed to open {}", tmp.display()))?;
+    f.write_all(serde_json::to_string(signed)?.as_bytes())
+        .context("Failed to write policy cache")?;
+    fs::rename(&tmp, &file).with_context(|| format!("Failed to write {}", file.display()))
+}
+
+/// Cached policy files of `username`.
+fn cache_files(dir: &Path, username: &str) -> Result<Vec<PathBuf>> {
+    let prefix = format!("{}.", username);
+    let entries = fs::read_dir(dir)
+        .with_context(|| format!("No policy cache in {}", dir.display()))?;
+    Ok(entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            name.starts_with(&prefix) && name.ends_with(".json")
+        })
+        .collect())
+}
+
+fn read_verified(path: &Path, key: &VerifyingKey, username: &str, now: u64) -> Result<PolicyBundle> {
+    fs::read_to_string(path)
+        .context("Failed to read policy cache")
+        .and_then(|s| serde_json::from_str::<SignedPolicies>(&s).context("Invalid policy cache"))
+        .and_then(|signed| signed.verify(key, username, now))
+}
+
+/// The most recently issued cached policies of `username` that verify and
+/// are still valid. Refused caches are reported on stderr.
+pub fn load_latest(
+    dir: &Path,
+    key: &VerifyingKey,
+    username: &str,
+    now: u64,
+) -> Result<PolicyBundle> {
+    let mut latest: Option<PolicyBundle> = None;
+    for path in cache_files(dir, username)? {
+        match read_verified(&path, key, username, now) {
+            Ok(b) if latest.as_ref().map_or(true, |l| b.issued_at > l.issued_at) => {
+                latest = Some(b)
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("Warning: Ignoring {}: {:#}", path.display(), e),
+        }
+    }
+    latest.ok_or_else(|| anyhow!("No valid cached policies for {}", username))
+}
+
+/// Whether `username` has cached policies that still verify and have not
+/// expired, in which case there is no need to fetch them again.
+pub fn is_fresh(dir: &Path, key: &VerifyingKey, username: &str, now: u64) -> bool {
+    cache_files(dir, username)
+        .map(|files| {
+            files
+                .iter()
+                .any(|path| read_verified(path, key, username, now).is_ok())
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    fn sign(key: &SigningKey, bundle: &PolicyBundle) -> SignedPolicies {
+        let payload = serde_json::to_vec(bundle).unwrap();
+        SignedPolicies {
+            payload: STANDARD.encode(&payload),
+            signature: STANDARD.encode(key.sign(&payload).to_bytes()),
+        }
+    }
+
+    fn bundle(username: &str, role_ids: &[i32], issued_at: u64) -> PolicyBundle {
+        PolicyBundle {
+            username: username.into(),
+            role_ids: role_ids.to_vec(),
+            issued_at,
+            expires_at: issued_at + 3600,
+            ruleset: serde_json::json!({ "default_policies": [], "app_policies": [] }),
+        }
+    }
+
+    #[test]
+    fn policy_cache_verify() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let public = parse_key(&STANDARD.encode(key.verifying_key().as_bytes())).unwrap();
+        let signed = sign(&key, &bundle("alice", &[1], 1000));
+
+        assert_eq!(signed.verify(&public, "alice", 1000).unwrap().role_ids, [1]);
+        assert!(signed.verify(&public, "bob", 1000).is_err());
+        assert!(signed.verify(&public, "alice", 4600).is_err());
+        assert!(signed.verify(&public, "alice", 1000 - CLOCK_SKEW - 1).is_err());
+
+        let other = SigningKey::from_bytes(&[8; 32]).verifying_key();
+        assert!(signed.verify(&other, "alice", 1000).is_err());
+
+        let mut tampered = bundle("alice", &[1], 1000);
+        tampered.expires_at += 3600;
+        let tampered = SignedPolicies {
+            payload: STANDARD.encode(serde_json::to_vec(&tampered).unwrap()),
+            signature: signed.signature.clone(),
+        };
+        assert!(tampered.verify(&public, "alice", 1000).is_err());
+    }
+
+    #[test]
+    fn policy_cache_load_latest() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let public = key.verifying_key();
+        let dir = std::env::temp_dir().join(format!("supernanny-cache-{}", std::process::id()));
+
+        for b in [bundle("alice", &[1], 1000), bundle("alice", &[1, 2], 2000), bundle("bob", &[3], 3000)] {
+            save(&dir, &sign(&key, &b), &b).unwrap();
+        }
+        assert_eq!(load_latest(&dir, &public, "alice", 2000).unwrap().role_ids, [1, 2]);
+        // A corrupted cache is skipped
+        fs::write(dir.join("alice.1-2.json"), "{}").unwrap();
+        assert_eq!(load_latest(&dir, &public, "alice", 2000).unwrap().role_ids, [1]);
+        assert!(load_latest(&dir, &public, "carol", 2000).is_err());
+
+        assert!(is_fresh(&dir, &public, "alice", 2000));
+        // Expired, or no longer verifying: fetched again
+        assert!(!is_fresh(&dir, &public, "alice", 4600));
+        assert!(!is_fresh(&dir, &SigningKey::from_bytes(&[8; 32]).verifying_key(), "alice", 2000));
+        assert!(!is_fresh(&dir, &public, "carol", 2000));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,10 +1,22 @@
 use anyhow::{Context, Result};
 use reqwest::blocking::{Client, ClientBuilder};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::env;
 use std::path::PathBuf;
 
+use crate::app_identity::{policy_name, AppIdentity, IdentityBinding};
+use crate::cgroup::ResourceLimits;
+use crate::env_policy::EnvPolicy;
+use crate::event_spool::SpooledEvent;
+use crate::fs_access::parse_fs_rules;
+use crate::isolation::Isolation;
+use crate::policy_cache::SignedPolicies;
+use crate::scope_access::parse_scopes;
+use crate::spec::RestrictionReport;
+use crate::syscall_profile::SyscallProfile;
+use crate::{AccessFs, BitFlags, Scope};
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct RuleSetRaw {
     #[serde(default)]
@@ -19,6 +31,18 @@ pub struct RuleSetRaw {
     pub allowed_ips: String,
     #[serde(default)]
     pub allowed_domains: String,
+    #[serde(default)]
+    pub fs_rules: String,
+    #[serde(default)]
+    pub scoped: String,
+    #[serde(default)]
+    pub seccomp: String,
+    #[serde(default)]
+    pub resources: String,
+    #[serde(default)]
+    pub isolation: String,
+    #[serde(default)]
+    pub environment: String,
 }
 
 #[derive(Debug)]
@@ -29,6 +53,12 @@ pub struct RuleSet {
     pub tcp_connect: HashSet<u16>,
     pub allowed_ips: HashSet<String>,
     pub allowed_domains: HashSet<String>,
+    pub fs_rules: BTreeMap<String, BitFlags<AccessFs>>,
+    pub scoped: BitFlags<Scope>,
+    pub seccomp: SyscallProfile,
+    pub resources: ResourceLimits,
+    pub isolation: Isolation,
+    pub environment: EnvPolicy,
 }
 
 #[derive(Debug, Deserialize)]
@@ -55,6 +85,26 @@ impl User {
     }
 }
 
+/// `GET /auth/ruleset/effective` response.
+#[derive(Debug, Deserialize)]
+struct EffectivePolicy {
+    policy: serde_json::Value,
+    #[serde(default)]
+    trace: Vec<ResolutionStep>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResolutionStep {
+    detail: String,
+}
+
+fn local_hostname() -> String {
+    hostname::get()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
 // Helper function to create a consistent HTTPS client
 fn create_https_client() -> Result<Client> {
     ClientBuilder::new()
@@ -64,55 +114,82 @@ fn create_https_client() -> Result<Client> {
 }
 
 impl RuleSet {
-    pub fn fetch_for_app(app: &str, token: &str) -> Result<Self> {
+    /// Fetches the policy the server resolved for the application identified
+    /// by `identity` on this host, and prints how it was resolved.
+    pub fn fetch_for_app(identity: &AppIdentity, token: &str) -> Result<Self> {
         let base_url = env::var("SERVER_URL")
             .unwrap_or_else(|_| "https://127.0.0.1:8443".to_string());
-        let url = format!("{}/auth/ruleset?app_name={}", base_url, app);
+        let url = format!("{}/auth/ruleset/effective", base_url);
 
         let client = create_https_client()?;
         let res = client
             .get(&url)
+            .query(&identity.query())
+            .query(&[("hostname", local_hostname())])
             .bearer_auth(token)
             .send()
-            .context("Failed to GET app ruleset")?;
+            .context("Failed to GET effective policy")?;
 
         if !res.status().is_success() {
-            return Err(anyhow::anyhow!("Error fetching app ruleset: {}", res.status()));
+            return Err(anyhow::anyhow!("Error fetching effective policy: {}", res.status()));
         }
 
-        let body = res.text().context("Failed to get response body")?;
-        let json_value: serde_json::Value = serde_json::from_str(&body)
-            .context("Failed to parse response as JSON")?;
-        
-        // Look for app-specific policy in app_policies array
-        if let Some(app_policies) = json_value.get("app_policies") {
-            if let Some(policies) = app_policies.as_array() {
-                for policy in policies {
-                    if let (Some(policy_app_name), Some(policy_data)) = (
-                        policy.get("app_name").and_then(|v| v.as_str()),
-                        policy.get("policy")
-                    ) {
-                        if policy_app_name == app {
-                            println!("Found specific policy for app: {}", app);
-                            return Self::parse_policy_object(policy_data);
-                        }
-                    }
-                }
+        let effective: EffectivePolicy = res.json().context("Failed to parse effective policy")?;
+        for step in &effective.trace {
+            println!("Policy: {}", step.detail);
+        }
+        Self::parse_policy_object(&effective.policy)
+    }
+
+    /// Picks the policy of the application identified by `identity` in an
+    /// `/auth/ruleset` response, as the server would: the policy bound to the
+    /// script it interprets, else to the binary, else the unknown policy, else
+    /// the default policy. The server resolved each of them already, so this
+    /// only serves offline runs from the signed cache.
+    pub fn for_app(json_value: &serde_json::Value, identity: &AppIdentity) -> Result<Self> {
+        let bindings: Vec<IdentityBinding> = json_value
+            .get("identities")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+        let policies: Vec<(&str, &serde_json::Value)> = json_value
+            .get("app_policies")
+            .and_then(|v| v.as_array())
+            .map(|policies| {
+                policies
+                    .iter()
+                    .filter_map(|p| Some((p.get("app_name")?.as_str()?, p.get("policy")?)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let names: Vec<&str> = policies.iter().map(|(name, _)| *name).collect();
+
+        let name = match policy_name(&bindings, &names, identity) {
+            Some(name) => Some(name),
+            None => {
+                println!("No policy bound to {}, unknown application", identity.name());
+                json_value
+                    .get("unknown_app_policy")
+                    .and_then(|v| v.as_str())
+                    .map(String::from)
             }
+        };
+        if let Some((name, policy)) = policies.iter().find(|(n, _)| Some(*n) == name.as_deref()) {
+            println!("Found specific policy for app: {}", name);
+            return Self::parse_policy_object(policy);
         }
-        
-        // If no app-specific policy found, use the first default policy
+
+        // Otherwise, the default policy
         if let Some(default_policies) = json_value.get("default_policies") {
             if let Some(policies) = default_policies.as_array() {
                 if !policies.is_empty() {
-                    println!("Using default policy for app: {}", app);
+                    println!("Using default policy for app: {}", identity.name());
                     return Self::parse_policy_object(&policies[0]);
                 }
             }
         }
         
         // If no policy found at all, return empty ruleset
-        println!("No policy found for app: {}, using empty policy", app);
+        println!("No policy found for app: {}, using empty policy", identity.name());
         Ok(Self::default())
     }
 
@@ -142,10 +219,60 @@ impl RuleSet {
         if let Some(domains) = policy_obj.get("allowed_domains").and_then(|v| v.as_str()) {
             ruleset.allowed_domains = split_list(domains);
         }
+
+        if let Some(rules) = policy_obj.get("fs_rules").and_then(|v| v.as_str()) {
+            ruleset.fs_rules = parse_fs_rules(rules).context("Invalid fs_rules in policy")?;
+        }
+
+        if let Some(scopes) = policy_obj.get("scoped").and_then(|v| v.as_str()) {
+            ruleset.scoped = parse_scopes(scopes).context("Invalid scoped in policy")?;
+        }
+
+        if let Some(profile) = policy_obj.get("seccomp").and_then(|v| v.as_str()) {
+            ruleset.seccomp =
+                SyscallProfile::parse(profile).context("Invalid seccomp in policy")?;
+        }
+
+        if let Some(limits) = policy_obj.get("resources").and_then(|v| v.as_str()) {
+            ruleset.resources =
+                ResourceLimits::parse(limits).context("Invalid resources in policy")?;
+        }
+
+        if let Some(options) = policy_obj.get("isolation").and_then(|v| v.as_str()) {
+            ruleset.isolation =
+                Isolation::parse(options).context("Invalid isolation in policy")?;
+        }
+
+        if let Some(list) = policy_obj.get("environment").and_then(|v| v.as_str()) {
+            ruleset.environment =
+                EnvPolicy::parse(list).context("Invalid environment in policy")?;
+        }
         
         Ok(ruleset)
     }
 
+    /// Fetches every policy of the user's roles on this host, signed for the
+    /// offline cache.
+    pub fn fetch_signed(token: &str) -> Result<SignedPolicies> {
+        let base_url = env::var("SERVER_URL")
+            .unwrap_or_else(|_| "https://127.0.0.1:8443".to_string());
+        let url = format!("{}/auth/ruleset/signed", base_url);
+
+        let client = create_https_client()?;
+        let res = client
+            .get(&url)
+            .query(&[("hostname", local_hostname())])
+            .bearer_auth(token)
+            .send()
+            .context("Failed to GET signed ruleset")?;
+
+        if !res.status().is_success() {
+            return Err(anyhow::anyhow!("Error fetching signed ruleset: {}", res.status()));
+        }
+
+        res.json().context("Failed to parse signed ruleset")
+    }
+
     pub fn upload(app: &str, ruleset_raw: &RuleSetRaw, token: &str) -> Result<()> {
         let base_url = env::var("SERVER_URL")
             .unwrap_or_else(|_| "https://127.0.0.1:8443".to_string());
@@ -232,10 +359,36 @@ impl Default for RuleSet {
             tcp_connect: HashSet::new(),
             allowed_ips: HashSet::new(),
             allowed_domains: HashSet::new(),
+            fs_rules: BTreeMap::new(),
+            scoped: BitFlags::EMPTY,
+            seccomp: SyscallProfile::default(),
+            resources: ResourceLimits::default(),
+            isolation: Isolation::default(),
+            environment: EnvPolicy::default(),
         }
     }
 }
 
+/// Whether `err` comes from the server being unreachable, rather than from
+/// the server refusing the request.
+pub fn is_unreachable(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| {
+        e.downcast_ref::<reqwest::Error>()
+            .map_or(false, |e| e.is_connect() || e.is_timeout())
+    })
+}
+
+/// Whether the request may succeed if sent again: the server could not be
+/// reached, or was overloaded or failing.
+pub fn is_transient(err: &anyhow::Error) -> bool {
+    is_unreachable(err)
+        || err.chain().any(|e| {
+            e.downcast_ref::<reqwest::Error>()
+                .and_then(|e| e.status())
+                .map_or(false, |s| s.is_server_error() || s.as_u16() == 429)
+        })
+}
+
 // Helpers
 fn split_paths(s: &str) -> HashSet<PathBuf> {
     s.split(':').filter(|s| !s.is_empty()).map(PathBuf::from).collect()
@@ -249,42 +402,77 @@ fn split_list(s: &str) -> HashSet<String> {
     s.split(':').filter(|s| !s.is_empty()).map(str::to_string).collect()
 }
 
-pub fn log_denial_event(
+/// Delivers spooled events. Fails on any non-success status, so that the
+/// events stay in the spool. Events the server rejects as invalid are
+/// reported and not sent again.
+pub fn send_event_batch(events: &[SpooledEvent], token: &str) -> Result<()> {
+    let base_url = env::var("SERVER_URL")
+        .unwrap_or_else(|_| "https://127.0.0.1:8443".to_string());
+    let url = format!("{}/events/batch", base_url);
+
+    let client = create_https_client()?;
+    let response: EventBatchResponse = client
+        .post(&url)
+        .bearer_auth(token)
+        .json(&serde_json::json!({ "events": events }))
+        .send()
+        .and_then(|res| res.error_for_status())
+        .context("Failed to send sandbox events")?
+        .json()
+        .context("Invalid response to sandbox events")?;
+
+    for rejected in response.results.iter().filter(|r| r.status == "rejected") {
+        eprintln!(
+            "Warning: Sandbox event {} rejected: {}",
+            rejected.event_id,
+            rejected.error.as_deref().unwrap_or("no reason given")
+        );
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct EventBatchResponse {
+    results: Vec<EventStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EventStatus {
+    event_id: String,
+    status: String,
+    error: Option<String>,
+}
+
+/// Sends what Landlock actually enforced for a run of `app_name`.
+pub fn log_enforcement_report(
     app_name: &str,
-    denied_path: &str,
-    operation: &str,
+    report: &RestrictionReport,
     token: &str,
 ) -> Result<()> {
     let base_url = env::var("SERVER_URL")
         .unwrap_or_else(|_| "https://127.0.0.1:8443".to_string());
-    let url = format!("{}/events/log", base_url);
-
-    let hostname = hostname::get()
-        .unwrap_or_default()
-        .to_string_lossy()
-        .to_string();
+    let url = format!("{}/events/enforcement", base_url);
 
     let client = create_https_client()?;
-    let event = serde_json::json!({
-        "hostname": hostname,
+    let body = serde_json::json!({
+        "hostname": local_hostname(),
         "app_name": app_name,
-        "denied_path": denied_path,
-        "operation": operation,
-        "result": "denied",
-        "remote_ip": "127.0.0.1",
-        "domain": "localhost"             
+        "abi": report.abi as i32,
+        "status": report.ruleset,
+        "skipped_rules": report.skipped().count(),
+        "report": report,
     });
 
     let res = client
         .post(&url)
         .bearer_auth(token)
-        .json(&event)
+        .json(&body)
         .send()
-        .context("Failed to send denial event")?;
+        .context("Failed to send enforcement report")?;
 
     if !res.status().is_success() {
-        eprintln!("Warning: Failed to log denial event: {}", res.status());
+        eprintln!("Warning: Failed to log enforcement report: {}", res.status());
     }
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,302 @@
+//! Non-interactive policy learning (`--learn`).
+//!
+//! Turns the denials observed during a run into a small set of rules: files
+//! that need to be created or removed grant rights on their parent directory,
+//! per-application directories under `~/.cache`, `~/.config`, ... are granted
+//! as a whole, and several denials in the same directory collapse into one
+//! directory rule. The result is written as a [`PolicyProposal`], which has the
+//! shape of the server's `PolicyChangeRequest`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::denial_collector::LandlockDenial;
+
+/// Number of denied entries in one directory from which the directory itself is proposed.
+pub const COLLAPSE_THRESHOLD: usize = 3;
+
+/// Per-user directories whose direct children belong to a single application.
+const APP_DATA_DIRS: &[&str] = &[".cache", ".config", ".local/share", ".local/state"];
+
+/// Directories never proposed as a whole, however many denials they contain.
+const TOO_BROAD: &[&str] = &[
+    "/", "/bin", "/boot", "/dev", "/etc", "/home", "/lib", "/lib64", "/opt", "/proc", "/root",
+    "/run", "/sbin", "/srv", "/sys", "/usr", "/var",
+];
+
+/// Landlock rights that modify the filesystem.
+const WRITE_ACCESS: &[&str] = &["write_file", "truncate", "ioctl_dev"];
+
+/// Landlock rights checked on the parent directory of the denied entry.
+const PARENT_ACCESS: &[&str] = &[
+    "remove_dir",
+    "remove_file",
+    "make_char",
+    "make_dir",
+    "make_reg",
+    "make_sock",
+    "make_fifo",
+    "make_block",
+    "make_sym",
+    "refer",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PathMode {
+    ReadOnly,
+    ReadWrite,
+}
+
+/// Rules learned from one run, before merging with the current policy.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LearnedRules {
+    pub ro_paths: BTreeSet<PathBuf>,
+    pub rw_paths: BTreeSet<PathBuf>,
+    pub tcp_bind: BTreeSet<u16>,
+    pub tcp_connect: BTreeSet<u16>,
+    pub allowed_ips: BTreeSet<String>,
+    pub allowed_domains: BTreeSet<String>,
+}
+
+impl LearnedRules {
+    pub fn is_empty(&self) -> bool {
+        self.ro_paths.is_empty()
+            && self.rw_paths.is_empty()
+            && self.tcp_bind.is_empty()
+            && self.tcp_connect.is_empty()
+            && self.allowed_ips.is_empty()
+            && self.allowed_domains.is_empty()
+    }
+}
+
+/// Policy proposal, serialized with the same fields as `PolicyChangeRequest`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyProposal {
+    pub app_name: String,
+    /// Unset, the server picks the requester's role with a policy for the app
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role_id: Option<i32>,
+    pub default_ro: String,
+    pub default_rw: String,
+    pub tcp_bind: String,
+    pub tcp_connect: String,
+    pub allowed_ips: String,
+    pub allowed_domains: String,
+    #[serde(default)]
+    pub fs_rules: String,
+    #[serde(default)]
+    pub scoped: String,
+    #[serde(default)]
+    pub seccomp: String,
+    #[serde(default)]
+    pub resources: String,
+    #[serde(default)]
+    pub isolation: String,
+    #[serde(default)]
+    pub environment: String,
+    pub allowed_ro_paths: Vec<String>,
+    pub allowed_rw_paths: Vec<String>,
+    pub change_justification: String,
+}
+
+fn is_write(access: &[String]) -> bool {
+    access
+        .iter()
+        .any(|a| WRITE_ACCESS.contains(&a.as_str()) || PARENT_ACCESS.contains(&a.as_str()))
+}
+
+fn needs_parent(access: &[String]) -> bool {
+    access.iter().any(|a| PARENT_ACCESS.contains(&a.as_str()))
+}
+
+/// `~/.cache/foo/bar/baz` → `~/.cache/foo`.
+fn app_data_root(path: &Path, home: Option<&Path>) -> Option<PathBuf> {
+    let home = home?;
+    APP_DATA_DIRS.iter().find_map(|dir| {
+        let base = home.join(dir);
+        let rest = path.strip_prefix(&base).ok()?;
+        let first = rest.components().next()?;
+        Some(base.join(first))
+    })
+}
+
+fn is_too_broad(dir: &Path, home: Option<&Path>) -> bool {
+    TOO_BROAD.iter().any(|b| Path::new(b) == dir)
+        || home == Some(dir)
+        || home.map_or(false, |h| APP_DATA_DIRS.iter().any(|d| h.join(d) == dir))
+}
+
+fn insert_mode(rules: &mut BTreeMap<PathBuf, PathMode>, path: PathBuf, mode: PathMode) {
+    let entry = rules.entry(path).or_insert(mode);
+    if mode > *entry {
+        *entry = mode;
+    }
+}
+
+/// Whether `path` is already granted with at least `mode` by another rule.
+fn covered(rules: &BTreeMap<PathBuf, PathMode>, path: &Path, mode: PathMode) -> bool {
+    path.ancestors()
+        .skip(1)
+        .any(|a| rules.get(a).map_or(false, |m| *m >= mode))
+}
+
+/// Builds rules from the denials of a run.
+///
+/// `reported` holds the `ip:`/`domain:` entries raised inside the sandbox.
+pub fn learn(
+    denials: &[LandlockDenial],
+    reported: &HashSet<String>,
+    home: Option<&Path>,
+) -> LearnedRules {
+    let mut learned = LearnedRules::default();
+
+    // 1. One candidate per denial
+    let mut candidates: BTreeMap<PathBuf, PathMode> = BTreeMap::new();
+    for denial in denials {
+        match (denial.syscall.as_str(), denial.port, &denial.path) {
+            ("bind", Some(port), _) => {
+                learned.tcp_bind.insert(port);
+            }
+            ("connect", Some(port), _) => {
+                learned.tcp_connect.insert(port);
+            }
+            (_, _, Some(path)) => {
+                let path = PathBuf::from(path);
+                let mode = if is_write(&denial.access) {
+                    PathMode::ReadWrite
+                } else {
+                    PathMode::ReadOnly
+                };
+                let target = match path.parent() {
+                    Some(parent) if needs_parent(&denial.access) => parent.to_path_buf(),
+                    _ => path.clone(),
+                };
+                let target = app_data_root(&target, home).unwrap_or(target);
+                insert_mode(&mut candidates, target, mode);
+            }
+            _ => {}
+        }
+    }
+
+    // 2. Collapse directories holding many denied entries
+    let mut by_parent: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+    for path in candidates.keys() {
+        if let Some(parent) = path.parent() {
+            by_parent
+                .entry(parent.to_path_buf())
+                .or_default()
+                .push(path.clone());
+        }
+    }
+    for (parent, children) in by_parent {
+        if children.len() < COLLAPSE_THRESHOLD || is_too_broad(&parent, home) {
+            continue;
+        }
+        let mode = children
+            .iter()
+            .filter_map(|c| candidates.get(c).copied())
+            .max()
+            .unwrap_or(PathMode::ReadOnly);
+        insert_mode(&mut candidates, parent, mode);
+    }
+
+    // 3. Drop rules made redundant by a broader one
+    for (path, mode) in &candidates {
+        if covered(&candidates, path, *mode) {
+            continue;
+        }
+        match mode {
+            PathMode::ReadOnly => learned.ro_paths.insert(path.clone()),
+            PathMode::ReadWrite => learned.rw_paths.insert(path.clone()),
+        };
+    }
+
+    for entry in reported {
+        if let Some(ip) = entry.strip_prefix("ip:") {
+            learned.allowed_ips.insert(ip.to_string());
+        } else if let Some(domain) = entry.strip_prefix("domain:") {
+            learned.allowed_domains.insert(domain.to_string());
+        }
+    }
+
+    learned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn denial(syscall: &str, path: &str, access: &[&str]) -> LandlockDenial {
+        LandlockDenial {
+            pid: 1,
+            comm: "app".into(),
+            syscall: syscall.into(),
+            path: Some(path.into()),
+            port: None,
+            access: access.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    fn paths(set: &BTreeSet<PathBuf>) -> Vec<&str> {
+        set.iter().map(|p| p.to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn app_cache_collapses_to_one_rw_rule() {
+        let home = Path::new("/home/alice");
+        let denials = vec![
+            denial("open", "/home/alice/.cache/foo/index", &["read_file"]),
+            denial("open", "/home/alice/.cache/foo/a/b/blob", &["write_file"]),
+            denial("mkdir", "/home/alice/.cache/foo/tmp", &["make_dir"]),
+        ];
+        let learned = learn(&denials, &HashSet::new(), Some(home));
+        assert_eq!(paths(&learned.rw_paths), vec!["/home/alice/.cache/foo"]);
+        assert!(learned.ro_paths.is_empty());
+    }
+
+    #[test]
+    fn many_files_collapse_to_directory() {
+        let denials = vec![
+            denial("open", "/srv/data/a.csv", &["read_file"]),
+            denial("open", "/srv/data/b.csv", &["read_file"]),
+            denial("open", "/srv/data/c.csv", &["read_file"]),
+            denial("open", "/etc/app.conf", &["read_file"]),
+            denial("open", "/etc/app.d", &["read_dir"]),
+            denial("open", "/etc/hosts", &["read_file"]),
+        ];
+        let learned = learn(&denials, &HashSet::new(), None);
+        // /etc is too broad to be proposed as a whole
+        assert_eq!(
+            paths(&learned.ro_paths),
+            vec!["/etc/app.conf", "/etc/app.d", "/etc/hosts", "/srv/data"]
+        );
+    }
+
+    #[test]
+    fn creation_grants_parent_directory() {
+        let denials = vec![
+            denial("open", "/var/lib/app/state.db", &["read_file"]),
+            denial("unlink", "/var/lib/app/state.db-journal", &["remove_file"]),
+        ];
+        let learned = learn(&denials, &HashSet::new(), None);
+        assert_eq!(paths(&learned.rw_paths), vec!["/var/lib/app"]);
+        assert!(learned.ro_paths.is_empty());
+    }
+
+    #[test]
+    fn network_entries() {
+        let mut connect = denial("connect", "", &["connect_tcp"]);
+        connect.path = None;
+        connect.port = Some(443);
+        let reported: HashSet<String> = ["ip:10.0.0.1", "domain:api.github.com"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let learned = learn(&[connect], &reported, None);
+        assert_eq!(learned.tcp_connect.iter().copied().collect::<Vec<_>>(), vec![443]);
+        assert!(learned.allowed_ips.contains("10.0.0.1"));
+        assert!(learned.allowed_domains.contains("api.github.com"));
+        assert!(learned.ro_paths.is_empty());
+    }
+}
@@ -1,10 +1,11 @@
 use crate::compat::private::OptionCompatLevelMut;
 use crate::{
-    uapi, Access, AccessFs, AccessNet, AddRuleError, AddRulesError, BitFlags, CompatLevel,
-    CompatState, Compatibility, Compatible, CreateRulesetError, RestrictSelfError, RulesetError,
-    TryCompat,
+    uapi, AccessFs, AccessNet, AddRuleError, AddRulesError, BitFlags, CompatLevel, CompatState,
+    Compatibility, Compatible, CreateRulesetError, HandledAccess, PrivateHandledAccess,
+    RestrictSelfError, RulesetError, Scope, ScopeError, TryCompat,
 };
 use libc::close;
+use serde::{Deserialize, Serialize};
 use std::io::Error;
 use std::mem::size_of_val;
 use std::os::unix::io::RawFd;
@@ -15,7 +16,7 @@ use crate::*;
 // Public interface without methods and which is impossible to implement outside this crate.
 pub trait Rule<T>: PrivateRule<T>
 where
-    T: Access,
+    T: HandledAccess,
 {
 }
 
@@ -23,7 +24,7 @@ where
 pub trait PrivateRule<T>
 where
     Self: TryCompat<T> + Compatible,
-    T: Access,
+    T: HandledAccess,
 {
     const TYPE_ID: uapi::landlock_rule_type;
 
@@ -37,7 +38,8 @@ where
 }
 
 /// Enforcement status of a ruleset.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RulesetStatus {
     /// All requested restrictions are enforced.
     FullyEnforced,
@@ -103,7 +105,7 @@ fn support_no_new_privs() -> bool {
 /// Simple helper handling only Landlock-related errors.
 ///
 /// ```
-/// use landlock::{
+/// use supernanny_sandboxer::{
 ///     Access, AccessFs, PathBeneath, PathFd, RestrictionStatus, Ruleset, RulesetAttr,
 ///     RulesetCreatedAttr, RulesetError, ABI,
 /// };
@@ -135,7 +137,7 @@ fn support_no_new_privs() -> bool {
 /// and [`PathFdError`](crate::PathFdError).
 ///
 /// ```
-/// use landlock::{
+/// use supernanny_sandboxer::{
 ///     Access, AccessFs, PathBeneath, PathFd, PathFdError, RestrictionStatus, Ruleset,
 ///     RulesetAttr, RulesetCreatedAttr, RulesetError, ABI,
 /// };
@@ -173,8 +175,10 @@ fn support_no_new_privs() -> bool {
 pub struct Ruleset {
     pub(crate) requested_handled_fs: BitFlags<AccessFs>,
     pub(crate) requested_handled_net: BitFlags<AccessNet>,
+    pub(crate) requested_scoped: BitFlags<Scope>,
     pub(crate) actual_handled_fs: BitFlags<AccessFs>,
     pub(crate) actual_handled_net: BitFlags<AccessNet>,
+    pub(crate) actual_scoped: BitFlags<Scope>,
     pub(crate) compat: Compatibility,
 }
 
@@ -184,8 +188,10 @@ impl From<Compatibility> for Ruleset {
             // Non-working default handled FS accesses to force users to set them explicitely.
             requested_handled_fs: Default::default(),
             requested_handled_net: Default::default(),
+            requested_scoped: Default::default(),
             actual_handled_fs: Default::default(),
             actual_handled_net: Default::default(),
+            actual_scoped: Default::default(),
             compat,
         }
     }
@@ -247,7 +253,7 @@ impl Ruleset {
             match self.compat.state {
                 CompatState::Init => {
                     // Checks that there is at least one requested access (e.g.
-                    // requested_handled_fs): one call to handle_access().
+                    // requested_handled_fs): one call to handle_access() or scope().
                     Err(CreateRulesetError::MissingHandledAccess)
                 }
                 CompatState::No | CompatState::Dummy => {
@@ -256,6 +262,7 @@ impl Ruleset {
                     assert!(
                         !self.requested_handled_fs.is_empty()
                             || !self.requested_handled_net.is_empty()
+                            || !self.requested_scoped.is_empty()
                     );
 
                     // CompatState::No should be handled as CompatState::Dummy because it is not
@@ -272,12 +279,15 @@ impl Ruleset {
                     // There is at least one actual handled access.
                     #[cfg(test)]
                     assert!(
-                        !self.actual_handled_fs.is_empty() || !self.actual_handled_net.is_empty()
+                        !self.actual_handled_fs.is_empty()
+                            || !self.actual_handled_net.is_empty()
+                            || !self.actual_scoped.is_empty()
                     );
 
                     let attr = uapi::landlock_ruleset_attr {
                         handled_access_fs: self.actual_handled_fs.bits(),
                         handled_access_net: self.actual_handled_net.bits(),
+                        scoped: self.actual_scoped.bits(),
                     };
                     match unsafe { uapi::landlock_create_ruleset(&attr, size_of_val(&attr), 0) } {
                         fd if fd >= 0 => Ok(RulesetCreated::new(self, fd)),
@@ -339,11 +349,37 @@ pub trait RulesetAttr: Sized + AsMut<Ruleset> + Compatible {
     fn handle_access<T, U>(mut self, access: T) -> Result<Self, RulesetError>
     where
         T: Into<BitFlags<U>>,
-        U: Access,
+        U: HandledAccess + PrivateHandledAccess,
     {
         U::ruleset_handle_access(self.as_mut(), access.into())?;
         Ok(self)
     }
+
+    /// Attempts to add a set of scopes that will be supported by this ruleset.
+    /// Consecutive calls to `scope()` will be interpreted as logical ORs
+    /// with the previous scopes.
+    ///
+    /// On error, returns a wrapped [`ScopeError`](crate::ScopeError).
+    /// E.g., `RulesetError::Scope(ScopeError)`
+    fn scope<T>(mut self, scope: T) -> Result<Self, RulesetError>
+    where
+        T: Into<BitFlags<Scope>>,
+    {
+        let scope = scope.into();
+        let ruleset = self.as_mut();
+        ruleset.requested_scoped |= scope;
+        if let Some(a) = scope
+            .try_compat(
+                ruleset.compat.abi(),
+                ruleset.compat.level,
+                &mut ruleset.compat.state,
+            )
+            .map_err(ScopeError::Compat)?
+        {
+            ruleset.actual_scoped |= a;
+        }
+        Ok(self)
+    }
 }
 
 impl RulesetAttr for Ruleset {}
@@ -428,6 +464,82 @@ fn ruleset_created_handle_access_net_tcp() {
     ));
 }
 
+#[test]
+fn ruleset_created_scope() {
+    let scopes = make_bitflags!(Scope::{AbstractUnixSocket | Signal});
+
+    // Tests Ruleset::scope() with ABI that doesn't support scopes.
+    let ruleset = Ruleset::from(ABI::V5).scope(scopes).unwrap();
+    assert_eq!(ruleset.requested_scoped, scopes);
+    assert_eq!(ruleset.actual_scoped, BitFlags::<Scope>::EMPTY);
+
+    // Tests Ruleset::scope() with ABI that supports scopes.
+    let ruleset = Ruleset::from(ABI::V6).scope(scopes).unwrap();
+    assert_eq!(ruleset.requested_scoped, scopes);
+    assert_eq!(ruleset.actual_scoped, scopes);
+
+    // Tests composition (binary OR) of scopes.
+    let ruleset = Ruleset::from(ABI::V6)
+        .scope(Scope::AbstractUnixSocket)
+        .unwrap()
+        .scope(Scope::Signal)
+        .unwrap()
+        .scope(Scope::AbstractUnixSocket)
+        .unwrap();
+    assert_eq!(ruleset.requested_scoped, scopes);
+    assert_eq!(ruleset.actual_scoped, scopes);
+
+    // Tests that only the required scopes are reported as incompatible:
+    // scope should not contain Scope::AbstractUnixSocket.
+    assert!(matches!(Ruleset::from(ABI::Unsupported)
+        .scope(Scope::AbstractUnixSocket)
+        .unwrap()
+        .set_compatibility(CompatLevel::HardRequirement)
+        .scope(Scope::Signal)
+        .unwrap_err(),
+        RulesetError::Scope(ScopeError::Compat(
+            CompatError::Access(AccessError::Incompatible { access })
+        )) if access == Scope::Signal
+    ));
+}
+
+#[test]
+fn ruleset_created_fs_net_scope() {
+    let access_fs = make_bitflags!(AccessFs::{Execute | ReadDir});
+    let access_net = make_bitflags!(AccessNet::{BindTcp | ConnectTcp});
+    let scopes = make_bitflags!(Scope::{AbstractUnixSocket | Signal});
+
+    // Tests composition (binary OR) of handled accesses.
+    let ruleset = Ruleset::from(ABI::V5)
+        .handle_access(access_fs)
+        .unwrap()
+        .scope(scopes)
+        .unwrap()
+        .handle_access(access_net)
+        .unwrap();
+    assert_eq!(ruleset.requested_handled_fs, access_fs);
+    assert_eq!(ruleset.actual_handled_fs, access_fs);
+    assert_eq!(ruleset.requested_handled_net, access_net);
+    assert_eq!(ruleset.actual_handled_net, access_net);
+    assert_eq!(ruleset.requested_scoped, scopes);
+    assert_eq!(ruleset.actual_scoped, BitFlags::<Scope>::EMPTY);
+
+    // Tests composition (binary OR) of handled accesses and scopes.
+    let ruleset = Ruleset::from(ABI::V6)
+        .handle_access(access_fs)
+        .unwrap()
+        .scope(scopes)
+        .unwrap()
+        .handle_access(access_net)
+        .unwrap();
+    assert_eq!(ruleset.requested_handled_fs, access_fs);
+    assert_eq!(ruleset.actual_handled_fs, access_fs);
+    assert_eq!(ruleset.requested_handled_net, access_net);
+    assert_eq!(ruleset.actual_handled_net, access_net);
+    assert_eq!(ruleset.requested_scoped, scopes);
+    assert_eq!(ruleset.actual_scoped, scopes);
+}
+
 impl OptionCompatLevelMut for RulesetCreated {
     fn as_option_compat_level_mut(&mut self) -> &mut Option<CompatLevel> {
         &mut self.compat.level
@@ -451,7 +563,7 @@ pub trait RulesetCreatedAttr: Sized + AsMut<RulesetCreated> + Compatible {
     fn add_rule<T, U>(mut self, rule: T) -> Result<Self, RulesetError>
     where
         T: Rule<U>,
-        U: Access,
+        U: HandledAccess + PrivateHandledAccess,
     {
         let body = || -> Result<Self, AddRulesError> {
             let self_ref = self.as_mut();
@@ -492,7 +604,7 @@ pub trait RulesetCreatedAttr: Sized + AsMut<RulesetCreated> + Compatible {
     /// Create a custom iterator to read paths from environment variable.
     ///
     /// ```
-    /// use landlock::{
+    /// use supernanny_sandboxer::{
     ///     Access, AccessFs, BitFlags, PathBeneath, PathFd, PathFdError, RestrictionStatus, Ruleset,
     ///     RulesetAttr, RulesetCreatedAttr, RulesetError, ABI,
     /// };
@@ -561,7 +673,7 @@ pub trait RulesetCreatedAttr: Sized + AsMut<RulesetCreated> + Compatible {
     where
         I: IntoIterator<Item = Result<T, E>>,
         T: Rule<U>,
-        U: Access,
+        U: HandledAccess + PrivateHandledAccess,
         E: From<RulesetError>,
     {
         for rule in rules {
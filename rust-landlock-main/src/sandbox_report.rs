@@ -0,0 +1,75 @@
+//! Side channel from the sandbox process back to the sandboxer.
+//!
+//! Denials that the kernel collector cannot see (e.g. the IP egress filter,
+//! which runs in the sandbox process itself) are written to a pipe the sandbox
+//! process inherits when forked, one `process_denials` entry per line. The
+//! enforcement report of the sandbox goes through the same pipe as a single
+//! [`ENFORCEMENT_PREFIX`] line.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::io::FromRawFd;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Prefix of the line carrying the JSON enforcement report.
+pub const ENFORCEMENT_PREFIX: &str = "enforcement:";
+
+/// Creates a pipe, read end first. Both ends are close-on-exec: the sandbox
+/// process gets them through `fork()`, the application it runs never does.
+pub fn pipe() -> std::io::Result<(File, File)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) })
+}
+
+/// Reads every reported entry until all writers are closed.
+pub fn collect(read: File) -> JoinHandle<HashSet<String>> {
+    thread::spawn(move || {
+        BufReader::new(read)
+            .lines()
+            .map_while(Result::ok)
+            .filter(|l| !l.is_empty())
+            .collect()
+    })
+}
+
+/// Removes the enforcement report from the collected entries, if any.
+pub fn take_enforcement(entries: &mut HashSet<String>) -> Option<String> {
+    let line = entries
+        .iter()
+        .find(|e| e.starts_with(ENFORCEMENT_PREFIX))?
+        .clone();
+    entries.remove(&line);
+    Some(line[ENFORCEMENT_PREFIX.len()..].to_string())
+}
+
+/// Sandbox-side writer, shared between the sandbox supervisor threads.
+#[derive(Clone)]
+pub struct Reporter {
+    inner: Arc<Mutex<(File, HashSet<String>)>>,
+}
+
+impl Reporter {
+    /// Writes to the write end of the report pipe.
+    pub fn new(file: File) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new((file, HashSet::new()))),
+        }
+    }
+
+    /// Reports an entry once; later duplicates are dropped.
+    pub fn report(&self, entry: &str) {
+        let mut guard = match self.inner.lock() {
+            Ok(g) => g,
+            Err(_) => return,
+        };
+        let (file, seen) = &mut *guard;
+        if seen.insert(entry.to_string()) {
+            let _ = writeln!(file, "{}", entry);
+        }
+    }
+}
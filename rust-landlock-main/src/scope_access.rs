@@ -0,0 +1,66 @@
+//! IPC scoping of the sandbox.
+//!
+//! A policy `scoped` list restricts the sandboxed processes from reaching
+//! outside the sandbox through IPCs Landlock can scope (ABI V6, Linux 6.12):
+//!
+//! ```text
+//! abstract_unix_socket:signal
+//! ```
+//!
+//! Older kernels silently ignore it (best-effort).
+
+use anyhow::{anyhow, Result};
+
+use crate::{BitFlags, Scope};
+
+/// Scope names, in UAPI bit order.
+pub(crate) const SCOPE_NAMES: [(&str, Scope); 2] = [
+    ("abstract_unix_socket", Scope::AbstractUnixSocket),
+    ("signal", Scope::Signal),
+];
+
+/// Parses a `scoped` list.
+pub fn parse_scopes(list: &str) -> Result<BitFlags<Scope>> {
+    let mut scoped = BitFlags::EMPTY;
+    for name in list.split(':').map(str::trim).filter(|n| !n.is_empty()) {
+        scoped |= SCOPE_NAMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, s)| *s)
+            .ok_or_else(|| anyhow!("Unknown scope: {}", name))?;
+    }
+    Ok(scoped)
+}
+
+/// Inverse of [`parse_scopes`].
+pub fn join_scopes(scoped: BitFlags<Scope>) -> String {
+    SCOPE_NAMES
+        .iter()
+        .filter(|(_, s)| scoped.contains(*s))
+        .map(|(n, _)| *n)
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Access, ABI};
+
+    #[test]
+    fn scopes_round_trip() {
+        let scoped = parse_scopes("signal:abstract_unix_socket").unwrap();
+        assert_eq!(scoped, Scope::from_all(ABI::V6));
+        assert_eq!(join_scopes(scoped), "abstract_unix_socket:signal");
+        assert_eq!(parse_scopes("signal").unwrap(), Scope::Signal);
+        assert!(parse_scopes("").unwrap().is_empty());
+        assert!(parse_scopes("ptrace").is_err());
+    }
+
+    #[test]
+    fn names_follow_uapi_order() {
+        for (i, (_, scope)) in SCOPE_NAMES.iter().enumerate() {
+            assert_eq!(BitFlags::from(*scope).bits(), 1 << i);
+        }
+    }
+}
@@ -0,0 +1,420 @@
+//! Declarative ruleset definition.
+//!
+//! A [`RulesetSpec`] describes a whole Landlock configuration (handled
+//! accesses, scopes, path and port rules, compatibility level) as plain data.
+//! It can be loaded from any serde format, e.g. JSON:
+//!
+//! ```json
+//! {
+//!   "compatibility": "best_effort",
+//!   "handled_fs": ["rw"],
+//!   "handled_net": ["bind_tcp", "connect_tcp"],
+//!   "scoped": ["abstract_unix_socket", "signal"],
+//!   "path_beneath": [
+//!     { "path": "/usr", "access": ["ro"] },
+//!     { "path": "/tmp", "access": ["rw"] }
+//!   ],
+//!   "net_port": [{ "port": 443, "access": ["connect_tcp"] }]
+//! }
+//! ```
+//!
+//! Access rights use the names of [`fs_access`](crate::fs_access) (`ro` and
+//! `rw` included), `bind_tcp`/`connect_tcp` and the
+//! [`scope_access`](crate::scope_access) names.
+//! [`RulesetSpec::restrict_self()`] enforces it and returns a
+//! [`RestrictionReport`] telling what the running kernel actually applied.
+
+use crate::fs_access::{self, ACCESS_FS_NAMES};
+use crate::scope_access::SCOPE_NAMES;
+use crate::{
+    Access, AccessFs, AccessNet, BitFlags, CompatLevel, Compatible, NetPort, PathBeneath, PathFd,
+    PathFdError, Ruleset, RulesetAttr, RulesetCreatedAttr, RulesetError, RulesetStatus, Scope, ABI,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::path::PathBuf;
+use thiserror::Error;
+
+const ACCESS_NET_NAMES: [(&str, AccessNet); 2] = [
+    ("bind_tcp", AccessNet::BindTcp),
+    ("connect_tcp", AccessNet::ConnectTcp),
+];
+
+/// Access types with user-facing names.
+pub trait AccessNames: Access {
+    fn names() -> &'static [(&'static str, Self)];
+
+    fn from_name(name: &str) -> Option<BitFlags<Self>> {
+        Self::names()
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, a)| BitFlags::from(*a))
+    }
+}
+
+impl AccessNames for AccessFs {
+    fn names() -> &'static [(&'static str, Self)] {
+        &ACCESS_FS_NAMES
+    }
+
+    // Also accepts the `ro` and `rw` shorthands.
+    fn from_name(name: &str) -> Option<BitFlags<Self>> {
+        fs_access::parse_access(name).ok()
+    }
+}
+
+impl AccessNames for AccessNet {
+    fn names() -> &'static [(&'static str, Self)] {
+        &ACCESS_NET_NAMES
+    }
+}
+
+impl AccessNames for Scope {
+    fn names() -> &'static [(&'static str, Self)] {
+        &SCOPE_NAMES
+    }
+}
+
+/// Serializes a set of rights as a list of names, in UAPI order.
+mod access_names {
+    use super::*;
+    use serde::de::Error;
+
+    pub fn serialize<T, S>(access: &BitFlags<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: AccessNames,
+        S: Serializer,
+    {
+        serializer.collect_seq(
+            T::names()
+                .iter()
+                .filter(|(_, a)| access.contains(*a))
+                .map(|(n, _)| *n),
+        )
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<BitFlags<T>, D::Error>
+    where
+        T: AccessNames,
+        D: Deserializer<'de>,
+    {
+        let mut access = BitFlags::EMPTY;
+        for name in Vec::<String>::deserialize(deserializer)? {
+            access |= T::from_name(&name)
+                .ok_or_else(|| D::Error::custom(format!("unknown access right: {}", name)))?;
+        }
+        Ok(access)
+    }
+}
+
+/// Complete Landlock configuration.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RulesetSpec {
+    pub compatibility: CompatLevel,
+    #[serde(with = "access_names")]
+    pub handled_fs: BitFlags<AccessFs>,
+    #[serde(with = "access_names")]
+    pub handled_net: BitFlags<AccessNet>,
+    #[serde(with = "access_names")]
+    pub scoped: BitFlags<Scope>,
+    pub path_beneath: Vec<PathBeneathSpec>,
+    pub net_port: Vec<NetPortSpec>,
+    pub no_new_privs: bool,
+}
+
+impl Default for RulesetSpec {
+    fn default() -> Self {
+        RulesetSpec {
+            compatibility: CompatLevel::BestEffort,
+            handled_fs: BitFlags::EMPTY,
+            handled_net: BitFlags::EMPTY,
+            scoped: BitFlags::EMPTY,
+            path_beneath: Vec::new(),
+            net_port: Vec::new(),
+            no_new_privs: true,
+        }
+    }
+}
+
+/// Rights granted beneath a path.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PathBeneathSpec {
+    pub path: PathBuf,
+    #[serde(with = "access_names")]
+    pub access: BitFlags<AccessFs>,
+}
+
+/// Rights granted on a TCP port.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NetPortSpec {
+    pub port: u16,
+    #[serde(with = "access_names")]
+    pub access: BitFlags<AccessNet>,
+}
+
+/// Identifies errors when enforcing a [`RulesetSpec`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum SpecError {
+    #[error(transparent)]
+    Ruleset(#[from] RulesetError),
+    /// A path of the spec cannot be opened and the spec is a
+    /// [`HardRequirement`](CompatLevel::HardRequirement).
+    #[error(transparent)]
+    PathFd(#[from] PathFdError),
+}
+
+/// Requested rights and the part of them the kernel enforces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Enforcement<T>
+where
+    T: AccessNames,
+{
+    #[serde(with = "access_names")]
+    pub requested: BitFlags<T>,
+    #[serde(with = "access_names")]
+    pub enforced: BitFlags<T>,
+}
+
+impl<T> Enforcement<T>
+where
+    T: AccessNames,
+{
+    /// Requested rights ignored by the running kernel.
+    pub fn dropped(&self) -> BitFlags<T> {
+        self.requested & !self.enforced
+    }
+}
+
+/// Outcome of a path rule.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathBeneathReport {
+    pub path: PathBuf,
+    pub access: Enforcement<AccessFs>,
+    /// Why the rule was not added, if it was not.
+    pub skipped: Option<String>,
+}
+
+/// Outcome of a port rule.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetPortReport {
+    pub port: u16,
+    pub access: Enforcement<AccessNet>,
+}
+
+/// What [`RulesetSpec::restrict_self()`] actually enforced on this kernel.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestrictionReport {
+    /// Landlock ABI of the running kernel.
+    pub abi: ABI,
+    pub ruleset: RulesetStatus,
+    pub no_new_privs: bool,
+    pub handled_fs: Enforcement<AccessFs>,
+    pub handled_net: Enforcement<AccessNet>,
+    pub scoped: Enforcement<Scope>,
+    pub path_beneath: Vec<PathBeneathReport>,
+    pub net_port: Vec<NetPortReport>,
+}
+
+impl RestrictionReport {
+    /// Path rules that were not added.
+    pub fn skipped(&self) -> impl Iterator<Item = &PathBeneathReport> {
+        self.path_beneath.iter().filter(|r| r.skipped.is_some())
+    }
+}
+
+impl RulesetSpec {
+    /// Builds the ruleset and restricts the calling thread with it.
+    ///
+    /// Paths that cannot be opened are skipped and reported, unless the spec
+    /// is a [`HardRequirement`](CompatLevel::HardRequirement).
+    pub fn restrict_self(&self) -> Result<RestrictionReport, SpecError> {
+        self.restrict(Ruleset::default())
+    }
+
+    fn restrict(&self, ruleset: Ruleset) -> Result<RestrictionReport, SpecError> {
+        let mut ruleset = ruleset.set_compatibility(self.compatibility);
+        if !self.handled_fs.is_empty() {
+            ruleset = ruleset.handle_access(self.handled_fs)?;
+        }
+        if !self.handled_net.is_empty() {
+            ruleset = ruleset.handle_access(self.handled_net)?;
+        }
+        if !self.scoped.is_empty() {
+            ruleset = ruleset.scope(self.scoped)?;
+        }
+
+        let abi = ruleset.compat.abi();
+        let mut handled_fs = Enforcement {
+            requested: self.handled_fs,
+            enforced: ruleset.actual_handled_fs,
+        };
+        let mut handled_net = Enforcement {
+            requested: self.handled_net,
+            enforced: ruleset.actual_handled_net,
+        };
+        let mut scoped = Enforcement {
+            requested: self.scoped,
+            enforced: ruleset.actual_scoped,
+        };
+
+        let mut created = ruleset.create()?;
+        let mut path_beneath = Vec::with_capacity(self.path_beneath.len());
+        for rule in &self.path_beneath {
+            let mut report = PathBeneathReport {
+                path: rule.path.clone(),
+                access: Enforcement {
+                    requested: rule.access,
+                    enforced: rule.access & handled_fs.enforced,
+                },
+                skipped: None,
+            };
+            match PathFd::new(&rule.path) {
+                Ok(fd) => created = created.add_rule(PathBeneath::new(fd, rule.access))?,
+                Err(e) if self.compatibility == CompatLevel::HardRequirement => {
+                    return Err(e.into())
+                }
+                Err(e) => {
+                    report.access.enforced = BitFlags::EMPTY;
+                    report.skipped = Some(e.to_string());
+                }
+            }
+            path_beneath.push(report);
+        }
+        let mut net_port = Vec::with_capacity(self.net_port.len());
+        for rule in &self.net_port {
+            created = created.add_rule(NetPort::new(rule.port, rule.access))?;
+            net_port.push(NetPortReport {
+                port: rule.port,
+                access: Enforcement {
+                    requested: rule.access,
+                    enforced: rule.access & handled_net.enforced,
+                },
+            });
+        }
+
+        let status = created
+            .set_no_new_privs(self.no_new_privs)
+            .restrict_self()?;
+        if status.ruleset == RulesetStatus::NotEnforced {
+            handled_fs.enforced = BitFlags::EMPTY;
+            handled_net.enforced = BitFlags::EMPTY;
+            scoped.enforced = BitFlags::EMPTY;
+            for rule in &mut path_beneath {
+                rule.access.enforced = BitFlags::EMPTY;
+            }
+            for rule in &mut net_port {
+                rule.access.enforced = BitFlags::EMPTY;
+            }
+        }
+
+        Ok(RestrictionReport {
+            abi,
+            ruleset: status.ruleset,
+            no_new_privs: status.no_new_privs,
+            handled_fs,
+            handled_net,
+            scoped,
+            path_beneath,
+            net_port,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compat::can_emulate;
+
+    const SPEC: &str = r#"{
+        "handled_fs": ["execute", "read_file", "truncate"],
+        "handled_net": ["connect_tcp"],
+        "scoped": ["signal"],
+        "path_beneath": [
+            { "path": "/", "access": ["execute", "read_file"] },
+            { "path": "/nonexistent/landlock/spec", "access": ["ro"] }
+        ],
+        "net_port": [{ "port": 443, "access": ["connect_tcp"] }]
+    }"#;
+
+    #[test]
+    fn spec_round_trip() {
+        let spec: RulesetSpec = serde_json::from_str(SPEC).unwrap();
+        assert_eq!(spec.compatibility, CompatLevel::BestEffort);
+        assert!(spec.no_new_privs);
+        assert_eq!(
+            spec.handled_fs,
+            AccessFs::Execute | AccessFs::ReadFile | AccessFs::Truncate
+        );
+        assert_eq!(spec.path_beneath[1].access, AccessFs::from_read(ABI::V5));
+        assert_eq!(spec.scoped, Scope::Signal);
+
+        let json = serde_json::to_string(&spec).unwrap();
+        assert_eq!(serde_json::from_str::<RulesetSpec>(&json).unwrap(), spec);
+        assert_eq!(
+            serde_json::from_str::<RulesetSpec>("{}").unwrap(),
+            RulesetSpec::default()
+        );
+    }
+
+    #[test]
+    fn spec_rejects_unknown_names() {
+        assert!(serde_json::from_str::<RulesetSpec>(r#"{"handled_fs": ["read"]}"#).is_err());
+        assert!(serde_json::from_str::<RulesetSpec>(r#"{"scoped": ["ptrace"]}"#).is_err());
+        assert!(serde_json::from_str::<RulesetSpec>(r#"{"handled": []}"#).is_err());
+        assert!(serde_json::from_str::<RulesetSpec>(r#"{"compatibility": "sometimes"}"#).is_err());
+    }
+
+    #[test]
+    fn spec_report_unsupported() {
+        let mut spec: RulesetSpec = serde_json::from_str(SPEC).unwrap();
+        spec.no_new_privs = false;
+        let report = spec.restrict(Ruleset::from(ABI::Unsupported)).unwrap();
+        assert_eq!(report.ruleset, RulesetStatus::NotEnforced);
+        assert_eq!(report.handled_fs.dropped(), spec.handled_fs);
+        assert!(report.path_beneath[0].skipped.is_none());
+        assert!(report.path_beneath[1].skipped.is_some());
+        assert!(report.net_port[0].access.enforced.is_empty());
+        assert_eq!(report.skipped().count(), 1);
+
+        let json = serde_json::to_string(&report).unwrap();
+        assert_eq!(
+            serde_json::from_str::<RestrictionReport>(&json).unwrap(),
+            report
+        );
+
+        spec.compatibility = CompatLevel::HardRequirement;
+        assert!(spec.restrict(Ruleset::from(ABI::Unsupported)).is_err());
+    }
+
+    #[test]
+    fn spec_report_partial() {
+        if !can_emulate(ABI::V1, ABI::V1, None) {
+            return;
+        }
+        let spec: RulesetSpec = serde_json::from_str(SPEC).unwrap();
+        // Ensures restrict_self() is called on a dedicated thread.
+        let report = std::thread::spawn(move || spec.restrict(Ruleset::from(ABI::V1)))
+            .join()
+            .unwrap()
+            .unwrap();
+        assert_eq!(report.ruleset, RulesetStatus::PartiallyEnforced);
+        assert_eq!(report.handled_fs.dropped(), AccessFs::Truncate);
+        assert!(report.handled_net.enforced.is_empty());
+        assert!(report.scoped.enforced.is_empty());
+        assert_eq!(
+            report.path_beneath[0].access.enforced,
+            AccessFs::Execute | AccessFs::ReadFile
+        );
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["abi"], "V1");
+        assert_eq!(json["ruleset"], "partially_enforced");
+        assert_eq!(
+            json["handled_fs"]["enforced"],
+            serde_json::json!(["execute", "read_file"])
+        );
+    }
+}
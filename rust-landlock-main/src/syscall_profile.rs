@@ -0,0 +1,364 @@
+//! Per-application seccomp syscall profiles.
+//!
+//! Landlock only covers the filesystem and TCP, so a policy can also carry a
+//! `seccomp` list, with the same `:` separator as the other policy fields:
+//!
+//! ```text
+//! no-ptrace:no-kernel-modules:deny=mount,umount2
+//! ```
+//!
+//! Entries are presets (`no-ptrace`, `no-kernel-modules`, `no-raw-sockets`),
+//! `deny=` lists of syscalls to refuse, or `allow=` lists. A single `allow=`
+//! entry turns the profile into an allowlist: every other syscall is refused,
+//! except those the sandbox itself needs to start the application
+//! ([`LAUNCH_SYSCALLS`]). Presets and `deny=` lists still apply on top of it.
+//!
+//! Refused syscalls fail with `EPERM` and are reported as `syscall:<name>`.
+//! The profile is compiled into the seccomp filter of the
+//! [`egress_filter`](crate::egress_filter), as a process can only have one
+//! notifying filter.
+
+use anyhow::{anyhow, Result};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::os::raw::{c_int, c_long};
+
+/// Syscalls the sandbox process needs once the filter is installed, to
+/// spawn the application, wait for it and exit. An allowlist always lets them
+/// through, for the application too.
+pub const LAUNCH_SYSCALLS: [&str; 14] = [
+    "clone",
+    "clone3",
+    "execve",
+    "wait4",
+    "exit",
+    "exit_group",
+    "rt_sigaction",
+    "rt_sigprocmask",
+    "rt_sigreturn",
+    "sigaltstack",
+    "mmap",
+    "munmap",
+    "futex",
+    // Error messages
+    "write",
+];
+
+/// Named syscall sets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Preset {
+    /// Refuses tracing and reading or writing other processes' memory.
+    NoPtrace,
+    /// Refuses loading and unloading kernel modules.
+    NoKernelModules,
+    /// Refuses raw IP sockets and packet sockets.
+    NoRawSockets,
+}
+
+const PRESETS: [(&str, Preset); 3] = [
+    ("no-ptrace", Preset::NoPtrace),
+    ("no-kernel-modules", Preset::NoKernelModules),
+    ("no-raw-sockets", Preset::NoRawSockets),
+];
+
+impl Preset {
+    /// Syscalls refused whatever their arguments.
+    fn syscalls(self) -> &'static [&'static str] {
+        match self {
+            Preset::NoPtrace => &[
+                "ptrace",
+                "process_vm_readv",
+                "process_vm_writev",
+                "pidfd_getfd",
+            ],
+            Preset::NoKernelModules => &["init_module", "finit_module", "delete_module"],
+            // socket() is checked on its arguments instead
+            Preset::NoRawSockets => &[],
+        }
+    }
+}
+
+/// A parsed `seccomp` list.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyscallProfile {
+    presets: BTreeSet<Preset>,
+    deny: BTreeSet<&'static str>,
+    allow: Option<BTreeSet<&'static str>>,
+}
+
+impl SyscallProfile {
+    /// Parses a `seccomp` list; syscall names must be known on this
+    /// architecture.
+    pub fn parse(list: &str) -> Result<Self> {
+        let mut profile = Self::default();
+        for entry in list.split(':').map(str::trim).filter(|e| !e.is_empty()) {
+            if let Some(names) = entry.strip_prefix("deny=") {
+                profile.deny.extend(parse_names(names)?);
+            } else if let Some(names) = entry.strip_prefix("allow=") {
+                profile
+                    .allow
+                    .get_or_insert_with(BTreeSet::new)
+                    .extend(parse_names(names)?);
+            } else {
+                let preset = PRESETS
+                    .iter()
+                    .find(|(n, _)| *n == entry)
+                    .map(|(_, p)| *p)
+                    .ok_or_else(|| anyhow!("Unknown seccomp preset: {}", entry))?;
+                profile.presets.insert(preset);
+            }
+        }
+        Ok(profile)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.presets.is_empty() && self.deny.is_empty() && self.allow.is_none()
+    }
+
+    /// Syscalls refused whatever their arguments, by number.
+    pub(crate) fn denied(&self) -> BTreeSet<c_long> {
+        self.presets
+            .iter()
+            .flat_map(|p| p.syscalls().iter())
+            .chain(self.deny.iter())
+            .filter_map(|name| syscall_nr(name))
+            .collect()
+    }
+
+    /// With an allowlist, the syscalls let through, by number.
+    pub(crate) fn allowed(&self) -> Option<BTreeSet<c_long>> {
+        let denied = self.denied();
+        self.allow.as_ref().map(|allow| {
+            allow
+                .iter()
+                .chain(LAUNCH_SYSCALLS.iter())
+                .filter_map(|name| syscall_nr(name))
+                .filter(|nr| !denied.contains(nr))
+                .collect()
+        })
+    }
+
+    pub(crate) fn denies_raw_sockets(&self) -> bool {
+        self.presets.contains(&Preset::NoRawSockets)
+    }
+
+    /// Whether the profile refuses this call.
+    pub(crate) fn refuses(&self, nr: c_long, args: &[u64; 6]) -> bool {
+        if nr == libc::SYS_socket && self.denies_raw_sockets() && is_raw_socket(args) {
+            return true;
+        }
+        match self.allowed() {
+            Some(allowed) => !allowed.contains(&nr),
+            None => self.denied().contains(&nr),
+        }
+    }
+}
+
+/// Canonical form, as accepted by [`SyscallProfile::parse()`].
+impl fmt::Display for SyscallProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut entries: Vec<String> = PRESETS
+            .iter()
+            .filter(|(_, p)| self.presets.contains(p))
+            .map(|(n, _)| n.to_string())
+            .collect();
+        if !self.deny.is_empty() {
+            entries.push(format!("deny={}", join_names(&self.deny)));
+        }
+        if let Some(allow) = &self.allow {
+            entries.push(format!("allow={}", join_names(allow)));
+        }
+        write!(f, "{}", entries.join(":"))
+    }
+}
+
+/// Mirrors the `socket()` check of the compiled filter: packet sockets, and
+/// raw sockets of the IP families. Raw netlink sockets stay allowed, as
+/// resolvers use them to list interfaces.
+pub(crate) fn is_raw_socket(args: &[u64; 6]) -> bool {
+    let domain = args[0] as u32 as c_int;
+    let kind = args[1] as u32 as c_int & SOCK_TYPE_MASK;
+    domain == libc::AF_PACKET
+        || ((domain == libc::AF_INET || domain == libc::AF_INET6) && kind == libc::SOCK_RAW)
+}
+
+/// Bits of the `socket()` type argument holding the socket type.
+pub(crate) const SOCK_TYPE_MASK: c_int = 0xf;
+
+fn parse_names(list: &str) -> Result<Vec<&'static str>> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .map(|name| {
+            syscall_nr(name)
+                .and_then(syscall_name)
+                .ok_or_else(|| anyhow!("Unknown syscall: {}", name))
+        })
+        .collect()
+}
+
+fn join_names(names: &BTreeSet<&str>) -> String {
+    names.iter().copied().collect::<Vec<_>>().join(",")
+}
+
+/// Number of a syscall on this architecture.
+pub fn syscall_nr(name: &str) -> Option<c_long> {
+    SYSCALLS
+        .iter()
+        .chain(ARCH_SYSCALLS.iter())
+        .find(|(n, _)| n.strip_prefix("SYS_") == Some(name))
+        .map(|(_, nr)| *nr)
+}
+
+/// Name of a syscall on this architecture.
+pub fn syscall_name(nr: c_long) -> Option<&'static str> {
+    SYSCALLS
+        .iter()
+        .chain(ARCH_SYSCALLS.iter())
+        .find(|(_, n)| *n == nr)
+        .and_then(|(name, _)| name.strip_prefix("SYS_"))
+}
+
+macro_rules! syscall_table {
+    ($($sys:ident)*) => {
+        &[$((stringify!($sys), libc::$sys)),*]
+    };
+}
+
+/// Syscalls shared by x86_64 and aarch64.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+const SYSCALLS: &[(&str, c_long)] = syscall_table![
+    SYS_read SYS_write SYS_close SYS_fstat SYS_lseek SYS_mmap SYS_mprotect SYS_munmap SYS_brk
+    SYS_rt_sigaction SYS_rt_sigprocmask SYS_rt_sigreturn SYS_ioctl SYS_pread64 SYS_pwrite64
+    SYS_readv SYS_writev SYS_sched_yield SYS_mremap SYS_msync SYS_mincore SYS_madvise SYS_shmget
+    SYS_shmat SYS_shmctl SYS_dup SYS_nanosleep SYS_getitimer SYS_setitimer SYS_getpid
+    SYS_sendfile SYS_socket SYS_connect SYS_accept SYS_sendto SYS_recvfrom SYS_sendmsg
+    SYS_recvmsg SYS_shutdown SYS_bind SYS_listen SYS_getsockname SYS_getpeername SYS_socketpair
+    SYS_setsockopt SYS_getsockopt SYS_clone SYS_execve SYS_exit SYS_wait4 SYS_kill SYS_uname
+    SYS_semget SYS_semop SYS_semctl SYS_shmdt SYS_msgget SYS_msgsnd SYS_msgrcv SYS_msgctl
+    SYS_fcntl SYS_flock SYS_fsync SYS_fdatasync SYS_truncate SYS_ftruncate SYS_getcwd SYS_chdir
+    SYS_fchdir SYS_fchmod SYS_fchown SYS_umask SYS_gettimeofday SYS_getrusage SYS_sysinfo
+    SYS_times SYS_ptrace SYS_getuid SYS_syslog SYS_getgid SYS_setuid SYS_setgid SYS_geteuid
+    SYS_getegid SYS_setpgid SYS_getppid SYS_setsid SYS_setreuid SYS_setregid SYS_getgroups
+    SYS_setgroups SYS_setresuid SYS_getresuid SYS_setresgid SYS_getresgid SYS_getpgid
+    SYS_setfsuid SYS_setfsgid SYS_getsid SYS_capget SYS_capset SYS_rt_sigpending
+    SYS_rt_sigtimedwait SYS_rt_sigqueueinfo SYS_rt_sigsuspend SYS_sigaltstack SYS_personality
+    SYS_statfs SYS_fstatfs SYS_getpriority SYS_setpriority SYS_sched_setparam SYS_sched_getparam
+    SYS_sched_setscheduler SYS_sched_getscheduler SYS_sched_get_priority_max
+    SYS_sched_get_priority_min SYS_sched_rr_get_interval SYS_mlock SYS_munlock SYS_mlockall
+    SYS_munlockall SYS_vhangup SYS_pivot_root SYS_prctl SYS_adjtimex SYS_chroot SYS_sync
+    SYS_acct SYS_settimeofday SYS_mount SYS_umount2 SYS_swapon SYS_swapoff SYS_reboot
+    SYS_sethostname SYS_setdomainname SYS_init_module SYS_delete_module SYS_quotactl
+    SYS_nfsservctl SYS_gettid SYS_readahead SYS_setxattr SYS_lsetxattr SYS_fsetxattr
+    SYS_getxattr SYS_lgetxattr SYS_fgetxattr SYS_listxattr SYS_llistxattr SYS_flistxattr
+    SYS_removexattr SYS_lremovexattr SYS_fremovexattr SYS_tkill SYS_futex SYS_sched_setaffinity
+    SYS_sched_getaffinity SYS_io_setup SYS_io_destroy SYS_io_getevents SYS_io_submit
+    SYS_io_cancel SYS_lookup_dcookie SYS_remap_file_pages SYS_getdents64 SYS_set_tid_address
+    SYS_restart_syscall SYS_semtimedop SYS_fadvise64 SYS_timer_create SYS_timer_settime
+    SYS_timer_gettime SYS_timer_getoverrun SYS_timer_delete SYS_clock_settime SYS_clock_gettime
+    SYS_clock_getres SYS_clock_nanosleep SYS_exit_group SYS_epoll_ctl SYS_tgkill SYS_mbind
+    SYS_set_mempolicy SYS_get_mempolicy SYS_mq_open SYS_mq_unlink SYS_mq_timedsend
+    SYS_mq_timedreceive SYS_mq_notify SYS_mq_getsetattr SYS_kexec_load SYS_waitid SYS_add_key
+    SYS_request_key SYS_keyctl SYS_ioprio_set SYS_ioprio_get SYS_inotify_add_watch
+    SYS_inotify_rm_watch SYS_migrate_pages SYS_openat SYS_mkdirat SYS_mknodat SYS_fchownat
+    SYS_newfstatat SYS_unlinkat SYS_linkat SYS_symlinkat SYS_readlinkat SYS_fchmodat
+    SYS_faccessat SYS_pselect6 SYS_ppoll SYS_unshare SYS_set_robust_list SYS_get_robust_list
+    SYS_splice SYS_tee SYS_vmsplice SYS_move_pages SYS_utimensat SYS_epoll_pwait
+    SYS_timerfd_create SYS_fallocate SYS_timerfd_settime SYS_timerfd_gettime SYS_accept4
+    SYS_signalfd4 SYS_eventfd2 SYS_epoll_create1 SYS_dup3 SYS_pipe2 SYS_inotify_init1 SYS_preadv
+    SYS_pwritev SYS_rt_tgsigqueueinfo SYS_perf_event_open SYS_recvmmsg SYS_fanotify_init
+    SYS_fanotify_mark SYS_prlimit64 SYS_name_to_handle_at SYS_open_by_handle_at
+    SYS_clock_adjtime SYS_syncfs SYS_sendmmsg SYS_setns SYS_getcpu SYS_process_vm_readv
+    SYS_process_vm_writev SYS_kcmp SYS_finit_module SYS_sched_setattr SYS_sched_getattr
+    SYS_renameat2 SYS_seccomp SYS_getrandom SYS_memfd_create SYS_kexec_file_load SYS_bpf
+    SYS_execveat SYS_userfaultfd SYS_membarrier SYS_mlock2 SYS_copy_file_range SYS_preadv2
+    SYS_pwritev2 SYS_pkey_mprotect SYS_pkey_alloc SYS_pkey_free SYS_statx SYS_rseq
+    SYS_pidfd_send_signal SYS_io_uring_setup SYS_io_uring_enter SYS_io_uring_register
+    SYS_open_tree SYS_move_mount SYS_fsopen SYS_fsconfig SYS_fsmount SYS_fspick SYS_pidfd_open
+    SYS_clone3 SYS_close_range SYS_openat2 SYS_pidfd_getfd SYS_faccessat2 SYS_process_madvise
+    SYS_epoll_pwait2 SYS_mount_setattr SYS_quotactl_fd SYS_landlock_create_ruleset
+    SYS_landlock_add_rule SYS_landlock_restrict_self SYS_memfd_secret SYS_process_mrelease
+    SYS_futex_waitv SYS_set_mempolicy_home_node SYS_mseal
+];
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const SYSCALLS: &[(&str, c_long)] = &[];
+
+/// Legacy syscalls aarch64 does not have.
+#[cfg(target_arch = "x86_64")]
+const ARCH_SYSCALLS: &[(&str, c_long)] = syscall_table![
+    SYS_open SYS_stat SYS_lstat SYS_poll SYS_access SYS_pipe SYS_select SYS_dup2 SYS_pause
+    SYS_alarm SYS_fork SYS_vfork SYS_getdents SYS_rename SYS_mkdir SYS_rmdir SYS_creat SYS_link
+    SYS_unlink SYS_symlink SYS_readlink SYS_chmod SYS_chown SYS_lchown SYS_getrlimit SYS_getpgrp
+    SYS_utime SYS_mknod SYS_ustat SYS_sysfs SYS_modify_ldt SYS_arch_prctl SYS_setrlimit SYS_iopl
+    SYS_ioperm SYS_time SYS_set_thread_area SYS_get_thread_area SYS_epoll_create SYS_epoll_wait
+    SYS_utimes SYS_inotify_init SYS_futimesat SYS_renameat SYS_sync_file_range SYS_signalfd
+    SYS_eventfd SYS_fchmodat2
+];
+#[cfg(not(target_arch = "x86_64"))]
+const ARCH_SYSCALLS: &[(&str, c_long)] = &[];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_round_trip() {
+        let profile =
+            SyscallProfile::parse("no-raw-sockets:deny=mount, umount2:no-ptrace:deny=mount")
+                .unwrap();
+        assert_eq!(
+            profile.to_string(),
+            "no-ptrace:no-raw-sockets:deny=mount,umount2"
+        );
+        assert_eq!(
+            SyscallProfile::parse(&profile.to_string()).unwrap(),
+            profile
+        );
+        assert!(SyscallProfile::parse("").unwrap().is_empty());
+        assert!(SyscallProfile::parse("no-fork").is_err());
+        assert!(SyscallProfile::parse("deny=not_a_syscall").is_err());
+    }
+
+    #[test]
+    fn profile_denylist() {
+        let profile = SyscallProfile::parse("no-ptrace:deny=mount").unwrap();
+        let args = [0; 6];
+        assert!(profile.refuses(libc::SYS_ptrace, &args));
+        assert!(profile.refuses(libc::SYS_process_vm_readv, &args));
+        assert!(profile.refuses(libc::SYS_mount, &args));
+        assert!(!profile.refuses(libc::SYS_read, &args));
+        assert!(profile.allowed().is_none());
+    }
+
+    #[test]
+    fn profile_allowlist() {
+        let profile = SyscallProfile::parse("allow=read,write,ptrace:no-ptrace").unwrap();
+        let args = [0; 6];
+        assert!(!profile.refuses(libc::SYS_read, &args));
+        assert!(profile.refuses(libc::SYS_ptrace, &args));
+        assert!(profile.refuses(libc::SYS_mount, &args));
+        for name in LAUNCH_SYSCALLS {
+            assert!(!profile.refuses(syscall_nr(name).unwrap(), &args));
+        }
+    }
+
+    #[test]
+    fn raw_sockets() {
+        let profile = SyscallProfile::parse("no-raw-sockets").unwrap();
+        let socket = |domain: c_int, kind: c_int| {
+            profile.refuses(libc::SYS_socket, &[domain as u64, kind as u64, 0, 0, 0, 0])
+        };
+        assert!(socket(libc::AF_INET, libc::SOCK_RAW));
+        assert!(socket(libc::AF_INET6, libc::SOCK_RAW | libc::SOCK_CLOEXEC));
+        assert!(socket(libc::AF_PACKET, libc::SOCK_DGRAM));
+        assert!(!socket(libc::AF_INET, libc::SOCK_STREAM));
+        assert!(!socket(libc::AF_NETLINK, libc::SOCK_RAW));
+    }
+
+    #[test]
+    fn syscall_names() {
+        assert_eq!(syscall_nr("openat"), Some(libc::SYS_openat));
+        assert_eq!(syscall_name(libc::SYS_openat), Some("openat"));
+        assert_eq!(syscall_nr("SYS_openat"), None);
+    }
+}
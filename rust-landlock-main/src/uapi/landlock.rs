@@ -22,6 +22,8 @@ pub const LANDLOCK_ACCESS_FS_TRUNCATE: u32 = 16384;
 pub const LANDLOCK_ACCESS_FS_IOCTL_DEV: u32 = 32768;
 pub const LANDLOCK_ACCESS_NET_BIND_TCP: u32 = 1;
 pub const LANDLOCK_ACCESS_NET_CONNECT_TCP: u32 = 2;
+pub const LANDLOCK_SCOPE_ABSTRACT_UNIX_SOCKET: u32 = 1;
+pub const LANDLOCK_SCOPE_SIGNAL: u32 = 2;
 pub type __s8 = ::std::os::raw::c_schar;
 pub type __u8 = ::std::os::raw::c_uchar;
 pub type __s16 = ::std::os::raw::c_short;
@@ -142,6 +144,8 @@ pub struct landlock_ruleset_attr {
     pub handled_access_fs: __u64,
     #[doc = " @handled_access_net: Bitmask of actions (cf. `Network flags`_)\n that is handled by this ruleset and should then be forbidden if no\n rule explicitly allow them."]
     pub handled_access_net: __u64,
+    #[doc = " @scoped: Bitmask of scopes (cf. `Scope flags`_)\n restricting a Landlock domain from accessing outside\n resources (e.g. IPCs)."]
+    pub scoped: __u64,
 }
 #[test]
 fn bindgen_test_layout_landlock_ruleset_attr() {
@@ -150,7 +154,7 @@ fn bindgen_test_layout_landlock_ruleset_attr() {
     let ptr = UNINIT.as_ptr();
     assert_eq!(
         ::std::mem::size_of::<landlock_ruleset_attr>(),
-        16usize,
+        24usize,
         concat!("Size of: ", stringify!(landlock_ruleset_attr))
     );
     assert_eq!(
@@ -178,6 +182,16 @@ fn bindgen_test_layout_landlock_ruleset_attr() {
             stringify!(handled_access_net)
         )
     );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).scoped) as usize - ptr as usize },
+        16usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(landlock_ruleset_attr),
+            "::",
+            stringify!(scoped)
+        )
+    );
 }
 #[doc = " @LANDLOCK_RULE_PATH_BENEATH: Type of a &struct\n landlock_path_beneath_attr ."]
 pub const landlock_rule_type_LANDLOCK_RULE_PATH_BENEATH: landlock_rule_type = 1;
@@ -0,0 +1,109 @@
+//! A policy can only reach a sandbox from the sandboxer that forked it.
+//!
+//! Forging one used to take a process creating the policy channel, writing a
+//! policy of its own to it, then exec'ing the sandboxer: the `--sandbox` child
+//! it had forked beforehand saw the channel created by its parent, running the
+//! sandboxer's executable.
+
+use std::ffi::CString;
+use std::fs;
+use std::io::Write;
+use std::os::unix::io::IntoRawFd;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::ptr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SANDBOXER: &str = env!("CARGO_BIN_EXE_sandboxer_db");
+
+/// Where the forger puts the sandbox end of its channel.
+const CHANNEL_FD: i32 = 100;
+
+fn cstrings(strings: &[&str]) -> Vec<CString> {
+    strings.iter().map(|s| CString::new(*s).unwrap()).collect()
+}
+
+fn exec(args: &[CString], env: &[CString]) -> ! {
+    let mut argv: Vec<_> = args.iter().map(|a| a.as_ptr()).collect();
+    argv.push(ptr::null());
+    let mut envp: Vec<_> = env.iter().map(|e| e.as_ptr()).collect();
+    envp.push(ptr::null());
+    unsafe {
+        libc::execve(argv[0], argv.as_ptr(), envp.as_ptr());
+        libc::_exit(127)
+    }
+}
+
+fn is_running(pid: libc::pid_t) -> bool {
+    fs::read_to_string(format!("/proc/{}/stat", pid))
+        .map(|stat| !stat.rsplit(')').next().unwrap_or("").trim_start().starts_with('Z'))
+        .unwrap_or(false)
+}
+
+#[test]
+fn sandbox_refuses_policy_from_exec_after_create() {
+    let marker = std::env::temp_dir().join(format!("forged-sandbox-{}", std::process::id()));
+    let _ = fs::remove_file(&marker);
+    let sandboxer = fs::canonicalize(SANDBOXER).unwrap();
+    let policy = r#"{"fs_ro":"/","fs_rw":"/","fs_rules":"","tcp_bind":"","tcp_connect":"",
+        "allowed_ips":"","allowed_domains":"","scoped":"","seccomp":"","isolation":"",
+        "environment":""}"#;
+    let mut payload = (policy.len() as u32).to_be_bytes().to_vec();
+    payload.extend_from_slice(policy.as_bytes());
+
+    // Prepared before forking, the test harness being multithreaded
+    let parent_args = cstrings(&[SANDBOXER]);
+    let script = format!("touch {}", marker.display());
+    let sandbox_args = cstrings(&[SANDBOXER, "--sandbox", "/bin/sh", "-c", &script]);
+    let sandbox_env = cstrings(&[&format!("SUPERNANNY_POLICY_FD={}", CHANNEL_FD), "PATH=/usr/bin:/bin"]);
+    let mut pids = [0; 2];
+    assert_eq!(unsafe { libc::pipe(pids.as_mut_ptr()) }, 0);
+
+    let creator = unsafe { libc::fork() };
+    if creator == 0 {
+        let (parent, child) = UnixStream::pair().unwrap();
+        unsafe { libc::dup2(child.into_raw_fd(), CHANNEL_FD) };
+        (&parent).write_all(&payload).unwrap();
+        let sandbox = unsafe { libc::fork() };
+        if sandbox == 0 {
+            // Waits for the creator to run the sandboxer, then claims to be its
+            // --sandbox child
+            let deadline = Instant::now() + Duration::from_secs(5);
+            let ppid = unsafe { libc::getppid() };
+            while fs::read_link(format!("/proc/{}/exe", ppid)).ok().as_deref()
+                != Some(sandboxer.as_path())
+                && Instant::now() < deadline
+            {
+                thread::sleep(Duration::from_millis(10));
+            }
+            exec(&sandbox_args, &sandbox_env);
+        }
+        unsafe {
+            libc::write(pids[1], &sandbox as *const _ as *const libc::c_void, 4);
+            // Stops right after the exec, keeping the sandbox's parent alive
+            libc::ptrace(libc::PTRACE_TRACEME, 0, 0, 0);
+        }
+        exec(&parent_args, &[]);
+    }
+    let mut sandbox: libc::pid_t = 0;
+    assert_eq!(
+        unsafe { libc::read(pids[0], &mut sandbox as *mut _ as *mut libc::c_void, 4) },
+        4
+    );
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while is_running(sandbox) && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    let finished = !is_running(sandbox);
+    unsafe {
+        libc::kill(sandbox, libc::SIGKILL);
+        libc::kill(creator, libc::SIGKILL);
+        libc::waitpid(creator, ptr::null_mut(), 0);
+    }
+
+    let ran = Path::new(&marker).exists();
+    let _ = fs::remove_file(&marker);
+    assert!(finished, "the forged sandbox did not exit");
+    assert!(!ran, "the forged sandbox ran the app");
+}
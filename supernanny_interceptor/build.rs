@@ -1,8 +1,18 @@
-use std::{env, path::PathBuf};
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
 
 fn main() {
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
-    let bpf_path = PathBuf::from("src/ebpf/kern/exec_intercept.c");
+
+    for name in ["exec_intercept", "landlock_denials"] {
+        compile_bpf(&out_dir, name);
+    }
+}
+
+fn compile_bpf(out_dir: &Path, name: &str) {
+    let src = format!("src/ebpf/kern/{}.c", name);
 
     let status = std::process::Command::new("clang")
         .args(&[
@@ -37,16 +47,16 @@ fn main() {
             "-I./src/ebpf/kern/include/",
             "-I./src/ebpf/kern/include/uapi",
             "-c",
-            "src/ebpf/kern/exec_intercept.c",
+            &src,
             "-o",
-            &format!("{}/exec_intercept.o", out_dir.display()),
+            &format!("{}/{}.o", out_dir.display(), name),
         ])
         .status()
         .expect("failed to compile eBPF program");
 
     if !status.success() {
-        panic!("BPF program compilation failed");
+        panic!("BPF program compilation failed: {}", src);
     }
 
-    println!("cargo:rerun-if-changed=src/ebpf/kern/exec_intercept.c");
+    println!("cargo:rerun-if-changed={}", src);
 }
@@ -0,0 +1,372 @@
+// src/ebpf/kern/landlock_denials.c
+
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+#include <bpf/bpf_tracing.h>
+#include <bpf/bpf_core_read.h>
+
+#define MAX_PATH_DEPTH 16
+#define COMPONENT_LEN 64
+#define MAX_ANCESTRY 8
+#define MAX_MOUNT_DEPTH 8
+
+#define EACCES 13
+#define EXDEV 18
+#define S_IFMT 00170000
+#define S_IFSOCK 0140000
+#define S_IFREG 0100000
+#define S_IFBLK 0060000
+#define S_IFDIR 0040000
+#define S_IFCHR 0020000
+#define S_IFIFO 0010000
+#define FMODE_READ 0x1
+#define FMODE_WRITE 0x2
+#define FMODE_EXEC 0x20
+#define AF_INET 2
+#define AF_INET6 10
+
+// Type de hook Landlock ayant refusé l'accès (doit rester aligné avec denial.rs).
+#define DENIAL_OPEN 1
+#define DENIAL_TRUNCATE 2
+#define DENIAL_MKDIR 3
+#define DENIAL_MKNOD 4
+#define DENIAL_UNLINK 5
+#define DENIAL_RMDIR 6
+#define DENIAL_BIND 7
+#define DENIAL_CONNECT 8
+#define DENIAL_LINK 9
+#define DENIAL_RENAME 10
+#define DENIAL_SYMLINK 11
+#define DENIAL_IOCTL 12
+
+// Droits Landlock (valeurs de l'UAPI LANDLOCK_ACCESS_FS_* / LANDLOCK_ACCESS_NET_*).
+#define ACCESS_FS_EXECUTE (1ULL << 0)
+#define ACCESS_FS_WRITE_FILE (1ULL << 1)
+#define ACCESS_FS_READ_FILE (1ULL << 2)
+#define ACCESS_FS_READ_DIR (1ULL << 3)
+#define ACCESS_FS_REMOVE_DIR (1ULL << 4)
+#define ACCESS_FS_REMOVE_FILE (1ULL << 5)
+#define ACCESS_FS_MAKE_CHAR (1ULL << 6)
+#define ACCESS_FS_MAKE_DIR (1ULL << 7)
+#define ACCESS_FS_MAKE_REG (1ULL << 8)
+#define ACCESS_FS_MAKE_SOCK (1ULL << 9)
+#define ACCESS_FS_MAKE_FIFO (1ULL << 10)
+#define ACCESS_FS_MAKE_BLOCK (1ULL << 11)
+#define ACCESS_FS_MAKE_SYM (1ULL << 12)
+#define ACCESS_FS_REFER (1ULL << 13)
+#define ACCESS_FS_TRUNCATE (1ULL << 14)
+#define ACCESS_FS_IOCTL_DEV (1ULL << 15)
+#define ACCESS_NET_BIND_TCP (1ULL << 0)
+#define ACCESS_NET_CONNECT_TCP (1ULL << 1)
+
+char LICENSE[] SEC("license") = "GPL";
+
+struct denial_event_t {
+    __u32 pid;
+    __u32 tgid;
+    __u32 root_tgid;
+    __u32 uid;
+    __u32 kind;
+    __u32 depth;
+    __u64 access;
+    __u16 port;
+    __u16 family;
+    // Non nul si la racine n'a pas été atteinte (chemin trop profond).
+    __u32 truncated;
+    char comm[16];
+    // Composants du chemin, de la feuille vers la racine.
+    char components[MAX_PATH_DEPTH][COMPONENT_LEN];
+} __attribute__((aligned(8)));
+
+// Processus racines surveillés (un sandboxer par entrée), renseignés par l'espace utilisateur.
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __uint(key_size, sizeof(u32));
+    __uint(value_size, sizeof(u8));
+    __uint(max_entries, 1024);
+} WATCHED_TGIDS SEC(".maps");
+
+struct {
+    __uint(type, BPF_MAP_TYPE_PERCPU_ARRAY);
+    __uint(key_size, sizeof(u32));
+    __uint(value_size, sizeof(struct denial_event_t));
+    __uint(max_entries, 1);
+} TMP_DENIAL SEC(".maps");
+
+struct {
+    __uint(type, BPF_MAP_TYPE_PERF_EVENT_ARRAY);
+    __uint(key_size, sizeof(u32));
+    __uint(value_size, sizeof(u32));
+    __uint(max_entries, 1024);
+} DENIAL_EVENTS SEC(".maps");
+
+// Remonte la lignée du processus courant jusqu'à trouver un sandboxer surveillé.
+static __always_inline u32 watched_root(void) {
+    struct task_struct *task = (struct task_struct *)bpf_get_current_task();
+
+#pragma unroll
+    for (int i = 0; i < MAX_ANCESTRY; i++) {
+        if (!task)
+            return 0;
+        u32 tgid = BPF_CORE_READ(task, tgid);
+        if (tgid == 0)
+            return 0;
+        if (bpf_map_lookup_elem(&WATCHED_TGIDS, &tgid))
+            return tgid;
+        task = BPF_CORE_READ(task, real_parent);
+    }
+    return 0;
+}
+
+static __always_inline struct denial_event_t *new_event(u32 root, u32 kind, u64 access) {
+    u32 key = 0;
+    struct denial_event_t *event = bpf_map_lookup_elem(&TMP_DENIAL, &key);
+    if (!event)
+        return NULL;
+
+    __builtin_memset(event, 0, sizeof(*event));
+    u64 pid_tgid = bpf_get_current_pid_tgid();
+    event->pid = (u32)pid_tgid;
+    event->tgid = pid_tgid >> 32;
+    event->root_tgid = root;
+    event->uid = bpf_get_current_uid_gid() & 0xFFFFFFFF;
+    event->kind = kind;
+    event->access = access;
+    bpf_get_current_comm(&event->comm, sizeof(event->comm));
+    return event;
+}
+
+// Reconstruit le chemin en remontant les dentries. À la racine d'un montage, on
+// repart de son point de montage dans le montage parent, jusqu'à la racine du
+// namespace. bpf_d_path n'est pas autorisé sur les hooks Landlock.
+static __always_inline void fill_path(struct denial_event_t *event, struct vfsmount *vfsmnt,
+                                      struct dentry *dentry) {
+    struct mount *mnt = (void *)vfsmnt - bpf_core_field_offset(struct mount, mnt);
+    u32 depth = 0;
+
+#pragma unroll
+    for (int i = 0; i < MAX_PATH_DEPTH + MAX_MOUNT_DEPTH; i++) {
+        if (!dentry)
+            break;
+        struct dentry *parent = BPF_CORE_READ(dentry, d_parent);
+        if (dentry == BPF_CORE_READ(mnt, mnt.mnt_root) || dentry == parent) {
+            struct mount *mnt_parent = BPF_CORE_READ(mnt, mnt_parent);
+            if (mnt_parent == mnt) {
+                event->depth = depth;
+                return;
+            }
+            dentry = BPF_CORE_READ(mnt, mnt_mountpoint);
+            mnt = mnt_parent;
+            continue;
+        }
+        if (depth >= MAX_PATH_DEPTH)
+            break;
+        const unsigned char *name = BPF_CORE_READ(dentry, d_name.name);
+        bpf_probe_read_kernel_str(event->components[depth & (MAX_PATH_DEPTH - 1)], COMPONENT_LEN, name);
+        depth++;
+        dentry = parent;
+    }
+    // Racine non atteinte : le chemin est incomplet.
+    event->depth = depth;
+    event->truncated = 1;
+}
+
+static __always_inline int emit(void *ctx, struct denial_event_t *event) {
+    return bpf_perf_event_output(ctx, &DENIAL_EVENTS, BPF_F_CURRENT_CPU,
+                                 event, sizeof(*event));
+}
+
+static __always_inline int report_file(void *ctx, struct file *file, u32 kind, u64 access) {
+    u32 root = watched_root();
+    if (!root)
+        return 0;
+
+    struct denial_event_t *event = new_event(root, kind, access);
+    if (!event)
+        return 0;
+
+    fill_path(event, BPF_CORE_READ(file, f_path.mnt), BPF_CORE_READ(file, f_path.dentry));
+    return emit(ctx, event);
+}
+
+// dentry est l'entrée créée ou supprimée dans dir, donc sur le même montage.
+static __always_inline int report_dentry(void *ctx, const struct path *dir, struct dentry *dentry,
+                                         u32 kind, u64 access) {
+    u32 root = watched_root();
+    if (!root)
+        return 0;
+
+    struct denial_event_t *event = new_event(root, kind, access);
+    if (!event)
+        return 0;
+
+    fill_path(event, BPF_CORE_READ(dir, mnt), dentry);
+    return emit(ctx, event);
+}
+
+static __always_inline int report_path(void *ctx, const struct path *path, u32 kind, u64 access) {
+    u32 root = watched_root();
+    if (!root)
+        return 0;
+
+    struct denial_event_t *event = new_event(root, kind, access);
+    if (!event)
+        return 0;
+
+    fill_path(event, BPF_CORE_READ(path, mnt), BPF_CORE_READ(path, dentry));
+    return emit(ctx, event);
+}
+
+static __always_inline int report_socket(void *ctx, struct sockaddr *address, u32 kind, u64 access) {
+    u32 root = watched_root();
+    if (!root)
+        return 0;
+
+    struct denial_event_t *event = new_event(root, kind, access);
+    if (!event)
+        return 0;
+
+    u16 family = BPF_CORE_READ(address, sa_family);
+    event->family = family;
+    if (family == AF_INET) {
+        event->port = bpf_ntohs(BPF_CORE_READ((struct sockaddr_in *)address, sin_port));
+    } else if (family == AF_INET6) {
+        event->port = bpf_ntohs(BPF_CORE_READ((struct sockaddr_in6 *)address, sin6_port));
+    }
+    return emit(ctx, event);
+}
+
+SEC("fexit/hook_file_open")
+int BPF_PROG(landlock_file_open, struct file *file, int ret) {
+    if (ret != -EACCES)
+        return 0;
+
+    u32 f_mode = BPF_CORE_READ(file, f_mode);
+    u16 i_mode = BPF_CORE_READ(file, f_inode, i_mode);
+    u64 access = 0;
+    if ((i_mode & S_IFMT) == S_IFDIR) {
+        access |= ACCESS_FS_READ_DIR;
+    } else {
+        if (f_mode & FMODE_READ)
+            access |= ACCESS_FS_READ_FILE;
+        if (f_mode & FMODE_WRITE)
+            access |= ACCESS_FS_WRITE_FILE;
+        if (f_mode & FMODE_EXEC)
+            access |= ACCESS_FS_EXECUTE;
+    }
+    return report_file(ctx, file, DENIAL_OPEN, access);
+}
+
+SEC("fexit/hook_file_truncate")
+int BPF_PROG(landlock_file_truncate, struct file *file, int ret) {
+    if (ret != -EACCES)
+        return 0;
+    return report_file(ctx, file, DENIAL_TRUNCATE, ACCESS_FS_TRUNCATE);
+}
+
+SEC("fexit/hook_path_truncate")
+int BPF_PROG(landlock_path_truncate, const struct path *path, int ret) {
+    if (ret != -EACCES)
+        return 0;
+    return report_path(ctx, path, DENIAL_TRUNCATE, ACCESS_FS_TRUNCATE);
+}
+
+SEC("fexit/hook_file_ioctl")
+int BPF_PROG(landlock_file_ioctl, struct file *file, unsigned int cmd, unsigned long arg, int ret) {
+    if (ret != -EACCES)
+        return 0;
+    return report_file(ctx, file, DENIAL_IOCTL, ACCESS_FS_IOCTL_DEV);
+}
+
+SEC("fexit/hook_path_mkdir")
+int BPF_PROG(landlock_path_mkdir, const struct path *dir, struct dentry *dentry, umode_t mode, int ret) {
+    if (ret != -EACCES)
+        return 0;
+    return report_dentry(ctx, dir, dentry, DENIAL_MKDIR, ACCESS_FS_MAKE_DIR);
+}
+
+SEC("fexit/hook_path_mknod")
+int BPF_PROG(landlock_path_mknod, const struct path *dir, struct dentry *dentry, umode_t mode,
+             unsigned int dev, int ret) {
+    if (ret != -EACCES)
+        return 0;
+
+    // Même correspondance que Landlock : un mode sans type crée un fichier régulier.
+    u64 access;
+    switch (mode & S_IFMT) {
+    case S_IFCHR:
+        access = ACCESS_FS_MAKE_CHAR;
+        break;
+    case S_IFBLK:
+        access = ACCESS_FS_MAKE_BLOCK;
+        break;
+    case S_IFIFO:
+        access = ACCESS_FS_MAKE_FIFO;
+        break;
+    case S_IFSOCK:
+        access = ACCESS_FS_MAKE_SOCK;
+        break;
+    default:
+        access = ACCESS_FS_MAKE_REG;
+        break;
+    }
+    return report_dentry(ctx, dir, dentry, DENIAL_MKNOD, access);
+}
+
+SEC("fexit/hook_path_unlink")
+int BPF_PROG(landlock_path_unlink, const struct path *dir, struct dentry *dentry, int ret) {
+    if (ret != -EACCES)
+        return 0;
+    return report_dentry(ctx, dir, dentry, DENIAL_UNLINK, ACCESS_FS_REMOVE_FILE);
+}
+
+SEC("fexit/hook_path_rmdir")
+int BPF_PROG(landlock_path_rmdir, const struct path *dir, struct dentry *dentry, int ret) {
+    if (ret != -EACCES)
+        return 0;
+    return report_dentry(ctx, dir, dentry, DENIAL_RMDIR, ACCESS_FS_REMOVE_DIR);
+}
+
+SEC("fexit/hook_path_symlink")
+int BPF_PROG(landlock_path_symlink, const struct path *dir, struct dentry *dentry, const char *old_name,
+             int ret) {
+    if (ret != -EACCES)
+        return 0;
+    return report_dentry(ctx, dir, dentry, DENIAL_SYMLINK, ACCESS_FS_MAKE_SYM);
+}
+
+// Landlock refuse un changement de parent sans droit refer avec EXDEV (l'appelant
+// se rabat alors sur une copie), et le reste avec EACCES. Les deux entrées sont
+// signalées : le refus peut venir de l'une ou de l'autre. Un lien ne traverse
+// pas les montages, old_dentry est donc sur celui de new_dir.
+SEC("fexit/hook_path_link")
+int BPF_PROG(landlock_path_link, struct dentry *old_dentry, const struct path *new_dir,
+             struct dentry *new_dentry, int ret) {
+    if (ret != -EACCES && ret != -EXDEV)
+        return 0;
+    report_dentry(ctx, new_dir, old_dentry, DENIAL_LINK, ACCESS_FS_REFER);
+    return report_dentry(ctx, new_dir, new_dentry, DENIAL_LINK, ACCESS_FS_REFER);
+}
+
+SEC("fexit/hook_path_rename")
+int BPF_PROG(landlock_path_rename, const struct path *old_dir, struct dentry *old_dentry,
+             const struct path *new_dir, struct dentry *new_dentry, unsigned int flags, int ret) {
+    if (ret != -EACCES && ret != -EXDEV)
+        return 0;
+    report_dentry(ctx, old_dir, old_dentry, DENIAL_RENAME, ACCESS_FS_REFER);
+    return report_dentry(ctx, new_dir, new_dentry, DENIAL_RENAME, ACCESS_FS_REFER);
+}
+
+SEC("fexit/hook_socket_bind")
+int BPF_PROG(landlock_socket_bind, struct socket *sock, struct sockaddr *address, int addrlen, int ret) {
+    if (ret != -EACCES)
+        return 0;
+    return report_socket(ctx, address, DENIAL_BIND, ACCESS_NET_BIND_TCP);
+}
+
+SEC("fexit/hook_socket_connect")
+int BPF_PROG(landlock_socket_connect, struct socket *sock, struct sockaddr *address, int addrlen, int ret) {
+    if (ret != -EACCES)
+        return 0;
+    return report_socket(ctx, address, DENIAL_CONNECT, ACCESS_NET_CONNECT_TCP);
+}
@@ -0,0 +1,142 @@
+// src/ebpf/user/denial.rs
+
+use aya::Pod;
+use serde::Serialize;
+
+pub const MAX_PATH_DEPTH: usize = 16;
+pub const COMPONENT_LEN: usize = 64;
+
+// Doit rester aligné avec les DENIAL_* de landlock_denials.c
+pub const DENIAL_OPEN: u32 = 1;
+pub const DENIAL_TRUNCATE: u32 = 2;
+pub const DENIAL_MKDIR: u32 = 3;
+pub const DENIAL_MKNOD: u32 = 4;
+pub const DENIAL_UNLINK: u32 = 5;
+pub const DENIAL_RMDIR: u32 = 6;
+pub const DENIAL_BIND: u32 = 7;
+pub const DENIAL_CONNECT: u32 = 8;
+pub const DENIAL_LINK: u32 = 9;
+pub const DENIAL_RENAME: u32 = 10;
+pub const DENIAL_SYMLINK: u32 = 11;
+pub const DENIAL_IOCTL: u32 = 12;
+
+/// Noms des droits Landlock (bit → nom), dans l'ordre de l'UAPI.
+const ACCESS_FS_NAMES: [&str; 16] = [
+    "execute",
+    "write_file",
+    "read_file",
+    "read_dir",
+    "remove_dir",
+    "remove_file",
+    "make_char",
+    "make_dir",
+    "make_reg",
+    "make_sock",
+    "make_fifo",
+    "make_block",
+    "make_sym",
+    "refer",
+    "truncate",
+    "ioctl_dev",
+];
+const ACCESS_NET_NAMES: [&str; 2] = ["bind_tcp", "connect_tcp"];
+
+#[repr(C, align(8))]
+#[derive(Debug, Clone, Copy)]
+pub struct DenialEvent {
+    pub pid: u32,
+    pub tgid: u32,
+    pub root_tgid: u32,
+    pub uid: u32,
+    pub kind: u32,
+    pub depth: u32,
+    pub access: u64,
+    pub port: u16,
+    pub family: u16,
+    pub truncated: u32,
+    pub comm: [u8; 16],
+    pub components: [[u8; COMPONENT_LEN]; MAX_PATH_DEPTH],
+}
+
+unsafe impl Pod for DenialEvent {}
+
+/// Refus Landlock tel qu'il est transmis au sandboxer (une ligne JSON par refus).
+#[derive(Debug, Clone, Serialize)]
+pub struct DenialRecord {
+    pub pid: u32,
+    pub comm: String,
+    pub syscall: String,
+    pub path: Option<String>,
+    pub port: Option<u16>,
+    pub access: Vec<String>,
+}
+
+fn c_str(raw: &[u8]) -> String {
+    let len = raw.iter().position(|&b| b == 0).unwrap_or(raw.len());
+    String::from_utf8_lossy(&raw[..len]).into_owned()
+}
+
+impl DenialEvent {
+    pub fn is_network(&self) -> bool {
+        matches!(self.kind, DENIAL_BIND | DENIAL_CONNECT)
+    }
+
+    pub fn syscall(&self) -> &'static str {
+        match self.kind {
+            DENIAL_OPEN => "open",
+            DENIAL_TRUNCATE => "truncate",
+            DENIAL_MKDIR => "mkdir",
+            DENIAL_MKNOD => "mknod",
+            DENIAL_UNLINK => "unlink",
+            DENIAL_RMDIR => "rmdir",
+            DENIAL_BIND => "bind",
+            DENIAL_CONNECT => "connect",
+            DENIAL_LINK => "link",
+            DENIAL_RENAME => "rename",
+            DENIAL_SYMLINK => "symlink",
+            DENIAL_IOCTL => "ioctl",
+            _ => "unknown",
+        }
+    }
+
+    /// Chemin absolu reconstruit depuis les composants (feuille → racine).
+    /// Aucun chemin si la remontée a été tronquée, plutôt qu'une fausse racine.
+    pub fn path(&self) -> Option<String> {
+        if self.truncated != 0 {
+            return None;
+        }
+        let depth = (self.depth as usize).min(MAX_PATH_DEPTH);
+        let parts: Vec<String> = self.components[..depth]
+            .iter()
+            .rev()
+            .map(|c| c_str(c))
+            .collect();
+        Some(format!("/{}", parts.join("/")))
+    }
+
+    pub fn access_names(&self) -> Vec<String> {
+        let names: &[&str] = if self.is_network() {
+            &ACCESS_NET_NAMES
+        } else {
+            &ACCESS_FS_NAMES
+        };
+        names
+            .iter()
+            .enumerate()
+            .filter(|(bit, _)| self.access & (1u64 << bit) != 0)
+            .map(|(_, name)| name.to_string())
+            .collect()
+    }
+
+    pub fn to_record(&self) -> DenialRecord {
+        let network = self.is_network();
+        DenialRecord {
+            pid: self.tgid,
+            comm: c_str(&self.comm),
+            syscall: self.syscall().to_string(),
+            path: if network { None } else { self.path() },
+            port: if network { Some(self.port) } else { None },
+            access: self.access_names(),
+        }
+    }
+}
@@ -0,0 +1,217 @@
+// src/integration/denials.rs
+//
+// Collecteur des refus Landlock : charge landlock_denials.o (fexit sur les hooks
+// Landlock) et redistribue les refus aux sandboxers via une socket Unix.
+//
+// Protocole (NDJSON) :
+//   1. le sandboxer se connecte ; son PID est lu via SO_PEERCRED et surveillé ;
+//   2. le service répond {"ready":true} ;
+//   3. chaque refus d'un descendant du sandboxer est envoyé sur une ligne ;
+//   4. le sandboxer ferme son côté écriture ; après un court délai (vidage des
+//      buffers perf), le service retire la surveillance et ferme la connexion.
+
+use anyhow::{Context, Result};
+use aya::{
+    maps::{perf::PerfEventArray, HashMap as BpfHashMap, MapData},
+    programs::FExit,
+    util::online_cpus,
+    Bpf, Btf,
+};
+use bytes::BytesMut;
+use log::{info, warn};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Read, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        io::AsRawFd,
+        net::{UnixListener, UnixStream},
+    },
+    path::Path,
+    sync::{
+        mpsc::{channel, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::ebpf::user::denial::{DenialEvent, DenialRecord};
+
+pub const DENIAL_SOCKET: &str = "/run/supernanny/denials.sock";
+
+/// Délai laissé aux buffers perf pour se vider après la fin du sandboxer.
+const DRAIN_GRACE: Duration = Duration::from_millis(300);
+
+/// Programmes fexit → fonction Landlock ciblée.
+const HOOKS: [(&str, &str); 13] = [
+    ("landlock_file_open", "hook_file_open"),
+    ("landlock_file_truncate", "hook_file_truncate"),
+    ("landlock_path_truncate", "hook_path_truncate"),
+    ("landlock_file_ioctl", "hook_file_ioctl"),
+    ("landlock_path_mkdir", "hook_path_mkdir"),
+    ("landlock_path_mknod", "hook_path_mknod"),
+    ("landlock_path_unlink", "hook_path_unlink"),
+    ("landlock_path_rmdir", "hook_path_rmdir"),
+    ("landlock_path_symlink", "hook_path_symlink"),
+    ("landlock_path_link", "hook_path_link"),
+    ("landlock_path_rename", "hook_path_rename"),
+    ("landlock_socket_bind", "hook_socket_bind"),
+    ("landlock_socket_connect", "hook_socket_connect"),
+];
+
+type Watched = Arc<Mutex<BpfHashMap<MapData, u32, u8>>>;
+type Subscribers = Arc<Mutex<HashMap<u32, Sender<DenialRecord>>>>;
+
+/// Charge les programmes et démarre la socket. Le `Bpf` retourné doit rester
+/// vivant tant que la collecte est active (il détient les attachements).
+pub fn start() -> Result<Bpf> {
+    info!("🔧 Loading Landlock denial collector...");
+    let mut bpf = Bpf::load_file(concat!(env!("OUT_DIR"), "/landlock_denials.o"))
+        .context("Failed to load landlock_denials.o")?;
+    let btf = Btf::from_sys_fs().context("Kernel BTF unavailable")?;
+
+    let mut attached = 0;
+    for (prog_name, hook) in HOOKS {
+        let prog: &mut FExit = match bpf.program_mut(prog_name) {
+            Some(p) => p.try_into().context("Failed to cast to FExit")?,
+            None => continue,
+        };
+        // Best effort : certains hooks n'existent pas sur les noyaux plus anciens.
+        match prog.load(hook, &btf).and_then(|_| prog.attach().map(|_| ())) {
+            Ok(()) => attached += 1,
+            Err(e) => warn!("⚠️ Cannot attach {} to {}: {}", prog_name, hook, e),
+        }
+    }
+    if attached == 0 {
+        anyhow::bail!("No Landlock hook could be attached");
+    }
+
+    let watched: Watched = Arc::new(Mutex::new(
+        BpfHashMap::try_from(bpf.take_map("WATCHED_TGIDS").context("Map WATCHED_TGIDS not found")?)
+            .context("WATCHED_TGIDS is not a hash map")?,
+    ));
+    let subscribers: Subscribers = Arc::new(Mutex::new(HashMap::new()));
+
+    let map = bpf.take_map("DENIAL_EVENTS").context("Map DENIAL_EVENTS not found")?;
+    let mut perf_array = PerfEventArray::try_from(map)?;
+    for cpu in online_cpus().context("Failed to get CPUs")? {
+        let mut buf = perf_array.open(cpu, None).context("open perf buffer")?;
+        let subscribers = subscribers.clone();
+        thread::spawn(move || {
+            let size = std::mem::size_of::<DenialEvent>();
+            let mut bufs = vec![BytesMut::with_capacity(size); 16];
+            let mut pfd = libc::pollfd { fd: buf.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+            loop {
+                if unsafe { libc::poll(&mut pfd, 1, 100) } <= 0 {
+                    continue;
+                }
+                let events = match buf.read_events(&mut bufs) {
+                    Ok(ev) => ev,
+                    Err(e) => {
+                        warn!("⚠️ read_events error on CPU {}: {}", cpu, e);
+                        continue;
+                    }
+                };
+                for b in bufs.iter().take(events.read) {
+                    if b.len() < size {
+                        continue;
+                    }
+                    let ev = unsafe { std::ptr::read_unaligned(b.as_ptr() as *const DenialEvent) };
+                    if let Some(tx) = subscribers.lock().unwrap().get(&ev.root_tgid) {
+                        let _ = tx.send(ev.to_record());
+                    }
+                }
+            }
+        });
+    }
+
+    let listener = bind_socket(Path::new(DENIAL_SOCKET))?;
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let watched = watched.clone();
+                    let subscribers = subscribers.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = handle_client(stream, watched, subscribers) {
+                            warn!("⚠️ Denial client error: {:#}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("⚠️ Denial socket accept failed: {}", e),
+            }
+        }
+    });
+
+    info!("✅ Landlock denial collector listening on {}", DENIAL_SOCKET);
+    Ok(bpf)
+}
+
+fn bind_socket(path: &Path) -> Result<UnixListener> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context("create socket directory")?;
+    }
+    let _ = fs::remove_file(path);
+    let listener = UnixListener::bind(path).context("bind denial socket")?;
+    // Tout utilisateur peut se connecter : il ne reçoit que les refus de ses propres descendants.
+    fs::set_permissions(path, fs::Permissions::from_mode(0o666))?;
+    Ok(listener)
+}
+
+/// PID du processus à l'autre bout de la socket (SO_PEERCRED).
+fn peer_pid(stream: &UnixStream) -> Result<u32> {
+    let mut cred = libc::ucred { pid: 0, uid: 0, gid: 0 };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut _ as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 || cred.pid <= 0 {
+        anyhow::bail!("SO_PEERCRED failed: {}", std::io::Error::last_os_error());
+    }
+    Ok(cred.pid as u32)
+}
+
+fn handle_client(stream: UnixStream, watched: Watched, subscribers: Subscribers) -> Result<()> {
+    let pid = peer_pid(&stream)?;
+    let (tx, rx) = channel::<DenialRecord>();
+
+    subscribers.lock().unwrap().insert(pid, tx);
+    watched.lock().unwrap().insert(pid, 1, 0).context("watch pid")?;
+    info!("👀 Watching Landlock denials for sandboxer {}", pid);
+
+    let mut writer = stream.try_clone()?;
+    writer.write_all(b"{\"ready\":true}\n")?;
+    let forward = thread::spawn(move || {
+        for record in rx {
+            let mut line = match serde_json::to_vec(&record) {
+                Ok(l) => l,
+                Err(_) => continue,
+            };
+            line.push(b'\n');
+            if writer.write_all(&line).is_err() {
+                break;
+            }
+        }
+    });
+
+    // Le client ne nous envoie rien : on attend simplement qu'il ferme son côté écriture.
+    let mut sink = [0u8; 64];
+    let mut reader = &stream;
+    while matches!(reader.read(&mut sink), Ok(n) if n > 0) {}
+
+    thread::sleep(DRAIN_GRACE);
+    let _ = watched.lock().unwrap().remove(&pid);
+    subscribers.lock().unwrap().remove(&pid);
+    let _ = forward.join();
+    let _ = stream.shutdown(std::net::Shutdown::Both);
+    info!("👋 Stopped watching sandboxer {}", pid);
+    Ok(())
+}
@@ -15,6 +15,8 @@ use std::{
 };
 use tokio::signal;
 
+pub mod denials;
+
 use crate::ebpf::user::event::{ExecEvent, MAX_ARGS};
 
 /// Démarre l’intercepteur eBPF et boucle sur les exec_events.
@@ -57,6 +59,15 @@ pub async fn run() -> Result<()> {
         });
     }
 
+    // Collecteur des refus Landlock pour les sandboxers (optionnel : nécessite BTF + fexit)
+    let _denials = match denials::start() {
+        Ok(bpf) => Some(bpf),
+        Err(e) => {
+            warn!("⚠️ Landlock denial collector disabled: {:#}", e);
+            None
+        }
+    };
+
     signal::ctrl_c().await?;
     info!("👋 Shutdown interceptor");
     Ok(())
@@ -249,8 +260,8 @@ fn handle_exec_event(ev: ExecEvent) {
         return;
     }
 
-    // 9) Reconstruire basename + argv brut (splitting "-it"/"-ti")
-    let (basename, raw_args) = {
+    // 9) Reconstruire chemin + basename + argv brut (splitting "-it"/"-ti")
+    let (fullpath, basename, raw_args) = {
         let raw_fn = &ev.filename;
         let fn_len = raw_fn.iter().position(|&b| b == 0).unwrap_or(raw_fn.len());
         let fullpath = String::from_utf8_lossy(&raw_fn[..fn_len]).into_owned();
@@ -278,7 +289,7 @@ fn handle_exec_event(ev: ExecEvent) {
                 pa.push(s);
             }
         }
-        (base, pa)
+        (fullpath, base, pa)
     };
 
     // 10) Skip les cas de base
@@ -342,10 +353,6 @@ fn handle_exec_event(ev: ExecEvent) {
         info!("🔒 Enforcing supernanny on {} {:?}", basename, args);
         unsafe { kill(pid as i32, libc::SIGKILL) };
 
-        let raw_fn = &ev.filename;
-        let fn_len = raw_fn.iter().position(|&b| b == 0).unwrap_or(raw_fn.len());
-        let fullpath = String::from_utf8_lossy(&raw_fn[..fn_len]).into_owned();
-
         let mut cmd = Command::new("/usr/bin/supernanny");
         cmd.current_dir(&original_cwd)
            .env_remove("LD_PRELOAD")
@@ -378,14 +385,16 @@ fn handle_exec_event(ev: ExecEvent) {
     }
 
     // 15) CATCH-ALL : tous les autres binaires
-    info!("🔍 Intercepted: {} → {:?}", basename, args);
+    // Le chemin exécuté, pas le basename : supernanny identifie le binaire
+    // par son chemin canonique et son empreinte (relatif à original_cwd)
+    info!("🔍 Intercepted: {} → {:?}", fullpath, args);
     unsafe { kill(pid as i32, libc::SIGKILL) };
 
     let mut cmd = Command::new("/usr/bin/supernanny");
     cmd.current_dir(&original_cwd)
        .env_remove("LD_PRELOAD")
        .env("NO_INTERCEPT", "1")
-       .arg(&basename)
+       .arg(&fullpath)
        .args(&args);
     if let Some(bus) = envs.get("DBUS_SESSION_BUS_ADDRESS") {
         cmd.env("DBUS_SESSION_BUS_ADDRESS", bus);
@@ -0,0 +1,86 @@
+//! `environment`: variables passed to the sandboxed application.
+//!
+//! Entries separated by `:`, e.g.
+//! `allow=PATH,HOME,LC_*:deny=AWS_*:redact=ghp_*:set=EDITOR=vim`. `\:` stands
+//! for a colon in the value of a `set=`.
+
+/// Splits on `:`, except on `\:`.
+fn split_entries(list: &str) -> Vec<String> {
+    let mut entries = vec![String::new()];
+    let mut chars = list.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&':') => {
+                chars.next();
+                entries.last_mut().unwrap().push(':');
+            }
+            ':' => entries.push(String::new()),
+            _ => entries.last_mut().unwrap().push(c),
+        }
+    }
+    entries
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn is_name_glob(glob: &str) -> bool {
+    !glob.is_empty()
+        && glob
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '*' || c == '?')
+}
+
+/// `environment`: variable filters and forced values.
+pub fn validate_environment(list: &str) -> Result<(), String> {
+    for entry in split_entries(list) {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+        let (key, value) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid environment entry: {}", entry))?;
+        let valid: fn(&str) -> bool = match key {
+            "allow" | "deny" => is_name_glob,
+            "redact" => |g| !g.is_empty(),
+            "set" => {
+                let (name, _) = value
+                    .split_once('=')
+                    .ok_or_else(|| format!("Missing value in set={}", value))?;
+                // The sandboxer's own variables, never passed to the application
+                if !is_name(name) || name.starts_with("SUPERNANNY_") {
+                    return Err(format!("Invalid variable name: {}", name));
+                }
+                continue;
+            }
+            _ => return Err(format!("Unknown environment entry: {}", key)),
+        };
+        if let Some(bad) = value.split(',').find(|g| !valid(g)) {
+            return Err(format!("Invalid environment pattern: {:?}", bad));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn environment() {
+        assert!(validate_environment("allow=PATH,LC_*:deny=AWS_*:redact=ghp_*").is_ok());
+        assert!(validate_environment("set=PATH=/usr/bin\\:/bin:set=EMPTY=").is_ok());
+        assert!(validate_environment("").is_ok());
+        assert!(validate_environment("allow=PATH,,HOME").is_err());
+        assert!(validate_environment("deny=AWS-*").is_err());
+        assert!(validate_environment("set=EDITOR").is_err());
+        assert!(validate_environment("set=1X=y").is_err());
+        assert!(validate_environment("set=SUPERNANNY_POLICY=x").is_err());
+        assert!(validate_environment("keep=PATH").is_err());
+        assert!(validate_environment("PATH").is_err());
+    }
+}
@@ -0,0 +1,125 @@
+//! `fs_rules`: explicit filesystem rights per path.
+//!
+//! Entries `<path>=<right>,<right>` separated by `:`, e.g.
+//! `/opt/toolchain=execute,read_dir:/dev/dri=read_file,ioctl_dev`. Rights are
+//! Landlock's; `ro` and `rw` stand for the rights of `default_ro` and
+//! `default_rw`.
+
+use std::collections::BTreeMap;
+
+use crate::path_template::validate_template;
+
+/// Rights names, in UAPI bit order.
+const FS_RIGHTS: [&str; 16] = [
+    "execute",
+    "write_file",
+    "read_file",
+    "read_dir",
+    "remove_dir",
+    "remove_file",
+    "make_char",
+    "make_dir",
+    "make_reg",
+    "make_sock",
+    "make_fifo",
+    "make_block",
+    "make_sym",
+    "refer",
+    "truncate",
+    "ioctl_dev",
+];
+
+/// `ro`: execute, read_file and read_dir, as the sandboxer's ABI V5 shorthand.
+const RO: u16 = 0b1101;
+/// `rw`: every right of ABI V5.
+const RW: u16 = u16::MAX;
+
+fn parse_rights(spec: &str) -> Result<u16, String> {
+    let mut access = 0;
+    for name in spec.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        access |= match name {
+            "ro" => RO,
+            "rw" => RW,
+            _ => FS_RIGHTS
+                .iter()
+                .position(|n| *n == name)
+                .map(|bit| 1 << bit)
+                .ok_or_else(|| format!("Unknown filesystem right: {}", name))?,
+        };
+    }
+    if access == 0 {
+        return Err("Empty set of filesystem rights".to_string());
+    }
+    Ok(access)
+}
+
+fn parse_fs_rules(list: &str) -> Result<BTreeMap<&str, u16>, String> {
+    let mut rules = BTreeMap::new();
+    for entry in list.split(':').filter(|e| !e.is_empty()) {
+        let (path, spec) = entry
+            .rsplit_once('=')
+            .ok_or_else(|| format!("Expected <path>=<rights> in fs rule: {}", entry))?;
+        if path.is_empty() {
+            return Err(format!("Missing path in fs rule: {}", entry));
+        }
+        *rules.entry(path).or_insert(0) |= parse_rights(spec)?;
+    }
+    Ok(rules)
+}
+
+/// `fs_rules`: `<path>=<rights>` entries.
+pub fn validate_fs_rules(list: &str) -> Result<(), String> {
+    parse_fs_rules(list)?
+        .keys()
+        .try_for_each(|path| validate_template(path))
+}
+
+/// Union of two `fs_rules` lists; rights on the same path are merged and
+/// listed in UAPI order. Invalid entries are dropped.
+pub fn merge_fs_rules(existing: &str, new: &str) -> String {
+    let mut rules = parse_fs_rules(existing).unwrap_or_default();
+    for (path, access) in parse_fs_rules(new).unwrap_or_default() {
+        *rules.entry(path).or_insert(0) |= access;
+    }
+    rules
+        .iter()
+        .map(|(path, access)| {
+            let names: Vec<&str> = FS_RIGHTS
+                .iter()
+                .enumerate()
+                .filter(|(bit, _)| access & (1 << bit) != 0)
+                .map(|(_, n)| *n)
+                .collect();
+            format!("{}={}", path, names.join(","))
+        })
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fs_rules() {
+        assert!(validate_fs_rules("/opt/tc=execute,read_dir:${HOME}/log=ro,make_reg").is_ok());
+        assert!(validate_fs_rules("").is_ok());
+        assert!(validate_fs_rules("/opt=fly").is_err());
+        assert!(validate_fs_rules("/opt=").is_err());
+        assert!(validate_fs_rules("=execute").is_err());
+        assert!(validate_fs_rules("relative=ro").is_err());
+        assert!(validate_fs_rules("/opt").is_err());
+    }
+
+    #[test]
+    fn merged_fs_rules() {
+        assert_eq!(
+            merge_fs_rules("/dev/dri=read_file", "/dev/dri=ioctl_dev:/opt=execute"),
+            "/dev/dri=read_file,ioctl_dev:/opt=execute"
+        );
+        assert_eq!(
+            merge_fs_rules("/opt=read_dir", "/opt=ro"),
+            "/opt=execute,read_file,read_dir"
+        );
+    }
+}
@@ -0,0 +1,48 @@
+//! `isolation`: namespaces the sandboxed application runs in.
+//!
+//! Options separated by `:`, e.g. `private-tmp:scratch-home:no-network:hostname=sandbox`.
+
+const OPTIONS: [&str; 4] = [
+    "private-tmp",
+    "scratch-home",
+    "read-only-root",
+    "no-network",
+];
+
+fn is_hostname(name: &str) -> bool {
+    (1..=64).contains(&name.len())
+        && !name.starts_with(['-', '.'])
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+}
+
+/// `isolation`: namespace options.
+pub fn validate_isolation(list: &str) -> Result<(), String> {
+    for entry in list.split(':').map(str::trim).filter(|e| !e.is_empty()) {
+        if OPTIONS.contains(&entry) {
+            continue;
+        }
+        match entry.strip_prefix("hostname=") {
+            Some(name) if is_hostname(name) => {}
+            Some(name) => return Err(format!("Invalid hostname: {}", name)),
+            None => return Err(format!("Unknown isolation option: {}", entry)),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn isolation() {
+        assert!(validate_isolation("private-tmp:scratch-home:read-only-root:no-network").is_ok());
+        assert!(validate_isolation("hostname=sandbox.local").is_ok());
+        assert!(validate_isolation("").is_ok());
+        assert!(validate_isolation("hostname=-bad").is_err());
+        assert!(validate_isolation("hostname=").is_err());
+        assert!(validate_isolation("private-net").is_err());
+    }
+}
@@ -0,0 +1,25 @@
+//! Syntax of the SuperNanny policy fields.
+//!
+//! Policies are stored as colon-separated lists that only the sandboxer
+//! interprets, on the client. The admin API, the policy service and the admin
+//! frontend check them with these functions before sending or storing them,
+//! so that a list they accept is a list the sandboxer can apply. Each module
+//! follows the parser of the sandboxer module of the same field.
+//!
+//! Errors are plain messages, returned as is to the administrator.
+
+mod environment;
+mod fs_rules;
+mod isolation;
+mod path_template;
+mod resources;
+mod scopes;
+mod seccomp;
+
+pub use environment::validate_environment;
+pub use fs_rules::{merge_fs_rules, validate_fs_rules};
+pub use isolation::validate_isolation;
+pub use path_template::{validate_path_list, validate_template};
+pub use resources::validate_resources;
+pub use scopes::validate_scopes;
+pub use seccomp::{merge_seccomp, validate_seccomp};
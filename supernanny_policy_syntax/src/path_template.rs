@@ -0,0 +1,165 @@
+//! Path templates, as in `default_ro`, `default_rw` and `fs_rules`.
+//!
+//! A path is absolute or starts with `${HOME}`, `${XDG_RUNTIME_DIR}` or
+//! `${APP_DIR}`; `${USER}` and `${UID}` may appear further in. Components may
+//! be bounded globs (`*`, `?`, `[a-z]`), except the first one, and `**` is not
+//! supported. The sandboxer expands the templates on the client.
+
+/// Maximum length of a template.
+const MAX_PATH_LEN: usize = 4096;
+
+/// Variables that may start a template, i.e. expand to an absolute path.
+const PATH_VARS: &[&str] = &["HOME", "XDG_RUNTIME_DIR", "APP_DIR"];
+
+/// Variables that may only appear after the first component.
+const NAME_VARS: &[&str] = &["USER", "UID"];
+
+/// Splits `s` into `${NAME}` references: start, end and name.
+fn variables(s: &str) -> Result<Vec<(usize, usize, &str)>, String> {
+    let mut vars = Vec::new();
+    let mut rest = 0;
+    while let Some(start) = s[rest..].find('$').map(|i| rest + i) {
+        if !s[start..].starts_with("${") {
+            return Err(format!("Expected '{{' after '$' in {}", s));
+        }
+        let end = match s[start..].find('}') {
+            Some(i) => start + i,
+            None => return Err(format!("Unterminated variable in {}", s)),
+        };
+        let name = &s[start + 2..end];
+        if !PATH_VARS.contains(&name) && !NAME_VARS.contains(&name) {
+            return Err(format!("Unknown variable ${{{}}} in {}", name, s));
+        }
+        vars.push((start, end + 1, name));
+        rest = end + 1;
+    }
+    Ok(vars)
+}
+
+fn is_glob(component: &str) -> bool {
+    component.contains(&['*', '?', '['][..])
+}
+
+fn valid_brackets(component: &str) -> bool {
+    let mut chars = component.chars();
+    while let Some(c) = chars.next() {
+        if c == '[' && !chars.by_ref().any(|c| c == ']') {
+            return false;
+        }
+    }
+    true
+}
+
+/// Checks the syntax of a policy path.
+pub fn validate_template(template: &str) -> Result<(), String> {
+    if template.len() > MAX_PATH_LEN {
+        return Err(format!("Path too long: {}", template));
+    }
+    if template.contains('\0') {
+        return Err(format!("Path contains null bytes: {}", template));
+    }
+    let vars = variables(template)?;
+    match vars.first() {
+        Some((0, _, name)) if !PATH_VARS.contains(name) => {
+            return Err(format!(
+                "${{{}}} cannot start a path, use one of ${{HOME}}, ${{XDG_RUNTIME_DIR}}, ${{APP_DIR}}: {}",
+                name, template
+            ));
+        }
+        Some((0, _, _)) => {}
+        _ if !template.starts_with('/') => {
+            return Err(format!("Path is not absolute: {}", template));
+        }
+        _ => {}
+    }
+    if vars
+        .iter()
+        .any(|(start, _, name)| *start > 0 && PATH_VARS.contains(name))
+    {
+        return Err(format!(
+            "Only ${{USER}} and ${{UID}} may appear inside a path: {}",
+            template
+        ));
+    }
+    if let Some((0, end, _)) = vars.first() {
+        if !template[*end..].is_empty() && !template[*end..].starts_with('/') {
+            return Err(format!(
+                "Expected '/' after the first variable: {}",
+                template
+            ));
+        }
+    }
+
+    if template
+        .split('/')
+        .find(|c| !c.is_empty())
+        .is_some_and(is_glob)
+    {
+        return Err(format!(
+            "First path component cannot be a glob: {}",
+            template
+        ));
+    }
+    for component in template.split('/') {
+        if component == ".." || component == "." {
+            return Err(format!("Path contains potential traversal: {}", template));
+        }
+        if component.contains("**") {
+            return Err(format!("Recursive globs are not supported: {}", template));
+        }
+        if component.contains('[') && !valid_brackets(component) {
+            return Err(format!("Unterminated '[' in {}", template));
+        }
+    }
+    Ok(())
+}
+
+/// `default_ro`, `default_rw`: colon-separated path templates.
+pub fn validate_path_list(list: &str) -> Result<(), String> {
+    list.split(':')
+        .filter(|p| !p.is_empty())
+        .try_for_each(validate_template)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_templates() {
+        for template in [
+            "/usr/lib/*/gconv",
+            "${HOME}/.config/app",
+            "${HOME}",
+            "/run/user/${UID}/app-[0-9]",
+        ] {
+            assert!(validate_template(template).is_ok(), "{}", template);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        for template in [
+            "relative/path",
+            "${HOME",
+            "$HOME/.config",
+            "${SHELL}/x",
+            "${USER}/x",
+            "/home/${HOME}",
+            "${HOME}x/.config",
+            "/*/passwd",
+            "/usr/**/lib",
+            "/usr/lib[",
+            "/usr/./lib",
+        ] {
+            assert!(validate_template(template).is_err(), "{}", template);
+        }
+    }
+
+    #[test]
+    fn path_lists() {
+        assert!(validate_path_list("/usr:${HOME}/.config/app:/run/user/${UID}").is_ok());
+        assert!(validate_path_list("").is_ok());
+        assert!(validate_path_list("/usr:relative").is_err());
+    }
+}
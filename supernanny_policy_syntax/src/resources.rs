@@ -0,0 +1,66 @@
+//! `resources`: cgroup v2 limits of the sandboxed application.
+//!
+//! `key=value` limits separated by `:`, e.g. `memory=512M:pids=256:cpu=50%:io=200`.
+//! `memory` is in bytes, or with a `K`, `M`, `G` or `T` suffix, `cpu` a share
+//! of one CPU and `io` a weight from 1 to 10000.
+
+const BYTE_SUFFIXES: [(char, u32); 4] = [('T', 40), ('G', 30), ('M', 20), ('K', 10)];
+
+fn is_bytes(value: &str) -> bool {
+    let (digits, shift) = match BYTE_SUFFIXES
+        .iter()
+        .find(|(suffix, _)| value.ends_with(*suffix))
+    {
+        Some((_, shift)) => (&value[..value.len() - 1], *shift),
+        None => (value, 0),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(1 << shift))
+        .is_some_and(|n| n > 0)
+}
+
+/// `resources`: cgroup v2 limits.
+pub fn validate_resources(list: &str) -> Result<(), String> {
+    for entry in list.split(':').map(str::trim).filter(|e| !e.is_empty()) {
+        let (key, value) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid resource limit: {}", entry))?;
+        let valid = match key {
+            "memory" => is_bytes(value),
+            "pids" => value.parse::<u64>().is_ok_and(|n| n > 0),
+            "cpu" => value
+                .strip_suffix('%')
+                .and_then(|p| p.parse::<u32>().ok())
+                .is_some_and(|p| p > 0),
+            "io" => value
+                .parse::<u16>()
+                .is_ok_and(|w| (1..=10_000).contains(&w)),
+            _ => return Err(format!("Unknown resource limit: {}", key)),
+        };
+        if !valid {
+            return Err(format!("Invalid {} limit: {}", key, value));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resources() {
+        assert!(validate_resources("memory=512M:pids=256:cpu=150%:io=200").is_ok());
+        assert!(validate_resources("memory=1048576").is_ok());
+        assert!(validate_resources("").is_ok());
+        assert!(validate_resources("memory=0").is_err());
+        assert!(validate_resources("memory=99999999999T").is_err());
+        assert!(validate_resources("cpu=50").is_err());
+        assert!(validate_resources("io=0").is_err());
+        assert!(validate_resources("io=10001").is_err());
+        assert!(validate_resources("swap=1G").is_err());
+        assert!(validate_resources("pids").is_err());
+    }
+}
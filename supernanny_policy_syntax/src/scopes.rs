@@ -0,0 +1,33 @@
+//! `scoped`: IPCs the sandboxed application cannot use to reach outside its
+//! sandbox.
+//!
+//! Landlock scope names separated by `:`, e.g. `abstract_unix_socket:signal`.
+
+/// Scope names, in UAPI bit order.
+const SCOPES: [&str; 2] = ["abstract_unix_socket", "signal"];
+
+/// `scoped`: Landlock scope names.
+pub fn validate_scopes(list: &str) -> Result<(), String> {
+    match list
+        .split(':')
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .find(|n| !SCOPES.contains(n))
+    {
+        Some(unknown) => Err(format!("Unknown scope: {}", unknown)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes() {
+        assert!(validate_scopes("abstract_unix_socket:signal").is_ok());
+        assert!(validate_scopes(" signal ").is_ok());
+        assert!(validate_scopes("").is_ok());
+        assert!(validate_scopes("ptrace").is_err());
+    }
+}
@@ -0,0 +1,126 @@
+//! `seccomp`: syscall profile of the sandboxed application.
+//!
+//! Presets and syscall lists separated by `:`, e.g.
+//! `no-ptrace:no-raw-sockets:deny=mount,umount2`. Syscall names depend on the
+//! architecture of the client: the sandboxer resolves them, only their syntax
+//! is checked here.
+
+use std::collections::BTreeSet;
+
+/// Named syscall sets.
+const PRESETS: [&str; 3] = ["no-ptrace", "no-kernel-modules", "no-raw-sockets"];
+
+fn is_syscall_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// `seccomp`: presets and `deny=`/`allow=` syscall lists.
+pub fn validate_seccomp(list: &str) -> Result<(), String> {
+    for entry in list.split(':').map(str::trim).filter(|e| !e.is_empty()) {
+        let names = match entry
+            .strip_prefix("deny=")
+            .or_else(|| entry.strip_prefix("allow="))
+        {
+            Some(names) => names,
+            None if PRESETS.contains(&entry) => continue,
+            None => return Err(format!("Unknown seccomp preset: {}", entry)),
+        };
+        if let Some(bad) = names
+            .split(',')
+            .map(str::trim)
+            .find(|n| !is_syscall_name(n))
+        {
+            return Err(format!("Invalid syscall name: {}", bad));
+        }
+    }
+    Ok(())
+}
+
+/// Canonical form of a `seccomp` list, as the sandboxer prints it: presets in
+/// order, then a single `deny=` and a single `allow=` list. Invalid entries
+/// are dropped.
+fn canonical_seccomp(list: &str) -> String {
+    let mut presets = BTreeSet::new();
+    let mut deny = BTreeSet::new();
+    let mut allow: Option<BTreeSet<&str>> = None;
+    for entry in list.split(':').map(str::trim).filter(|e| !e.is_empty()) {
+        if validate_seccomp(entry).is_err() {
+            continue;
+        }
+        if let Some(names) = entry.strip_prefix("deny=") {
+            deny.extend(names.split(',').map(str::trim));
+        } else if let Some(names) = entry.strip_prefix("allow=") {
+            allow
+                .get_or_insert_with(BTreeSet::new)
+                .extend(names.split(',').map(str::trim));
+        } else if let Some(i) = PRESETS.iter().position(|p| *p == entry) {
+            presets.insert(i);
+        }
+    }
+    let mut entries: Vec<String> = presets
+        .into_iter()
+        .map(|i| PRESETS[i].to_string())
+        .collect();
+    if !deny.is_empty() {
+        entries.push(format!(
+            "deny={}",
+            deny.into_iter().collect::<Vec<_>>().join(",")
+        ));
+    }
+    if let Some(allow) = allow {
+        entries.push(format!(
+            "allow={}",
+            allow.into_iter().collect::<Vec<_>>().join(",")
+        ));
+    }
+    entries.join(":")
+}
+
+/// Profile of an approved request over the stored one. A non-empty profile
+/// replaces the stored one instead of adding up: a single `allow=` entry turns
+/// a profile into an allowlist, so a union would refuse every syscall the
+/// stored profile let through.
+pub fn merge_seccomp(existing: &str, new: &str) -> String {
+    match canonical_seccomp(new) {
+        new if new.is_empty() => canonical_seccomp(existing),
+        new => new,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seccomp_names_are_not_resolved() {
+        assert!(validate_seccomp("no-ptrace:deny=mount,umount2:allow=open").is_ok());
+        assert!(validate_seccomp("deny=not_a_syscall_anywhere").is_ok());
+        assert!(validate_seccomp("").is_ok());
+        assert!(validate_seccomp("no-such-preset").is_err());
+        assert!(validate_seccomp("deny=Mount").is_err());
+        assert!(validate_seccomp("deny=").is_err());
+    }
+
+    #[test]
+    fn merged_seccomp() {
+        assert_eq!(
+            merge_seccomp("no-ptrace:deny=mount", "allow=read,write"),
+            "allow=read,write"
+        );
+        assert_eq!(
+            merge_seccomp(
+                "",
+                "deny=umount2:no-raw-sockets:deny=mount, umount2:no-ptrace:allow=write:allow=read"
+            ),
+            "no-ptrace:no-raw-sockets:deny=mount,umount2:allow=read,write"
+        );
+        assert_eq!(
+            merge_seccomp("deny=mount:no-ptrace", ""),
+            "no-ptrace:deny=mount"
+        );
+        assert_eq!(merge_seccomp("no-ptrace", "deny=Mount:bogus"), "no-ptrace");
+    }
+}
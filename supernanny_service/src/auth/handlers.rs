@@ -4,6 +4,7 @@ use axum::{
 };
 use bcrypt::verify;
 use jsonwebtoken::{encode, EncodingKey, Header};
+use postgres::GenericClient;
 use serde::Serialize;
 use std::{
     env,
@@ -15,16 +16,21 @@ use tokio::task::spawn_blocking;
 
 use crate::{
     auth::jwt::{AuthUser, Claims},
-    models::dto::{AuthResponse, LoginRequest},
+    auth::refresh::{find_refresh_token, issue_refresh_token, revoke_family},
+    models::dto::{AuthResponse, LoginRequest, RefreshRequest},
     models::security_log::SecurityLogEntry,
     state::AppState,
     utils::logger::log_security_event,
 };
 
+/// Lifetime of the access tokens, in seconds. Sessions last longer through
+/// refresh tokens.
+const ACCESS_TOKEN_TTL: u64 = 3600;
+
 #[derive(Serialize)]
 pub struct WhoAmIResponse {
     pub user_id: i32,
-    pub role_id: i32,
+    pub role_ids: Vec<i32>,
 }
 
 #[axum::debug_handler]
@@ -57,16 +63,11 @@ pub async fn login(
             None => return Err((StatusCode::UNAUTHORIZED, "Invalid credentials".to_string())),
         };
 
-        // Fetch role_id from user_roles
-        let role_row = conn.query_opt(
-            "SELECT role_id FROM user_roles WHERE user_id = $1",
-            &[&user_id],
-        ).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Role lookup error: {e}")))?;
-
-        let role_id: i32 = match role_row {
-            Some(row) => row.get(0),
-            None => return Err((StatusCode::UNAUTHORIZED, "User has no role assigned".into())),
-        };
+        // Fetch all of the user's roles
+        let role_ids = fetch_role_ids(&mut *conn, user_id)?;
+        if role_ids.is_empty() {
+            return Err((StatusCode::UNAUTHORIZED, "User has no role assigned".into()));
+        }
 
         // Check password
         let valid = verify(&password, &password_hash)
@@ -77,36 +78,29 @@ pub async fn login(
         }
 
         // Token generation
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or(Duration::new(0, 0))
-            .as_secs();
-        let exp = now + 3600;
-
-        let claims = Claims {
-            sub: username.clone(),
+        let token = issue_access_token(&username, user_id, role_ids)?;
+        let refresh_token = issue_refresh_token(
+            &mut *conn,
             user_id,
-            role_id,
-            exp: exp as usize,
-        };
-
-        let secret = env::var("JWT_SECRET").unwrap_or_else(|_| "my_very_secret_key".into());
+            None,
+            state_clone.refresh_token_ttl,
+        )
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
 
-        let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes()))
-            .map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Token generation error: {e}"),
-                )
-            })?;
-
-        Ok::<(String, String), (StatusCode, String)>((username, token))
+        Ok::<(String, AuthResponse), (StatusCode, String)>((
+            username,
+            AuthResponse {
+                token,
+                refresh_token,
+                expires_in: ACCESS_TOKEN_TTL,
+            },
+        ))
     })
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Join error: {e}")))?;
 
     match result {
-        Ok((username, token)) => {
+        Ok((username, response)) => {
             let _ = log_security_event(
                 Arc::new(state.clone()),
                 SecurityLogEntry {
@@ -118,7 +112,7 @@ pub async fn login(
                 },
             ).await;
 
-            Ok(Json(AuthResponse { token }))
+            Ok(Json(response))
         }
 
         Err((status, message)) => {
@@ -138,9 +132,219 @@ pub async fn login(
     }
 }
 
+/// Roles of a user, by increasing id.
+fn fetch_role_ids(
+    conn: &mut impl GenericClient,
+    user_id: i32,
+) -> Result<Vec<i32>, (StatusCode, String)> {
+    let rows = conn
+        .query(
+            "SELECT role_id FROM user_roles WHERE user_id = $1 ORDER BY role_id",
+            &[&user_id],
+        )
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Role lookup error: {e}")))?;
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// Signs an access token for `username`.
+fn issue_access_token(
+    username: &str,
+    user_id: i32,
+    role_ids: Vec<i32>,
+) -> Result<String, (StatusCode, String)> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::new(0, 0))
+        .as_secs();
+    let exp = now + ACCESS_TOKEN_TTL;
+
+    let claims = Claims {
+        sub: username.to_string(),
+        user_id,
+        role_ids,
+        exp: exp as usize,
+    };
+
+    let secret = env::var("JWT_SECRET").unwrap_or_else(|_| "my_very_secret_key".into());
+
+    encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes()))
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Token generation error: {e}"),
+            )
+        })
+}
+
+/// Outcome of a refresh attempt that is worth a security log entry.
+enum RefreshOutcome {
+    Refreshed(AuthResponse),
+    /// A rotated token was presented again: it leaked, its family is revoked
+    Reused(String),
+    Refused(StatusCode, String),
+}
+
+/// Trades a refresh token for a new access token and a new refresh token of
+/// the same family. The presented token is rotated, i.e. cannot be used
+/// again; presenting it anyway revokes the whole family, which logs out both
+/// the legitimate client and whoever stole the token.
+pub async fn refresh(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Extension(state): Extension<AppState>,
+    Json(payload): Json<RefreshRequest>,
+) -> Result<Json<AuthResponse>, (StatusCode, String)> {
+    let client_ip = Some(addr.ip().to_string());
+    let state_clone = state.clone();
+
+    let outcome = spawn_blocking(move || {
+        let mut conn = state_clone
+            .db_pool
+            .get()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB pool error: {e}")))?;
+        let mut tx = conn
+            .transaction()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {e}")))?;
+
+        let stored = match find_refresh_token(&mut tx, &payload.refresh_token)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?
+        {
+            Some(stored) => stored,
+            None => {
+                return Ok(RefreshOutcome::Refused(
+                    StatusCode::UNAUTHORIZED,
+                    "Invalid refresh token".into(),
+                ))
+            }
+        };
+
+        if stored.revoked {
+            return Ok(RefreshOutcome::Refused(
+                StatusCode::UNAUTHORIZED,
+                "Refresh token revoked".into(),
+            ));
+        }
+        if stored.rotated {
+            revoke_family(&mut tx, &stored.family_id)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            tx.commit()
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Commit error: {e}")))?;
+            return Ok(RefreshOutcome::Reused(stored.username));
+        }
+        if stored.expired {
+            return Ok(RefreshOutcome::Refused(
+                StatusCode::UNAUTHORIZED,
+                "Refresh token expired".into(),
+            ));
+        }
+
+        // The roles may have changed since login
+        let role_ids = fetch_role_ids(&mut tx, stored.user_id)?;
+        if role_ids.is_empty() {
+            return Ok(RefreshOutcome::Refused(
+                StatusCode::UNAUTHORIZED,
+                "User has no role assigned".into(),
+            ));
+        }
+
+        tx.execute(
+            "UPDATE refresh_tokens SET rotated_at = NOW() WHERE token_id = $1",
+            &[&stored.token_id],
+        )
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Rotation error: {e}")))?;
+        let refresh_token = issue_refresh_token(
+            &mut tx,
+            stored.user_id,
+            Some(&stored.family_id),
+            state_clone.refresh_token_ttl,
+        )
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        let token = issue_access_token(&stored.username, stored.user_id, role_ids)?;
+        tx.commit()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Commit error: {e}")))?;
+
+        Ok::<RefreshOutcome, (StatusCode, String)>(RefreshOutcome::Refreshed(AuthResponse {
+            token,
+            refresh_token,
+            expires_in: ACCESS_TOKEN_TTL,
+        }))
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Join error: {e}")))??;
+
+    match outcome {
+        RefreshOutcome::Refreshed(response) => Ok(Json(response)),
+        RefreshOutcome::Reused(username) => {
+            let _ = log_security_event(
+                Arc::new(state.clone()),
+                SecurityLogEntry {
+                    username: Some(username),
+                    ip_address: client_ip,
+                    action: "refresh_token_reuse".into(),
+                    detail: Some("Rotated refresh token presented again, session revoked".into()),
+                    severity: "critical".into(),
+                },
+            ).await;
+
+            Err((StatusCode::UNAUTHORIZED, "Refresh token revoked".into()))
+        }
+        RefreshOutcome::Refused(status, message) => Err((status, message)),
+    }
+}
+
+/// Ends the session of a refresh token by revoking its family. Used when the
+/// PAM session closes, when the access token may already have expired, so
+/// the refresh token is the only credential asked for.
+pub async fn logout(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Extension(state): Extension<AppState>,
+    Json(payload): Json<RefreshRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let client_ip = Some(addr.ip().to_string());
+    let state_clone = state.clone();
+
+    let username = spawn_blocking(move || {
+        let mut conn = state_clone
+            .db_pool
+            .get()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB pool error: {e}")))?;
+        let mut tx = conn
+            .transaction()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {e}")))?;
+
+        let stored = find_refresh_token(&mut tx, &payload.refresh_token)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        if let Some(stored) = &stored {
+            revoke_family(&mut tx, &stored.family_id)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        }
+        tx.commit()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Commit error: {e}")))?;
+
+        Ok::<Option<String>, (StatusCode, String)>(stored.map(|s| s.username))
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Join error: {e}")))??;
+
+    // Unknown tokens get the same answer: nothing to learn from logging out
+    if let Some(username) = username {
+        let _ = log_security_event(
+            Arc::new(state.clone()),
+            SecurityLogEntry {
+                username: Some(username),
+                ip_address: client_ip,
+                action: "logout".into(),
+                detail: Some("Session closed, refresh tokens revoked".into()),
+                severity: "info".into(),
+            },
+        ).await;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub async fn who_am_i(AuthUser { claims }: AuthUser) -> Json<WhoAmIResponse> {
     Json(WhoAmIResponse {
         user_id: claims.user_id,
-        role_id: claims.role_id,
+        role_ids: claims.role_ids,
     })
 }
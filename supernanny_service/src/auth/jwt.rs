@@ -13,7 +13,8 @@ use std::env;
 pub struct Claims {
     pub sub: String,
     pub user_id: i32,
-    pub role_id: i32,
+    /// All of the user's roles, by increasing id
+    pub role_ids: Vec<i32>,
     pub exp: usize,
 }
 
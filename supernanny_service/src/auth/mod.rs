@@ -1,2 +1,3 @@
 pub mod jwt;
 pub mod handlers;
+pub mod refresh;
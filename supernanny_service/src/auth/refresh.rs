@@ -0,0 +1,100 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use postgres::GenericClient;
+use sha2::{Digest, Sha256};
+use std::{
+    fs::File,
+    io::{self, Read},
+};
+
+/// A refresh token as found in the database.
+pub struct StoredRefreshToken {
+    pub token_id: i32,
+    pub user_id: i32,
+    pub username: String,
+    pub family_id: String,
+    pub expired: bool,
+    /// Already traded for a new token: presenting it again is a reuse
+    pub rotated: bool,
+    pub revoked: bool,
+}
+
+/// 256 random bits, URL-safe base64.
+fn random_token() -> io::Result<String> {
+    let mut bytes = [0u8; 32];
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// Only hashes are stored: a leaked table does not give out sessions.
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Issues a refresh token valid `ttl` seconds, in `family_id` or, at login,
+/// in a new family. Returns the token, which is never stored in clear.
+pub fn issue_refresh_token(
+    conn: &mut impl GenericClient,
+    user_id: i32,
+    family_id: Option<&str>,
+    ttl: u64,
+) -> Result<String, String> {
+    let token = random_token().map_err(|e| format!("Refresh token generation error: {e}"))?;
+    let family_id = match family_id {
+        Some(family_id) => family_id.to_string(),
+        None => random_token().map_err(|e| format!("Refresh token generation error: {e}"))?,
+    };
+
+    conn.execute(
+        "
+        INSERT INTO refresh_tokens (user_id, family_id, token_hash, expires_at)
+        VALUES ($1, $2, $3, NOW() + make_interval(secs => $4))
+        ",
+        &[&user_id, &family_id, &hash_token(&token), &(ttl as f64)],
+    )
+    .map_err(|e| format!("Refresh token insert error: {e}"))?;
+
+    Ok(token)
+}
+
+/// Looks up `token`, locking its row until the end of the transaction.
+pub fn find_refresh_token(
+    conn: &mut impl GenericClient,
+    token: &str,
+) -> Result<Option<StoredRefreshToken>, String> {
+    let row = conn
+        .query_opt(
+            "
+            SELECT t.token_id, t.user_id, u.username, t.family_id,
+                   t.expires_at <= NOW(),
+                   t.rotated_at IS NOT NULL, t.revoked_at IS NOT NULL
+            FROM refresh_tokens t
+            JOIN users u ON u.user_id = t.user_id
+            WHERE t.token_hash = $1
+            FOR UPDATE OF t
+            ",
+            &[&hash_token(token)],
+        )
+        .map_err(|e| format!("Refresh token lookup error: {e}"))?;
+
+    Ok(row.map(|row| StoredRefreshToken {
+        token_id: row.get(0),
+        user_id: row.get(1),
+        username: row.get(2),
+        family_id: row.get(3),
+        expired: row.get(4),
+        rotated: row.get(5),
+        revoked: row.get(6),
+    }))
+}
+
+/// Revokes every token of a family, e.g. on logout or on reuse.
+pub fn revoke_family(conn: &mut impl GenericClient, family_id: &str) -> Result<u64, String> {
+    conn.execute(
+        "UPDATE refresh_tokens SET revoked_at = NOW() WHERE family_id = $1 AND revoked_at IS NULL",
+        &[&family_id],
+    )
+    .map_err(|e| format!("Refresh token revocation error: {e}"))
+}
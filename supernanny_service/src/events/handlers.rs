@@ -4,10 +4,14 @@ use axum::{
 };
 use crate::{
     auth::jwt::AuthUser,
-    models::dto::LogEventRequest,
+    models::dto::{
+        BatchedEvent, BatchedEventStatus, EventStatus, LogEnforcementRequest, LogEventBatch,
+        LogEventBatchResponse, LogEventRequest,
+    },
     state::AppState,
 };
 
+use std::collections::HashSet;
 use validator::Validate;
 
 pub async fn log_event(
@@ -40,8 +44,8 @@ pub async fn log_event(
             "
             INSERT INTO sandbox_events (
                 hostname, app_name, denied_path, operation, result,
-                user_id, remote_ip, domain, timestamp
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, NOW())
+                user_id, remote_ip, domain, cgroup_id, script, script_sha256, timestamp
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, NOW())
             ",
             &[
                 &payload.hostname,
@@ -52,6 +56,222 @@ pub async fn log_event(
                 &user_id,
                 &payload.remote_ip,
                 &payload.domain,
+                &payload.cgroup_id,
+                &payload.script,
+                &payload.script_sha256,
+            ],
+        ).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Insert failed: {e}")))?;
+
+        Ok(())
+    })
+    .await
+    .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Join error".to_string()))??;
+
+    Ok(StatusCode::CREATED)
+}
+
+/// Largest batch accepted by [`log_event_batch`].
+const MAX_BATCH_EVENTS: usize = 1000;
+
+/// Records the events spooled by a workstation, with one status per event.
+///
+/// Events are deduplicated on their `event_id`, within the batch and against
+/// those already recorded, so a batch can safely be sent again when the
+/// response was lost. Invalid events are rejected without failing the others.
+/// The valid ones are inserted by a single statement.
+pub async fn log_event_batch(
+    AuthUser { claims }: AuthUser,
+    Extension(state): Extension<AppState>,
+    Json(payload): Json<LogEventBatch>,
+) -> Result<Json<LogEventBatchResponse>, (StatusCode, String)> {
+    if payload.events.len() > MAX_BATCH_EVENTS {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("At most {MAX_BATCH_EVENTS} events per batch"),
+        ));
+    }
+
+    // Valid events are duplicates until the insert says otherwise. A batch
+    // comes from a single user, so its event ids are deduplicated as is
+    let mut results = Vec::with_capacity(payload.events.len());
+    let mut valid = Vec::new();
+    let mut seen = HashSet::new();
+    for batched in payload.events {
+        let (status, error) = match check_batched_event(&batched) {
+            Err(e) => (EventStatus::Rejected, Some(e)),
+            Ok(()) => {
+                if seen.insert(batched.event_id.clone()) {
+                    valid.push(batched.clone());
+                }
+                (EventStatus::Duplicate, None)
+            }
+        };
+        results.push(BatchedEventStatus {
+            event_id: batched.event_id,
+            status,
+            error,
+        });
+    }
+
+    let username = claims.sub;
+    let state = state.clone();
+
+    let mut inserted = tokio::task::spawn_blocking(move || {
+        if valid.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let mut conn = state.db_pool.get()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB pool error: {e}")))?;
+
+        // Event ids are only unique per user: without a user, duplicates
+        // could not be detected
+        let user_id: i32 = conn.query_opt(
+            "SELECT user_id FROM users WHERE username = $1",
+            &[&username],
+        )
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("User ID lookup failed: {e}")))?
+        .map(|row| row.get(0))
+        .ok_or((StatusCode::UNAUTHORIZED, "Unknown user".to_string()))?;
+
+        let column = |f: fn(&BatchedEvent) -> Option<String>| -> Vec<Option<String>> {
+            valid.iter().map(f).collect()
+        };
+        let hostnames = column(|b| Some(b.event.hostname.clone()));
+        let app_names = column(|b| Some(b.event.app_name.clone()));
+        let denied_paths = column(|b| b.event.denied_path.clone());
+        let operations = column(|b| Some(b.event.operation.clone()));
+        let event_results = column(|b| Some(b.event.result.clone()));
+        let remote_ips = column(|b| b.event.remote_ip.clone());
+        let domains = column(|b| b.event.domain.clone());
+        let event_ids = column(|b| Some(b.event_id.clone()));
+        let scripts = column(|b| b.event.script.clone());
+        let script_sha256s = column(|b| b.event.script_sha256.clone());
+        let cgroup_ids: Vec<Option<i64>> = valid.iter().map(|b| b.event.cgroup_id).collect();
+        let occurred_at: Vec<i64> = valid.iter().map(|b| b.occurred_at).collect();
+
+        let rows = conn.query(
+            "
+            INSERT INTO sandbox_events (
+                hostname, app_name, denied_path, operation, result,
+                user_id, remote_ip, domain, cgroup_id, client_event_id, timestamp,
+                script, script_sha256
+            )
+            SELECT e.hostname, e.app_name, e.denied_path, e.operation, e.result,
+                   $1,
+                   e.remote_ip, e.domain, e.cgroup_id, e.event_id, to_timestamp(e.occurred_at),
+                   e.script, e.script_sha256
+            FROM UNNEST(
+                $2::TEXT[], $3::TEXT[], $4::TEXT[], $5::TEXT[], $6::TEXT[],
+                $7::TEXT[], $8::TEXT[], $9::BIGINT[], $10::TEXT[], $11::BIGINT[],
+                $12::TEXT[], $13::TEXT[]
+            ) AS e(
+                hostname, app_name, denied_path, operation, result,
+                remote_ip, domain, cgroup_id, event_id, occurred_at,
+                script, script_sha256
+            )
+            ON CONFLICT (user_id, client_event_id) DO NOTHING
+            RETURNING client_event_id
+            ",
+            &[
+                &user_id,
+                &hostnames,
+                &app_names,
+                &denied_paths,
+                &operations,
+                &event_results,
+                &remote_ips,
+                &domains,
+                &cgroup_ids,
+                &event_ids,
+                &occurred_at,
+                &scripts,
+                &script_sha256s,
+            ],
+        ).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Insert failed: {e}")))?;
+
+        Ok::<_, (StatusCode, String)>(rows.iter().map(|row| row.get::<_, String>(0)).collect())
+    })
+    .await
+    .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Join error".to_string()))??;
+
+    // The first copy of an inserted event is accepted, later copies in the
+    // batch stay duplicates
+    for result in results.iter_mut() {
+        if result.status == EventStatus::Duplicate && inserted.remove(&result.event_id) {
+            result.status = EventStatus::Accepted;
+        }
+    }
+
+    let count = |status| results.iter().filter(|r| r.status == status).count();
+    Ok(Json(LogEventBatchResponse {
+        accepted: count(EventStatus::Accepted),
+        duplicates: count(EventStatus::Duplicate),
+        rejected: count(EventStatus::Rejected),
+        results,
+    }))
+}
+
+fn check_batched_event(batched: &BatchedEvent) -> Result<(), String> {
+    if batched.event_id.is_empty() || batched.event_id.len() > 64 {
+        return Err("event_id must be 1 to 64 characters long".to_string());
+    }
+    if batched.occurred_at < 0 {
+        return Err("occurred_at cannot be negative".to_string());
+    }
+    batched
+        .event
+        .validate()
+        .map_err(|validation_errors| format!("Validation failed: {validation_errors}"))
+}
+
+const ENFORCEMENT_STATUSES: [&str; 3] = ["fully_enforced", "partially_enforced", "not_enforced"];
+
+pub async fn log_enforcement(
+    AuthUser { claims }: AuthUser,
+    Extension(state): Extension<AppState>,
+    Json(payload): Json<LogEnforcementRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    if let Err(validation_errors) = payload.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Validation failed: {validation_errors}"),
+        ));
+    }
+    if !ENFORCEMENT_STATUSES.contains(&payload.status.as_str()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Unknown enforcement status: {}", payload.status),
+        ));
+    }
+
+    let username = claims.sub;
+    let state = state.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let mut conn = state.db_pool.get()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB pool error: {e}")))?;
+
+        let user_id: Option<i32> = conn.query_opt(
+            "SELECT user_id FROM users WHERE username = $1",
+            &[&username],
+        )
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("User ID lookup failed: {e}")))?
+        .map(|row| row.get(0));
+
+        conn.execute(
+            "
+            INSERT INTO enforcement_reports (
+                hostname, app_name, user_id, abi, status, skipped_rules, report, timestamp
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, NOW())
+            ",
+            &[
+                &payload.hostname,
+                &payload.app_name,
+                &user_id,
+                &payload.abi,
+                &payload.status,
+                &payload.skipped_rules,
+                &payload.report.to_string(),
             ],
         ).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Insert failed: {e}")))?;
 
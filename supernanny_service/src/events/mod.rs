@@ -1,2 +1,2 @@
 pub mod handlers;
-pub use handlers::log_event;
+pub use handlers::{log_enforcement, log_event, log_event_batch};
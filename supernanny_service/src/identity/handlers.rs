@@ -0,0 +1,113 @@
+use axum::{
+    extract::{Extension, Json, Path},
+    http::StatusCode,
+};
+use std::sync::Arc;
+use tokio::task::spawn_blocking;
+use validator::Validate;
+
+use crate::{
+    auth::jwt::AuthUser,
+    models::dto::IdentityBindingRequest,
+    models::policy::IdentityBinding,
+    ruleset::identity::get_identity_bindings,
+    state::AppState,
+    utils::permissions::has_permission,
+};
+
+/// Lists the identities the application policies are bound to.
+pub async fn list_identities(
+    AuthUser { claims }: AuthUser,
+    Extension(state): Extension<AppState>,
+) -> Result<Json<Vec<IdentityBinding>>, (StatusCode, String)> {
+    if !has_permission(Arc::new(state.clone()), claims.user_id, "manage_policies").await? {
+        return Err((StatusCode::FORBIDDEN, "Permission denied".to_string()));
+    }
+
+    get_identity_bindings(Extension(state), None).await
+}
+
+/// Binds a policy to the binaries at `exe_path`, or to those of them with a
+/// digest or from a package. A binary already bound is bound again.
+pub async fn add_identity(
+    AuthUser { claims }: AuthUser,
+    Extension(state): Extension<AppState>,
+    Json(body): Json<IdentityBindingRequest>,
+) -> Result<(StatusCode, Json<IdentityBinding>), (StatusCode, String)> {
+    if !has_permission(Arc::new(state.clone()), claims.user_id, "manage_policies").await? {
+        return Err((StatusCode::FORBIDDEN, "Permission denied".to_string()));
+    }
+
+    if let Err(validation_errors) = body.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Validation failed: {validation_errors}"),
+        ));
+    }
+    // The sandboxer sends canonical paths: anything else would never match
+    if !body.exe_path.starts_with('/') || body.exe_path.split('/').any(|c| c == "." || c == "..") {
+        return Err((StatusCode::BAD_REQUEST, "exe_path must be a canonical absolute path".into()));
+    }
+    let sha256 = body.sha256.map(|d| d.to_ascii_lowercase());
+    if sha256.as_ref().is_some_and(|d| !d.bytes().all(|b| b.is_ascii_hexdigit())) {
+        return Err((StatusCode::BAD_REQUEST, "sha256 must be 64 hex digits".into()));
+    }
+
+    let pool = state.db_pool.clone();
+
+    let binding = spawn_blocking(move || {
+        let mut conn = pool.get()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+        let row = conn.query_one(
+            "
+            INSERT INTO app_identities (app_name, exe_path, sha256, package)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (exe_path, COALESCE(sha256, ''), COALESCE(package, ''))
+            DO UPDATE SET app_name = EXCLUDED.app_name, created_at = NOW()
+            RETURNING identity_id
+            ",
+            &[&body.app_name, &body.exe_path, &sha256, &body.package],
+        ).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Insert error: {e}")))?;
+
+        Ok::<_, (StatusCode, String)>(IdentityBinding {
+            identity_id: row.get(0),
+            app_name: body.app_name,
+            exe_path: body.exe_path,
+            sha256,
+            package: body.package,
+        })
+    })
+    .await
+    .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Task join error".to_string()))??;
+
+    Ok((StatusCode::CREATED, Json(binding)))
+}
+
+/// Unbinds a policy from an identity.
+pub async fn delete_identity(
+    AuthUser { claims }: AuthUser,
+    Extension(state): Extension<AppState>,
+    Path(identity_id): Path<i32>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    if !has_permission(Arc::new(state.clone()), claims.user_id, "manage_policies").await? {
+        return Err((StatusCode::FORBIDDEN, "Permission denied".to_string()));
+    }
+
+    let pool = state.db_pool.clone();
+
+    let deleted = spawn_blocking(move || {
+        let mut conn = pool.get()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+
+        conn.execute("DELETE FROM app_identities WHERE identity_id = $1", &[&identity_id])
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Delete error: {e}")))
+    })
+    .await
+    .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Task join error".to_string()))??;
+
+    match deleted {
+        0 => Err((StatusCode::NOT_FOUND, "Identity not found".to_string())),
+        _ => Ok(StatusCode::NO_CONTENT),
+    }
+}
@@ -0,0 +1,2 @@
+pub mod handlers;
+pub use handlers::{add_identity, delete_identity, list_identities};
@@ -1,5 +1,6 @@
 mod auth;
 mod events;
+mod identity;
 mod models;
 mod policy;
 mod roles;
@@ -7,12 +8,13 @@ mod ruleset;
 mod state;
 mod utils;
 mod tls;
+mod signing;
 
 use axum::{
     extract::{Extension, Path},
     http::{Request, StatusCode},
     response::IntoResponse,
-    routing::{get, post},
+    routing::{delete, get, post, put},
     Router,
 };
 use axum_server::tls_rustls::RustlsConfig;
@@ -32,13 +34,17 @@ use tower_governor::{
 };
 use tracing::{debug, info};
 
-use crate::auth::handlers::{login, who_am_i};
-use crate::events::log_event;
+use crate::auth::handlers::{login, logout, refresh, who_am_i};
+use crate::events::{log_enforcement, log_event, log_event_batch};
+use crate::identity::{add_identity, delete_identity, list_identities};
 use crate::policy::handler::{
     add_app_policy, get_policy_requests, process_policy_request, request_policy_change,
 };
+use crate::policy::requests::{
+    amend_policy_request, list_own_requests, list_pending_requests, withdraw_policy_request,
+};
 use crate::roles::get_roles;
-use crate::ruleset::handlers::get_ruleset;
+use crate::ruleset::handlers::{get_effective_policy, get_ruleset, get_signed_ruleset};
 use crate::state::AppState;
 use crate::tls::generate_self_signed_cert;
 
@@ -90,7 +96,36 @@ async fn main() {
         .build(manager)
         .expect("Failed to create connection pool");
 
-    let app_state = AppState { db_pool: pool };
+    // 🔏 Policy signing key, for the workstations' offline cache
+    let policy_signing_key =
+        signing::load_or_generate_signing_key().expect("Failed to load policy signing key");
+    let policy_cache_ttl = std::env::var("POLICY_CACHE_TTL_HOURS")
+        .ok()
+        .and_then(|h| h.parse::<u64>().ok())
+        .unwrap_or(72)
+        * 3600;
+    let refresh_token_ttl = std::env::var("REFRESH_TOKEN_TTL_HOURS")
+        .ok()
+        .and_then(|h| h.parse::<u64>().ok())
+        .unwrap_or(8)
+        * 3600;
+    // Unset, binaries matching no identity get the default policy
+    let unknown_app_policy = std::env::var("UNKNOWN_APP_POLICY")
+        .ok()
+        .filter(|p| !p.is_empty());
+    let policy_request_ttl_days = std::env::var("POLICY_REQUEST_TTL_DAYS")
+        .ok()
+        .and_then(|d| d.parse::<i32>().ok())
+        .unwrap_or(30);
+
+    let app_state = AppState {
+        db_pool: pool,
+        policy_signing_key: Arc::new(policy_signing_key),
+        policy_cache_ttl,
+        refresh_token_ttl,
+        unknown_app_policy,
+        policy_request_ttl_days,
+    };
 
     // 🧠 Rate limiting
     let governor_cfg = Arc::new(
@@ -102,22 +137,50 @@ async fn main() {
             .expect("Failed to build rate limiter"),
     );
 
+    // Workstations flush their event spool in bursts after an outage: event
+    // ingestion gets its own, larger budget, so that a noisy workstation
+    // neither locks itself out of the other routes nor is refused its events
+    let ingestion_governor_cfg = Arc::new(
+        GovernorConfigBuilder::default()
+            .per_millisecond(100)
+            .burst_size(60)
+            .key_extractor(SafeIpExtractor)
+            .finish()
+            .expect("Failed to build ingestion rate limiter"),
+    );
+    let ingestion = Router::new()
+        .route("/events/batch", post(log_event_batch))
+        .layer(GovernorLayer {
+            config: ingestion_governor_cfg,
+        });
+
     // 🔧 App router
     let app = Router::new()
         .route("/", get(|| async { "Hello, World!" }))
         .route("/auth/login", post(login))
+        .route("/auth/refresh", post(refresh))
+        .route("/auth/logout", post(logout))
         .route("/whoami", get(who_am_i))
         .route("/auth/roles", get(get_roles))
         .route("/auth/ruleset", get(get_ruleset))
+        .route("/auth/ruleset/signed", get(get_signed_ruleset))
+        .route("/auth/ruleset/effective", get(get_effective_policy))
         .route("/auth/ruleset/update", post(add_app_policy))
         .route("/events/log", post(log_event))
+        .route("/events/enforcement", post(log_enforcement))
         .route("/policy/request", post(request_policy_change))
+        .route("/policy/request/{request_id}", put(amend_policy_request).delete(withdraw_policy_request))
+        .route("/policy/requests", get(list_own_requests))
+        .route("/policy/pending-requests", get(list_pending_requests))
         .route("/admin/policy/requests", get(get_policy_requests))
         .route("/admin/policy/requests/{request_id}", post(process_policy_request))
+        .route("/admin/apps/identities", get(list_identities).post(add_identity))
+        .route("/admin/apps/identities/{identity_id}", delete(delete_identity))
         .route("/.well-known/acme-challenge/{token}", get(serve_acme_challenge)) 
         .layer(GovernorLayer {
             config: governor_cfg,
         })
+        .merge(ingestion)
         .layer(Extension(app_state));
 
     // 🔐 Load TLS config
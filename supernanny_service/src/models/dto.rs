@@ -10,6 +10,56 @@ pub struct LoginRequest {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AuthResponse {
     pub token: String,
+    /// Opaque, single use: `/auth/refresh` trades it for a new pair
+    pub refresh_token: String,
+    /// Lifetime of `token`, in seconds
+    pub expires_in: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+/// Filters of `/auth/ruleset`
+#[derive(Deserialize, Debug)]
+pub struct RuleSetQuery {
+    /// Only the policy of this application
+    pub app_name: Option<String>,
+    /// Workstation asking, for its host baseline
+    pub hostname: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EffectivePolicyQuery {
+    /// Canonical path of the binary, or of the script it interprets
+    pub app_name: String,
+    /// SHA-256 digest of the binary, in hex
+    pub sha256: Option<String>,
+    /// Package owning the binary
+    pub package: Option<String>,
+    /// The interpreter running the `app_name` script, whose policy applies
+    /// when the script has none
+    pub interpreter: Option<String>,
+    pub interpreter_sha256: Option<String>,
+    pub interpreter_package: Option<String>,
+    pub hostname: Option<String>,
+}
+
+/// Body of `POST /admin/apps/identities`
+#[derive(Deserialize, Debug, Validate)]
+pub struct IdentityBindingRequest {
+    #[validate(length(min = 1, message = "app_name cannot be empty"))]
+    pub app_name: String,
+
+    #[validate(length(min = 1, message = "exe_path cannot be empty"))]
+    pub exe_path: String,
+
+    #[validate(length(equal = 64, message = "sha256 must be 64 hex digits"))]
+    pub sha256: Option<String>,
+
+    #[validate(length(min = 1, message = "package cannot be empty"))]
+    pub package: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -18,7 +68,7 @@ pub struct RoleInfo {
     pub permissions: Vec<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Validate)]
+#[derive(Debug, Clone, Deserialize, Serialize, Validate)]
 pub struct LogEventRequest {
     #[validate(length(min = 1, message = "hostname cannot be empty"))]
     pub hostname: String,
@@ -37,6 +87,84 @@ pub struct LogEventRequest {
     pub remote_ip: Option<String>,
 
     pub domain: Option<String>,
+
+    #[serde(default)]
+    pub cgroup_id: Option<i64>,
+
+    /// Canonical path of the script the application interprets
+    #[serde(default)]
+    pub script: Option<String>,
+
+    #[serde(default)]
+    pub script_sha256: Option<String>,
+}
+
+/// A sandbox event spooled by a workstation, sent again until acknowledged.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BatchedEvent {
+    /// Random id chosen by the workstation, recorded once whatever the retries
+    pub event_id: String,
+
+    /// When the event happened, as a Unix timestamp in seconds
+    pub occurred_at: i64,
+
+    #[serde(flatten)]
+    pub event: LogEventRequest,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogEventBatch {
+    pub events: Vec<BatchedEvent>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventStatus {
+    /// Newly recorded
+    Accepted,
+    /// Already recorded, by an earlier delivery or earlier in the batch
+    Duplicate,
+    /// Invalid, not recorded; sending it again will not help
+    Rejected,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchedEventStatus {
+    pub event_id: String,
+    pub status: EventStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LogEventBatchResponse {
+    pub accepted: usize,
+    pub duplicates: usize,
+    pub rejected: usize,
+    /// One status per event, in the order of the batch
+    pub results: Vec<BatchedEventStatus>,
+}
+
+/// What Landlock actually enforced for one sandboxed run.
+#[derive(Debug, Deserialize, Serialize, Validate)]
+pub struct LogEnforcementRequest {
+    #[validate(length(min = 1, message = "hostname cannot be empty"))]
+    pub hostname: String,
+
+    #[validate(length(min = 1, message = "app_name cannot be empty"))]
+    pub app_name: String,
+
+    #[validate(range(min = 0, message = "abi cannot be negative"))]
+    pub abi: i32,
+
+    /// `fully_enforced`, `partially_enforced` or `not_enforced`.
+    pub status: String,
+
+    #[validate(range(min = 0, message = "skipped_rules cannot be negative"))]
+    pub skipped_rules: i32,
+
+    /// Full report as sent by the sandboxer.
+    pub report: serde_json::Value,
 }
 
 #[derive(Debug, Deserialize)]
@@ -49,18 +177,48 @@ pub struct AppPolicyCreateRequest {
     pub tcp_connect: String,
     pub allowed_ips: String,
     pub allowed_domains: String,
+    #[serde(default)]
+    pub fs_rules: String,
+    #[serde(default)]
+    pub scoped: String,
+    #[serde(default)]
+    pub seccomp: String,
+    #[serde(default)]
+    pub resources: String,
+    #[serde(default)]
+    pub isolation: String,
+    #[serde(default)]
+    pub environment: String,
+    /// Recorded with the new version in the policy history
+    #[serde(default)]
+    pub reason: String,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct PolicyChangeRequest {
     pub app_name: String,
-    pub role_id: i32,
+    /// One of the requester's roles; unset, the first of them with a policy
+    /// for the app, else their first role
+    #[serde(default)]
+    pub role_id: Option<i32>,
     pub default_ro: String,       
     pub default_rw: String,
     pub tcp_bind: String,
     pub tcp_connect: String,
     pub allowed_ips: String,      
     pub allowed_domains: String,  
+    #[serde(default)]
+    pub fs_rules: String,
+    #[serde(default)]
+    pub scoped: String,
+    #[serde(default)]
+    pub seccomp: String,
+    #[serde(default)]
+    pub resources: String,
+    #[serde(default)]
+    pub isolation: String,
+    #[serde(default)]
+    pub environment: String,
     pub allowed_ro_paths: Vec<String>,     
     pub allowed_rw_paths: Vec<String>,     
     pub change_justification: String,
@@ -85,8 +243,67 @@ pub struct PolicyRequestDetail {
     pub tcp_connect: String,
     pub allowed_ips: String,
     pub allowed_domains: String,
+    pub fs_rules: String,
+    pub scoped: String,
+    pub seccomp: String,
+    pub resources: String,
+    pub isolation: String,
+    pub environment: String,
 
     pub change_justification: String,
+    pub amended_at: Option<String>,
+    pub reviewed_at: Option<String>,
+    pub superseded_by: Option<i32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PolicyRequestQuery {
+    pub status: Option<String>,
+    pub app_name: Option<String>,
+}
+
+/// Status of a policy change request. A pending request changes once, to any
+/// other status; the others are final.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestStatus {
+    Pending,
+    Approved,
+    Rejected,
+    /// Withdrawn by its author
+    Withdrawn,
+    /// Replaced by a later request of its author for the same app and role
+    Superseded,
+    /// Left pending longer than `POLICY_REQUEST_TTL_DAYS`
+    Expired,
+}
+
+impl RequestStatus {
+    pub fn parse(status: &str) -> Option<Self> {
+        Some(match status {
+            "pending" => Self::Pending,
+            "approved" => Self::Approved,
+            "rejected" => Self::Rejected,
+            "withdrawn" => Self::Withdrawn,
+            "superseded" => Self::Superseded,
+            "expired" => Self::Expired,
+            _ => return None,
+        })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Approved => "approved",
+            Self::Rejected => "rejected",
+            Self::Withdrawn => "withdrawn",
+            Self::Superseded => "superseded",
+            Self::Expired => "expired",
+        }
+    }
+
+    pub fn can_become(self, next: Self) -> bool {
+        self == Self::Pending && next != Self::Pending
+    }
 }
 
 
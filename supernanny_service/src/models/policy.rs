@@ -1,6 +1,6 @@
 use serde::Serialize;
 
-#[derive(Serialize)]
+#[derive(Serialize, Default)]
 pub struct Policy {
     pub default_ro: String,
     pub default_rw: String,
@@ -8,16 +8,112 @@ pub struct Policy {
     pub tcp_connect: String,
     pub allowed_ips: String,
     pub allowed_domains: String,
+    pub fs_rules: String,
+    pub scoped: String,
+    pub seccomp: String,
+    pub resources: String,
+    pub isolation: String,
+    pub environment: String,
+}
+
+/// A policy as stored for one role
+pub struct RolePolicy {
+    pub role_id: i32,
+    /// `None` for the role's default policy
+    pub app_name: Option<String>,
+    pub policy: Policy,
+}
+
+/// A baseline applied to every user, on every host (`*`) or on one
+pub struct BaselinePolicy {
+    pub hostname: String,
+    pub policy: Policy,
+}
+
+/// Binds the policy named `app_name` to the binaries at `exe_path`, and
+/// only to those with this digest or from this package when given
+#[derive(Serialize, Clone)]
+pub struct IdentityBinding {
+    pub identity_id: i32,
+    pub app_name: String,
+    pub exe_path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+}
+
+/// A rule of an effective policy, and the roles it comes from
+#[derive(Serialize)]
+pub struct RuleSource {
+    pub field: &'static str,
+    pub rule: String,
+    pub role_ids: Vec<i32>,
+    /// Host pattern of the baseline that imposes, or also grants, the rule
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub baseline: Option<String>,
+}
+
+/// One step of the resolution of an effective policy
+#[derive(Serialize)]
+pub struct ResolutionStep {
+    /// `identity`, `app`, `role_default`, `no_policy`, `merge` or `baseline`
+    pub layer: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    pub detail: String,
+}
+
+/// The single policy resolved for one application of the user
+#[derive(Serialize)]
+pub struct EffectivePolicyResponse {
+    pub app_name: String,
+    pub hostname: Option<String>,
+    pub role_ids: Vec<i32>,
+    pub policy: Policy,
+    pub sources: Vec<RuleSource>,
+    pub trace: Vec<ResolutionStep>,
 }
 
 #[derive(Serialize)]
 pub struct AppRuleSet {
     pub app_name: String,
     pub policy: Policy,
+    pub sources: Vec<RuleSource>,
 }
 
+/// Effective policies of a user, merged from all of their roles
 #[derive(Serialize)]
 pub struct RuleSetResponse {
+    pub role_ids: Vec<i32>,
+    /// The merged default policy, absent when neither the roles nor the
+    /// baselines have one
     pub default_policies: Vec<Policy>,
+    pub default_sources: Vec<RuleSource>,
     pub app_policies: Vec<AppRuleSet>,
+    /// For workstations to pick the policy of a binary offline
+    pub identities: Vec<IdentityBinding>,
+    /// Policy of the binaries no identity matches, the default policy when
+    /// `None`
+    pub unknown_app_policy: Option<String>,
+}
+
+/// Policies of a user's roles, as cached by workstations for offline use
+#[derive(Serialize)]
+pub struct PolicyBundle {
+    pub username: String,
+    pub role_ids: Vec<i32>,
+    /// Unix timestamps, in seconds
+    pub issued_at: u64,
+    pub expires_at: u64,
+    pub ruleset: RuleSetResponse,
+}
+
+/// Base64 of a JSON `PolicyBundle` and of its Ed25519 signature
+#[derive(Serialize)]
+pub struct SignedRuleSet {
+    pub payload: String,
+    pub signature: String,
 }
@@ -12,11 +12,21 @@ use crate::{
         AppPolicyCreateRequest,
         PolicyChangeRequest,
         PolicyRequestDetail,
-        PolicyRequestDecision
+        PolicyRequestDecision,
+        RequestStatus,
+    },
+    policy::history::record_version,
+    policy::requests::{
+        check_transition, expire_stale_requests, request_detail, request_role,
+        validate_change_request, REQUEST_COLUMNS,
     },
     state::AppState,
     utils::permissions::has_permission,
 };
+use supernanny_policy_syntax::{
+    merge_fs_rules, merge_seccomp, validate_environment, validate_fs_rules, validate_isolation,
+    validate_path_list, validate_resources, validate_scopes, validate_seccomp,
+};
 use tokio::task::spawn_blocking;
 use std::collections::HashSet;
 
@@ -29,6 +39,16 @@ pub async fn add_app_policy(
         return Err((StatusCode::FORBIDDEN, "Permission denied".to_string()));
     }
 
+    for paths in [&body.default_ro, &body.default_rw] {
+        validate_path_list(paths).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    }
+    validate_fs_rules(&body.fs_rules).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    validate_scopes(&body.scoped).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    validate_seccomp(&body.seccomp).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    validate_resources(&body.resources).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    validate_isolation(&body.isolation).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    validate_environment(&body.environment).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
     // Clone everything we'll need in the blocking task
     let app_name = body.app_name.clone();
     let role_id = body.role_id;
@@ -38,6 +58,14 @@ pub async fn add_app_policy(
     let tcp_connect = body.tcp_connect.clone();
     let allowed_ips = body.allowed_ips.clone();
     let allowed_domains = body.allowed_domains.clone();
+    let fs_rules = body.fs_rules.clone();
+    let scoped = body.scoped.clone();
+    let seccomp = body.seccomp.clone();
+    let resources = body.resources.clone();
+    let isolation = body.isolation.clone();
+    let environment = body.environment.clone();
+    let reason = body.reason.clone();
+    let author = claims.sub.clone();
     
     // Get a pool we can move into the blocking task
     let pool = state.db_pool.clone();
@@ -45,14 +73,16 @@ pub async fn add_app_policy(
     spawn_blocking(move || {
         let mut conn = pool.get()
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+        let mut tx = conn.transaction()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {e}")))?;
 
-        conn.execute(
+        tx.execute(
             "
             INSERT INTO app_policy (
                 app_name, role_id, default_ro, default_rw, tcp_bind, tcp_connect,
-                allowed_ips, allowed_domains, updated_at
+                allowed_ips, allowed_domains, fs_rules, scoped, seccomp, resources, isolation, environment, updated_at
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, NOW())
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, NOW())
             ON CONFLICT (app_name, role_id)
             DO UPDATE SET
                 default_ro = EXCLUDED.default_ro,
@@ -61,6 +91,12 @@ pub async fn add_app_policy(
                 tcp_connect = EXCLUDED.tcp_connect,
                 allowed_ips = EXCLUDED.allowed_ips,
                 allowed_domains = EXCLUDED.allowed_domains,
+                fs_rules = EXCLUDED.fs_rules,
+                scoped = EXCLUDED.scoped,
+                seccomp = EXCLUDED.seccomp,
+                resources = EXCLUDED.resources,
+                isolation = EXCLUDED.isolation,
+                environment = EXCLUDED.environment,
                 updated_at = NOW()
             ",
             &[
@@ -72,9 +108,20 @@ pub async fn add_app_policy(
                 &tcp_connect,
                 &allowed_ips,
                 &allowed_domains,
+                &fs_rules,
+                &scoped,
+                &seccomp,
+                &resources,
+                &isolation,
+                &environment,
             ],
         ).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Insert error: {e}")))?;
 
+        record_version(&mut tx, &app_name, role_id, &author, &reason, None)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("History error: {e}")))?;
+        tx.commit()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Commit error: {e}")))?;
+
         Ok::<_, (StatusCode, String)>(())
     })
     .await
@@ -83,6 +130,8 @@ pub async fn add_app_policy(
     Ok(StatusCode::CREATED)
 }
 
+/// Requests a change to a policy. A pending request of the user for the same
+/// app and role is superseded by this one.
 pub async fn request_policy_change(
     AuthUser { claims }: AuthUser,
     Extension(state): Extension<AppState>,
@@ -91,82 +140,89 @@ pub async fn request_policy_change(
     if !has_permission(Arc::new(state.clone()), claims.user_id, "manage_policies").await? {
         return Err((StatusCode::FORBIDDEN, "Permission denied".to_string()));
     }
+    validate_change_request(&request)?;
 
-    // Clone all the data we need to move into the blocking task
-    let app_name = request.app_name.clone();
-    let role_id = request.role_id;
     let user_id = claims.user_id;
-    let default_ro = request.default_ro.clone();
-    let default_rw = request.default_rw.clone();
-    let tcp_bind = request.tcp_bind.clone();
-    let tcp_connect = request.tcp_connect.clone();
-    let allowed_ips = request.allowed_ips.clone();
-    let allowed_domains = request.allowed_domains.clone();
-    let allowed_ro_paths = request.allowed_ro_paths.clone();
-    let allowed_rw_paths = request.allowed_rw_paths.clone();
-    let change_justification = request.change_justification.clone();
-    
-    // Clone the pool to move it
+    let ttl_days = state.policy_request_ttl_days;
     let pool = state.db_pool.clone();
     
     spawn_blocking(move || {
         let mut conn = pool.get()
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
-
-        let has_role = conn.query_one(
-            "SELECT EXISTS(SELECT 1 FROM user_roles WHERE user_id = $1 AND role_id = $2)",
-            &[&user_id, &role_id]
+        let mut tx = conn.transaction()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {e}")))?;
+        expire_stale_requests(&mut tx, ttl_days)?;
+
+        let role_id = request_role(&mut tx, user_id, &request.app_name, request.role_id)?;
+        let request_id: i32 = tx.query_one(
+            "SELECT nextval(pg_get_serial_sequence('policy_change_requests', 'request_id'))::INT",
+            &[],
         ).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Query error: {e}")))?
-        .get::<_, bool>(0);
+        .get(0);
 
-        if !has_role {
-            return Err((StatusCode::FORBIDDEN, "You can only request changes for roles you belong to".to_string()));
-        }
+        // Only one request of a user per app and role is pending
+        let superseded = tx.query(
+            "UPDATE policy_change_requests SET status = 'superseded', superseded_by = $4
+             WHERE app_name = $1 AND role_id = $2 AND requested_by = $3 AND status = 'pending'
+             RETURNING request_id",
+            &[&request.app_name, &role_id, &user_id, &request_id],
+        ).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Supersede error: {e}")))?;
 
-        conn.execute(
+        tx.execute(
             "INSERT INTO policy_change_requests (
-                app_name, role_id, requested_by, status,
+                request_id, app_name, role_id, requested_by, status,
                 default_ro, default_rw, tcp_bind, tcp_connect,
-                allowed_ips, allowed_domains,
+                allowed_ips, allowed_domains, fs_rules, scoped, seccomp, resources, isolation, environment,
                 allowed_ro_paths, allowed_rw_paths,
                 change_justification
             ) VALUES (
-                $1, $2, $3, 'pending',
-                $4, $5, $6, $7,
-                $8, $9,
-                $10, $11,
-                $12
+                $1, $2, $3, $4, 'pending',
+                $5, $6, $7, $8,
+                $9, $10, $11, $12, $13, $14, $15, $16,
+                $17, $18,
+                $19
             )",
             &[
-                &app_name,
+                &request_id,
+                &request.app_name,
                 &role_id,
                 &user_id,
-                &default_ro,
-                &default_rw,
-                &tcp_bind,
-                &tcp_connect,
-                &allowed_ips,
-                &allowed_domains,
-                &allowed_ro_paths,
-                &allowed_rw_paths,
-                &change_justification,
+                &request.default_ro,
+                &request.default_rw,
+                &request.tcp_bind,
+                &request.tcp_connect,
+                &request.allowed_ips,
+                &request.allowed_domains,
+                &request.fs_rules,
+                &request.scoped,
+                &request.seccomp,
+                &request.resources,
+                &request.isolation,
+                &request.environment,
+                &request.allowed_ro_paths,
+                &request.allowed_rw_paths,
+                &request.change_justification,
             ]
         ).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Insert error: {e}")))?;
 
         // Log it - note we need to create a string compatible with &str for the query
-        let detail = format!(
-            "Requested policy change for app {} for role ID {}",
-            app_name, role_id
+        let mut detail = format!(
+            "Requested policy change #{} for app {} for role ID {}",
+            request_id, request.app_name, role_id
         );
+        if let Some(old) = superseded.first() {
+            detail.push_str(&format!(", superseding #{}", old.get::<_, i32>(0)));
+        }
         
-        conn.execute(
+        tx.execute(
             "INSERT INTO security_logs (username, action, detail, severity)
              SELECT u.username, 'policy_change_requested', $1, 'info'
              FROM users u WHERE u.user_id = $2",
             &[&detail, &user_id]
         ).ok();
 
-        Ok::<_, (StatusCode, String)>(())
+        tx.commit()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Commit error: {e}")))
     })
     .await
     .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Task join error".to_string()))??;
@@ -185,20 +241,16 @@ pub async fn get_policy_requests(
 
     // Clone params and whatever else we need
     let params_clone = params.clone();
-    let _user_id = claims.user_id;
+    let ttl_days = state.policy_request_ttl_days;
     let pool = state.db_pool.clone();
     
     let requests = spawn_blocking(move || {
         let mut conn = pool.get()
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+        expire_stale_requests(&mut *conn, ttl_days)?;
 
-        let mut query = String::from(
-            "SELECT 
-                pcr.request_id, pcr.app_name, pcr.role_id, r.role_name,
-                u.username as requested_by, pcr.requested_at::text, 
-                pcr.status::text, -- Cast enum to text
-                pcr.default_ro, pcr.default_rw, pcr.tcp_bind, pcr.tcp_connect,
-                pcr.allowed_ips, pcr.allowed_domains, pcr.change_justification
+        let mut query = format!(
+            "SELECT {REQUEST_COLUMNS}
              FROM policy_change_requests pcr
              JOIN roles r ON pcr.role_id = r.role_id
              JOIN users u ON pcr.requested_by = u.user_id
@@ -240,24 +292,7 @@ pub async fn get_policy_requests(
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Query error: {e}")))?;
         
         tracing::info!("Fetched {} rows from DB", rows.len());
-        let requests: Vec<PolicyRequestDetail> = rows.iter().map(|row| {
-            PolicyRequestDetail {
-                request_id: row.get("request_id"),
-                app_name: row.get("app_name"),
-                role_id: row.get("role_id"),
-                role_name: row.get("role_name"),
-                requested_by: row.get("requested_by"),
-                requested_at: row.get("requested_at"),
-                status: row.get("status"),
-                default_ro: row.get("default_ro"),
-                default_rw: row.get("default_rw"),
-                tcp_bind: row.get("tcp_bind"),
-                tcp_connect: row.get("tcp_connect"),
-                allowed_ips: row.get("allowed_ips"),
-                allowed_domains: row.get("allowed_domains"),
-                change_justification: row.get("change_justification"),
-            }
-        }).collect();
+        let requests: Vec<PolicyRequestDetail> = rows.iter().map(request_detail).collect();
         tracing::info!("Returning {} requests", requests.len());
         Ok::<_, (StatusCode, String)>(requests)
     })
@@ -278,28 +313,42 @@ pub async fn process_policy_request(
     }
 
     let user_id = claims.user_id;
+    let reviewer = claims.sub.clone();
+    let ttl_days = state.policy_request_ttl_days;
     let pool = state.db_pool.clone();
 
     spawn_blocking(move || {
         let mut conn = pool.get().map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+        // Expired requests can no longer be approved
+        expire_stale_requests(&mut *conn, ttl_days)?;
         conn.execute("BEGIN", &[]).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {e}")))?;
 
         let request = conn.query_opt(
             "SELECT request_id, app_name, role_id, requested_by, 
                     default_ro, default_rw, tcp_bind, tcp_connect,
-                    allowed_ips, allowed_domains
+                    allowed_ips, allowed_domains, fs_rules, scoped, seccomp, resources, isolation, environment,
+                    change_justification, status::text
              FROM policy_change_requests 
-             WHERE request_id = $1 AND status = 'pending'",
+             WHERE request_id = $1 FOR UPDATE",
             &[&request_id]
         ).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Query error: {e}")))?;
 
         let row = match request {
             Some(row) => row,
-            None => return Err((StatusCode::NOT_FOUND, "Request not found or already processed".to_string())),
+            None => {
+                let _ = conn.execute("ROLLBACK", &[]);
+                return Err((StatusCode::NOT_FOUND, "Request not found".to_string()));
+            }
         };
+        let next = if decision.approve { RequestStatus::Approved } else { RequestStatus::Rejected };
+        if let Err(e) = check_transition(request_id, row.get("status"), next) {
+            let _ = conn.execute("ROLLBACK", &[]);
+            return Err(e);
+        }
 
         let requested_by: i32 = row.get("requested_by");
         if requested_by == user_id {
+            let _ = conn.execute("ROLLBACK", &[]);
             return Err((StatusCode::FORBIDDEN, "Cannot approve your own policy change requests".to_string()));
         }
 
@@ -311,6 +360,12 @@ pub async fn process_policy_request(
         let new_connect: String = row.get("tcp_connect");
         let new_ips: String = row.get("allowed_ips");
         let new_domains: String = row.get("allowed_domains");
+        let new_fs_rules: String = row.get("fs_rules");
+        let new_scoped: String = row.get("scoped");
+        let new_seccomp: String = row.get("seccomp");
+        let new_resources: String = row.get("resources");
+        let new_isolation: String = row.get("isolation");
+        let new_environment: String = row.get("environment");
 
         // Merge function for colon-separated values
         let merge_colon_strings = |existing: String, new: String| -> String {
@@ -323,9 +378,9 @@ pub async fn process_policy_request(
             merged.join(":")
         };
 
-        let (final_ro, final_rw, final_bind, final_connect, final_ips, final_domains) =
+        let (final_ro, final_rw, final_bind, final_connect, final_ips, final_domains, final_fs_rules, final_scoped, final_seccomp, final_resources, final_isolation, final_environment) =
             if let Some(existing_row) = conn.query_opt(
-                "SELECT default_ro, default_rw, tcp_bind, tcp_connect, allowed_ips, allowed_domains
+                "SELECT default_ro, default_rw, tcp_bind, tcp_connect, allowed_ips, allowed_domains, fs_rules, scoped, seccomp, resources, isolation, environment
                  FROM app_policy WHERE app_name = $1 AND role_id = $2",
                 &[&app_name, &role_id]
             ).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Fetch existing policy error: {e}")))? {
@@ -336,18 +391,27 @@ pub async fn process_policy_request(
                     merge_colon_strings(existing_row.get("tcp_connect"), new_connect),
                     merge_colon_strings(existing_row.get("allowed_ips"), new_ips),
                     merge_colon_strings(existing_row.get("allowed_domains"), new_domains),
+                    merge_fs_rules(existing_row.get("fs_rules"), &new_fs_rules),
+                    merge_colon_strings(existing_row.get("scoped"), new_scoped),
+                    merge_seccomp(existing_row.get("seccomp"), &new_seccomp),
+                    // Limits replace each other instead of adding up
+                    if new_resources.is_empty() { existing_row.get("resources") } else { new_resources },
+                    // So do isolation options, which carry the hostname
+                    if new_isolation.is_empty() { existing_row.get("isolation") } else { new_isolation },
+                    // And the environment section, as forced values would clash
+                    if new_environment.is_empty() { existing_row.get("environment") } else { new_environment },
                 )
             } else {
-                (new_ro, new_rw, new_bind, new_connect, new_ips, new_domains)
+                (new_ro, new_rw, new_bind, new_connect, new_ips, new_domains, new_fs_rules, new_scoped, new_seccomp, new_resources, new_isolation, new_environment)
             };
 
         if decision.approve {
             conn.execute(
                 "INSERT INTO app_policy (
                     app_name, role_id, default_ro, default_rw, tcp_bind, tcp_connect,
-                    allowed_ips, allowed_domains, updated_at
+                    allowed_ips, allowed_domains, fs_rules, scoped, seccomp, resources, isolation, environment, updated_at
                 )
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, NOW())
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, NOW())
                 ON CONFLICT (app_name, role_id)
                 DO UPDATE SET
                     default_ro = $3,
@@ -356,13 +420,25 @@ pub async fn process_policy_request(
                     tcp_connect = $6,
                     allowed_ips = $7,
                     allowed_domains = $8,
+                    fs_rules = $9,
+                    scoped = $10,
+                    seccomp = $11,
+                    resources = $12,
+                    isolation = $13,
+                    environment = $14,
                     updated_at = NOW()",
-                &[&app_name, &role_id, &final_ro, &final_rw, &final_bind, &final_connect, &final_ips, &final_domains]
+                &[&app_name, &role_id, &final_ro, &final_rw, &final_bind, &final_connect, &final_ips, &final_domains, &final_fs_rules, &final_scoped, &final_seccomp, &final_resources, &final_isolation, &final_environment]
             ).map_err(|e| {
                 let _ = conn.execute("ROLLBACK", &[]);
                 (StatusCode::INTERNAL_SERVER_ERROR, format!("Policy update error: {e}"))
             })?;
 
+            let justification: String = row.get("change_justification");
+            record_version(&mut *conn, &app_name, role_id, &reviewer, &justification, Some(request_id)).map_err(|e| {
+                let _ = conn.execute("ROLLBACK", &[]);
+                (StatusCode::INTERNAL_SERVER_ERROR, format!("History error: {e}"))
+            })?;
+
             conn.execute(
                 "UPDATE policy_change_requests SET status = 'approved', reviewed_by = $1, reviewed_at = NOW() WHERE request_id = $2",
                 &[&user_id, &request_id]
@@ -0,0 +1,34 @@
+use postgres::GenericClient;
+
+/// Records the current state of the `app_name` policy for `role_id` as its
+/// next version, in the transaction that changed it. `request_id` links the
+/// version to the change request whose approval produced it.
+pub fn record_version(
+    conn: &mut impl GenericClient,
+    app_name: &str,
+    role_id: i32,
+    author: &str,
+    reason: &str,
+    request_id: Option<i32>,
+) -> Result<i32, postgres::Error> {
+    let row = conn.query_one(
+        "
+        INSERT INTO app_policy_versions (
+            app_name, role_id, version, default_ro, default_rw, tcp_bind, tcp_connect,
+            allowed_ips, allowed_domains, fs_rules, scoped, seccomp, resources, isolation, environment,
+            author, reason, request_id
+        )
+        SELECT p.app_name, p.role_id,
+               COALESCE((SELECT MAX(v.version) FROM app_policy_versions v
+                         WHERE v.app_name = p.app_name AND v.role_id = p.role_id), 0) + 1,
+               p.default_ro, p.default_rw, p.tcp_bind, p.tcp_connect,
+               p.allowed_ips, p.allowed_domains, p.fs_rules, p.scoped, p.seccomp, p.resources, p.isolation, p.environment,
+               $3, $4, $5
+        FROM app_policy p
+        WHERE p.app_name = $1 AND p.role_id = $2
+        RETURNING version
+        ",
+        &[&app_name, &role_id, &author, &reason, &request_id],
+    )?;
+    Ok(row.get(0))
+}
@@ -1 +1,3 @@
 pub mod handler;
+pub mod history;
+pub mod requests;
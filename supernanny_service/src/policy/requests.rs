@@ -0,0 +1,318 @@
+use axum::{
+    extract::{Extension, Json, Path, Query},
+    http::StatusCode,
+};
+use postgres::{GenericClient, Row};
+use std::sync::Arc;
+use tokio::task::spawn_blocking;
+
+use crate::{
+    auth::jwt::AuthUser,
+    models::dto::{PolicyChangeRequest, PolicyRequestDetail, PolicyRequestQuery, RequestStatus},
+    state::AppState,
+    utils::permissions::has_permission,
+};
+use supernanny_policy_syntax::{
+    validate_environment, validate_fs_rules, validate_isolation, validate_path_list,
+    validate_resources, validate_scopes, validate_seccomp,
+};
+
+/// Columns read by `request_detail`, from `policy_change_requests pcr` joined
+/// with `roles r` and `users u`
+pub const REQUEST_COLUMNS: &str = "
+    pcr.request_id, pcr.app_name, pcr.role_id, r.role_name,
+    u.username as requested_by, pcr.requested_at::text,
+    pcr.status::text, -- Cast enum to text
+    pcr.default_ro, pcr.default_rw, pcr.tcp_bind, pcr.tcp_connect,
+    pcr.allowed_ips, pcr.allowed_domains, pcr.fs_rules, pcr.scoped, pcr.seccomp, pcr.resources, pcr.isolation, pcr.environment,
+    pcr.change_justification, pcr.amended_at::text, pcr.reviewed_at::text, pcr.superseded_by";
+
+pub fn request_detail(row: &Row) -> PolicyRequestDetail {
+    PolicyRequestDetail {
+        request_id: row.get("request_id"),
+        app_name: row.get("app_name"),
+        role_id: row.get("role_id"),
+        role_name: row.get("role_name"),
+        requested_by: row.get("requested_by"),
+        requested_at: row.get("requested_at"),
+        status: row.get("status"),
+        default_ro: row.get("default_ro"),
+        default_rw: row.get("default_rw"),
+        tcp_bind: row.get("tcp_bind"),
+        tcp_connect: row.get("tcp_connect"),
+        allowed_ips: row.get("allowed_ips"),
+        allowed_domains: row.get("allowed_domains"),
+        fs_rules: row.get("fs_rules"),
+        scoped: row.get("scoped"),
+        seccomp: row.get("seccomp"),
+        resources: row.get("resources"),
+        isolation: row.get("isolation"),
+        environment: row.get("environment"),
+        change_justification: row.get("change_justification"),
+        amended_at: row.get("amended_at"),
+        reviewed_at: row.get("reviewed_at"),
+        superseded_by: row.get("superseded_by"),
+    }
+}
+
+pub fn validate_change_request(request: &PolicyChangeRequest) -> Result<(), (StatusCode, String)> {
+    for paths in [&request.default_ro, &request.default_rw] {
+        validate_path_list(paths).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    }
+    for path in request.allowed_ro_paths.iter().chain(&request.allowed_rw_paths) {
+        validate_path_list(path).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    }
+    validate_fs_rules(&request.fs_rules).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    validate_scopes(&request.scoped).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    validate_seccomp(&request.seccomp).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    validate_resources(&request.resources).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    validate_isolation(&request.isolation).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    validate_environment(&request.environment).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    Ok(())
+}
+
+/// Expires the requests left pending more than `ttl_days` since they were
+/// made or last amended
+pub fn expire_stale_requests(conn: &mut impl GenericClient, ttl_days: i32) -> Result<u64, (StatusCode, String)> {
+    conn.execute(
+        "UPDATE policy_change_requests SET status = 'expired'
+         WHERE status = 'pending'
+           AND COALESCE(amended_at, requested_at) < NOW() - make_interval(days => $1)",
+        &[&ttl_days],
+    )
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Expiry error: {e}")))
+}
+
+/// The role a request of `user_id` for `app_name` is made for: `role_id` if
+/// the user has it, else the first of their roles with a policy for the app,
+/// else their first role
+pub fn request_role(
+    conn: &mut impl GenericClient,
+    user_id: i32,
+    app_name: &str,
+    role_id: Option<i32>,
+) -> Result<i32, (StatusCode, String)> {
+    let row = conn.query_opt(
+        "SELECT ur.role_id
+         FROM user_roles ur
+         LEFT JOIN app_policy ap ON ap.role_id = ur.role_id AND ap.app_name = $2
+         WHERE ur.user_id = $1 AND ($3::INT IS NULL OR ur.role_id = $3)
+         ORDER BY ap.policy_id IS NULL, ur.role_id
+         LIMIT 1",
+        &[&user_id, &app_name, &role_id],
+    )
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Query error: {e}")))?;
+
+    match (row, role_id) {
+        (Some(row), _) => Ok(row.get(0)),
+        (None, Some(_)) => Err((StatusCode::FORBIDDEN, "You can only request changes for roles you belong to".to_string())),
+        (None, None) => Err((StatusCode::FORBIDDEN, "You have no role to request changes for".to_string())),
+    }
+}
+
+/// Locks request `request_id` of `user_id` for a change to `next`, returning
+/// its app and role
+fn lock_own_request(
+    conn: &mut impl GenericClient,
+    request_id: i32,
+    user_id: i32,
+    next: RequestStatus,
+) -> Result<(String, i32), (StatusCode, String)> {
+    let row = conn.query_opt(
+        "SELECT app_name, role_id, requested_by, status::text FROM policy_change_requests
+         WHERE request_id = $1 FOR UPDATE",
+        &[&request_id],
+    )
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Query error: {e}")))?
+    .ok_or((StatusCode::NOT_FOUND, "Request not found".to_string()))?;
+
+    if row.get::<_, i32>("requested_by") != user_id {
+        return Err((StatusCode::FORBIDDEN, "You can only change your own policy change requests".to_string()));
+    }
+    check_transition(request_id, row.get("status"), next)?;
+    Ok((row.get("app_name"), row.get("role_id")))
+}
+
+/// Refuses to move request `request_id` from `status` to `next`; amending
+/// leaves a request pending
+pub fn check_transition(request_id: i32, status: &str, next: RequestStatus) -> Result<(), (StatusCode, String)> {
+    let current = RequestStatus::parse(status)
+        .ok_or((StatusCode::INTERNAL_SERVER_ERROR, format!("Unknown request status {status}")))?;
+    if (current == RequestStatus::Pending && next == RequestStatus::Pending) || current.can_become(next) {
+        return Ok(());
+    }
+    Err((StatusCode::CONFLICT, format!("Request #{request_id} is already {}", current.as_str())))
+}
+
+fn log_request_event(conn: &mut impl GenericClient, user_id: i32, action: &str, detail: &str) {
+    conn.execute(
+        "INSERT INTO security_logs (username, action, detail, severity)
+         SELECT u.username, $1, $2, 'info'
+         FROM users u WHERE u.user_id = $3",
+        &[&action, &detail, &user_id],
+    ).ok();
+}
+
+/// Lists the user's own requests, newest first, optionally only those with a
+/// status or for an app
+pub async fn list_own_requests(
+    AuthUser { claims }: AuthUser,
+    Extension(state): Extension<AppState>,
+    Query(query): Query<PolicyRequestQuery>,
+) -> Result<Json<Vec<PolicyRequestDetail>>, (StatusCode, String)> {
+    if let Some(status) = query.status.as_deref()
+        && RequestStatus::parse(status).is_none()
+    {
+        return Err((StatusCode::BAD_REQUEST, format!("Unknown request status {status}")));
+    }
+
+    let user_id = claims.user_id;
+    let ttl_days = state.policy_request_ttl_days;
+    let pool = state.db_pool.clone();
+
+    let requests = spawn_blocking(move || {
+        let mut conn = pool.get()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+        expire_stale_requests(&mut *conn, ttl_days)?;
+
+        let rows = conn.query(
+            &format!(
+                "SELECT {REQUEST_COLUMNS}
+                 FROM policy_change_requests pcr
+                 JOIN roles r ON pcr.role_id = r.role_id
+                 JOIN users u ON pcr.requested_by = u.user_id
+                 WHERE pcr.requested_by = $1
+                   AND ($2::TEXT IS NULL OR pcr.status::text = $2)
+                   AND ($3::TEXT IS NULL OR pcr.app_name = $3)
+                 ORDER BY pcr.requested_at DESC"
+            ),
+            &[&user_id, &query.status, &query.app_name],
+        )
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Query error: {e}")))?;
+
+        Ok::<_, (StatusCode, String)>(rows.iter().map(request_detail).collect())
+    })
+    .await
+    .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Task join error".to_string()))??;
+
+    Ok(Json(requests))
+}
+
+/// Lists the user's own pending requests, optionally only those for an app
+pub async fn list_pending_requests(
+    user: AuthUser,
+    state: Extension<AppState>,
+    Query(query): Query<PolicyRequestQuery>,
+) -> Result<Json<Vec<PolicyRequestDetail>>, (StatusCode, String)> {
+    let query = PolicyRequestQuery {
+        status: Some(RequestStatus::Pending.as_str().to_string()),
+        app_name: query.app_name,
+    };
+    list_own_requests(user, state, Query(query)).await
+}
+
+/// Replaces the content of one of the user's pending requests. Its app and
+/// role stay: a request for another one is a new request.
+pub async fn amend_policy_request(
+    AuthUser { claims }: AuthUser,
+    Extension(state): Extension<AppState>,
+    Path(request_id): Path<i32>,
+    Json(request): Json<PolicyChangeRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    if !has_permission(Arc::new(state.clone()), claims.user_id, "manage_policies").await? {
+        return Err((StatusCode::FORBIDDEN, "Permission denied".to_string()));
+    }
+    validate_change_request(&request)?;
+
+    let user_id = claims.user_id;
+    let ttl_days = state.policy_request_ttl_days;
+    let pool = state.db_pool.clone();
+
+    spawn_blocking(move || {
+        let mut conn = pool.get()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+        let mut tx = conn.transaction()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {e}")))?;
+        expire_stale_requests(&mut tx, ttl_days)?;
+
+        let (app_name, role_id) = lock_own_request(&mut tx, request_id, user_id, RequestStatus::Pending)?;
+        if request.app_name != app_name || request.role_id.is_some_and(|r| r != role_id) {
+            return Err((StatusCode::BAD_REQUEST, "A request cannot move to another app or role".to_string()));
+        }
+        // The user may have lost the role since
+        request_role(&mut tx, user_id, &app_name, Some(role_id))?;
+
+        tx.execute(
+            "UPDATE policy_change_requests SET
+                default_ro = $2, default_rw = $3, tcp_bind = $4, tcp_connect = $5,
+                allowed_ips = $6, allowed_domains = $7, fs_rules = $8, scoped = $9,
+                seccomp = $10, resources = $11, isolation = $12, environment = $13,
+                allowed_ro_paths = $14, allowed_rw_paths = $15,
+                change_justification = $16, amended_at = NOW()
+             WHERE request_id = $1",
+            &[
+                &request_id,
+                &request.default_ro,
+                &request.default_rw,
+                &request.tcp_bind,
+                &request.tcp_connect,
+                &request.allowed_ips,
+                &request.allowed_domains,
+                &request.fs_rules,
+                &request.scoped,
+                &request.seccomp,
+                &request.resources,
+                &request.isolation,
+                &request.environment,
+                &request.allowed_ro_paths,
+                &request.allowed_rw_paths,
+                &request.change_justification,
+            ],
+        ).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Update error: {e}")))?;
+
+        let detail = format!("Amended policy change request #{} for app {} and role {}", request_id, app_name, role_id);
+        log_request_event(&mut tx, user_id, "policy_change_amended", &detail);
+
+        tx.commit()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Commit error: {e}")))
+    })
+    .await
+    .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Task join error".to_string()))??;
+
+    Ok(StatusCode::OK)
+}
+
+/// Withdraws one of the user's pending requests
+pub async fn withdraw_policy_request(
+    AuthUser { claims }: AuthUser,
+    Extension(state): Extension<AppState>,
+    Path(request_id): Path<i32>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let user_id = claims.user_id;
+    let ttl_days = state.policy_request_ttl_days;
+    let pool = state.db_pool.clone();
+
+    spawn_blocking(move || {
+        let mut conn = pool.get()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {e}")))?;
+        let mut tx = conn.transaction()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {e}")))?;
+        expire_stale_requests(&mut tx, ttl_days)?;
+
+        let (app_name, role_id) = lock_own_request(&mut tx, request_id, user_id, RequestStatus::Withdrawn)?;
+        tx.execute(
+            "UPDATE policy_change_requests SET status = 'withdrawn' WHERE request_id = $1",
+            &[&request_id],
+        ).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Update error: {e}")))?;
+
+        let detail = format!("Withdrew policy change request #{} for app {} and role {}", request_id, app_name, role_id);
+        log_request_event(&mut tx, user_id, "policy_change_withdrawn", &detail);
+
+        tx.commit()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Commit error: {e}")))
+    })
+    .await
+    .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Task join error".to_string()))??;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,12 +1,14 @@
 use axum::{extract:: Extension, http::StatusCode, Json};
-use crate::{models::policy::{Policy, AppRuleSet}, state::AppState};
+use crate::{models::policy::{Policy, RolePolicy}, state::AppState};
 use tokio::task::spawn_blocking;
 
-/// Get app policies for a given role_id
+/// Get app policies of the given roles, ordered by role id, of `app_name` only
+/// when given
 pub async fn get_app_rules(
     Extension(state): Extension<AppState>,
-    role_id: i32,
-) -> Result<Json<Vec<AppRuleSet>>, (StatusCode, String)> {
+    role_ids: Vec<i32>,
+    app_name: Option<String>,
+) -> Result<Json<Vec<RolePolicy>>, (StatusCode, String)> {
     let state = state.clone();
 
     let result = spawn_blocking(move || {
@@ -18,25 +20,33 @@ pub async fn get_app_rules(
         let rows = conn
             .query(
                 "
-                SELECT app_name, default_ro, default_rw, tcp_bind, tcp_connect, allowed_ips, allowed_domains
+                SELECT role_id, app_name, default_ro, default_rw, tcp_bind, tcp_connect, allowed_ips, allowed_domains, fs_rules, scoped, seccomp, resources, isolation, environment
                 FROM app_policy
-                WHERE role_id = $1
+                WHERE role_id = ANY($1) AND ($2::TEXT IS NULL OR app_name = $2)
+                ORDER BY role_id
                 ",
-                &[&role_id],
+                &[&role_ids, &app_name],
             )
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Query error: {e}")))?;
 
         let apps = rows
             .into_iter()
-            .map(|row| AppRuleSet {
-                app_name: row.get(0),
+            .map(|row| RolePolicy {
+                role_id: row.get(0),
+                app_name: Some(row.get(1)),
                 policy: Policy {
-                    default_ro: row.get(1),
-                    default_rw: row.get(2),
-                    tcp_bind: row.get(3),
-                    tcp_connect: row.get(4),
-                    allowed_ips: row.get(5),
-                    allowed_domains: row.get(6),
+                    default_ro: row.get(2),
+                    default_rw: row.get(3),
+                    tcp_bind: row.get(4),
+                    tcp_connect: row.get(5),
+                    allowed_ips: row.get(6),
+                    allowed_domains: row.get(7),
+                    fs_rules: row.get(8),
+                    scoped: row.get(9),
+                    seccomp: row.get(10),
+                    resources: row.get(11),
+                    isolation: row.get(12),
+                    environment: row.get(13),
                 },
             })
             .collect();
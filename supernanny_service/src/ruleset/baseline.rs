@@ -0,0 +1,57 @@
+use axum::{extract:: Extension, http::StatusCode, Json};
+use crate::{models::policy::{BaselinePolicy, Policy}, state::AppState};
+use tokio::task::spawn_blocking;
+
+/// Get the baselines of every host and of `hostname`, the former first
+pub async fn get_baseline_rules(
+    Extension(state): Extension<AppState>,
+    hostname: Option<String>,
+) -> Result<Json<Vec<BaselinePolicy>>, (StatusCode, String)> {
+    let state = state.clone();
+
+    let result = spawn_blocking(move || {
+        let mut conn = state
+            .db_pool
+            .get()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB pool error: {e}")))?;
+
+        let rows = conn
+            .query(
+                "
+                SELECT hostname, default_ro, default_rw, tcp_bind, tcp_connect, allowed_ips, allowed_domains, fs_rules, scoped, seccomp, resources, isolation, environment
+                FROM baseline_policies
+                WHERE hostname = '*' OR hostname = $1
+                ORDER BY hostname <> '*'
+                ",
+                &[&hostname],
+            )
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Query error: {e}")))?;
+
+        let baselines = rows
+            .into_iter()
+            .map(|row| BaselinePolicy {
+                hostname: row.get(0),
+                policy: Policy {
+                    default_ro: row.get(1),
+                    default_rw: row.get(2),
+                    tcp_bind: row.get(3),
+                    tcp_connect: row.get(4),
+                    allowed_ips: row.get(5),
+                    allowed_domains: row.get(6),
+                    fs_rules: row.get(7),
+                    scoped: row.get(8),
+                    seccomp: row.get(9),
+                    resources: row.get(10),
+                    isolation: row.get(11),
+                    environment: row.get(12),
+                },
+            })
+            .collect();
+
+        Ok(baselines)
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Join error: {e}")))??;
+
+    Ok(Json(result))
+}
@@ -1,12 +1,12 @@
 use axum::{extract:: Extension, http::StatusCode, Json};
-use crate::{models::policy::Policy, state::AppState};
+use crate::{models::policy::{Policy, RolePolicy}, state::AppState};
 use tokio::task::spawn_blocking;
 
-/// Get default policies for a given role_id
+/// Get default policies of the given roles, ordered by role id
 pub async fn get_default_rules(
     Extension(state): Extension<AppState>,
-    role_id: i32,
-) -> Result<Json<Vec<Policy>>, (StatusCode, String)> {
+    role_ids: Vec<i32>,
+) -> Result<Json<Vec<RolePolicy>>, (StatusCode, String)> {
     let state = state.clone();
 
     let result = spawn_blocking(move || {
@@ -18,23 +18,34 @@ pub async fn get_default_rules(
         let rows = conn
             .query(
                 "
-                SELECT default_ro, default_rw, tcp_bind, tcp_connect, allowed_ips, allowed_domains
+                SELECT role_id, default_ro, default_rw, tcp_bind, tcp_connect, allowed_ips, allowed_domains, fs_rules, scoped, seccomp, resources, isolation, environment
                 FROM default_policies
-                WHERE role_id = $1
+                WHERE role_id = ANY($1)
+                ORDER BY role_id
                 ",
-                &[&role_id],
+                &[&role_ids],
             )
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Query error: {e}")))?;
 
         let policies = rows
             .into_iter()
-            .map(|row| Policy {
-                default_ro: row.get(0),
-                default_rw: row.get(1),
-                tcp_bind: row.get(2),
-                tcp_connect: row.get(3),
-                allowed_ips: row.get(4),
-                allowed_domains: row.get(5),
+            .map(|row| RolePolicy {
+                role_id: row.get(0),
+                app_name: None,
+                policy: Policy {
+                    default_ro: row.get(1),
+                    default_rw: row.get(2),
+                    tcp_bind: row.get(3),
+                    tcp_connect: row.get(4),
+                    allowed_ips: row.get(5),
+                    allowed_domains: row.get(6),
+                    fs_rules: row.get(7),
+                    scoped: row.get(8),
+                    seccomp: row.get(9),
+                    resources: row.get(10),
+                    isolation: row.get(11),
+                    environment: row.get(12),
+                },
             })
             .collect();
 
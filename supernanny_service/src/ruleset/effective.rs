@@ -0,0 +1,1159 @@
+//! Effective policies: the single policy an application of a user runs with.
+//!
+//! A policy is resolved in three layers, by decreasing precedence:
+//!
+//! 1. app: each role's policy for the application
+//! 2. role default: for the roles without one, their default policy; a role
+//!    with neither contributes nothing
+//! 3. baseline: the `baseline_policies` of every host (`*`) and of the host
+//!    asking, applied on top of whatever the roles resolved to
+//!
+//! The policies the roles contribute are merged permissively for the fields
+//! granting access, and keep restrictions only when every role asks for them:
+//!
+//! - `default_ro`, `default_rw`, `tcp_bind`, `tcp_connect`, `allowed_ips`,
+//!   `allowed_domains`: union
+//! - `fs_rules`: union of the rights granted on each path
+//! - `scoped`, `isolation`: intersection
+//! - `seccomp`: intersection of the presets and `deny=` syscalls; union of the
+//!   `allow=` syscalls, dropped when a role has no allowlist
+//! - `resources`: the most generous value of each limit, dropped when a role
+//!   does not limit it
+//! - `environment`: intersection of the `deny=` and `redact=` globs; union of
+//!   the `allow=` globs, dropped when a role has no allowlist; for `set=`, the
+//!   value of the role with the lowest id
+//!
+//! A baseline is imposed on everyone: what it grants is added, its
+//! restrictions always apply.
+//!
+//! - grants, `fs_rules`, `scoped`, `isolation`, seccomp presets and `deny=`,
+//!   environment `deny=` and `redact=`: union
+//! - `allow=` lists: narrowed to the baseline's, or the baseline's own when
+//!   the roles have none or none in common
+//! - `resources`: the lowest of each limit
+//! - environment `set=`: only for the variables the roles do not set
+//!
+//! Every rule of the result comes with the roles and baseline it was taken
+//! from.
+
+use std::collections::BTreeSet;
+
+use crate::models::policy::{
+    AppRuleSet, BaselinePolicy, EffectivePolicyResponse, Policy, ResolutionStep, RolePolicy,
+    RuleSetResponse, RuleSource,
+};
+
+/// Rules of a field merged from the roles, each with the roles it comes from.
+type Merged = Vec<(String, Vec<i32>)>;
+
+/// The rules of one field of a contributing role, split into single rules.
+type Contribution = (i32, Vec<String>);
+
+/// How one policy field is merged.
+struct Field {
+    name: &'static str,
+    get: fn(&Policy) -> &String,
+    set: fn(&mut Policy) -> &mut String,
+    /// Splits the field into single rules
+    split: fn(&str) -> Vec<String>,
+    /// Merges the rules of the roles
+    merge: fn(&[Contribution]) -> Merged,
+    /// Applies the rules of a baseline on top
+    baseline: fn(&mut Vec<RuleSource>, &[String], &'static str, &str),
+    /// Joins single rules back into the field
+    join: fn(&[String]) -> String,
+}
+
+const FIELDS: [Field; 12] = [
+    Field {
+        name: "default_ro",
+        get: |p| &p.default_ro,
+        set: |p| &mut p.default_ro,
+        split: split_list,
+        merge: union,
+        baseline: add,
+        join: join_list,
+    },
+    Field {
+        name: "default_rw",
+        get: |p| &p.default_rw,
+        set: |p| &mut p.default_rw,
+        split: split_list,
+        merge: union,
+        baseline: add,
+        join: join_list,
+    },
+    Field {
+        name: "tcp_bind",
+        get: |p| &p.tcp_bind,
+        set: |p| &mut p.tcp_bind,
+        split: split_list,
+        merge: union,
+        baseline: add,
+        join: join_list,
+    },
+    Field {
+        name: "tcp_connect",
+        get: |p| &p.tcp_connect,
+        set: |p| &mut p.tcp_connect,
+        split: split_list,
+        merge: union,
+        baseline: add,
+        join: join_list,
+    },
+    Field {
+        name: "allowed_ips",
+        get: |p| &p.allowed_ips,
+        set: |p| &mut p.allowed_ips,
+        split: split_list,
+        merge: union,
+        baseline: add,
+        join: join_list,
+    },
+    Field {
+        name: "allowed_domains",
+        get: |p| &p.allowed_domains,
+        set: |p| &mut p.allowed_domains,
+        split: split_list,
+        merge: union,
+        baseline: add,
+        join: join_list,
+    },
+    Field {
+        name: "fs_rules",
+        get: |p| &p.fs_rules,
+        set: |p| &mut p.fs_rules,
+        split: split_fs_rules,
+        merge: union,
+        baseline: add,
+        join: |rules| join_grouped(rules, |rule| rule.rsplit_once('=')),
+    },
+    Field {
+        name: "scoped",
+        get: |p| &p.scoped,
+        set: |p| &mut p.scoped,
+        split: split_list,
+        merge: common,
+        baseline: add,
+        join: join_list,
+    },
+    Field {
+        name: "seccomp",
+        get: |p| &p.seccomp,
+        set: |p| &mut p.seccomp,
+        split: split_seccomp,
+        merge: merge_seccomp,
+        baseline: seccomp_baseline,
+        join: |rules| join_grouped(rules, |rule| rule.split_once('=')),
+    },
+    Field {
+        name: "resources",
+        get: |p| &p.resources,
+        set: |p| &mut p.resources,
+        split: split_list,
+        merge: merge_resources,
+        baseline: resources_baseline,
+        join: join_list,
+    },
+    Field {
+        name: "isolation",
+        get: |p| &p.isolation,
+        set: |p| &mut p.isolation,
+        split: split_list,
+        merge: common,
+        baseline: add,
+        join: join_list,
+    },
+    Field {
+        name: "environment",
+        get: |p| &p.environment,
+        set: |p| &mut p.environment,
+        split: split_environment,
+        merge: merge_environment,
+        baseline: environment_baseline,
+        join: |rules| {
+            join_grouped(rules, |rule| {
+                rule.split_once('=').filter(|(key, _)| *key != "set")
+            })
+        },
+    },
+];
+
+/// Name and role policies of the policy a binary at `path` runs with, and
+/// why: the policy it was `identified` with, else the `unknown` application
+/// policy when one is configured, else none, leaving the roles' defaults.
+pub fn select_app_policy(
+    path: &str,
+    identified: Option<(String, Vec<RolePolicy>, String)>,
+    unknown: Option<(String, Vec<RolePolicy>)>,
+) -> (String, Vec<RolePolicy>, String) {
+    match (identified, unknown) {
+        (Some(identified), _) => identified,
+        (None, Some((unknown, app))) => {
+            let detail = format!("{path} unknown, unknown application policy {unknown}");
+            (unknown, app, detail)
+        }
+        (None, None) => (path.to_string(), Vec::new(), format!("{path} unknown, default policy")),
+    }
+}
+
+/// Resolves the policy of `app_name`, explaining each step. `apps` are the
+/// roles' policies for this application.
+pub fn resolve_app(
+    app_name: &str,
+    role_ids: &[i32],
+    defaults: &[RolePolicy],
+    apps: &[RolePolicy],
+    baselines: &[BaselinePolicy],
+) -> EffectivePolicyResponse {
+    let mut trace = Vec::new();
+    let mut contributions = Vec::new();
+    for &role_id in role_ids {
+        let app = apps
+            .iter()
+            .find(|a| a.role_id == role_id && a.app_name.as_deref() == Some(app_name));
+        let (layer, detail, policy) = match (app, defaults.iter().find(|d| d.role_id == role_id)) {
+            (Some(app), _) => ("app", format!("Role {role_id}: policy of {app_name}"), Some(app)),
+            (None, Some(default)) => (
+                "role_default",
+                format!("Role {role_id}: no policy for {app_name}, default policy"),
+                Some(default),
+            ),
+            (None, None) => (
+                "no_policy",
+                format!("Role {role_id}: no policy for {app_name} and no default policy"),
+                None,
+            ),
+        };
+        if let Some(policy) = policy {
+            contributions.push((role_id, &policy.policy));
+        }
+        trace.push(ResolutionStep {
+            layer,
+            role_id: Some(role_id),
+            hostname: None,
+            detail,
+        });
+    }
+    if contributions.len() > 1 {
+        trace.push(ResolutionStep {
+            layer: "merge",
+            role_id: None,
+            hostname: None,
+            detail: format!("Merged the policies of {} roles", contributions.len()),
+        });
+    }
+    for baseline in baselines {
+        trace.push(ResolutionStep {
+            layer: "baseline",
+            role_id: None,
+            hostname: Some(baseline.hostname.clone()),
+            detail: match baseline.hostname.as_str() {
+                "*" => "Baseline of every host applied".to_string(),
+                host => format!("Baseline of host {host} applied"),
+            },
+        });
+    }
+    if contributions.is_empty() && baselines.is_empty() {
+        trace.push(ResolutionStep {
+            layer: "no_policy",
+            role_id: None,
+            hostname: None,
+            detail: format!("No policy applies to {app_name}, it runs with an empty policy"),
+        });
+    }
+
+    let (policy, sources) = resolve_policy(&contributions, baselines);
+    EffectivePolicyResponse {
+        app_name: app_name.to_string(),
+        hostname: None,
+        role_ids: role_ids.to_vec(),
+        policy,
+        sources,
+        trace,
+    }
+}
+
+/// Resolves the default policy of the roles and the policy of each of the
+/// applications in `apps`.
+pub fn resolve(
+    role_ids: &[i32],
+    defaults: &[RolePolicy],
+    apps: &[RolePolicy],
+    baselines: &[BaselinePolicy],
+) -> RuleSetResponse {
+    let default_of = |role_id: i32| defaults.iter().find(|d| d.role_id == role_id);
+
+    let contributions: Vec<(i32, &Policy)> = role_ids
+        .iter()
+        .filter_map(|&role_id| default_of(role_id).map(|d| (role_id, &d.policy)))
+        .collect();
+    let (default_policies, default_sources) = if contributions.is_empty() && baselines.is_empty() {
+        (Vec::new(), Vec::new())
+    } else {
+        let (policy, sources) = resolve_policy(&contributions, baselines);
+        (vec![policy], sources)
+    };
+
+    let app_names: BTreeSet<&str> = apps.iter().filter_map(|a| a.app_name.as_deref()).collect();
+    let app_policies = app_names
+        .into_iter()
+        .map(|app_name| {
+            let contributions: Vec<(i32, &Policy)> = role_ids
+                .iter()
+                .filter_map(|&role_id| {
+                    apps.iter()
+                        .find(|a| a.role_id == role_id && a.app_name.as_deref() == Some(app_name))
+                        .or_else(|| default_of(role_id))
+                        .map(|p| (role_id, &p.policy))
+                })
+                .collect();
+            let (policy, sources) = resolve_policy(&contributions, baselines);
+            AppRuleSet {
+                app_name: app_name.to_string(),
+                policy,
+                sources,
+            }
+        })
+        .collect();
+
+    RuleSetResponse {
+        role_ids: role_ids.to_vec(),
+        default_policies,
+        default_sources,
+        app_policies,
+        identities: Vec::new(),
+        unknown_app_policy: None,
+    }
+}
+
+/// Merges the policies of several roles, ordered by role id, then applies
+/// the baselines.
+fn resolve_policy(
+    contributions: &[(i32, &Policy)],
+    baselines: &[BaselinePolicy],
+) -> (Policy, Vec<RuleSource>) {
+    let mut fields: Vec<Vec<RuleSource>> = FIELDS
+        .iter()
+        .map(|field| {
+            let rules: Vec<Contribution> = contributions
+                .iter()
+                .map(|(role_id, policy)| (*role_id, (field.split)((field.get)(policy))))
+                .collect();
+            (field.merge)(&rules)
+                .into_iter()
+                .map(|(rule, role_ids)| RuleSource {
+                    field: field.name,
+                    rule,
+                    role_ids,
+                    baseline: None,
+                })
+                .collect()
+        })
+        .collect();
+
+    for baseline in baselines {
+        for (field, rules) in FIELDS.iter().zip(fields.iter_mut()) {
+            let imposed = (field.split)((field.get)(&baseline.policy));
+            (field.baseline)(rules, &imposed, field.name, &baseline.hostname);
+        }
+    }
+
+    let mut policy = Policy::default();
+    for (field, rules) in FIELDS.iter().zip(&fields) {
+        let rules: Vec<String> = rules.iter().map(|r| r.rule.clone()).collect();
+        *(field.set)(&mut policy) = (field.join)(&rules);
+    }
+    (policy, fields.into_iter().flatten().collect())
+}
+
+/// Every rule of any role.
+fn union(contributions: &[Contribution]) -> Merged {
+    let mut merged: Merged = Vec::new();
+    for (role_id, rules) in contributions {
+        for rule in rules {
+            match merged.iter_mut().find(|(r, _)| r == rule) {
+                Some((_, role_ids)) if role_ids.contains(role_id) => {}
+                Some((_, role_ids)) => role_ids.push(*role_id),
+                None => merged.push((rule.clone(), vec![*role_id])),
+            }
+        }
+    }
+    merged
+}
+
+/// The rules every role has.
+fn common(contributions: &[Contribution]) -> Merged {
+    let role_ids: Vec<i32> = contributions.iter().map(|(role_id, _)| *role_id).collect();
+    let mut merged: Merged = Vec::new();
+    if let Some((_, first)) = contributions.first() {
+        for rule in first {
+            let everywhere = contributions.iter().all(|(_, rules)| rules.contains(rule));
+            if everywhere && !merged.iter().any(|(r, _)| r == rule) {
+                merged.push((rule.clone(), role_ids.clone()));
+            }
+        }
+    }
+    merged
+}
+
+/// Union of the rules starting with `prefix`, an allowlist: a role without
+/// any allows everything, and so does the merged policy.
+fn union_allowlists(contributions: &[Contribution], prefix: &str) -> Merged {
+    let lists = select(contributions, |rule| rule.starts_with(prefix));
+    if lists.iter().any(|(_, rules)| rules.is_empty()) {
+        return Vec::new();
+    }
+    union(&lists)
+}
+
+fn select(contributions: &[Contribution], keep: impl Fn(&str) -> bool) -> Vec<Contribution> {
+    contributions
+        .iter()
+        .map(|(role_id, rules)| {
+            (*role_id, rules.iter().filter(|r| keep(r)).cloned().collect())
+        })
+        .collect()
+}
+
+fn merge_seccomp(contributions: &[Contribution]) -> Merged {
+    let mut merged = common(&select(contributions, |rule| !rule.starts_with("allow=")));
+    merged.extend(union_allowlists(contributions, "allow="));
+    merged
+}
+
+fn merge_environment(contributions: &[Contribution]) -> Merged {
+    let mut merged = union_allowlists(contributions, "allow=");
+    merged.extend(common(&select(contributions, |rule| {
+        rule.starts_with("deny=") || rule.starts_with("redact=")
+    })));
+
+    // Roles are ordered by id: the first to set a variable wins
+    let mut names: Vec<&str> = Vec::new();
+    for (role_id, rules) in contributions {
+        for rule in rules.iter().filter(|r| r.starts_with("set=")) {
+            if !names.contains(&set_name(rule)) {
+                names.push(set_name(rule));
+                merged.push((rule.clone(), vec![*role_id]));
+            }
+        }
+    }
+    merged
+}
+
+/// The highest of each limit all roles set, from the roles setting it.
+fn merge_resources(contributions: &[Contribution]) -> Merged {
+    let mut merged: Merged = Vec::new();
+    let Some((_, first)) = contributions.first() else {
+        return merged;
+    };
+    for (key, _) in first.iter().map(|rule| split_limit(rule)) {
+        let values: Vec<(i32, &str)> = contributions
+            .iter()
+            .filter_map(|(role_id, rules)| {
+                rules
+                    .iter()
+                    .map(|rule| split_limit(rule))
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, value)| (*role_id, value))
+            })
+            .collect();
+        let seen = merged.iter().any(|(rule, _)| split_limit(rule).0 == key);
+        if values.len() < contributions.len() || seen {
+            continue;
+        }
+        let highest = values.iter().map(|(_, v)| limit_value(v)).max().unwrap_or(0);
+        let from: Vec<&(i32, &str)> = values.iter().filter(|(_, v)| limit_value(v) == highest).collect();
+        merged.push((
+            format!("{key}={}", from[0].1),
+            from.iter().map(|(role_id, _)| *role_id).collect(),
+        ));
+    }
+    merged
+}
+
+/// Adds the rules of a baseline, granted by it too or imposed by it.
+fn add(rules: &mut Vec<RuleSource>, baseline: &[String], field: &'static str, hostname: &str) {
+    for rule in baseline {
+        match rules.iter_mut().find(|r| r.rule == *rule) {
+            Some(r) => r.baseline = Some(hostname.to_string()),
+            None => rules.push(imposed(field, rule, hostname)),
+        }
+    }
+}
+
+fn imposed(field: &'static str, rule: &str, hostname: &str) -> RuleSource {
+    RuleSource {
+        field,
+        rule: rule.to_string(),
+        role_ids: Vec::new(),
+        baseline: Some(hostname.to_string()),
+    }
+}
+
+/// Narrows the allowlist of the rules starting with `prefix` to the
+/// baseline's.
+fn narrow_allowlist(
+    rules: &mut Vec<RuleSource>,
+    baseline: &[String],
+    field: &'static str,
+    hostname: &str,
+    prefix: &str,
+) {
+    let allowed: Vec<&String> = baseline.iter().filter(|r| r.starts_with(prefix)).collect();
+    if allowed.is_empty() {
+        return;
+    }
+    rules.retain(|r| !r.rule.starts_with(prefix) || allowed.contains(&&r.rule));
+    let mut kept = false;
+    for rule in rules.iter_mut().filter(|r| r.rule.starts_with(prefix)) {
+        rule.baseline = Some(hostname.to_string());
+        kept = true;
+    }
+    if !kept {
+        rules.extend(allowed.iter().map(|rule| imposed(field, rule, hostname)));
+    }
+}
+
+fn seccomp_baseline(rules: &mut Vec<RuleSource>, baseline: &[String], field: &'static str, hostname: &str) {
+    let restrictions: Vec<String> = baseline
+        .iter()
+        .filter(|rule| !rule.starts_with("allow="))
+        .cloned()
+        .collect();
+    add(rules, &restrictions, field, hostname);
+    narrow_allowlist(rules, baseline, field, hostname, "allow=");
+}
+
+fn environment_baseline(rules: &mut Vec<RuleSource>, baseline: &[String], field: &'static str, hostname: &str) {
+    narrow_allowlist(rules, baseline, field, hostname, "allow=");
+    let restrictions: Vec<String> = baseline
+        .iter()
+        .filter(|rule| rule.starts_with("deny=") || rule.starts_with("redact="))
+        .cloned()
+        .collect();
+    add(rules, &restrictions, field, hostname);
+    for rule in baseline.iter().filter(|r| r.starts_with("set=")) {
+        let set = |r: &RuleSource| r.rule.starts_with("set=") && set_name(&r.rule) == set_name(rule);
+        if !rules.iter().any(set) {
+            rules.push(imposed(field, rule, hostname));
+        }
+    }
+}
+
+/// Caps each limit at the baseline's.
+fn resources_baseline(rules: &mut Vec<RuleSource>, baseline: &[String], field: &'static str, hostname: &str) {
+    for rule in baseline {
+        let (key, value) = split_limit(rule);
+        match rules.iter_mut().find(|r| split_limit(&r.rule).0 == key) {
+            None => rules.push(imposed(field, rule, hostname)),
+            Some(current) => {
+                let current_value = limit_value(split_limit(&current.rule).1);
+                if limit_value(value) < current_value {
+                    *current = imposed(field, rule, hostname);
+                } else if limit_value(value) == current_value {
+                    current.baseline = Some(hostname.to_string());
+                }
+            }
+        }
+    }
+}
+
+/// Name and value of a `key=value` limit.
+fn split_limit(rule: &str) -> (&str, &str) {
+    rule.split_once('=').unwrap_or((rule, ""))
+}
+
+/// Magnitude of a resource limit: bytes for `memory` (`K`, `M`, `G` and `T`
+/// suffixes), a percentage for `cpu`, a number otherwise. Unreadable values
+/// count as the lowest.
+fn limit_value(value: &str) -> u64 {
+    let (digits, shift) = match value.chars().last() {
+        Some('K') => (&value[..value.len() - 1], 10),
+        Some('M') => (&value[..value.len() - 1], 20),
+        Some('G') => (&value[..value.len() - 1], 30),
+        Some('T') => (&value[..value.len() - 1], 40),
+        Some('%') => (&value[..value.len() - 1], 0),
+        _ => (value, 0),
+    };
+    digits
+        .trim()
+        .parse::<u64>()
+        .map(|n| n.saturating_mul(1 << shift))
+        .unwrap_or(0)
+}
+
+/// Variable name of a `set=NAME=VALUE` rule.
+fn set_name(rule: &str) -> &str {
+    rule["set=".len()..].split('=').next().unwrap_or_default()
+}
+
+fn split_list(list: &str) -> Vec<String> {
+    list.split(':')
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// One `path=right` rule per right.
+fn split_fs_rules(list: &str) -> Vec<String> {
+    split_list(list)
+        .iter()
+        .flat_map(|entry| match entry.rsplit_once('=') {
+            Some((path, rights)) => split_items(rights)
+                .map(|right| format!("{path}={right}"))
+                .collect(),
+            None => vec![entry.clone()],
+        })
+        .collect()
+}
+
+/// Presets as they are, one `deny=` or `allow=` rule per syscall.
+fn split_seccomp(list: &str) -> Vec<String> {
+    split_list(list)
+        .iter()
+        .flat_map(|entry| match entry.split_once('=') {
+            Some((key, names)) => split_items(names).map(|name| format!("{key}={name}")).collect(),
+            None => vec![entry.clone()],
+        })
+        .collect()
+}
+
+/// One `allow=`, `deny=` or `redact=` rule per glob; `set=` entries keep their
+/// `\:` escapes.
+fn split_environment(list: &str) -> Vec<String> {
+    let mut entries = vec![String::new()];
+    let mut chars = list.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&':') => {
+                chars.next();
+                entries.last_mut().unwrap().push_str("\\:");
+            }
+            ':' => entries.push(String::new()),
+            c => entries.last_mut().unwrap().push(c),
+        }
+    }
+    entries
+        .iter()
+        .map(|e| e.trim())
+        .filter(|e| !e.is_empty())
+        .flat_map(|entry| match entry.split_once('=') {
+            Some((key, globs)) if key != "set" => {
+                split_items(globs).map(|glob| format!("{key}={glob}")).collect()
+            }
+            _ => vec![entry.to_string()],
+        })
+        .collect()
+}
+
+fn split_items(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',').map(str::trim).filter(|i| !i.is_empty())
+}
+
+fn join_list(rules: &[String]) -> String {
+    rules.join(":")
+}
+
+/// Joins single rules back into a policy field: rules `key` splits into
+/// `(key, item)` are grouped as `key=item,item`, the others kept as they are.
+fn join_grouped(rules: &[String], key: impl Fn(&str) -> Option<(&str, &str)>) -> String {
+    let mut entries: Vec<(Option<&str>, Vec<&str>)> = Vec::new();
+    for rule in rules {
+        match key(rule) {
+            Some((k, item)) => match entries.iter_mut().find(|(g, _)| *g == Some(k)) {
+                Some((_, items)) => items.push(item),
+                None => entries.push((Some(k), vec![item])),
+            },
+            None => entries.push((None, vec![rule])),
+        }
+    }
+    entries
+        .into_iter()
+        .map(|(k, items)| match k {
+            Some(k) => format!("{k}={}", items.join(",")),
+            None => items.join(","),
+        })
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role(role_id: i32, app_name: Option<&str>, policy: Policy) -> RolePolicy {
+        RolePolicy {
+            role_id,
+            app_name: app_name.map(String::from),
+            policy,
+        }
+    }
+
+    fn baseline(hostname: &str, policy: Policy) -> BaselinePolicy {
+        BaselinePolicy {
+            hostname: hostname.to_string(),
+            policy,
+        }
+    }
+
+    /// The rules of `field` and the roles they come from.
+    fn sources<'a>(sources: &'a [RuleSource], field: &str) -> Vec<(&'a str, Vec<i32>)> {
+        sources
+            .iter()
+            .filter(|s| s.field == field)
+            .map(|s| (s.rule.as_str(), s.role_ids.clone()))
+            .collect()
+    }
+
+    fn source<'a>(sources: &'a [RuleSource], field: &str, rule: &str) -> &'a RuleSource {
+        sources
+            .iter()
+            .find(|s| s.field == field && s.rule == rule)
+            .unwrap_or_else(|| panic!("no {field} rule {rule}"))
+    }
+
+    fn layers(effective: &EffectivePolicyResponse) -> Vec<(&str, Option<i32>)> {
+        effective
+            .trace
+            .iter()
+            .map(|s| (s.layer, s.role_id))
+            .collect()
+    }
+
+    fn merged(policies: &[(i32, Policy)]) -> (Policy, Vec<RuleSource>) {
+        let contributions: Vec<(i32, &Policy)> = policies.iter().map(|(id, p)| (*id, p)).collect();
+        resolve_policy(&contributions, &[])
+    }
+
+    #[test]
+    fn lists_are_merged_by_union() {
+        let (policy, sources_) = merged(&[
+            (
+                1,
+                Policy {
+                    default_ro: "/usr:/lib".into(),
+                    tcp_connect: "443".into(),
+                    fs_rules: "/dev/dri=read_file".into(),
+                    ..Default::default()
+                },
+            ),
+            (
+                2,
+                Policy {
+                    default_ro: "/usr:/opt".into(),
+                    tcp_connect: "443:80".into(),
+                    fs_rules: "/dev/dri=read_file,ioctl_dev:/opt/tc=execute".into(),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        assert_eq!(policy.default_ro, "/usr:/lib:/opt");
+        assert_eq!(policy.tcp_connect, "443:80");
+        assert_eq!(
+            policy.fs_rules,
+            "/dev/dri=read_file,ioctl_dev:/opt/tc=execute"
+        );
+        assert_eq!(
+            sources(&sources_, "default_ro"),
+            [("/usr", vec![1, 2]), ("/lib", vec![1]), ("/opt", vec![2])]
+        );
+        assert_eq!(
+            source(&sources_, "fs_rules", "/dev/dri=ioctl_dev").role_ids,
+            [2]
+        );
+        assert_eq!(
+            source(&sources_, "fs_rules", "/dev/dri=read_file").role_ids,
+            [1, 2]
+        );
+    }
+
+    #[test]
+    fn restrictions_are_merged_by_intersection() {
+        let (policy, sources_) = merged(&[
+            (
+                1,
+                Policy {
+                    scoped: "abstract_unix_socket:signal".into(),
+                    isolation: "private-tmp:no-network".into(),
+                    ..Default::default()
+                },
+            ),
+            (
+                2,
+                Policy {
+                    scoped: "signal".into(),
+                    isolation: "no-network:private-tmp:hostname=sandbox".into(),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        assert_eq!(policy.scoped, "signal");
+        assert_eq!(policy.isolation, "private-tmp:no-network");
+        assert_eq!(sources(&sources_, "scoped"), [("signal", vec![1, 2])]);
+    }
+
+    #[test]
+    fn seccomp_keeps_common_denials_and_joins_allowlists() {
+        let (policy, sources_) = merged(&[
+            (
+                1,
+                Policy {
+                    seccomp: "no-ptrace:no-raw-sockets:deny=mount,umount2:allow=read,write".into(),
+                    ..Default::default()
+                },
+            ),
+            (
+                2,
+                Policy {
+                    seccomp: "no-ptrace:deny=mount:allow=read,openat".into(),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        assert_eq!(
+            policy.seccomp,
+            "no-ptrace:deny=mount:allow=read,write,openat"
+        );
+        assert_eq!(source(&sources_, "seccomp", "allow=read").role_ids, [1, 2]);
+        assert_eq!(source(&sources_, "seccomp", "allow=openat").role_ids, [2]);
+
+        // A role without an allowlist allows every syscall
+        let (policy, _) = merged(&[
+            (
+                1,
+                Policy {
+                    seccomp: "deny=mount:allow=read".into(),
+                    ..Default::default()
+                },
+            ),
+            (
+                2,
+                Policy {
+                    seccomp: "deny=mount".into(),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        assert_eq!(policy.seccomp, "deny=mount");
+    }
+
+    #[test]
+    fn environment_joins_allowlists_and_keeps_common_denials() {
+        let (policy, sources_) = merged(&[
+            (
+                1,
+                Policy {
+                    environment: "allow=PATH,HOME:deny=AWS_*:redact=ghp_*:set=EDITOR=vim".into(),
+                    ..Default::default()
+                },
+            ),
+            (
+                2,
+                Policy {
+                    environment: "allow=PATH,LANG:deny=AWS_*,GH_*:set=EDITOR=nano:set=PAGER=less"
+                        .into(),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        assert_eq!(
+            policy.environment,
+            "allow=PATH,HOME,LANG:deny=AWS_*:set=EDITOR=vim:set=PAGER=less"
+        );
+        assert_eq!(
+            source(&sources_, "environment", "allow=PATH").role_ids,
+            [1, 2]
+        );
+        assert_eq!(
+            source(&sources_, "environment", "set=EDITOR=vim").role_ids,
+            [1]
+        );
+        assert_eq!(
+            source(&sources_, "environment", "set=PAGER=less").role_ids,
+            [2]
+        );
+    }
+
+    #[test]
+    fn resources_take_the_highest_limit_all_roles_set() {
+        let (policy, sources_) = merged(&[
+            (
+                1,
+                Policy {
+                    resources: "memory=512M:pids=100:cpu=50%".into(),
+                    ..Default::default()
+                },
+            ),
+            (
+                2,
+                Policy {
+                    resources: "memory=1G:pids=100:io=200".into(),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        assert_eq!(policy.resources, "memory=1G:pids=100");
+        assert_eq!(
+            sources(&sources_, "resources"),
+            [("memory=1G", vec![2]), ("pids=100", vec![1, 2])]
+        );
+    }
+
+    #[test]
+    fn app_policy_then_role_default() {
+        let defaults = [
+            role(
+                1,
+                None,
+                Policy {
+                    default_ro: "/usr".into(),
+                    ..Default::default()
+                },
+            ),
+            role(
+                2,
+                None,
+                Policy {
+                    default_ro: "/srv".into(),
+                    ..Default::default()
+                },
+            ),
+        ];
+        let apps = [
+            role(
+                1,
+                Some("firefox"),
+                Policy {
+                    default_ro: "/opt/firefox".into(),
+                    ..Default::default()
+                },
+            ),
+            role(
+                2,
+                Some("thunderbird"),
+                Policy {
+                    default_ro: "/opt/tb".into(),
+                    ..Default::default()
+                },
+            ),
+        ];
+        let effective = resolve_app("firefox", &[1, 2, 3], &defaults, &apps, &[]);
+
+        assert_eq!(effective.policy.default_ro, "/opt/firefox:/srv");
+        assert_eq!(
+            layers(&effective),
+            [
+                ("app", Some(1)),
+                ("role_default", Some(2)),
+                ("no_policy", Some(3)),
+                ("merge", None)
+            ]
+        );
+        assert_eq!(effective.trace[0].detail, "Role 1: policy of firefox");
+        assert_eq!(
+            source(&effective.sources, "default_ro", "/srv").role_ids,
+            [2]
+        );
+    }
+
+    #[test]
+    fn no_policy_at_all() {
+        let effective = resolve_app("firefox", &[3], &[], &[], &[]);
+        assert_eq!(
+            layers(&effective),
+            [("no_policy", Some(3)), ("no_policy", None)]
+        );
+        assert!(effective.sources.is_empty());
+        assert_eq!(effective.policy.default_ro, "");
+    }
+
+    #[test]
+    fn identified_policy_then_unknown_then_default() {
+        let app = vec![role(1, Some("browser"), Policy::default())];
+        let identified = Some(("browser".to_string(), app, "bound".to_string()));
+        let unknown = || {
+            Some((
+                "unknown".to_string(),
+                vec![role(1, Some("unknown"), Policy::default())],
+            ))
+        };
+
+        let (name, app, detail) = select_app_policy("/usr/bin/firefox", identified, unknown());
+        assert_eq!(
+            (name.as_str(), app.len(), detail.as_str()),
+            ("browser", 1, "bound")
+        );
+
+        let (name, app, detail) = select_app_policy("/usr/bin/firefox", None, unknown());
+        assert_eq!(name, "unknown");
+        assert_eq!(app[0].app_name.as_deref(), Some("unknown"));
+        assert_eq!(
+            detail,
+            "/usr/bin/firefox unknown, unknown application policy unknown"
+        );
+
+        let (name, app, detail) = select_app_policy("/usr/bin/firefox", None, None);
+        assert_eq!(name, "/usr/bin/firefox");
+        assert!(app.is_empty());
+        assert_eq!(detail, "/usr/bin/firefox unknown, default policy");
+
+        // Without application policies, the roles' defaults apply
+        let defaults = [role(
+            1,
+            None,
+            Policy {
+                default_ro: "/usr".into(),
+                ..Default::default()
+            },
+        )];
+        let effective = resolve_app(&name, &[1], &defaults, &app, &[]);
+        assert_eq!(layers(&effective), [("role_default", Some(1))]);
+        assert_eq!(effective.policy.default_ro, "/usr");
+    }
+
+    #[test]
+    fn baseline_adds_grants_and_narrows_allowlists() {
+        let defaults = [role(
+            1,
+            None,
+            Policy {
+                default_ro: "/usr".into(),
+                seccomp: "allow=read,write,openat".into(),
+                resources: "memory=1G:pids=100".into(),
+                environment: "allow=PATH,HOME,LANG".into(),
+                ..Default::default()
+            },
+        )];
+        let baselines = [
+            baseline(
+                "*",
+                Policy {
+                    default_ro: "/etc/ssl".into(),
+                    seccomp: "deny=ptrace:allow=read,write".into(),
+                    resources: "memory=256M:cpu=50%".into(),
+                    environment: "allow=PATH:deny=AWS_*".into(),
+                    ..Default::default()
+                },
+            ),
+            baseline(
+                "ws1",
+                Policy {
+                    default_ro: "/usr".into(),
+                    ..Default::default()
+                },
+            ),
+        ];
+        let effective = resolve_app("firefox", &[1], &defaults, &[], &baselines);
+
+        assert_eq!(effective.policy.default_ro, "/usr:/etc/ssl");
+        assert_eq!(effective.policy.seccomp, "allow=read,write:deny=ptrace");
+        assert_eq!(effective.policy.resources, "memory=256M:pids=100:cpu=50%");
+        assert_eq!(effective.policy.environment, "allow=PATH:deny=AWS_*");
+
+        let imposed = source(&effective.sources, "resources", "memory=256M");
+        assert!(imposed.role_ids.is_empty());
+        assert_eq!(imposed.baseline.as_deref(), Some("*"));
+        let granted = source(&effective.sources, "default_ro", "/usr");
+        assert_eq!(
+            (granted.role_ids.as_slice(), granted.baseline.as_deref()),
+            (&[1][..], Some("ws1"))
+        );
+        assert_eq!(
+            source(&effective.sources, "seccomp", "allow=read")
+                .baseline
+                .as_deref(),
+            Some("*")
+        );
+
+        assert_eq!(
+            layers(&effective),
+            [
+                ("role_default", Some(1)),
+                ("baseline", None),
+                ("baseline", None)
+            ]
+        );
+        let hosts: Vec<_> = effective
+            .trace
+            .iter()
+            .filter_map(|s| s.hostname.as_deref())
+            .collect();
+        assert_eq!(hosts, ["*", "ws1"]);
+        assert_eq!(effective.trace[1].detail, "Baseline of every host applied");
+        assert_eq!(effective.trace[2].detail, "Baseline of host ws1 applied");
+    }
+
+    #[test]
+    fn baseline_allowlist_applies_when_roles_have_none() {
+        let defaults = [role(
+            1,
+            None,
+            Policy {
+                seccomp: "deny=mount".into(),
+                ..Default::default()
+            },
+        )];
+        let baselines = [baseline(
+            "*",
+            Policy {
+                seccomp: "allow=read".into(),
+                ..Default::default()
+            },
+        )];
+        let effective = resolve_app("firefox", &[1], &defaults, &[], &baselines);
+        assert_eq!(effective.policy.seccomp, "deny=mount:allow=read");
+    }
+
+    #[test]
+    fn ruleset_resolves_defaults_and_each_application() {
+        let defaults = [
+            role(
+                1,
+                None,
+                Policy {
+                    default_ro: "/usr".into(),
+                    ..Default::default()
+                },
+            ),
+            role(
+                2,
+                None,
+                Policy {
+                    default_ro: "/srv".into(),
+                    ..Default::default()
+                },
+            ),
+        ];
+        let apps = [role(
+            1,
+            Some("firefox"),
+            Policy {
+                default_ro: "/opt/firefox".into(),
+                ..Default::default()
+            },
+        )];
+        let baselines = [baseline(
+            "*",
+            Policy {
+                default_rw: "/tmp".into(),
+                ..Default::default()
+            },
+        )];
+        let ruleset = resolve(&[1, 2], &defaults, &apps, &baselines);
+
+        assert_eq!(ruleset.default_policies.len(), 1);
+        assert_eq!(ruleset.default_policies[0].default_ro, "/usr:/srv");
+        assert_eq!(ruleset.default_policies[0].default_rw, "/tmp");
+        assert_eq!(ruleset.app_policies.len(), 1);
+        let firefox = &ruleset.app_policies[0];
+        assert_eq!(firefox.app_name, "firefox");
+        assert_eq!(firefox.policy.default_ro, "/opt/firefox:/srv");
+        assert_eq!(firefox.policy.default_rw, "/tmp");
+        assert_eq!(source(&firefox.sources, "default_ro", "/srv").role_ids, [2]);
+
+        let empty = resolve(&[3], &defaults, &[], &[]);
+        assert!(empty.default_policies.is_empty() && empty.app_policies.is_empty());
+    }
+}
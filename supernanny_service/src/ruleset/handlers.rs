@@ -1,31 +1,169 @@
 use axum::{
-    extract::Extension,
+    extract::{Extension, Query},
     http::StatusCode,
     Json,
 };
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ed25519_dalek::Signer;
 
 use crate::{
     auth::jwt::AuthUser,
-    models::policy::RuleSetResponse,
-    ruleset::{default::get_default_rules, app_name::get_app_rules},
+    models::dto::{EffectivePolicyQuery, RuleSetQuery},
+    models::policy::{
+        EffectivePolicyResponse, PolicyBundle, ResolutionStep, RolePolicy, RuleSetResponse,
+        SignedRuleSet,
+    },
+    ruleset::{
+        app_name::get_app_rules,
+        baseline::get_baseline_rules,
+        default::get_default_rules,
+        effective::{resolve, resolve_app, select_app_policy},
+        identity::{get_identity_bindings, matching_binding},
+    },
     state::AppState,
 };
 
-/// Returns a combined set of default and application-specific policies for the authenticated user.
+/// Returns the effective default and application-specific policies of the authenticated user,
+/// merged from all of their roles. `app_name` limits the application policies to one.
 pub async fn get_ruleset(
     AuthUser { claims }: AuthUser,
     Extension(state): Extension<AppState>,
+    Query(query): Query<RuleSetQuery>,
 ) -> Result<Json<RuleSetResponse>, (StatusCode, String)> {
-    let role_id = claims.role_id;
+    Ok(Json(role_ruleset(state, claims.role_ids, query).await?))
+}
+
+/// Returns the same policies as `get_ruleset`, signed for the workstations' offline cache.
+pub async fn get_signed_ruleset(
+    AuthUser { claims }: AuthUser,
+    Extension(state): Extension<AppState>,
+    Query(query): Query<RuleSetQuery>,
+) -> Result<Json<SignedRuleSet>, (StatusCode, String)> {
+    let issued_at = chrono::Utc::now().timestamp() as u64;
+    let bundle = PolicyBundle {
+        username: claims.sub,
+        role_ids: claims.role_ids.clone(),
+        issued_at,
+        expires_at: issued_at + state.policy_cache_ttl,
+        ruleset: role_ruleset(state.clone(), claims.role_ids, query).await?,
+    };
+
+    let payload = serde_json::to_vec(&bundle)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Serialization error: {e}")))?;
+    let signature = state.policy_signing_key.sign(&payload);
+
+    Ok(Json(SignedRuleSet {
+        payload: STANDARD.encode(&payload),
+        signature: STANDARD.encode(signature.to_bytes()),
+    }))
+}
+
+/// Returns the single policy the binary at `app_name` runs with for the authenticated user on
+/// `hostname`, and how it was resolved.
+///
+/// The binary gets the policy bound to its identity, else the policy named after its path when no
+/// binding claims that name. A script is identified first, then its `interpreter`. Binaries and
+/// scripts identified by neither get the unknown policy, else the default policy.
+pub async fn get_effective_policy(
+    AuthUser { claims }: AuthUser,
+    Extension(state): Extension<AppState>,
+    Query(query): Query<EffectivePolicyQuery>,
+) -> Result<Json<EffectivePolicyResponse>, (StatusCode, String)> {
+    if query.app_name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "app_name cannot be empty".into()));
+    }
+    let role_ids = claims.role_ids;
+    let path = query.app_name.clone();
 
+    let mut identified = identify(
+        &state,
+        &role_ids,
+        &path,
+        query.sha256.as_deref(),
+        query.package.as_deref(),
+    )
+    .await?;
+    if let (None, Some(interpreter)) = (&identified, &query.interpreter) {
+        identified = identify(
+            &state,
+            &role_ids,
+            interpreter,
+            query.interpreter_sha256.as_deref(),
+            query.interpreter_package.as_deref(),
+        )
+        .await?
+        .map(|(name, app, detail)| (name, app, format!("Script {path} not bound, {detail}")));
+    }
+    let unknown = match (&identified, &state.unknown_app_policy) {
+        (None, Some(unknown)) => {
+            let app = get_app_rules(Extension(state.clone()), role_ids.clone(), Some(unknown.clone())).await?;
+            Some((unknown.clone(), app.0))
+        }
+        _ => None,
+    };
+    let (app_name, app, detail) = select_app_policy(&path, identified, unknown);
+
+    let default = get_default_rules(Extension(state.clone()), role_ids.clone()).await?;
+    let baselines = get_baseline_rules(Extension(state), query.hostname.clone()).await?;
+
+    let mut effective = resolve_app(&app_name, &role_ids, &default.0, &app, &baselines.0);
+    effective.hostname = query.hostname;
+    effective.trace.insert(
+        0,
+        ResolutionStep {
+            layer: "identity",
+            role_id: None,
+            hostname: None,
+            detail,
+        },
+    );
+    Ok(Json(effective))
+}
+
+/// The name and role policies of the policy of the binary, or script, at `path`, and how it was
+/// found: bound to its identity, or named after its path when no binding claims that name.
+async fn identify(
+    state: &AppState,
+    role_ids: &[i32],
+    path: &str,
+    sha256: Option<&str>,
+    package: Option<&str>,
+) -> Result<Option<(String, Vec<RolePolicy>, String)>, (StatusCode, String)> {
+    let bindings = get_identity_bindings(Extension(state.clone()), Some(path.to_string())).await?;
+    if let Some(binding) = matching_binding(&bindings.0, path, sha256, package) {
+        let app = get_app_rules(Extension(state.clone()), role_ids.to_vec(), Some(binding.app_name.clone())).await?;
+        let detail = format!("{path} bound to policy {}", binding.app_name);
+        return Ok(Some((binding.app_name.clone(), app.0, detail)));
+    }
+
+    if bindings.0.iter().any(|b| b.app_name == path) {
+        return Ok(None);
+    }
+    let app = get_app_rules(Extension(state.clone()), role_ids.to_vec(), Some(path.to_string())).await?;
+    Ok((!app.0.is_empty()).then(|| {
+        (path.to_string(), app.0, format!("{path} not bound, policy named after its path"))
+    }))
+}
+
+async fn role_ruleset(
+    state: AppState,
+    role_ids: Vec<i32>,
+    query: RuleSetQuery,
+) -> Result<RuleSetResponse, (StatusCode, String)> {
     // Get default policies
-    let default = get_default_rules(Extension(state.clone()), role_id).await?;
+    let default = get_default_rules(Extension(state.clone()), role_ids.clone()).await?;
     
     // Get application-specific policies
-    let app = get_app_rules(Extension(state), role_id).await?;
+    let app = get_app_rules(Extension(state.clone()), role_ids.clone(), query.app_name).await?;
 
-    Ok(Json(RuleSetResponse {
-        default_policies: default.0,
-        app_policies: app.0,
-    }))
+    // Get the baselines imposed on everyone
+    let baselines = get_baseline_rules(Extension(state.clone()), query.hostname).await?;
+
+    // Get the identities the application policies are bound to
+    let identities = get_identity_bindings(Extension(state.clone()), None).await?;
+
+    let mut ruleset = resolve(&role_ids, &default.0, &app.0, &baselines.0);
+    ruleset.identities = identities.0;
+    ruleset.unknown_app_policy = state.unknown_app_policy;
+    Ok(ruleset)
 }
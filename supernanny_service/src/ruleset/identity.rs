@@ -0,0 +1,67 @@
+use axum::{extract:: Extension, http::StatusCode, Json};
+use crate::{models::policy::IdentityBinding, state::AppState};
+use tokio::task::spawn_blocking;
+
+/// Get the identity bindings of the binaries at `exe_path` and of the policy
+/// named after it, or all of them
+pub async fn get_identity_bindings(
+    Extension(state): Extension<AppState>,
+    exe_path: Option<String>,
+) -> Result<Json<Vec<IdentityBinding>>, (StatusCode, String)> {
+    let state = state.clone();
+
+    let result = spawn_blocking(move || {
+        let mut conn = state
+            .db_pool
+            .get()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB pool error: {e}")))?;
+
+        let rows = conn
+            .query(
+                "
+                SELECT identity_id, app_name, exe_path, sha256, package
+                FROM app_identities
+                WHERE $1::TEXT IS NULL OR exe_path = $1 OR app_name = $1
+                ORDER BY identity_id
+                ",
+                &[&exe_path],
+            )
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Query error: {e}")))?;
+
+        let bindings = rows
+            .into_iter()
+            .map(|row| IdentityBinding {
+                identity_id: row.get(0),
+                app_name: row.get(1),
+                exe_path: row.get(2),
+                sha256: row.get(3),
+                package: row.get(4),
+            })
+            .collect();
+
+        Ok(bindings)
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Join error: {e}")))??;
+
+    Ok(Json(result))
+}
+
+/// The binding of the binary at `exe_path` with digest `sha256`, from
+/// `package`. Bindings pinning the digest, then the package, take precedence
+/// over those of the path alone.
+pub fn matching_binding<'a>(
+    bindings: &'a [IdentityBinding],
+    exe_path: &str,
+    sha256: Option<&str>,
+    package: Option<&str>,
+) -> Option<&'a IdentityBinding> {
+    bindings
+        .iter()
+        .filter(|b| {
+            b.exe_path == exe_path
+                && b.sha256.as_deref().is_none_or(|d| sha256.is_some_and(|s| d.eq_ignore_ascii_case(s)))
+                && b.package.as_deref().is_none_or(|p| package == Some(p))
+        })
+        .max_by_key(|b| b.sha256.is_some() as usize * 2 + b.package.is_some() as usize)
+}
@@ -1,4 +1,6 @@
 pub mod handlers;
 pub mod default;
 pub mod app_name;
-
+pub mod baseline;
+pub mod effective;
+pub mod identity;
@@ -0,0 +1,49 @@
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ed25519_dalek::SigningKey;
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::{Read, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::Path,
+};
+
+const SIGNING_KEY_PATH: &str = "policy-signing.key";
+
+/// Loads the Ed25519 key signing the policies cached by workstations, from
+/// `POLICY_SIGNING_KEY` (base64 seed), generating it on first start.
+///
+/// Its public half is written next to it, with a `.pub` extension, to be
+/// installed as `/etc/supernanny/policy_signing.pub` on the workstations.
+pub fn load_or_generate_signing_key() -> Result<SigningKey> {
+    let path = env::var("POLICY_SIGNING_KEY").unwrap_or_else(|_| SIGNING_KEY_PATH.to_string());
+    let path = Path::new(&path);
+
+    let seed = if path.exists() {
+        let encoded = fs::read_to_string(path)?;
+        STANDARD
+            .decode(encoded.trim())
+            .with_context(|| format!("Invalid policy signing key in {}", path.display()))?
+    } else {
+        let mut seed = vec![0; 32];
+        File::open("/dev/urandom")?.read_exact(&mut seed)?;
+        OpenOptions::new()
+            .create_new(true)
+            .write(true)
+            .mode(0o600)
+            .open(path)?
+            .write_all(STANDARD.encode(&seed).as_bytes())?;
+        seed
+    };
+    let seed: [u8; 32] = seed
+        .try_into()
+        .map_err(|_| anyhow!("Invalid policy signing key length in {}", path.display()))?;
+
+    let key = SigningKey::from_bytes(&seed);
+    fs::write(
+        path.with_extension("pub"),
+        STANDARD.encode(key.verifying_key().as_bytes()),
+    )?;
+    Ok(key)
+}
@@ -1,8 +1,21 @@
 use r2d2::Pool;
 use r2d2_postgres::PostgresConnectionManager;
 use postgres::NoTls;
+use ed25519_dalek::SigningKey;
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db_pool: Pool<PostgresConnectionManager<NoTls>>,
+    /// Signs the policies cached by workstations for offline use
+    pub policy_signing_key: Arc<SigningKey>,
+    /// Validity of those cached policies, in seconds
+    pub policy_cache_ttl: u64,
+    /// Validity of a refresh token, in seconds; each refresh starts it over
+    pub refresh_token_ttl: u64,
+    /// Policy of the binaries matching no identity, the role defaults when
+    /// `None`
+    pub unknown_app_policy: Option<String>,
+    /// Days a policy change request stays pending before it expires
+    pub policy_request_ttl_days: i32,
 }